@@ -138,6 +138,15 @@ where
 
     /// Trigger the barrier of the given trigger id (based on propose id) and log index.
     fn trigger(&self, id: InflightId, index: LogIndex);
+
+    /// Verify that the command is deterministic by executing it twice in an isolated sandbox,
+    /// which shares no state with the live executor. Returns an error to reject the command
+    /// if the results differ. The default implementation has no sandbox and accepts every
+    /// command.
+    #[inline]
+    async fn verify_determinism(&self, _cmd: &C) -> Result<(), C::Error> {
+        Ok(())
+    }
 }
 
 /// Codec for encoding and decoding data into/from the Protobuf format
@@ -1,7 +1,7 @@
 use std::{
     fmt::Display,
     sync::{
-        atomic::{AtomicI64, Ordering},
+        atomic::{AtomicI64, AtomicU32, Ordering},
         Arc,
     },
     time::Duration,
@@ -25,6 +25,9 @@ use crate::{META_TABLE, REVISION_TABLE, TEST_TABLE};
 pub(crate) const APPLIED_INDEX_KEY: &str = "applied_index";
 pub(crate) const LAST_REVISION_KEY: &str = "last_revision";
 
+/// A source of non-determinism such as a clock, read by the non-deterministic commands
+static NON_DETERMINISTIC_SOURCE: AtomicU32 = AtomicU32::new(0);
+
 #[derive(Error, Debug, Clone, Serialize, Deserialize)]
 pub struct ExecuteError(pub String);
 
@@ -72,6 +75,8 @@ impl Default for TestCommand {
 pub enum TestCommandType {
     Get,
     Put(u32),
+    /// A read whose result differs in every execution
+    NonDeterministic,
 }
 
 /// value and revision
@@ -125,6 +130,17 @@ impl TestCommand {
         }
     }
 
+    pub fn new_non_deterministic(keys: Vec<u32>) -> Self {
+        Self {
+            keys,
+            exe_dur: Duration::ZERO,
+            as_dur: Duration::ZERO,
+            exe_should_fail: false,
+            as_should_fail: false,
+            cmd_type: TestCommandType::NonDeterministic,
+        }
+    }
+
     pub fn set_exe_dur(mut self, dur: Duration) -> Self {
         self.exe_dur = dur;
         self
@@ -262,33 +278,7 @@ impl CommandExecutor<TestCommand> for TestCE {
 
         debug!("{} execute cmd({:?})", self.server_name, cmd);
 
-        let keys = cmd
-            .keys
-            .iter()
-            .map(|k| k.to_be_bytes().to_vec())
-            .collect_vec();
-        let result: TestCommandResult = match cmd.cmd_type {
-            TestCommandType::Get => {
-                let value = self
-                    .store
-                    .get_multi(TEST_TABLE, &keys)
-                    .map_err(|e| ExecuteError(e.to_string()))?
-                    .into_iter()
-                    .flatten()
-                    .map(|v| u32::from_be_bytes(v.as_slice().try_into().unwrap()))
-                    .collect();
-                let revision = self
-                    .store
-                    .get_multi(REVISION_TABLE, &keys)
-                    .map_err(|e| ExecuteError(e.to_string()))?
-                    .into_iter()
-                    .flatten()
-                    .map(|v| i64::from_be_bytes(v.as_slice().try_into().unwrap()))
-                    .collect_vec();
-                TestCommandResult::new(value, revision)
-            }
-            TestCommandType::Put(_) => TestCommandResult::default(),
-        };
+        let result = Self::exe_result(&self.store, cmd)?;
 
         self.exe_sender
             .send((cmd.clone(), result.clone()))
@@ -402,9 +392,72 @@ impl CommandExecutor<TestCommand> for TestCE {
     }
 
     fn trigger(&self, _id: InflightId, _index: LogIndex) {}
+
+    async fn verify_determinism(
+        &self,
+        cmd: &TestCommand,
+    ) -> Result<(), <TestCommand as Command>::Error> {
+        if cmd.exe_should_fail {
+            return Ok(());
+        }
+        // the sandbox is a memory engine holding a copy of the current state
+        let tables = [TEST_TABLE, REVISION_TABLE];
+        let mut snapshot = self
+            .store
+            .get_snapshot("", &tables)
+            .map_err(|e| ExecuteError(e.to_string()))?;
+        snapshot.rewind().unwrap();
+        let sandbox = Engine::new(EngineType::Memory, &tables).unwrap();
+        sandbox
+            .apply_snapshot(snapshot, &tables)
+            .await
+            .map_err(|e| ExecuteError(e.to_string()))?;
+        let first = Self::exe_result(&sandbox, cmd)?;
+        let second = Self::exe_result(&sandbox, cmd)?;
+        if first != second {
+            return Err(ExecuteError(format!(
+                "not deterministic, results {first:?} and {second:?} differ"
+            )));
+        }
+        Ok(())
+    }
 }
 
 impl TestCE {
+    /// Compute the execution result of the command against the store
+    fn exe_result(store: &Engine, cmd: &TestCommand) -> Result<TestCommandResult, ExecuteError> {
+        let keys = cmd
+            .keys
+            .iter()
+            .map(|k| k.to_be_bytes().to_vec())
+            .collect_vec();
+        let result = match cmd.cmd_type {
+            TestCommandType::Get => {
+                let value = store
+                    .get_multi(TEST_TABLE, &keys)
+                    .map_err(|e| ExecuteError(e.to_string()))?
+                    .into_iter()
+                    .flatten()
+                    .map(|v| u32::from_be_bytes(v.as_slice().try_into().unwrap()))
+                    .collect();
+                let revision = store
+                    .get_multi(REVISION_TABLE, &keys)
+                    .map_err(|e| ExecuteError(e.to_string()))?
+                    .into_iter()
+                    .flatten()
+                    .map(|v| i64::from_be_bytes(v.as_slice().try_into().unwrap()))
+                    .collect_vec();
+                TestCommandResult::new(value, revision)
+            }
+            TestCommandType::Put(_) => TestCommandResult::default(),
+            TestCommandType::NonDeterministic => TestCommandResult::new(
+                vec![NON_DETERMINISTIC_SOURCE.fetch_add(1, Ordering::Relaxed)],
+                vec![],
+            ),
+        };
+        Ok(result)
+    }
+
     pub fn new(
         server_name: String,
         exe_sender: mpsc::UnboundedSender<(TestCommand, TestCommandResult)>,
@@ -414,6 +467,7 @@ impl TestCE {
         let engine_type = match engine_cfg {
             EngineConfig::Memory => EngineType::Memory,
             EngineConfig::RocksDB(path) => EngineType::Rocks(path),
+            EngineConfig::Sled(path) => EngineType::Sled(path),
             _ => unreachable!("Not supported storage type"),
         };
         let store =
@@ -0,0 +1,145 @@
+use std::{collections::VecDeque, time::Instant};
+
+/// Default number of the latest response times used to estimate the distribution
+const DEFAULT_WINDOW_SIZE: usize = 100;
+
+/// Default min standard deviation of response times in seconds, which avoids a too
+/// sharp suspicion curve when responses arrive at a very regular pace
+const DEFAULT_MIN_STD_DEVIATION: f64 = 0.05;
+
+/// Phi accrual failure detector of a server.
+///
+/// Instead of a boolean of whether the server is up, it outputs a suspicion level `phi`
+/// which rises continuously as a request to the server stays unanswered, compared to the
+/// distribution of the previous response times. A `phi` of 1 means the probability
+/// of a false suspicion is about 10%, 2 means about 1%, and so on.
+///
+/// Any successful response of the server is regarded as a heartbeat. The client only
+/// talks to the servers on demand, so the silence of a server is only measured while a
+/// request to it is pending, and the suspicion does not grow while the client is idle.
+#[derive(Debug)]
+pub(super) struct PhiAccrual {
+    /// The latest response times in seconds
+    intervals: VecDeque<f64>,
+    /// Max number of intervals
+    window_size: usize,
+    /// Min standard deviation of intervals in seconds
+    min_std_deviation: f64,
+    /// Send time of the earliest request not answered by a heartbeat yet, `None` if no
+    /// request is pending
+    waiting_since: Option<Instant>,
+}
+
+impl Default for PhiAccrual {
+    fn default() -> Self {
+        Self::new(DEFAULT_WINDOW_SIZE, DEFAULT_MIN_STD_DEVIATION)
+    }
+}
+
+impl PhiAccrual {
+    /// Create a new failure detector
+    pub(super) fn new(window_size: usize, min_std_deviation: f64) -> Self {
+        Self {
+            intervals: VecDeque::with_capacity(window_size),
+            window_size,
+            min_std_deviation,
+            waiting_since: None,
+        }
+    }
+
+    /// Record a request sent at `now`
+    pub(super) fn request(&mut self, now: Instant) {
+        let _ig = self.waiting_since.get_or_insert(now);
+    }
+
+    /// Record a heartbeat received at `now`, which answers all pending requests
+    pub(super) fn heartbeat(&mut self, now: Instant) {
+        if let Some(since) = self.waiting_since.take() {
+            if self.intervals.len() >= self.window_size {
+                let _ig = self.intervals.pop_front();
+            }
+            self.intervals
+                .push_back(now.saturating_duration_since(since).as_secs_f64());
+        }
+    }
+
+    /// Get the suspicion level at `now`, which is 0 if no request is pending or there are
+    /// not enough heartbeats to estimate the distribution
+    #[allow(clippy::arithmetic_side_effects, clippy::float_arithmetic)] // float calculation
+    #[allow(clippy::as_conversions, clippy::cast_precision_loss)] // the window is small
+    pub(super) fn phi(&self, now: Instant) -> f64 {
+        let Some(since) = self.waiting_since else {
+            return 0.0;
+        };
+        if self.intervals.is_empty() {
+            return 0.0;
+        }
+        let n = self.intervals.len() as f64;
+        let mean = self.intervals.iter().sum::<f64>() / n;
+        let variance = self
+            .intervals
+            .iter()
+            .map(|interval| (interval - mean).powi(2))
+            .sum::<f64>()
+            / n;
+        let std_deviation = variance.sqrt().max(self.min_std_deviation);
+        let elapsed = now.saturating_duration_since(since).as_secs_f64();
+
+        // logistic approximation of the cumulative distribution function of the normal
+        // distribution, which is precise enough and avoids the error function
+        let y = (elapsed - mean) / std_deviation;
+        let e = (-y * (1.5976 + 0.070_566 * y * y)).exp();
+        let phi = if elapsed > mean {
+            -(e / (1.0 + e)).log10()
+        } else {
+            -(1.0 - 1.0 / (1.0 + e)).log10()
+        };
+        // the probability underflows if the server is silent for too long
+        if phi.is_finite() {
+            phi.max(0.0)
+        } else {
+            f64::MAX
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[test]
+    fn phi_should_rise_as_the_interval_lengthens() {
+        let mut detector = PhiAccrual::default();
+        let start = Instant::now();
+        assert!(detector.phi(start).abs() < f64::EPSILON);
+
+        let mut now = start;
+        for millis in [100, 140, 90, 120, 80, 110, 130, 95, 105, 115] {
+            detector.request(now);
+            now += Duration::from_millis(millis);
+            detector.heartbeat(now);
+        }
+        // the server is not suspected while the client is idle
+        assert!(detector.phi(now + Duration::from_secs(10)).abs() < f64::EPSILON);
+
+        detector.request(now);
+        let mut prev = detector.phi(now);
+        for millis in [50, 100, 200, 400, 800, 1600] {
+            let phi = detector.phi(now + Duration::from_millis(millis));
+            assert!(
+                phi > prev,
+                "phi {phi} after {millis}ms should be above {prev}"
+            );
+            prev = phi;
+        }
+        // an interval far beyond the usual ones is highly suspected
+        assert!(prev > 8.0);
+
+        // a new heartbeat clears the suspicion
+        now += Duration::from_millis(1600);
+        detector.heartbeat(now);
+        assert!(detector.phi(now) < 1.0);
+    }
+}
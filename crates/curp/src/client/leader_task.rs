@@ -0,0 +1,85 @@
+use std::{future::Future, sync::Arc, time::Duration};
+
+use tokio::task::JoinHandle;
+use tracing::{debug, warn};
+
+use super::ClientApi;
+
+/// While the cached leader is another server, the leadership is validated once in every
+/// `VALIDATE_TICKS` ticks to learn about a regained leadership
+const VALIDATE_TICKS: u64 = 10;
+
+/// A task running periodically only while the local server of a client is the leader
+///
+/// Each step validates the leadership with a linearizable fetch before running the task, and
+/// runs it only if the local server is the leader in a term no older than the latest term
+/// seen, so the task stops at the first step after the leadership is lost. While the cached
+/// leader is another server, the fetch is skipped in most ticks. The task is stopped when the
+/// `LeaderTask` is dropped.
+#[derive(Debug)]
+pub struct LeaderTask {
+    /// Handle of the background task
+    handle: JoinHandle<()>,
+}
+
+impl LeaderTask {
+    /// Spawn a task running `task_fn` every `interval` while the local server of the client is
+    /// the leader. A client without a local server never runs the task.
+    #[inline]
+    pub fn spawn<C, F, Fut>(client: Arc<C>, interval: Duration, mut task_fn: F) -> Self
+    where
+        C: ClientApi + Send + Sync + 'static,
+        C::Error: std::fmt::Debug,
+        F: FnMut() -> Fut + Send + 'static,
+        Fut: Future<Output = ()> + Send,
+    {
+        let handle = tokio::spawn(async move {
+            let Some(local_id) = client.local_server_id() else {
+                warn!("the client has no local server, the leader task never runs");
+                return;
+            };
+            let mut ticker = tokio::time::interval(interval);
+            let mut ticks: u64 = 0;
+            let mut term: u64 = 0;
+            loop {
+                let _instant = ticker.tick().await;
+                let validate = ticks.checked_rem(VALIDATE_TICKS) == Some(0);
+                ticks = ticks.wrapping_add(1);
+                if !validate && client.cached_leader_id().await != Some(local_id) {
+                    continue;
+                }
+                // a linearizable fetch also refreshes the cached leader
+                let resp = match client.fetch_cluster(true).await {
+                    Ok(resp) => resp,
+                    Err(e) => {
+                        debug!("failed to validate the leadership, {e:?}");
+                        continue;
+                    }
+                };
+                if resp.term < term {
+                    debug!("ignore the stale leadership of term {}", resp.term);
+                    continue;
+                }
+                term = resp.term;
+                if resp.leader_id == Some(local_id) {
+                    task_fn().await;
+                }
+            }
+        });
+        Self { handle }
+    }
+
+    /// Stop the task, a running `task_fn` is cancelled. The task is aborted in `Drop`, so
+    /// this is the same as dropping it.
+    #[inline]
+    pub fn stop(self) {
+        drop(self);
+    }
+}
+
+impl Drop for LeaderTask {
+    #[inline]
+    fn drop(&mut self) {
+        self.handle.abort();
+    }
+}
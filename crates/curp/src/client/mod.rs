@@ -17,16 +17,33 @@ mod retry;
 /// State for clients
 mod state;
 
+/// Connection pool shared by clients
+mod pool;
+
+/// Failure detector of servers
+mod failure_detector;
+
+/// Operation traces of clients
+mod trace;
+
+/// Leader-only periodic tasks
+mod leader_task;
+
 /// Tests for client
 #[cfg(test)]
 mod tests;
 
-use std::{collections::HashMap, fmt::Debug, sync::Arc};
+use std::{
+    collections::HashMap,
+    fmt::Debug,
+    sync::Arc,
+    time::{Duration, Instant, SystemTime},
+};
 
 use async_trait::async_trait;
-use curp_external_api::cmd::Command;
-use futures::{stream::FuturesUnordered, StreamExt};
-use tokio::task::JoinHandle;
+use curp_external_api::cmd::{Command, ConflictCheck};
+use futures::{future::join_all, stream::FuturesUnordered, StreamExt};
+use tokio::{sync::mpsc, task::JoinHandle};
 #[cfg(not(madsim))]
 use tonic::transport::ClientTlsConfig;
 use tracing::debug;
@@ -34,6 +51,7 @@ use tracing::debug;
 use utils::ClientTlsConfig;
 use utils::{build_endpoint, config::ClientConfig};
 
+pub use self::{leader_task::LeaderTask, pool::ConnectionPool};
 use self::{
     retry::{Retry, RetryConfig},
     state::StateBuilder,
@@ -42,8 +60,9 @@ use self::{
 use crate::{
     members::ServerId,
     rpc::{
-        protocol_client::ProtocolClient, ConfChange, FetchClusterRequest, FetchClusterResponse,
-        Member, ProposeId, Protocol, ReadState,
+        protocol_client::ProtocolClient, ConfChange, CurpError, FetchClusterRequest,
+        FetchClusterResponse, Member, ProposeCompression, ProposeId, Protocol, ReadState,
+        ServerFeatures, ServerLoad,
     },
 };
 
@@ -52,6 +71,115 @@ use crate::{
 #[allow(type_alias_bounds)] // that's not bad
 type ProposeResponse<C: Command> = Result<(C::ER, Option<C::ASR>), C::Error>;
 
+/// The callback to deliver the result of a propose after the command is applied
+pub type ProposeCallback<C, E> = Box<dyn FnOnce(Result<ProposeResponse<C>, E>) + Send + 'static>;
+
+/// The outcome of a successful propose, with some statistics of how it succeeded
+#[derive(Debug)]
+#[non_exhaustive]
+pub struct ProposeOutcome<C: Command> {
+    /// The propose result
+    pub result: ProposeResponse<C>,
+    /// How many attempts were made, including the final successful one
+    pub attempts: usize,
+    /// The total latency of all attempts and backoffs
+    pub total_latency: Duration,
+}
+
+/// A propose which exhausted all retries, delivered to the dead-letter channel
+#[derive(Debug)]
+#[non_exhaustive]
+pub struct DeadLetter<C: Command> {
+    /// The command failed to be proposed
+    pub cmd: C,
+    /// The error returned after the retries were exhausted
+    pub err: tonic::Status,
+}
+
+/// The outcome of a traced operation
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub enum TraceOutcome {
+    /// The command is executed successfully
+    Succeeded,
+    /// The command is executed but returns an error
+    CommandError(String),
+    /// The operation fails, e.g. the retries are exhausted
+    Failed(tonic::Status),
+}
+
+/// The traced operation
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub enum TraceOp<C: Command> {
+    /// A propose of the command
+    Propose {
+        /// The proposed command
+        cmd: C,
+        /// Whether the fast path is used
+        use_fast_path: bool,
+    },
+    /// A fetch cluster request
+    FetchCluster {
+        /// Whether the cluster is fetched from the leader
+        linearizable: bool,
+    },
+    /// A request of the upper layer sent on its own channel, e.g. a watch or a lease keep
+    /// alive, recorded by [`ClientApi::trace_rpc`]
+    Rpc(&'static str),
+}
+
+/// A structured trace of an operation, recorded when the client is built with
+/// [`ClientBuilder::trace_capacity`]. A propose trace carries the command, so a recorded
+/// sequence of proposes can be replayed against a test cluster.
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct TraceEntry<C: Command> {
+    /// The traced operation
+    pub op: TraceOp<C>,
+    /// The server which served the operation, i.e. the one returning the execution result
+    /// of a propose. `None` if no server served it, or the requests go to every server.
+    pub connect: Option<ServerId>,
+    /// The outcome of the operation
+    pub outcome: TraceOutcome,
+    /// How many attempts were made
+    pub attempts: usize,
+    /// When the operation started
+    pub start: SystemTime,
+    /// The total latency of all attempts and backoffs
+    pub latency: Duration,
+}
+
+/// The decision of a retry hook on a failed attempt
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum RetryDecision {
+    /// Follow the default retry policy
+    Default,
+    /// Stop retrying and return the error
+    GiveUp,
+    /// Retry after the given delay instead of the backoff delay
+    RetryWithDelay(Duration),
+}
+
+/// A hook invoked with the error and the number of attempts made so far whenever a
+/// retryable error occurs
+#[derive(Clone)]
+pub(crate) struct RetryHook(Arc<dyn Fn(&CurpError, usize) -> RetryDecision + Send + Sync>);
+
+impl RetryHook {
+    /// Decide what to do with the failed attempt
+    fn decide(&self, err: &CurpError, attempts: usize) -> RetryDecision {
+        (self.0)(err, attempts)
+    }
+}
+
+impl Debug for RetryHook {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("RetryHook").finish()
+    }
+}
+
 /// `ClientApi`, a higher wrapper for `ConnectApi`, providing some methods for communicating to
 /// the whole curp cluster. Automatically discovery curp server to update it's quorum.
 #[async_trait]
@@ -72,12 +200,81 @@ pub trait ClientApi {
         use_fast_path: bool,
     ) -> Result<ProposeResponse<Self::Cmd>, Self::Error>;
 
+    /// Send propose to the whole cluster and return the propose id once the command is
+    /// committed, without waiting for it to be applied. The result of the command is
+    /// delivered to the `callback` after the command is applied.
+    async fn propose_with_callback(
+        &self,
+        cmd: &Self::Cmd,
+        token: Option<&String>,
+        callback: ProposeCallback<Self::Cmd, Self::Error>,
+    ) -> Result<ProposeId, Self::Error>;
+
+    /// Send propose like [`ClientApi::propose`], and report how many attempts were made
+    /// and how long it took in total
+    #[inline]
+    async fn propose_with_outcome(
+        &self,
+        cmd: &Self::Cmd,
+        token: Option<&String>,
+        use_fast_path: bool,
+    ) -> Result<ProposeOutcome<Self::Cmd>, Self::Error> {
+        let start = Instant::now();
+        let result = self.propose(cmd, token, use_fast_path).await?;
+        Ok(ProposeOutcome {
+            result,
+            attempts: 1,
+            total_latency: start.elapsed(),
+        })
+    }
+
+    /// Send a batch of proposes to the whole cluster, and return the results in the order of
+    /// `cmds`. Conflicting commands could not all take the fast path, so the batch is grouped
+    /// by conflicts: groups are proposed concurrently, while the commands of one group are
+    /// proposed one by one in their order. If the client is built without
+    /// [`ClientBuilder::batch_by_conflicts`], the whole batch is proposed one by one.
+    #[inline]
+    async fn propose_batch(
+        &self,
+        cmds: &[Self::Cmd],
+        token: Option<&String>,
+        use_fast_path: bool,
+    ) -> Vec<Result<ProposeResponse<Self::Cmd>, Self::Error>>
+    where
+        Self::Error: Send,
+    {
+        let groups = if self.batches_by_conflicts() {
+            conflict_groups(cmds)
+        } else {
+            vec![cmds.iter().enumerate().collect()]
+        };
+        let proposes = groups.into_iter().map(|group| async move {
+            let mut results = Vec::with_capacity(group.len());
+            for (idx, cmd) in group {
+                results.push((idx, self.propose(cmd, token, use_fast_path).await));
+            }
+            results
+        });
+        let mut results: Vec<_> = join_all(proposes).await.into_iter().flatten().collect();
+        results.sort_by_key(|&(idx, _)| idx);
+        results.into_iter().map(|(_idx, res)| res).collect()
+    }
+
     /// Send propose configuration changes to the cluster
     async fn propose_conf_change(
         &self,
         changes: Vec<ConfChange>,
     ) -> Result<Vec<Member>, Self::Error>;
 
+    /// Send propose configuration changes to the cluster only if its cluster version is
+    /// still `expected_cluster_version`, return `WrongClusterVersion` without retrying if
+    /// the cluster has been reconfigured since then
+    async fn propose_conf_change_if_version(
+        &self,
+        changes: Vec<ConfChange>,
+        expected_cluster_version: u64,
+    ) -> Result<Vec<Member>, Self::Error>;
+
     /// Send propose to shutdown cluster
     async fn propose_shutdown(&self) -> Result<(), Self::Error>;
 
@@ -100,6 +297,14 @@ pub trait ClientApi {
     /// Note: The fetched cluster may still be outdated if `linearizable` is false
     async fn fetch_cluster(&self, linearizable: bool) -> Result<FetchClusterResponse, Self::Error>;
 
+    /// Fetch the cluster version, which is increased by every configuration change
+    #[inline]
+    async fn fetch_cluster_version(&self) -> Result<u64, Self::Error> {
+        self.fetch_cluster(true)
+            .await
+            .map(|resp| resp.cluster_version)
+    }
+
     /// Fetch leader id
     #[inline]
     async fn fetch_leader_id(&self, linearizable: bool) -> Result<ServerId, Self::Error> {
@@ -117,6 +322,130 @@ pub trait ClientApi {
         // fallback to linearizable fetch
         self.fetch_leader_id(true).await
     }
+
+    /// Wait until a stable leader is elected, the leader is stable after it is observed
+    /// with the same term by consecutive fetches. Return `None` if no stable leader is
+    /// elected before the timeout.
+    #[inline]
+    async fn wait_for_leader(&self, timeout: Duration) -> Option<ServerId> {
+        /// Interval between two fetches
+        const FETCH_INTERVAL: Duration = Duration::from_millis(100);
+        /// Number of consecutive fetches that should observe the same leader
+        const STABLE_OBSERVATIONS: usize = 2;
+
+        let wait = async {
+            let mut last_leader = None;
+            let mut observations: usize = 0;
+            loop {
+                // errors are expected before the cluster is ready, just fetch again
+                if let Ok(resp) = self.fetch_cluster(false).await {
+                    match resp.leader_id.map(|id| (id, resp.term)) {
+                        Some(leader) if last_leader == Some(leader) => {
+                            observations = observations.saturating_add(1);
+                        }
+                        leader => {
+                            last_leader = leader;
+                            observations = usize::from(leader.is_some());
+                        }
+                    }
+                }
+                if observations >= STABLE_OBSERVATIONS {
+                    if let Some((id, _term)) = last_leader {
+                        return id;
+                    }
+                }
+                tokio::time::sleep(FETCH_INTERVAL).await;
+            }
+        };
+        tokio::time::timeout(timeout, wait).await.ok()
+    }
+
+    /// Wait until the client is ready to serve, i.e. at least the number of servers set by
+    /// [`ClientBuilder::min_ready_connects`] are reachable. Orchestrators can use it to
+    /// hold traffic back until the cluster is reachable. Return `false` if the client is
+    /// not ready before the timeout.
+    #[inline]
+    async fn wait_ready(&self, timeout: Duration) -> bool {
+        /// Interval between two fetches
+        const FETCH_INTERVAL: Duration = Duration::from_millis(100);
+
+        let wait = async {
+            // a quorum of the servers is reachable once the fetch succeeds
+            while self.fetch_cluster(false).await.is_err() {
+                tokio::time::sleep(FETCH_INTERVAL).await;
+            }
+        };
+        tokio::time::timeout(timeout, wait).await.is_ok()
+    }
+
+    /// Get the cached leader id without sending any request, which may be outdated. Return
+    /// `None` if the leader is unknown.
+    #[inline]
+    async fn cached_leader_id(&self) -> Option<ServerId> {
+        None
+    }
+
+    /// Get the id of the local server of a client built with a bypassed server, `None` if the
+    /// client is not built with it
+    #[inline]
+    fn local_server_id(&self) -> Option<ServerId> {
+        None
+    }
+
+    /// Whether [`ClientApi::propose_batch`] groups a batch by conflicts, which is enabled
+    /// unless the client is built with [`ClientBuilder::batch_by_conflicts`] set to `false`
+    #[inline]
+    fn batches_by_conflicts(&self) -> bool {
+        true
+    }
+
+    /// Get the heaviest server load piggybacked on the latest propose responses, clients
+    /// can slow down when servers are falling behind. Return `None` if no load is known yet.
+    #[inline]
+    fn server_load(&self) -> Option<ServerLoad> {
+        None
+    }
+
+    /// Get the build version and features reported by the servers, operations requiring a
+    /// feature the servers do not support fail with an unsupported by server error. Return
+    /// an unreported one if no server has reported them yet.
+    #[inline]
+    fn server_features(&self) -> ServerFeatures {
+        ServerFeatures::default()
+    }
+
+    /// Get the suspicion level of a server, which rises continuously as a request to the
+    /// server stays unanswered for longer than its usual response time, so that callers can
+    /// deprioritize increasingly suspect servers smoothly instead of waiting for a binary up
+    /// or down. A `phi` of 1 means the probability of a false suspicion is about 10%, 2 means
+    /// about 1%, and so on. A server which never responds successfully, or to which no request
+    /// is pending, is not suspected.
+    #[inline]
+    fn suspicion(&self, _id: ServerId) -> f64 {
+        0.0
+    }
+
+    /// Export the traces of the latest operations, from the oldest to the latest. Return an
+    /// empty list if the client is not built with [`ClientBuilder::trace_capacity`].
+    #[inline]
+    fn export_trace(&self) -> Vec<TraceEntry<Self::Cmd>> {
+        Vec::new()
+    }
+
+    /// Record the trace of a request of the upper layer which is not sent by this client,
+    /// e.g. a watch sent on its own channel, so that it is exported along with the traces of
+    /// this client. It is ignored if the client is not built with
+    /// [`ClientBuilder::trace_capacity`].
+    #[inline]
+    fn trace_rpc(
+        &self,
+        _method: &'static str,
+        _connect: Option<ServerId>,
+        _outcome: TraceOutcome,
+        _start: SystemTime,
+        _latency: Duration,
+    ) {
+    }
 }
 
 /// This trait override some unrepeatable methods in ClientApi, and a client with this trait will be able to retry.
@@ -125,21 +454,41 @@ trait RepeatableClientApi: ClientApi {
     /// Generate a unique propose id during the retry process.
     fn gen_propose_id(&self) -> Result<ProposeId, Self::Error>;
 
+    /// Replace the client id of a propose id with the current one, which may have been
+    /// renewed since the propose id was generated
+    fn refresh_propose_id(&self, propose_id: ProposeId) -> ProposeId;
+
+    /// Register a new client id to the cluster to replace the expired one
+    async fn register_client_id(&self) -> Result<u64, Self::Error>;
+
     /// Send propose to the whole cluster, `use_fast_path` set to `false` to fallback into ordered
-    /// requests (event the requests are commutative).
+    /// requests (event the requests are commutative). Also return the server which returned
+    /// the result.
     async fn propose(
         &self,
         propose_id: ProposeId,
         cmd: &Self::Cmd,
         token: Option<&String>,
         use_fast_path: bool,
-    ) -> Result<ProposeResponse<Self::Cmd>, Self::Error>;
+    ) -> Result<(ProposeResponse<Self::Cmd>, ServerId), Self::Error>;
 
-    /// Send propose configuration changes to the cluster
+    /// Send propose to the whole cluster and return once the command is committed, the
+    /// result is delivered to the `callback` after the command is applied
+    async fn propose_with_callback(
+        &self,
+        propose_id: ProposeId,
+        cmd: &Self::Cmd,
+        token: Option<&String>,
+        callback: ProposeCallback<Self::Cmd, Self::Error>,
+    ) -> Result<(), Self::Error>;
+
+    /// Send propose configuration changes to the cluster, the changes are rejected if the
+    /// cluster version is not `cluster_version`, which defaults to the cached one
     async fn propose_conf_change(
         &self,
         propose_id: ProposeId,
         changes: Vec<ConfChange>,
+        cluster_version: Option<u64>,
     ) -> Result<Vec<Member>, Self::Error>;
 
     /// Send propose to shutdown cluster
@@ -155,11 +504,45 @@ trait RepeatableClientApi: ClientApi {
     ) -> Result<(), Self::Error>;
 }
 
+/// Group the commands by conflicts, commands conflicting with each other, directly or
+/// through other commands, are in the same group. Commands keep their order in each group,
+/// together with their indexes in `cmds`.
+fn conflict_groups<C: Command>(cmds: &[C]) -> Vec<Vec<(usize, &C)>> {
+    let mut groups: Vec<Vec<(usize, &C)>> = Vec::new();
+    for (idx, cmd) in cmds.iter().enumerate() {
+        let (conflicted, mut others): (Vec<_>, Vec<_>) = groups
+            .into_iter()
+            .partition(|group| group.iter().any(|&(_i, other)| cmd.is_conflict(other)));
+        let mut merged: Vec<_> = conflicted.into_iter().flatten().collect();
+        merged.sort_by_key(|&(i, _cmd)| i);
+        merged.push((idx, cmd));
+        others.push(merged);
+        groups = others;
+    }
+    groups
+}
+
 /// Update leader state
 #[async_trait]
 trait LeaderStateUpdate {
     /// update
     async fn update_leader(&self, leader_id: Option<ServerId>, term: u64) -> bool;
+
+    /// Get the cached leader, `None` if the leader is unknown
+    async fn cached_leader(&self) -> Option<ServerId>;
+
+    /// Whether the client has a connection to the server
+    async fn is_connected(&self, id: ServerId) -> bool;
+}
+
+/// A client builder with a dead-letter channel
+#[derive(Debug, Clone)]
+#[allow(clippy::module_name_repetitions)] // same as above
+pub struct ClientBuilderWithDeadLetter<C: Command> {
+    /// inner builder
+    inner: ClientBuilder,
+    /// the sender of dead-letter channel
+    dead_letter: mpsc::Sender<DeadLetter<C>>,
 }
 
 /// Client builder to build a client
@@ -178,6 +561,20 @@ pub struct ClientBuilder {
     config: ClientConfig,
     /// Client tls config
     tls_config: Option<ClientTlsConfig>,
+    /// Hook invoked on retryable errors
+    retry_hook: Option<RetryHook>,
+    /// Connection pool shared with other clients
+    connection_pool: Option<Arc<ConnectionPool>>,
+    /// Min number of reachable servers before the client is ready
+    min_ready_connects: Option<usize>,
+    /// Number of acks required by the fast path
+    fast_quorum: Option<usize>,
+    /// Number of the latest propose traces kept
+    trace_capacity: Option<usize>,
+    /// Compression of the commands of proposes
+    propose_compression: Option<ProposeCompression>,
+    /// Whether a batch of proposes is grouped by conflicts
+    batch_by_conflicts: Option<bool>,
 }
 
 /// A client builder with bypass with local server
@@ -219,6 +616,21 @@ impl ClientBuilder {
         }
     }
 
+    /// Set the dead-letter channel, proposes which exhausted all retries will be
+    /// delivered to it while the propose still returns the error.
+    /// Note that the propose waits for the channel capacity.
+    #[inline]
+    #[must_use]
+    pub fn dead_letter<C: Command>(
+        self,
+        sender: mpsc::Sender<DeadLetter<C>>,
+    ) -> ClientBuilderWithDeadLetter<C> {
+        ClientBuilderWithDeadLetter {
+            inner: self,
+            dead_letter: sender,
+        }
+    }
+
     /// Set the initial cluster version
     #[inline]
     #[must_use]
@@ -251,6 +663,84 @@ impl ClientBuilder {
         self
     }
 
+    /// Set a hook invoked with the error and the number of attempts made so far whenever a
+    /// retryable error occurs, it can be used to log, emit metrics, or override the retry
+    /// decision of the error
+    #[inline]
+    #[must_use]
+    pub fn on_retry(
+        mut self,
+        hook: Box<dyn Fn(&CurpError, usize) -> RetryDecision + Send + Sync>,
+    ) -> Self {
+        self.retry_hook = Some(RetryHook(Arc::from(hook)));
+        self
+    }
+
+    /// Set the connection pool shared with other clients of the same cluster, the client reuses
+    /// the pooled connections instead of dialing all members again
+    #[inline]
+    #[must_use]
+    pub fn connection_pool(mut self, pool: Arc<ConnectionPool>) -> Self {
+        self.connection_pool = Some(pool);
+        self
+    }
+
+    /// Set the min number of servers which should be reachable before the client is ready,
+    /// see [`ClientApi::wait_ready`]. Defaults to 1.
+    #[inline]
+    #[must_use]
+    pub fn min_ready_connects(mut self, n: usize) -> Self {
+        self.min_ready_connects = Some(n);
+        self
+    }
+
+    /// Set the number of acks the fast path requires before a proposal is committed in one
+    /// round trip. A larger fast quorum makes the fast path tolerate fewer slow replicas, in
+    /// exchange the recovery after a leader failure finds more copies of the command.
+    ///
+    /// The value must be within `[super_quorum, cluster_size]`, since a smaller fast quorum
+    /// cannot guarantee that a new leader recovers the speculatively executed commands and a
+    /// larger one can never be met. Otherwise proposes fail with `InvalidConfig` without being
+    /// sent. Defaults to the super quorum, e.g. 4 in a 5-node cluster.
+    #[inline]
+    #[must_use]
+    pub fn fast_quorum(mut self, n: usize) -> Self {
+        self.fast_quorum = Some(n);
+        self
+    }
+
+    /// Set whether [`ClientApi::propose_batch`] groups a batch by conflicts. If enabled,
+    /// commands not conflicting with each other are proposed concurrently, each of them may
+    /// take the fast path, while conflicting ones are proposed one by one in their order.
+    /// Otherwise the whole batch is proposed one by one. Enabled by default.
+    #[inline]
+    #[must_use]
+    pub fn batch_by_conflicts(mut self, enabled: bool) -> Self {
+        self.batch_by_conflicts = Some(enabled);
+        self
+    }
+
+    /// Record the traces of the latest `capacity` proposes in a ring buffer, which can be
+    /// exported by [`ClientApi::export_trace`] to reproduce production issues. Tracing is
+    /// disabled by default.
+    #[inline]
+    #[must_use]
+    pub fn trace_capacity(mut self, capacity: usize) -> Self {
+        self.trace_capacity = Some(capacity);
+        self
+    }
+
+    /// Compress the commands of proposes larger than the threshold of `compression`, so
+    /// that large commands take less bandwidth while small ones avoid the overhead. Commands
+    /// are sent uncompressed until the servers report the support of the compression.
+    /// Compression is disabled by default.
+    #[inline]
+    #[must_use]
+    pub fn propose_compression(mut self, compression: ProposeCompression) -> Self {
+        self.propose_compression = Some(compression);
+        self
+    }
+
     /// Discover the initial states from some endpoints
     ///
     /// # Errors
@@ -315,12 +805,15 @@ impl ClientBuilder {
             builder.set_leader_state(id, term);
         }
         builder.set_is_raw_curp(self.is_raw_curp);
+        if let Some(ref pool) = self.connection_pool {
+            builder.set_connection_pool(Arc::clone(pool));
+        }
         builder
     }
 
     /// Init retry config
     fn init_retry_config(&self) -> RetryConfig {
-        if *self.config.fixed_backoff() {
+        let config = if *self.config.fixed_backoff() {
             RetryConfig::new_fixed(
                 *self.config.initial_retry_timeout(),
                 *self.config.retry_count(),
@@ -331,15 +824,35 @@ impl ClientBuilder {
                 *self.config.max_retry_timeout(),
                 *self.config.retry_count(),
             )
+        };
+        match self.retry_hook.clone() {
+            Some(hook) => config.with_hook(hook),
+            None => config,
         }
     }
 
     /// Init unary config
     fn init_unary_config(&self) -> UnaryConfig {
-        UnaryConfig::new(
+        let config = UnaryConfig::new(
             *self.config.propose_timeout(),
             *self.config.wait_synced_timeout(),
-        )
+        );
+        let config = match self.min_ready_connects {
+            Some(n) => config.with_min_ready_connects(n),
+            None => config,
+        };
+        let config = match self.fast_quorum {
+            Some(n) => config.with_fast_quorum(n),
+            None => config,
+        };
+        let config = match self.propose_compression {
+            Some(compression) => config.with_propose_compression(compression),
+            None => config,
+        };
+        match self.batch_by_conflicts {
+            Some(enabled) => config.with_batch_by_conflicts(enabled),
+            None => config,
+        }
     }
 
     /// Spawn background tasks for the client
@@ -364,13 +877,45 @@ impl ClientBuilder {
         impl ClientApi<Error = tonic::Status, Cmd = C> + Send + Sync + 'static,
         tonic::transport::Error,
     > {
+        self.build_with_dead_letter(None).await
+    }
+
+    /// Build the client, the proposes whose retries are exhausted are delivered to
+    /// `dead_letter` if it is set
+    async fn build_with_dead_letter<C: Command>(
+        &self,
+        dead_letter: Option<mpsc::Sender<DeadLetter<C>>>,
+    ) -> Result<Retry<Unary<C>>, tonic::transport::Error> {
         let state = Arc::new(self.init_state_builder().build().await?);
         let client = Retry::new(
             Unary::new(Arc::clone(&state), self.init_unary_config()),
             self.init_retry_config(),
             Some(self.spawn_bg_tasks(state)),
         );
-        Ok(client)
+        let client = match dead_letter {
+            Some(dead_letter) => client.with_dead_letter(dead_letter),
+            None => client,
+        };
+        Ok(client.with_trace_capacity(self.trace_capacity))
+    }
+}
+
+impl<C: Command> ClientBuilderWithDeadLetter<C> {
+    /// Build the client with the dead-letter channel
+    ///
+    /// # Errors
+    ///
+    /// Return `tonic::transport::Error` for connection failure.
+    #[inline]
+    pub async fn build(
+        self,
+    ) -> Result<
+        impl ClientApi<Error = tonic::Status, Cmd = C> + Send + Sync + 'static,
+        tonic::transport::Error,
+    > {
+        self.inner
+            .build_with_dead_letter(Some(self.dead_letter))
+            .await
     }
 }
 
@@ -394,7 +939,8 @@ impl<P: Protocol> ClientBuilderWithBypass<P> {
             Unary::new(Arc::clone(&state), self.inner.init_unary_config()),
             self.inner.init_retry_config(),
             Some(self.inner.spawn_bg_tasks(state)),
-        );
+        )
+        .with_trace_capacity(self.inner.trace_capacity);
         Ok(client)
     }
 }
@@ -0,0 +1,111 @@
+use std::{
+    collections::HashMap,
+    fmt::Debug,
+    sync::{Arc, Weak},
+};
+
+use parking_lot::Mutex;
+#[cfg(not(madsim))]
+use tonic::transport::ClientTlsConfig;
+use tracing::debug;
+#[cfg(madsim)]
+use utils::ClientTlsConfig;
+
+use crate::{
+    members::ServerId,
+    rpc::{self, connect::ConnectApi},
+};
+
+/// The key of a pooled connection, which is the server id and the sorted addresses, so that
+/// a server is connected again once its addresses change
+type PoolKey = (ServerId, Vec<String>);
+
+/// A pool of connections which could be shared across multiple clients of the same cluster.
+///
+/// The pool only holds weak references to the connections, a connection is reused by every
+/// client built from the pool and closed after the last client holding it is dropped.
+#[derive(Default)]
+pub struct ConnectionPool {
+    /// Connections of members
+    connects: Mutex<HashMap<PoolKey, Weak<dyn ConnectApi>>>,
+}
+
+impl Debug for ConnectionPool {
+    #[inline]
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ConnectionPool")
+            .field("connects", &self.len())
+            .finish()
+    }
+}
+
+impl ConnectionPool {
+    /// Create an empty connection pool
+    #[inline]
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Get the number of alive connections in the pool
+    #[inline]
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.connects
+            .lock()
+            .values()
+            .filter(|conn| conn.strong_count() > 0)
+            .count()
+    }
+
+    /// Return true if there are no alive connections in the pool
+    #[inline]
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Get the alive connection of the key
+    fn get(&self, key: &PoolKey) -> Option<Arc<dyn ConnectApi>> {
+        self.connects.lock().get(key).and_then(Weak::upgrade)
+    }
+
+    /// Get the connection of the server from the pool, or connect to it if there is no alive one
+    pub(super) async fn connect(
+        &self,
+        id: ServerId,
+        addrs: Vec<String>,
+        tls_config: Option<ClientTlsConfig>,
+    ) -> Result<Arc<dyn ConnectApi>, tonic::transport::Error> {
+        let mut sorted_addrs = addrs.clone();
+        sorted_addrs.sort();
+        let key = (id, sorted_addrs);
+        if let Some(conn) = self.get(&key) {
+            debug!("reuse the pooled connection of server({id})");
+            return Ok(conn);
+        }
+        let new_conn = rpc::connect(id, addrs, tls_config).await?;
+        let mut connects = self.connects.lock();
+        // another client may have connected to the server in the meantime
+        if let Some(conn) = connects.get(&key).and_then(Weak::upgrade) {
+            return Ok(conn);
+        }
+        let _prev = connects.insert(key, Arc::downgrade(&new_conn));
+        connects.retain(|_, conn| conn.strong_count() > 0);
+        Ok(new_conn)
+    }
+
+    /// Get the connections of all members
+    pub(super) async fn connects(
+        &self,
+        members: HashMap<ServerId, Vec<String>>,
+        tls_config: Option<&ClientTlsConfig>,
+    ) -> Result<HashMap<ServerId, Arc<dyn ConnectApi>>, tonic::transport::Error> {
+        let mut connects = HashMap::with_capacity(members.len());
+        for (id, addrs) in members {
+            let conn = self.connect(id, addrs, tls_config.cloned()).await?;
+            let _ig = connects.insert(id, conn);
+        }
+        Ok(connects)
+    }
+}
@@ -1,16 +1,30 @@
-use std::{ops::SubAssign, time::Duration};
+use std::{
+    ops::{AddAssign, SubAssign},
+    time::{Duration, Instant, SystemTime},
+};
 
 use async_trait::async_trait;
 use futures::Future;
-use tokio::task::JoinHandle;
-use tracing::warn;
+use tokio::{sync::mpsc, task::JoinHandle};
+use tracing::{debug, warn};
 
-use super::{ClientApi, LeaderStateUpdate, ProposeResponse, RepeatableClientApi};
+use super::{
+    trace::Tracer, ClientApi, DeadLetter, LeaderStateUpdate, ProposeCallback, ProposeOutcome,
+    ProposeResponse, RepeatableClientApi, RetryDecision, RetryHook, TraceEntry, TraceOp,
+    TraceOutcome,
+};
 use crate::{
     members::ServerId,
-    rpc::{ConfChange, CurpError, FetchClusterResponse, Member, ReadState, Redirect},
+    rpc::{
+        ConfChange, CurpError, FetchClusterResponse, InternalErrorKind, Member, ProposeId,
+        ReadState, Redirect, ServerFeatures, ServerLoad,
+    },
 };
 
+/// Max delay of the backoff after rate limited errors, it never goes below the delay of the
+/// retry policy
+const RATE_LIMITED_MAX_DELAY: Duration = Duration::from_secs(5);
+
 /// Backoff config
 #[derive(Debug, Clone)]
 enum BackoffConfig {
@@ -32,6 +46,8 @@ pub(super) struct RetryConfig {
     delay: Duration,
     /// Retry count
     count: usize,
+    /// Hook to override the retry decision
+    hook: Option<RetryHook>,
 }
 
 /// Backoff tool
@@ -53,6 +69,7 @@ impl RetryConfig {
             backoff: BackoffConfig::Fixed,
             delay,
             count,
+            hook: None,
         }
     }
 
@@ -63,6 +80,24 @@ impl RetryConfig {
             backoff: BackoffConfig::Exponential { max_delay },
             delay,
             count,
+            hook: None,
+        }
+    }
+
+    /// Set the retry hook
+    pub(super) fn with_hook(self, hook: RetryHook) -> Self {
+        Self {
+            hook: Some(hook),
+            ..self
+        }
+    }
+
+    /// Decide the delay before the next attempt, `None` means giving up
+    fn decide(&self, err: &CurpError, attempts: usize, delay: Duration) -> Option<Duration> {
+        match self.hook.as_ref().map(|hook| hook.decide(err, attempts)) {
+            None | Some(RetryDecision::Default) => Some(delay),
+            Some(RetryDecision::GiveUp) => None,
+            Some(RetryDecision::RetryWithDelay(delay)) => Some(delay),
         }
     }
 
@@ -98,16 +133,20 @@ impl Backoff {
 /// The retry client automatically retry the requests of the inner client api
 /// which raises the [`tonic::Status`] error
 #[derive(Debug)]
-pub(super) struct Retry<Api> {
+pub(super) struct Retry<Api: ClientApi> {
     /// Inner client
     inner: Api,
     /// Retry config
     config: RetryConfig,
     /// Background task handle
     bg_handle: Option<JoinHandle<()>>,
+    /// Dead-letter channel of proposes which exhausted all retries
+    dead_letter: Option<mpsc::Sender<DeadLetter<Api::Cmd>>>,
+    /// Traces of the latest proposes
+    tracer: Option<Tracer<Api::Cmd>>,
 }
 
-impl<Api> Drop for Retry<Api> {
+impl<Api: ClientApi> Drop for Retry<Api> {
     fn drop(&mut self) {
         if let Some(handle) = self.bg_handle.as_ref() {
             handle.abort();
@@ -125,19 +164,105 @@ where
             inner,
             config,
             bg_handle,
+            dead_letter: None,
+            tracer: None,
+        }
+    }
+
+    /// Set the dead-letter channel
+    pub(super) fn with_dead_letter(self, dead_letter: mpsc::Sender<DeadLetter<Api::Cmd>>) -> Self {
+        Self {
+            dead_letter: Some(dead_letter),
+            ..self
+        }
+    }
+
+    /// Keep the traces of the latest `capacity` proposes, `None` disables tracing
+    pub(super) fn with_trace_capacity(self, capacity: Option<usize>) -> Self {
+        Self {
+            tracer: capacity.map(Tracer::new),
+            ..self
+        }
+    }
+
+    /// Record the trace of a completed operation
+    fn trace(
+        &self,
+        op: impl FnOnce() -> TraceOp<Api::Cmd>,
+        connect: Option<ServerId>,
+        outcome: TraceOutcome,
+        attempts: usize,
+        start: (SystemTime, Instant),
+    ) {
+        let Some(tracer) = self.tracer.as_ref() else {
+            return;
+        };
+        tracer.record(TraceEntry {
+            op: op(),
+            connect,
+            outcome,
+            attempts,
+            start: start.0,
+            latency: start.1.elapsed(),
+        });
+    }
+
+    /// Deliver the command, whose retries were exhausted, to the dead-letter channel
+    async fn send_dead_letter(&self, cmd: &Api::Cmd, err: &tonic::Status) {
+        let Some(dead_letter) = self.dead_letter.as_ref() else {
+            return;
+        };
+        let letter = DeadLetter {
+            cmd: cmd.clone(),
+            err: err.clone(),
+        };
+        if dead_letter.send(letter).await.is_err() {
+            warn!("dead-letter channel is closed, the failed propose is dropped");
+        }
+    }
+
+    /// Handle a redirect before the retry. If the redirect target is a known server, it
+    /// becomes the cached leader so that the retry goes to it directly. Otherwise, the target
+    /// is unknown or not connected yet, the leader is rediscovered from the cluster.
+    async fn handle_redirect(&self, leader_id: Option<ServerId>, term: u64) {
+        if let Some(id) = leader_id {
+            if self.inner.is_connected(id).await {
+                let _ig = self.inner.update_leader(Some(id), term).await;
+                return;
+            }
+        }
+        debug!("redirect to an unknown leader {leader_id:?}, rediscover the leader");
+        if let Err(e) = self.inner.fetch_cluster(true).await {
+            warn!("fetch cluster failed, error {e:?}");
         }
     }
 
     /// Takes a function f and run retry.
     async fn retry<'a, R, F>(&'a self, f: impl Fn(&'a Api) -> F) -> Result<R, tonic::Status>
+    where
+        F: Future<Output = Result<R, CurpError>>,
+    {
+        self.retry_counted(f).await.0
+    }
+
+    /// Takes a function f and run retry, returns the result, how many attempts were made and
+    /// whether the retries were exhausted, as opposed to an error returned without retry.
+    async fn retry_counted<'a, R, F>(
+        &'a self,
+        f: impl Fn(&'a Api) -> F,
+    ) -> (Result<R, tonic::Status>, usize, bool)
     where
         F: Future<Output = Result<R, CurpError>>,
     {
         let mut backoff = self.config.init_backoff();
         let mut last_err = None;
-        while let Some(delay) = backoff.next_delay() {
+        let mut attempts: usize = 0;
+        let mut client_id_renewed = false;
+        let mut rate_limited_delay: Option<Duration> = None;
+        while let Some(mut delay) = backoff.next_delay() {
+            attempts.add_assign(1);
             let err = match f(&self.inner).await {
-                Ok(res) => return Ok(res),
+                Ok(res) => return (Ok(res), attempts, false),
                 Err(err) => err,
             };
 
@@ -149,12 +274,40 @@ where
                 | CurpError::NodeNotExists(_)
                 | CurpError::NodeAlreadyExists(_)
                 | CurpError::LearnerNotCatchUp(_) => {
-                    return Err(tonic::Status::from(err));
+                    return (Err(tonic::Status::from(err)), attempts, false);
+                }
+                CurpError::Internal(_)
+                    if err.is_unsupported_by_server()
+                        || err.internal_kind() == Some(InternalErrorKind::DeadlineExceeded) =>
+                {
+                    return (Err(tonic::Status::from(err)), attempts, false);
+                }
+
+                // only back off, the delay doubles on every rejection no matter the backoff
+                // policy, so that a rate limited client does not keep hitting the server
+                CurpError::Internal(_)
+                    if err.internal_kind() == Some(InternalErrorKind::RateLimited) =>
+                {
+                    let next = rate_limited_delay
+                        .map_or(delay, |prev| prev.saturating_mul(2))
+                        .clamp(delay, RATE_LIMITED_MAX_DELAY.max(delay));
+                    rate_limited_delay = Some(next);
+                    delay = next;
+                }
+
+                // register a new client id and retry once
+                CurpError::ExpiredClientId(_) => {
+                    if client_id_renewed {
+                        return (Err(tonic::Status::from(err)), attempts, false);
+                    }
+                    client_id_renewed = true;
+                    if let Err(e) = self.inner.register_client_id().await {
+                        warn!("register client id failed, error {e:?}");
+                    }
                 }
 
                 // some errors that could have a retry
-                CurpError::ExpiredClientId(_)
-                | CurpError::KeyConflict(_)
+                CurpError::KeyConflict(_)
                 | CurpError::Internal(_)
                 | CurpError::LeaderTransfer(_) => {}
 
@@ -173,12 +326,17 @@ where
                     }
                 }
 
-                // update the leader state if got Redirect
+                // follow the redirect or rediscover the leader
                 CurpError::Redirect(Redirect { leader_id, term }) => {
-                    let _ig = self.inner.update_leader(leader_id, term).await;
+                    self.handle_redirect(leader_id, term).await;
                 }
             }
 
+            let Some(delay) = self.config.decide(&err, attempts, delay) else {
+                warn!("got error: {err:?}, the retry hook gives up after {attempts} attempts");
+                return (Err(tonic::Status::from(err)), attempts, false);
+            };
+
             #[cfg(feature = "client-metrics")]
             super::metrics::get().client_retry_count.add(1, &[]);
 
@@ -190,10 +348,11 @@ where
             tokio::time::sleep(delay).await;
         }
 
-        Err(tonic::Status::deadline_exceeded(format!(
+        let err = tonic::Status::deadline_exceeded(format!(
             "request timeout, last error: {:?}",
             last_err.unwrap_or_else(|| unreachable!("last error must be set"))
-        )))
+        ));
+        (Err(err), attempts, true)
     }
 }
 
@@ -216,11 +375,70 @@ where
         token: Option<&String>,
         use_fast_path: bool,
     ) -> Result<ProposeResponse<Self::Cmd>, tonic::Status> {
+        self.propose_with_outcome(cmd, token, use_fast_path)
+            .await
+            .map(|outcome| outcome.result)
+    }
+
+    /// Send propose like [`ClientApi::propose`], and report how many attempts were made
+    /// and how long it took in total
+    async fn propose_with_outcome(
+        &self,
+        cmd: &Self::Cmd,
+        token: Option<&String>,
+        use_fast_path: bool,
+    ) -> Result<ProposeOutcome<Self::Cmd>, tonic::Status> {
+        let start = (SystemTime::now(), Instant::now());
         let propose_id = self.inner.gen_propose_id()?;
-        self.retry::<_, _>(|client| {
-            RepeatableClientApi::propose(client, propose_id, cmd, token, use_fast_path)
+        let (res, attempts, exhausted) = self
+            .retry_counted::<_, _>(|client| {
+                RepeatableClientApi::propose(
+                    client,
+                    client.refresh_propose_id(propose_id),
+                    cmd,
+                    token,
+                    use_fast_path,
+                )
+            })
+            .await;
+        let (connect, outcome) = match res {
+            Ok((Ok(_), id)) => (Some(id), TraceOutcome::Succeeded),
+            Ok((Err(ref err), id)) => (Some(id), TraceOutcome::CommandError(err.to_string())),
+            Err(ref err) => {
+                if exhausted {
+                    self.send_dead_letter(cmd, err).await;
+                }
+                (None, TraceOutcome::Failed(err.clone()))
+            }
+        };
+        let op = || TraceOp::Propose {
+            cmd: cmd.clone(),
+            use_fast_path,
+        };
+        self.trace(op, connect, outcome, attempts, start);
+        let (result, _id) = res?;
+        Ok(ProposeOutcome {
+            result,
+            attempts,
+            total_latency: start.1.elapsed(),
         })
-        .await
+    }
+
+    /// Send propose and deliver the result to the callback after the command is applied
+    ///
+    /// The callback could only be consumed once, so the propose is not retried.
+    async fn propose_with_callback(
+        &self,
+        cmd: &Self::Cmd,
+        token: Option<&String>,
+        callback: ProposeCallback<Self::Cmd, tonic::Status>,
+    ) -> Result<ProposeId, tonic::Status> {
+        let propose_id = self.inner.gen_propose_id()?;
+        let callback: ProposeCallback<Self::Cmd, CurpError> =
+            Box::new(move |res| callback(res.map_err(tonic::Status::from)));
+        RepeatableClientApi::propose_with_callback(&self.inner, propose_id, cmd, token, callback)
+            .await?;
+        Ok(propose_id)
     }
 
     /// Send propose configuration changes to the cluster
@@ -231,16 +449,53 @@ where
         let propose_id = self.inner.gen_propose_id()?;
         self.retry::<_, _>(|client| {
             let changes_c = changes.clone();
-            RepeatableClientApi::propose_conf_change(client, propose_id, changes_c)
+            RepeatableClientApi::propose_conf_change(
+                client,
+                client.refresh_propose_id(propose_id),
+                changes_c,
+                None,
+            )
         })
         .await
     }
 
+    /// Send propose configuration changes to the cluster if the cluster version matches
+    async fn propose_conf_change_if_version(
+        &self,
+        changes: Vec<ConfChange>,
+        expected_cluster_version: u64,
+    ) -> Result<Vec<Member>, tonic::Status> {
+        let propose_id = self.inner.gen_propose_id()?;
+        let res = self
+            .retry::<_, _>(|client| {
+                let changes_c = changes.clone();
+                async move {
+                    let res = RepeatableClientApi::propose_conf_change(
+                        client,
+                        client.refresh_propose_id(propose_id),
+                        changes_c,
+                        Some(expected_cluster_version),
+                    )
+                    .await;
+                    // a changed cluster version should be returned instead of
+                    // being refreshed and retried
+                    match res {
+                        Err(err @ CurpError::WrongClusterVersion(_)) => Ok(Err(err)),
+                        res => res.map(Ok),
+                    }
+                }
+            })
+            .await?;
+        res.map_err(tonic::Status::from)
+    }
+
     /// Send propose to shutdown cluster
     async fn propose_shutdown(&self) -> Result<(), tonic::Status> {
         let propose_id = self.inner.gen_propose_id()?;
-        self.retry::<_, _>(|client| RepeatableClientApi::propose_shutdown(client, propose_id))
-            .await
+        self.retry::<_, _>(|client| {
+            RepeatableClientApi::propose_shutdown(client, client.refresh_propose_id(propose_id))
+        })
+        .await
     }
 
     /// Send propose to publish a node id and name
@@ -256,7 +511,7 @@ where
             let node_client_urls_c = node_client_urls.clone();
             RepeatableClientApi::propose_publish(
                 client,
-                propose_id,
+                client.refresh_propose_id(propose_id),
                 node_id,
                 name_c,
                 node_client_urls_c,
@@ -284,8 +539,79 @@ where
         &self,
         linearizable: bool,
     ) -> Result<FetchClusterResponse, tonic::Status> {
-        self.retry::<_, _>(|client| client.fetch_cluster(linearizable))
-            .await
+        let start = (SystemTime::now(), Instant::now());
+        let (res, attempts, _exhausted) = self
+            .retry_counted::<_, _>(|client| client.fetch_cluster(linearizable))
+            .await;
+        let outcome = match res {
+            Ok(_) => TraceOutcome::Succeeded,
+            Err(ref err) => TraceOutcome::Failed(err.clone()),
+        };
+        // the requests go to every server
+        let op = || TraceOp::FetchCluster { linearizable };
+        self.trace(op, None, outcome, attempts, start);
+        res
+    }
+
+    /// Wait until enough servers are reachable
+    async fn wait_ready(&self, timeout: Duration) -> bool {
+        self.inner.wait_ready(timeout).await
+    }
+
+    /// Get the cached leader id
+    async fn cached_leader_id(&self) -> Option<ServerId> {
+        self.inner.cached_leader().await
+    }
+
+    /// Get the id of the local server
+    fn local_server_id(&self) -> Option<ServerId> {
+        self.inner.local_server_id()
+    }
+
+    /// Whether a batch of proposes is grouped by conflicts
+    fn batches_by_conflicts(&self) -> bool {
+        self.inner.batches_by_conflicts()
+    }
+
+    /// Get the heaviest server load piggybacked on the latest propose responses
+    fn server_load(&self) -> Option<ServerLoad> {
+        self.inner.server_load()
+    }
+
+    /// Get the version and features reported by the servers
+    fn server_features(&self) -> ServerFeatures {
+        self.inner.server_features()
+    }
+
+    /// Get the suspicion level of the server
+    fn suspicion(&self, id: ServerId) -> f64 {
+        self.inner.suspicion(id)
+    }
+
+    /// Export the traces of the latest operations
+    fn export_trace(&self) -> Vec<TraceEntry<Self::Cmd>> {
+        self.tracer.as_ref().map_or_else(Vec::new, Tracer::export)
+    }
+
+    /// Record the trace of a request of the upper layer
+    fn trace_rpc(
+        &self,
+        method: &'static str,
+        connect: Option<ServerId>,
+        outcome: TraceOutcome,
+        start: SystemTime,
+        latency: Duration,
+    ) {
+        if let Some(tracer) = self.tracer.as_ref() {
+            tracer.record(TraceEntry {
+                op: TraceOp::Rpc(method),
+                connect,
+                outcome,
+                attempts: 1,
+                start,
+                latency,
+            });
+        }
     }
 }
 
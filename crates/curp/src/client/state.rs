@@ -13,6 +13,7 @@ use tracing::{debug, info};
 #[cfg(madsim)]
 use utils::ClientTlsConfig;
 
+use super::pool::ConnectionPool;
 use crate::{
     members::ServerId,
     rpc::{
@@ -44,6 +45,8 @@ struct StateStatic {
     leader_notifier: Arc<Event>,
     /// Client tls config
     tls_config: Option<ClientTlsConfig>,
+    /// The shared connection pool
+    pool: Option<Arc<ConnectionPool>>,
 }
 
 /// Mutable client state
@@ -92,6 +95,7 @@ impl State {
                 leader_notifier: Arc::new(Event::new()),
                 tls_config,
                 is_raw_curp: true,
+                pool: None,
             },
             client_id: Arc::new(AtomicU64::new(0)),
         })
@@ -112,6 +116,12 @@ impl State {
         self.client_id.load(std::sync::atomic::Ordering::Relaxed)
     }
 
+    /// Replace the client id
+    pub(super) fn set_client_id(&self, id: u64) {
+        self.client_id
+            .store(id, std::sync::atomic::Ordering::Relaxed);
+    }
+
     /// Generate client id if it does not exist when it is the leader
     pub(crate) async fn check_gen_local_client_id(&self) {
         let local_server_id = self.immutable.local_server;
@@ -148,6 +158,16 @@ impl State {
         self.mutable.read().await.leader
     }
 
+    /// Get the id of the local server which is bypassed
+    pub(super) fn local_server(&self) -> Option<ServerId> {
+        self.immutable.local_server
+    }
+
+    /// Whether the server is in the local connects
+    pub(super) async fn contains_server(&self, id: ServerId) -> bool {
+        self.mutable.read().await.connects.contains_key(&id)
+    }
+
     /// Take an async function and map to the dedicated server, return `Err(CurpError:WrongClusterVersion(()))`
     /// if the server can not found in local state
     pub(super) async fn map_server<R, F: Future<Output = Result<R, CurpError>>>(
@@ -273,7 +293,11 @@ impl State {
                     .remove(&diff)
                     .unwrap_or_else(|| unreachable!("{diff} must in new member addrs"));
                 debug!("client connects to a new server({diff}), address({addrs:?})");
-                let new_conn = rpc::connect(diff, addrs, self.immutable.tls_config.clone()).await?;
+                let tls_config = self.immutable.tls_config.clone();
+                let new_conn = match self.immutable.pool {
+                    Some(ref pool) => pool.connect(diff, addrs, tls_config).await?,
+                    None => rpc::connect(diff, addrs, tls_config).await?,
+                };
                 let _ig = e.insert(new_conn);
             } else {
                 debug!("client removes old server({diff})");
@@ -308,6 +332,8 @@ pub(super) struct StateBuilder {
     tls_config: Option<ClientTlsConfig>,
     /// is current client send request to raw curp server
     is_raw_curp: bool,
+    /// The shared connection pool (optional)
+    pool: Option<Arc<ConnectionPool>>,
 }
 
 impl StateBuilder {
@@ -322,6 +348,7 @@ impl StateBuilder {
             cluster_version: None,
             tls_config,
             is_raw_curp: false,
+            pool: None,
         }
     }
 
@@ -340,6 +367,23 @@ impl StateBuilder {
         self.cluster_version = Some(cluster_version);
     }
 
+    /// Set the shared connection pool (optional)
+    pub(super) fn set_connection_pool(&mut self, pool: Arc<ConnectionPool>) {
+        self.pool = Some(pool);
+    }
+
+    /// Connect to all members, reuse the pooled connections if there is a pool
+    async fn connects(
+        &self,
+    ) -> Result<HashMap<ServerId, Arc<dyn ConnectApi>>, tonic::transport::Error> {
+        let members = self.all_members.clone();
+        let tls_config = self.tls_config.as_ref();
+        match self.pool {
+            Some(ref pool) => pool.connects(members, tls_config).await,
+            None => Ok(rpc::connects(members, tls_config).await?.collect()),
+        }
+    }
+
     /// Build the state with local server
     pub(super) async fn build_bypassed<P: Protocol>(
         mut self,
@@ -349,10 +393,7 @@ impl StateBuilder {
         debug!("client bypassed server({local_server_id})");
 
         let _ig = self.all_members.remove(&local_server_id);
-        let mut connects: HashMap<_, _> =
-            rpc::connects(self.all_members.clone(), self.tls_config.as_ref())
-                .await?
-                .collect();
+        let mut connects = self.connects().await?;
         let __ig = connects.insert(
             local_server_id,
             Arc::new(BypassedConnect::new(local_server_id, local_server)),
@@ -370,6 +411,7 @@ impl StateBuilder {
                 leader_notifier: Arc::new(Event::new()),
                 tls_config: self.tls_config.take(),
                 is_raw_curp: self.is_raw_curp,
+                pool: self.pool.take(),
             },
             client_id: Arc::new(AtomicU64::new(0)),
         })
@@ -377,10 +419,7 @@ impl StateBuilder {
 
     /// Build the state
     pub(super) async fn build(self) -> Result<State, tonic::transport::Error> {
-        let connects: HashMap<_, _> =
-            rpc::connects(self.all_members.clone(), self.tls_config.as_ref())
-                .await?
-                .collect();
+        let connects = self.connects().await?;
         Ok(State {
             mutable: RwLock::new(StateMut {
                 leader: self.leader_state.map(|state| state.0),
@@ -393,6 +432,7 @@ impl StateBuilder {
                 leader_notifier: Arc::new(Event::new()),
                 tls_config: self.tls_config,
                 is_raw_curp: self.is_raw_curp,
+                pool: self.pool,
             },
             client_id: Arc::new(AtomicU64::new(0)),
         })
@@ -2,14 +2,14 @@ use std::{
     collections::HashMap,
     ops::AddAssign,
     sync::{
-        atomic::{AtomicBool, AtomicU64},
+        atomic::{AtomicBool, AtomicU64, AtomicUsize},
         Arc, Mutex,
     },
     time::Duration,
 };
 
-use curp_external_api::LogIndex;
-use curp_test_utils::test_cmd::{LogIndexResult, TestCommand, TestCommandResult};
+use curp_external_api::{cmd::PbCodec, LogIndex};
+use curp_test_utils::test_cmd::{ExecuteError, LogIndexResult, TestCommand, TestCommandResult};
 use futures::future::BoxFuture;
 use tokio::time::Instant;
 #[cfg(not(madsim))]
@@ -19,10 +19,13 @@ use tracing_test::traced_test;
 use utils::ClientTlsConfig;
 
 use super::{
+    conflict_groups,
+    pool::ConnectionPool,
     retry::{Retry, RetryConfig},
-    state::State,
+    state::{State, StateBuilder},
     stream::{Streaming, StreamingConfig},
     unary::{Unary, UnaryConfig},
+    LeaderTask, RetryDecision, RetryHook, TraceOp, TraceOutcome,
 };
 use crate::{
     client::ClientApi,
@@ -106,6 +109,67 @@ async fn test_unary_fetch_clusters_serializable() {
     );
 }
 
+#[traced_test]
+#[tokio::test]
+async fn test_wait_for_leader_returns_elected_leader() {
+    let connects = init_mocked_connects(3, |_id, conn| {
+        let mut fetched: usize = 0;
+        conn.expect_fetch_cluster()
+            .returning(move |_req, _timeout| {
+                fetched.add_assign(1);
+                // the leader is not elected in the first two fetches
+                let leader_id = (fetched > 2).then_some(0);
+                Ok(tonic::Response::new(FetchClusterResponse {
+                    leader_id,
+                    term: 1,
+                    cluster_id: 123,
+                    members: vec![
+                        Member::new(0, "S0", vec!["A0".to_owned()], [], false),
+                        Member::new(1, "S1", vec!["A1".to_owned()], [], false),
+                        Member::new(2, "S2", vec!["A2".to_owned()], [], false),
+                    ],
+                    cluster_version: 1,
+                }))
+            });
+    });
+    let unary = init_unary_client(connects, None, None, 0, 0, None);
+    let leader = unary.wait_for_leader(Duration::from_secs(5)).await;
+    assert_eq!(leader, Some(0));
+}
+
+#[traced_test]
+#[tokio::test]
+async fn test_wait_ready_waits_for_min_ready_connects() {
+    let second_up = Arc::new(AtomicBool::new(false));
+    let connects = init_mocked_connects(3, |id, conn| {
+        let second_up_c = Arc::clone(&second_up);
+        conn.expect_fetch_cluster()
+            .returning(move |_req, _timeout| {
+                let up =
+                    id == 0 || (id == 1 && second_up_c.load(std::sync::atomic::Ordering::Relaxed));
+                if !up {
+                    return Err(CurpError::RpcTransport(()));
+                }
+                Ok(tonic::Response::new(FetchClusterResponse {
+                    leader_id: None,
+                    term: 1,
+                    cluster_id: 123,
+                    members: vec![],
+                    cluster_version: 1,
+                }))
+            });
+    });
+    let state = State::new_arc(connects, None, None, 0, 0, None);
+    let unary = Unary::<TestCommand>::new(
+        state,
+        UnaryConfig::new(Duration::from_secs(1), Duration::from_secs(2)).with_min_ready_connects(2),
+    );
+    // only 1 of 3 servers is reachable
+    assert!(!unary.wait_ready(Duration::from_millis(500)).await);
+    second_up.store(true, std::sync::atomic::Ordering::Relaxed);
+    assert!(unary.wait_ready(Duration::from_millis(500)).await);
+}
+
 #[traced_test]
 #[tokio::test]
 async fn test_unary_fetch_clusters_serializable_local_first() {
@@ -198,6 +262,71 @@ async fn test_unary_fetch_clusters_linearizable() {
     );
 }
 
+#[traced_test]
+#[tokio::test]
+async fn test_unary_fetch_clusters_linearizable_leader_tie() {
+    let connects = init_mocked_connects(5, |id, conn| {
+        conn.expect_fetch_cluster()
+            .return_once(move |_req, _timeout| {
+                let members = (0..5)
+                    .map(|i| Member::new(i, format!("S{i}"), vec![format!("A{i}")], [], false))
+                    .collect();
+                // two leaders are reported in the same term
+                let leader_id = match id {
+                    0 | 1 => Some(0),
+                    2 | 3 => Some(3),
+                    4 => None,
+                    _ => unreachable!("there are only 5 nodes"),
+                };
+                Ok(tonic::Response::new(FetchClusterResponse {
+                    leader_id,
+                    term: 2,
+                    cluster_id: 123,
+                    members,
+                    cluster_version: 1,
+                }))
+            });
+    });
+    let unary = init_unary_client(connects, None, None, 0, 0, None);
+    let res = unary.fetch_cluster(true).await.unwrap_err();
+    assert_eq!(res.internal_kind(), Some(InternalErrorKind::LeaderConflict));
+    // the conflict must survive the round trip through the status
+    let status = tonic::Status::from(res);
+    assert_eq!(status.code(), tonic::Code::Aborted);
+    assert_eq!(
+        CurpError::from(status).internal_kind(),
+        Some(InternalErrorKind::LeaderConflict)
+    );
+}
+
+#[traced_test]
+#[tokio::test]
+async fn test_unary_fetch_clusters_linearizable_ignores_unknown_leaders() {
+    let connects = init_mocked_connects(5, |id, conn| {
+        conn.expect_fetch_cluster()
+            .return_once(move |_req, _timeout| {
+                let members = (0..5)
+                    .map(|i| Member::new(i, format!("S{i}"), vec![format!("A{i}")], [], false))
+                    .collect();
+                // the servers not knowing the leader of the first term do not conflict
+                let leader_id = match id {
+                    0..=2 => Some(0),
+                    _ => None,
+                };
+                Ok(tonic::Response::new(FetchClusterResponse {
+                    leader_id,
+                    term: 0,
+                    cluster_id: 123,
+                    members,
+                    cluster_version: 1,
+                }))
+            });
+    });
+    let unary = init_unary_client(connects, None, None, 0, 0, None);
+    let res = unary.fetch_cluster(true).await.unwrap();
+    assert_eq!(res.leader_id, Some(0));
+}
+
 #[traced_test]
 #[tokio::test]
 async fn test_unary_fetch_clusters_linearizable_failed() {
@@ -291,135 +420,204 @@ async fn test_unary_fast_round_works() {
 
 #[traced_test]
 #[tokio::test]
-async fn test_unary_fast_round_return_early_err() {
-    for early_err in [
-        CurpError::duplicated(),
-        CurpError::shutting_down(),
-        CurpError::invalid_config(),
-        CurpError::node_already_exists(),
-        CurpError::node_not_exist(),
-        CurpError::learner_not_catch_up(),
-        CurpError::expired_client_id(),
-        CurpError::redirect(Some(1), 0),
-    ] {
-        assert!(early_err.should_abort_fast_round());
-        // record how many times `handle_propose` was invoked.
-        let counter = Arc::new(Mutex::new(0));
-        let connects = init_mocked_connects(3, |_id, conn| {
-            let counter_c = Arc::clone(&counter);
-            let err = early_err.clone();
+async fn test_unary_fast_round_requires_fast_quorum() {
+    /// Run a fast round in a 5-node cluster where only `acks` servers accept the proposal
+    async fn fast_round_with(
+        acks: usize,
+        config: UnaryConfig,
+    ) -> Result<Result<TestCommandResult, ExecuteError>, CurpError> {
+        let connects = init_mocked_connects(5, |id, conn| {
             conn.expect_propose()
                 .return_once(move |_req, _token, _timeout| {
-                    counter_c.lock().unwrap().add_assign(1);
-                    Err(err)
+                    let resp = match id {
+                        0 => ProposeResponse::new_result::<TestCommand>(&Ok(
+                            TestCommandResult::default(),
+                        )),
+                        _ if id < acks => ProposeResponse::new_empty(),
+                        _ => return Err(CurpError::key_conflict()),
+                    };
+                    Ok(tonic::Response::new(resp))
                 });
         });
-        let unary = init_unary_client(connects, None, None, 0, 0, None);
-        let err = unary
+        let state = State::new_arc(connects, None, None, 0, 0, None);
+        let unary = Unary::<TestCommand>::new(state, config);
+        unary
             .fast_round(ProposeId(0, 0), &TestCommand::default(), None)
             .await
-            .unwrap_err();
-        assert_eq!(err, early_err);
-        assert_eq!(*counter.lock().unwrap(), 1);
+    }
+    let config = || UnaryConfig::new(Duration::from_secs(1), Duration::from_secs(2));
+
+    // the super quorum of 5 nodes is 4
+    assert!(fast_round_with(4, config()).await.is_ok());
+    assert!(fast_round_with(3, config()).await.is_err());
+    // a larger fast quorum requires all acks
+    assert!(fast_round_with(4, config().with_fast_quorum(5))
+        .await
+        .is_err());
+    assert!(fast_round_with(5, config().with_fast_quorum(5))
+        .await
+        .is_ok());
+    // a majority is below the safety bound and a fast quorum larger than the cluster can not
+    // be met, both are rejected
+    for invalid in [3, 6] {
+        assert!(matches!(
+            fast_round_with(5, config().with_fast_quorum(invalid)).await,
+            Err(CurpError::InvalidConfig(()))
+        ));
     }
 }
 
 #[traced_test]
 #[tokio::test]
-async fn test_unary_fast_round_less_quorum() {
+async fn test_unary_adopts_client_hints_from_fetch_cluster() {
     let connects = init_mocked_connects(5, |id, conn| {
+        conn.expect_fetch_cluster()
+            .return_once(move |_req, _timeout| {
+                let mut resp = tonic::Response::new(FetchClusterResponse {
+                    leader_id: Some(0),
+                    term: 2,
+                    cluster_id: 123,
+                    members: vec![
+                        Member::new(0, "S0", vec!["A0".to_owned()], [], false),
+                        Member::new(1, "S1", vec!["A1".to_owned()], [], false),
+                        Member::new(2, "S2", vec!["A2".to_owned()], [], false),
+                        Member::new(3, "S3", vec!["A3".to_owned()], [], false),
+                        Member::new(4, "S4", vec!["A4".to_owned()], [], false),
+                    ],
+                    cluster_version: 1,
+                });
+                ClientHints::new(Some(Duration::from_millis(1234)), None, Some(5))
+                    .inject(resp.metadata_mut());
+                Ok(resp)
+            });
         conn.expect_propose()
-            .return_once(move |_req, _token, _timeout| {
+            .return_once(move |_req, _token, timeout| {
+                assert_eq!(timeout, Duration::from_millis(1234));
                 let resp = match id {
                     0 => ProposeResponse::new_result::<TestCommand>(&Ok(
                         TestCommandResult::default(),
                     )),
-                    1 | 2 => ProposeResponse::new_empty(),
-                    3 | 4 => return Err(CurpError::key_conflict()),
-                    _ => unreachable!("there are only 5 nodes"),
+                    1 | 2 | 3 => ProposeResponse::new_empty(),
+                    _ => return Err(CurpError::key_conflict()),
                 };
                 Ok(tonic::Response::new(resp))
             });
     });
     let unary = init_unary_client(connects, None, None, 0, 0, None);
-    let err = unary
+    let _cluster = unary.fetch_cluster(true).await.unwrap();
+    // 4 acks reach the super quorum, but not the fast quorum recommended by the servers
+    let res = unary
         .fast_round(ProposeId(0, 0), &TestCommand::default(), None)
-        .await
-        .unwrap_err();
-    assert_eq!(err, CurpError::KeyConflict(()));
+        .await;
+    assert!(res.is_err());
 }
 
-/// FIXME: two leader
-/// TODO: fix in subsequence PR
 #[traced_test]
 #[tokio::test]
-#[should_panic]
-async fn test_unary_fast_round_with_two_leader() {
-    let connects = init_mocked_connects(5, |id, conn| {
+async fn test_unary_clears_client_hints_missing_in_the_leader_response() {
+    let fetched = Arc::new(AtomicU64::new(0));
+    let timeouts = Arc::new(Mutex::new(Vec::new()));
+    let connects = init_mocked_connects(3, |id, conn| {
+        let fetched = Arc::clone(&fetched);
+        conn.expect_fetch_cluster()
+            .returning(move |_req, _timeout| {
+                let mut resp = tonic::Response::new(FetchClusterResponse {
+                    leader_id: Some(0),
+                    term: 2,
+                    cluster_id: 123,
+                    members: vec![
+                        Member::new(0, "S0", vec!["A0".to_owned()], [], false),
+                        Member::new(1, "S1", vec!["A1".to_owned()], [], false),
+                        Member::new(2, "S2", vec!["A2".to_owned()], [], false),
+                    ],
+                    cluster_version: 1,
+                });
+                // the leader recommends a timeout in the first fetch only, while the
+                // followers keep recommending an outdated one
+                let hint = match id {
+                    0 if fetched.fetch_add(1, std::sync::atomic::Ordering::Relaxed) == 0 => {
+                        Some(Duration::from_millis(1234))
+                    }
+                    0 => None,
+                    _ => Some(Duration::from_millis(999)),
+                };
+                ClientHints::new(hint, None, None).inject(resp.metadata_mut());
+                Ok(resp)
+            });
+        let timeouts = Arc::clone(&timeouts);
         conn.expect_propose()
-            .return_once(move |_req, _token, _timeout| {
-                let resp =
-                    match id {
-                        // The execution result has been returned, indicating that server(0) has also recorded the command.
-                        0 => ProposeResponse::new_result::<TestCommand>(&Ok(
-                            TestCommandResult::new(vec![1], vec![1]),
-                        )),
-                        // imagine that server(1) is the new leader
-                        1 => ProposeResponse::new_result::<TestCommand>(&Ok(
-                            TestCommandResult::new(vec![2], vec![2]),
-                        )),
-                        2 | 3 => ProposeResponse::new_empty(),
-                        4 => return Err(CurpError::key_conflict()),
-                        _ => unreachable!("there are only 5 nodes"),
-                    };
-                Ok(tonic::Response::new(resp))
+            .returning(move |_req, _token, timeout| {
+                if id == 0 {
+                    timeouts.lock().unwrap().push(timeout);
+                }
+                Ok(tonic::Response::new(ProposeResponse::new_result::<
+                    TestCommand,
+                >(&Ok(
+                    TestCommandResult::default(),
+                ))))
             });
     });
-    // old local leader(0), term 1
-    let unary = init_unary_client(connects, None, Some(0), 1, 0, None);
-    let res = unary
-        .fast_round(ProposeId(0, 0), &TestCommand::default(), None)
-        .await
-        .unwrap()
-        .unwrap();
-    // quorum: server(0, 1, 2, 3)
-    assert_eq!(res, TestCommandResult::new(vec![2], vec![2]));
+    let unary = init_unary_client(connects, None, None, 0, 0, None);
+    for _ in 0..2 {
+        let _cluster = unary.fetch_cluster(true).await.unwrap();
+        let _res = unary
+            .fast_round(ProposeId(0, 0), &TestCommand::default(), None)
+            .await;
+    }
+    // the hint of the leader is adopted, then cleared back to the local config
+    assert_eq!(
+        *timeouts.lock().unwrap(),
+        vec![Duration::from_millis(1234), Duration::ZERO]
+    );
 }
 
-// We may encounter this scenario during leader election
 #[traced_test]
 #[tokio::test]
-async fn test_unary_fast_round_without_leader() {
-    let connects = init_mocked_connects(5, |id, conn| {
+async fn test_unary_fast_round_exposes_server_load() {
+    let connects = init_mocked_connects(3, |id, conn| {
         conn.expect_propose()
             .return_once(move |_req, _token, _timeout| {
                 let resp = match id {
-                    0 | 1 | 2 | 3 | 4 => ProposeResponse::new_empty(),
-                    _ => unreachable!("there are only 5 nodes"),
+                    0 => ProposeResponse::new_result::<TestCommand>(&Ok(
+                        TestCommandResult::default(),
+                    )),
+                    1 | 2 => ProposeResponse::new_empty(),
+                    _ => unreachable!("there are only 3 nodes"),
                 };
-                Ok(tonic::Response::new(resp))
+                let mut resp = tonic::Response::new(resp);
+                // server 2 is falling behind
+                let load = if id == 2 {
+                    ServerLoad::new(5, 1000)
+                } else {
+                    ServerLoad::new(1, 0)
+                };
+                load.inject(resp.metadata_mut());
+                Ok(resp)
             });
     });
-    // old local leader(0), term 1
-    let unary = init_unary_client(connects, None, Some(0), 1, 0, None);
+    let unary = init_unary_client(connects, None, None, 0, 0, None);
+    assert_eq!(unary.server_load(), None);
     let res = unary
         .fast_round(ProposeId(0, 0), &TestCommand::default(), None)
         .await
-        .unwrap_err();
-    // quorum: server(0, 1, 2, 3)
-    assert_eq!(res, CurpError::WrongClusterVersion(()));
+        .unwrap()
+        .unwrap();
+    assert_eq!(res, TestCommandResult::default());
+    assert_eq!(unary.server_load(), Some(ServerLoad::new(5, 1000)));
 }
 
 #[traced_test]
 #[tokio::test]
-async fn test_unary_slow_round_fetch_leader_first() {
-    let flag = Arc::new(AtomicBool::new(false));
+async fn test_unary_fast_round_compresses_only_large_commands() {
+    let small = TestCommand::default();
+    let large = TestCommand::new_put(vec![1; 1024], 1);
+    let small_encoded = small.encode();
+    let large_encoded = large.encode();
+    assert!(small_encoded.len() <= 64 && large_encoded.len() > 64);
     let connects = init_mocked_connects(3, |id, conn| {
-        let flag_c = Arc::clone(&flag);
         conn.expect_fetch_cluster()
-            .return_once(move |_req, _timeout| {
-                flag_c.store(true, std::sync::atomic::Ordering::Relaxed);
-                Ok(tonic::Response::new(FetchClusterResponse {
+            .returning(move |_req, _timeout| {
+                let mut resp = tonic::Response::new(FetchClusterResponse {
                     leader_id: Some(0),
                     term: 1,
                     cluster_id: 123,
@@ -429,50 +627,1078 @@ async fn test_unary_slow_round_fetch_leader_first() {
                         Member::new(2, "S2", vec!["A2".to_owned()], [], false),
                     ],
                     cluster_version: 1,
-                }))
+                });
+                ServerFeatures::current().inject(resp.metadata_mut());
+                Ok(resp)
             });
-        let flag_c = Arc::clone(&flag);
-        conn.expect_wait_synced()
-            .return_once(move |_req, _timeout| {
-                assert!(id == 0, "wait synced should send to leader");
-                assert!(
-                    flag_c.load(std::sync::atomic::Ordering::Relaxed),
-                    "fetch_leader should invoke first"
-                );
-                Ok(tonic::Response::new(WaitSyncedResponse::new_from_result::<
-                    TestCommand,
-                >(
-                    Ok(TestCommandResult::default()),
-                    Some(Ok(1.into())),
-                )))
+        let response = move || {
+            let resp = match id {
+                0 => ProposeResponse::new_result::<TestCommand>(&Ok(TestCommandResult::default())),
+                1 | 2 => ProposeResponse::new_empty(),
+                _ => unreachable!("there are only 3 nodes"),
+            };
+            Ok(tonic::Response::new(resp))
+        };
+        // the small command is sent as is
+        let small_encoded = small_encoded.clone();
+        conn.expect_propose()
+            .times(1)
+            .returning(move |req, _token, _timeout| {
+                assert_eq!(req.command, small_encoded);
+                response()
+            });
+        // the large one is compressed
+        let large_encoded = large_encoded.clone();
+        conn.expect_propose_compressed()
+            .times(1)
+            .returning(move |req, codec, _token, _timeout| {
+                assert_eq!(codec, CompressionCodec::Deflate);
+                assert!(req.command.len() < large_encoded.len());
+                let mut req = tonic::Request::new(req);
+                codec.inject(req.metadata_mut());
+                decompress_propose(&mut req).unwrap();
+                assert_eq!(req.get_ref().command, large_encoded);
+                response()
             });
     });
-    let unary = init_unary_client(connects, None, None, 0, 0, None);
-    let res = unary.slow_round(ProposeId(0, 0)).await.unwrap().unwrap();
-    assert_eq!(LogIndex::from(res.0), 1);
-    assert_eq!(res.1, TestCommandResult::default());
+    let state = State::new_arc(connects, None, Some(0), 1, 1, None);
+    let config = UnaryConfig::new(Duration::from_secs(0), Duration::from_secs(0))
+        .with_propose_compression(ProposeCompression::new(64, CompressionCodec::Deflate));
+    let unary = Unary::<TestCommand>::new(state, config);
+    // the servers report the support of the compression
+    let _cluster = unary.fetch_cluster(true).await.unwrap();
+
+    for cmd in [&small, &large] {
+        let res = unary
+            .fast_round(ProposeId(0, 0), cmd, None)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(res, TestCommandResult::default());
+    }
 }
 
-#[traced_test]
-#[tokio::test]
-async fn test_unary_propose_fast_path_works() {
-    let connects = init_mocked_connects(5, |id, conn| {
-        conn.expect_propose()
-            .return_once(move |_req, _token, _timeout| {
-                let resp = match id {
-                    0 => ProposeResponse::new_result::<TestCommand>(&Ok(
-                        TestCommandResult::default(),
-                    )),
-                    1 | 2 | 3 => ProposeResponse::new_empty(),
-                    4 => return Err(CurpError::key_conflict()),
-                    _ => unreachable!("there are only 5 nodes"),
+/// A connect that answers proposes and wait synced requests only after a delay, used to
+/// simulate slow replicas
+struct SlowProposeConnectApi {
+    inner: Arc<dyn ConnectApi>,
+    delay: Duration,
+    synced_delay: Duration,
+    in_flight: Arc<InFlight>,
+}
+
+/// Proposes in flight of a connect, and the peak of them
+#[derive(Debug, Default)]
+struct InFlight {
+    current: AtomicUsize,
+    peak: AtomicUsize,
+}
+
+impl InFlight {
+    /// Wait for `delay` as a propose in flight
+    async fn delay(&self, delay: Duration) {
+        let current = self
+            .current
+            .fetch_add(1, std::sync::atomic::Ordering::SeqCst)
+            .saturating_add(1);
+        let _prev = self
+            .peak
+            .fetch_max(current, std::sync::atomic::Ordering::SeqCst);
+        tokio::time::sleep(delay).await;
+        let _prev = self
+            .current
+            .fetch_sub(1, std::sync::atomic::Ordering::SeqCst);
+    }
+}
+
+#[async_trait::async_trait]
+impl ConnectApi for SlowProposeConnectApi {
+    fn id(&self) -> ServerId {
+        self.inner.id()
+    }
+
+    async fn update_addrs(&self, addrs: Vec<String>) -> Result<(), tonic::transport::Error> {
+        self.inner.update_addrs(addrs).await
+    }
+
+    async fn propose(
+        &self,
+        request: ProposeRequest,
+        token: Option<String>,
+        timeout: Duration,
+    ) -> Result<tonic::Response<ProposeResponse>, CurpError> {
+        self.in_flight.delay(self.delay).await;
+        self.inner.propose(request, token, timeout).await
+    }
+
+    async fn propose_compressed(
+        &self,
+        request: ProposeRequest,
+        codec: CompressionCodec,
+        token: Option<String>,
+        timeout: Duration,
+    ) -> Result<tonic::Response<ProposeResponse>, CurpError> {
+        self.in_flight.delay(self.delay).await;
+        self.inner
+            .propose_compressed(request, codec, token, timeout)
+            .await
+    }
+
+    async fn propose_conf_change(
+        &self,
+        request: ProposeConfChangeRequest,
+        timeout: Duration,
+    ) -> Result<tonic::Response<ProposeConfChangeResponse>, CurpError> {
+        self.inner.propose_conf_change(request, timeout).await
+    }
+
+    async fn publish(
+        &self,
+        request: PublishRequest,
+        timeout: Duration,
+    ) -> Result<tonic::Response<PublishResponse>, CurpError> {
+        self.inner.publish(request, timeout).await
+    }
+
+    async fn wait_synced(
+        &self,
+        request: WaitSyncedRequest,
+        timeout: Duration,
+    ) -> Result<tonic::Response<WaitSyncedResponse>, CurpError> {
+        tokio::time::sleep(self.synced_delay).await;
+        self.inner.wait_synced(request, timeout).await
+    }
+
+    async fn shutdown(
+        &self,
+        request: ShutdownRequest,
+        timeout: Duration,
+    ) -> Result<tonic::Response<ShutdownResponse>, CurpError> {
+        self.inner.shutdown(request, timeout).await
+    }
+
+    async fn fetch_cluster(
+        &self,
+        request: FetchClusterRequest,
+        timeout: Duration,
+    ) -> Result<tonic::Response<FetchClusterResponse>, CurpError> {
+        self.inner.fetch_cluster(request, timeout).await
+    }
+
+    async fn fetch_read_state(
+        &self,
+        request: FetchReadStateRequest,
+        timeout: Duration,
+    ) -> Result<tonic::Response<FetchReadStateResponse>, CurpError> {
+        self.inner.fetch_read_state(request, timeout).await
+    }
+
+    async fn move_leader(
+        &self,
+        request: MoveLeaderRequest,
+        timeout: Duration,
+    ) -> Result<tonic::Response<MoveLeaderResponse>, CurpError> {
+        self.inner.move_leader(request, timeout).await
+    }
+
+    async fn lease_keep_alive(&self, client_id: Arc<AtomicU64>, interval: Duration) -> CurpError {
+        self.inner.lease_keep_alive(client_id, interval).await
+    }
+}
+
+#[traced_test]
+#[tokio::test]
+async fn test_unary_fast_round_returns_on_earliest_quorum() {
+    // the super quorum of 4 nodes is 3
+    let mut connects = init_mocked_connects(4, |id, conn| {
+        conn.expect_propose()
+            .return_once(move |_req, _token, _timeout| {
+                let resp = match id {
+                    0 => ProposeResponse::new_result::<TestCommand>(&Ok(
+                        TestCommandResult::default(),
+                    )),
+                    1 | 2 | 3 => ProposeResponse::new_empty(),
+                    _ => unreachable!("there are only 4 nodes"),
+                };
+                Ok(tonic::Response::new(resp))
+            });
+    });
+    let slow = connects.remove(&3).unwrap();
+    let _ig = connects.insert(
+        3,
+        Arc::new(SlowProposeConnectApi {
+            inner: slow,
+            delay: Duration::from_secs(30),
+            synced_delay: Duration::ZERO,
+            in_flight: Arc::default(),
+        }),
+    );
+    let unary = init_unary_client(connects, None, None, 0, 0, None);
+    let start_at = Instant::now();
+    let res = unary
+        .fast_round(ProposeId(0, 0), &TestCommand::default(), None)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(res, TestCommandResult::default());
+    assert!(
+        start_at.elapsed() < Duration::from_secs(1),
+        "fast round should not wait for the slow replica"
+    );
+}
+
+#[traced_test]
+#[tokio::test]
+async fn test_unary_fast_round_return_early_err() {
+    for early_err in [
+        CurpError::duplicated(),
+        CurpError::shutting_down(),
+        CurpError::invalid_config(),
+        CurpError::node_already_exists(),
+        CurpError::node_not_exist(),
+        CurpError::learner_not_catch_up(),
+        CurpError::expired_client_id(),
+        CurpError::redirect(Some(1), 0),
+    ] {
+        assert!(early_err.should_abort_fast_round());
+        // record how many times `handle_propose` was invoked.
+        let counter = Arc::new(Mutex::new(0));
+        let connects = init_mocked_connects(3, |_id, conn| {
+            let counter_c = Arc::clone(&counter);
+            let err = early_err.clone();
+            conn.expect_propose()
+                .return_once(move |_req, _token, _timeout| {
+                    counter_c.lock().unwrap().add_assign(1);
+                    Err(err)
+                });
+        });
+        let unary = init_unary_client(connects, None, None, 0, 0, None);
+        let err = unary
+            .fast_round(ProposeId(0, 0), &TestCommand::default(), None)
+            .await
+            .unwrap_err();
+        assert_eq!(err, early_err);
+        assert_eq!(*counter.lock().unwrap(), 1);
+    }
+}
+
+#[traced_test]
+#[tokio::test]
+async fn test_unary_fast_round_less_quorum() {
+    let connects = init_mocked_connects(5, |id, conn| {
+        conn.expect_propose()
+            .return_once(move |_req, _token, _timeout| {
+                let resp = match id {
+                    0 => ProposeResponse::new_result::<TestCommand>(&Ok(
+                        TestCommandResult::default(),
+                    )),
+                    1 | 2 => ProposeResponse::new_empty(),
+                    3 | 4 => return Err(CurpError::key_conflict()),
+                    _ => unreachable!("there are only 5 nodes"),
+                };
+                Ok(tonic::Response::new(resp))
+            });
+    });
+    let unary = init_unary_client(connects, None, None, 0, 0, None);
+    let err = unary
+        .fast_round(ProposeId(0, 0), &TestCommand::default(), None)
+        .await
+        .unwrap_err();
+    assert_eq!(err, CurpError::KeyConflict(()));
+}
+
+/// FIXME: two leader
+/// TODO: fix in subsequence PR
+#[traced_test]
+#[tokio::test]
+#[should_panic]
+async fn test_unary_fast_round_with_two_leader() {
+    let connects = init_mocked_connects(5, |id, conn| {
+        conn.expect_propose()
+            .return_once(move |_req, _token, _timeout| {
+                let resp =
+                    match id {
+                        // The execution result has been returned, indicating that server(0) has also recorded the command.
+                        0 => ProposeResponse::new_result::<TestCommand>(&Ok(
+                            TestCommandResult::new(vec![1], vec![1]),
+                        )),
+                        // imagine that server(1) is the new leader
+                        1 => ProposeResponse::new_result::<TestCommand>(&Ok(
+                            TestCommandResult::new(vec![2], vec![2]),
+                        )),
+                        2 | 3 => ProposeResponse::new_empty(),
+                        4 => return Err(CurpError::key_conflict()),
+                        _ => unreachable!("there are only 5 nodes"),
+                    };
+                Ok(tonic::Response::new(resp))
+            });
+    });
+    // old local leader(0), term 1
+    let unary = init_unary_client(connects, None, Some(0), 1, 0, None);
+    let res = unary
+        .fast_round(ProposeId(0, 0), &TestCommand::default(), None)
+        .await
+        .unwrap()
+        .unwrap();
+    // quorum: server(0, 1, 2, 3)
+    assert_eq!(res, TestCommandResult::new(vec![2], vec![2]));
+}
+
+// We may encounter this scenario during leader election
+#[traced_test]
+#[tokio::test]
+async fn test_unary_fast_round_without_leader() {
+    let connects = init_mocked_connects(5, |id, conn| {
+        conn.expect_propose()
+            .return_once(move |_req, _token, _timeout| {
+                let resp = match id {
+                    0 | 1 | 2 | 3 | 4 => ProposeResponse::new_empty(),
+                    _ => unreachable!("there are only 5 nodes"),
+                };
+                Ok(tonic::Response::new(resp))
+            });
+    });
+    // old local leader(0), term 1
+    let unary = init_unary_client(connects, None, Some(0), 1, 0, None);
+    let res = unary
+        .fast_round(ProposeId(0, 0), &TestCommand::default(), None)
+        .await
+        .unwrap_err();
+    // quorum: server(0, 1, 2, 3)
+    assert_eq!(res, CurpError::WrongClusterVersion(()));
+}
+
+#[traced_test]
+#[tokio::test]
+async fn test_unary_slow_round_fetch_leader_first() {
+    let flag = Arc::new(AtomicBool::new(false));
+    let connects = init_mocked_connects(3, |id, conn| {
+        let flag_c = Arc::clone(&flag);
+        conn.expect_fetch_cluster()
+            .return_once(move |_req, _timeout| {
+                flag_c.store(true, std::sync::atomic::Ordering::Relaxed);
+                Ok(tonic::Response::new(FetchClusterResponse {
+                    leader_id: Some(0),
+                    term: 1,
+                    cluster_id: 123,
+                    members: vec![
+                        Member::new(0, "S0", vec!["A0".to_owned()], [], false),
+                        Member::new(1, "S1", vec!["A1".to_owned()], [], false),
+                        Member::new(2, "S2", vec!["A2".to_owned()], [], false),
+                    ],
+                    cluster_version: 1,
+                }))
+            });
+        let flag_c = Arc::clone(&flag);
+        conn.expect_wait_synced()
+            .return_once(move |_req, _timeout| {
+                assert!(id == 0, "wait synced should send to leader");
+                assert!(
+                    flag_c.load(std::sync::atomic::Ordering::Relaxed),
+                    "fetch_leader should invoke first"
+                );
+                Ok(tonic::Response::new(WaitSyncedResponse::new_from_result::<
+                    TestCommand,
+                >(
+                    Ok(TestCommandResult::default()),
+                    Some(Ok(1.into())),
+                )))
+            });
+    });
+    let unary = init_unary_client(connects, None, None, 0, 0, None);
+    let res = unary.slow_round(ProposeId(0, 0)).await.unwrap().unwrap();
+    assert_eq!(LogIndex::from(res.0), 1);
+    assert_eq!(res.1, TestCommandResult::default());
+}
+
+#[traced_test]
+#[tokio::test]
+async fn test_unary_propose_fast_path_works() {
+    let connects = init_mocked_connects(5, |id, conn| {
+        conn.expect_propose()
+            .return_once(move |_req, _token, _timeout| {
+                let resp = match id {
+                    0 => ProposeResponse::new_result::<TestCommand>(&Ok(
+                        TestCommandResult::default(),
+                    )),
+                    1 | 2 | 3 => ProposeResponse::new_empty(),
+                    4 => return Err(CurpError::key_conflict()),
+                    _ => unreachable!("there are only 5 nodes"),
+                };
+                Ok(tonic::Response::new(resp))
+            });
+        conn.expect_wait_synced()
+            .return_once(move |_req, _timeout| {
+                assert!(id == 0, "wait synced should send to leader");
+                std::thread::sleep(Duration::from_millis(100));
+                Ok(tonic::Response::new(WaitSyncedResponse::new_from_result::<
+                    TestCommand,
+                >(
+                    Ok(TestCommandResult::default()),
+                    Some(Ok(1.into())),
+                )))
+            });
+    });
+    let unary = init_unary_client(connects, None, Some(0), 1, 0, None);
+    let res = unary
+        .propose(&TestCommand::default(), None, true)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(res, (TestCommandResult::default(), None));
+}
+
+#[test]
+fn test_conflict_groups_serialize_conflicting_commands() {
+    let cmds = vec![
+        TestCommand::new_put(vec![1], 1),
+        TestCommand::new_put(vec![2], 2),
+        TestCommand::new_put(vec![3], 3),
+        TestCommand::new_put(vec![1], 4),
+        TestCommand::new_put(vec![4], 5),
+    ];
+    let mut groups: Vec<Vec<usize>> = conflict_groups(&cmds)
+        .into_iter()
+        .map(|group| group.into_iter().map(|(idx, _cmd)| idx).collect())
+        .collect();
+    groups.sort();
+    // the non-conflicting commands are proposed concurrently, the conflicting pair in order
+    assert_eq!(groups, vec![vec![0, 3], vec![1], vec![2], vec![4]]);
+
+    // a command conflicting with two groups merges them
+    let cmds = vec![
+        TestCommand::new_put(vec![1], 1),
+        TestCommand::new_put(vec![2], 2),
+        TestCommand::new_put(vec![1, 2], 3),
+    ];
+    let groups = conflict_groups(&cmds);
+    assert_eq!(groups.len(), 1);
+    assert_eq!(
+        groups[0].iter().map(|&(idx, _cmd)| idx).collect::<Vec<_>>(),
+        vec![0, 1, 2]
+    );
+}
+
+/// Propose a batch of 5 commands to a leader answering each propose after 100ms. Return the
+/// peak number of proposes in flight at the leader and the order in which the leader
+/// received the commands.
+async fn propose_batch_to_slow_leader(
+    cmds: &[TestCommand],
+    batch_by_conflicts: bool,
+) -> (usize, Vec<TestCommand>) {
+    let received = Arc::new(Mutex::new(Vec::new()));
+    let mut connects = init_mocked_connects(3, |id, conn| {
+        let received = Arc::clone(&received);
+        conn.expect_propose()
+            .returning(move |req, _token, _timeout| {
+                let resp = if id == 0 {
+                    received
+                        .lock()
+                        .unwrap()
+                        .push(req.cmd::<TestCommand>().unwrap());
+                    ProposeResponse::new_result::<TestCommand>(&Ok(TestCommandResult::default()))
+                } else {
+                    ProposeResponse::new_empty()
+                };
+                Ok(tonic::Response::new(resp))
+            });
+        conn.expect_wait_synced().returning(|_req, _timeout| {
+            Ok(tonic::Response::new(WaitSyncedResponse::new_from_result::<
+                TestCommand,
+            >(
+                Ok(TestCommandResult::default()),
+                Some(Ok(1.into())),
+            )))
+        });
+    });
+    let leader = connects.remove(&0).unwrap();
+    let in_flight = Arc::new(InFlight::default());
+    let _ig = connects.insert(
+        0,
+        Arc::new(SlowProposeConnectApi {
+            inner: leader,
+            delay: Duration::from_millis(100),
+            synced_delay: Duration::ZERO,
+            in_flight: Arc::clone(&in_flight),
+        }),
+    );
+    let state = State::new_arc(connects, None, Some(0), 1, 0, None);
+    let config = UnaryConfig::new(Duration::from_secs(1), Duration::from_secs(1))
+        .with_batch_by_conflicts(batch_by_conflicts);
+    let unary = Unary::<TestCommand>::new(state, config);
+
+    let results = unary.propose_batch(cmds, None, true).await;
+    assert_eq!(results.len(), 5);
+    assert!(results.iter().all(|res| matches!(res, Ok(Ok(_)))));
+    let received = received.lock().unwrap().clone();
+    assert_eq!(received.len(), 5);
+    (
+        in_flight.peak.load(std::sync::atomic::Ordering::SeqCst),
+        received,
+    )
+}
+
+#[traced_test]
+#[tokio::test]
+async fn test_unary_propose_batch_keeps_conflicting_order() {
+    let cmds = vec![
+        TestCommand::new_put(vec![1], 1),
+        TestCommand::new_put(vec![2], 2),
+        TestCommand::new_put(vec![3], 3),
+        TestCommand::new_put(vec![1], 4),
+        TestCommand::new_put(vec![4], 5),
+    ];
+    let (peak, received) = propose_batch_to_slow_leader(&cmds, true).await;
+    let pos = |cmd: &TestCommand| received.iter().position(|c| c == cmd).unwrap();
+    assert!(
+        pos(&cmds[0]) < pos(&cmds[3]),
+        "conflicting commands should be serialized"
+    );
+    // the non-conflicting commands are proposed along with the first of the conflicting
+    // pair, then the second one follows
+    assert_eq!(
+        peak, 4,
+        "non-conflicting commands should be proposed concurrently"
+    );
+
+    // without grouping, the batch is proposed one by one in its order
+    let (peak, received) = propose_batch_to_slow_leader(&cmds, false).await;
+    assert_eq!(received, cmds);
+    assert_eq!(peak, 1);
+}
+
+#[traced_test]
+#[tokio::test(flavor = "multi_thread")]
+async fn test_unary_propose_with_callback_returns_before_applied() {
+    let mut connects = init_mocked_connects(5, |id, conn| {
+        conn.expect_propose()
+            .return_once(move |_req, _token, _timeout| {
+                let resp = match id {
+                    0 => ProposeResponse::new_result::<TestCommand>(&Ok(
+                        TestCommandResult::default(),
+                    )),
+                    _ => ProposeResponse::new_empty(),
+                };
+                Ok(tonic::Response::new(resp))
+            });
+        conn.expect_wait_synced()
+            .return_once(move |_req, _timeout| {
+                assert!(id == 0, "wait synced should send to leader");
+                Ok(tonic::Response::new(WaitSyncedResponse::new_from_result::<
+                    TestCommand,
+                >(
+                    Ok(TestCommandResult::default()),
+                    Some(Ok(1.into())),
+                )))
+            });
+    });
+    // the command is applied later
+    let leader = connects.remove(&0).unwrap();
+    let _ig = connects.insert(
+        0,
+        Arc::new(SlowProposeConnectApi {
+            inner: leader,
+            delay: Duration::ZERO,
+            synced_delay: Duration::from_millis(500),
+            in_flight: Arc::default(),
+        }),
+    );
+    let unary = init_unary_client(connects, None, Some(0), 1, 0, None);
+    let (tx, rx) = tokio::sync::oneshot::channel();
+    let start = Instant::now();
+    let _propose_id = unary
+        .propose_with_callback(
+            &TestCommand::default(),
+            None,
+            Box::new(move |res| {
+                let _ig = tx.send((res, Instant::now()));
+            }),
+        )
+        .await
+        .unwrap();
+    let returned = Instant::now();
+    assert!(returned - start < Duration::from_millis(500));
+    let (res, applied) = rx.await.unwrap();
+    assert!(applied > returned);
+    assert_eq!(
+        res.unwrap().unwrap(),
+        (TestCommandResult::default(), Some(LogIndexResult::from(1)))
+    );
+}
+
+#[traced_test]
+#[tokio::test]
+async fn test_unary_propose_slow_path_works() {
+    let connects = init_mocked_connects(5, |id, conn| {
+        conn.expect_propose()
+            .return_once(move |_req, _token, _timeout| {
+                let resp = match id {
+                    0 => ProposeResponse::new_result::<TestCommand>(&Ok(
+                        TestCommandResult::default(),
+                    )),
+                    1 | 2 | 3 => ProposeResponse::new_empty(),
+                    4 => return Err(CurpError::key_conflict()),
+                    _ => unreachable!("there are only 5 nodes"),
+                };
+                Ok(tonic::Response::new(resp))
+            });
+        conn.expect_wait_synced()
+            .return_once(move |_req, _timeout| {
+                assert!(id == 0, "wait synced should send to leader");
+                std::thread::sleep(Duration::from_millis(100));
+                Ok(tonic::Response::new(WaitSyncedResponse::new_from_result::<
+                    TestCommand,
+                >(
+                    Ok(TestCommandResult::default()),
+                    Some(Ok(1.into())),
+                )))
+            });
+    });
+    let unary = init_unary_client(connects, None, Some(0), 1, 0, None);
+    let start_at = Instant::now();
+    let res = unary
+        .propose(&TestCommand::default(), None, false)
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(
+        start_at.elapsed() > Duration::from_millis(100),
+        "slow round takes at least 100ms"
+    );
+    assert_eq!(
+        res,
+        (TestCommandResult::default(), Some(LogIndexResult::from(1)))
+    );
+}
+
+#[traced_test]
+#[tokio::test]
+async fn test_unary_propose_fast_path_fallback_slow_path() {
+    // record how many times `handle_propose` was invoked.
+    let counter = Arc::new(Mutex::new(0));
+    let connects = init_mocked_connects(5, |id, conn| {
+        let counter_c = Arc::clone(&counter);
+        conn.expect_propose()
+            .return_once(move |_req, _token, _timeout| {
+                counter_c.lock().unwrap().add_assign(1);
+                // insufficient quorum
+                let resp = match id {
+                    0 => ProposeResponse::new_result::<TestCommand>(&Ok(
+                        TestCommandResult::default(),
+                    )),
+                    1 | 2 => ProposeResponse::new_empty(),
+                    3 | 4 => return Err(CurpError::key_conflict()),
+                    _ => unreachable!("there are only 5 nodes"),
+                };
+                Ok(tonic::Response::new(resp))
+            });
+        conn.expect_wait_synced()
+            .return_once(move |_req, _timeout| {
+                assert!(id == 0, "wait synced should send to leader");
+                std::thread::sleep(Duration::from_millis(100));
+                Ok(tonic::Response::new(WaitSyncedResponse::new_from_result::<
+                    TestCommand,
+                >(
+                    Ok(TestCommandResult::default()),
+                    Some(Ok(1.into())),
+                )))
+            });
+    });
+    let unary = init_unary_client(connects, None, Some(0), 1, 0, None);
+    let start_at = Instant::now();
+    let res = unary
+        .propose(&TestCommand::default(), None, true)
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(
+        start_at.elapsed() > Duration::from_millis(100),
+        "slow round takes at least 100ms"
+    );
+    // indicate that we actually run out of fast round
+    assert_eq!(*counter.lock().unwrap(), 5);
+    assert_eq!(
+        res,
+        (TestCommandResult::default(), Some(LogIndexResult::from(1)))
+    );
+}
+
+#[traced_test]
+#[tokio::test]
+async fn test_unary_propose_return_early_err() {
+    for early_err in [
+        CurpError::shutting_down(),
+        CurpError::invalid_config(),
+        CurpError::node_already_exists(),
+        CurpError::node_not_exist(),
+        CurpError::learner_not_catch_up(),
+        CurpError::expired_client_id(),
+        CurpError::redirect(Some(1), 0),
+    ] {
+        assert!(early_err.should_abort_fast_round());
+        // record how many times rpc was invoked.
+        let counter = Arc::new(Mutex::new(0));
+        let connects = init_mocked_connects(5, |id, conn| {
+            let err = early_err.clone();
+            let counter_c = Arc::clone(&counter);
+            conn.expect_propose()
+                .return_once(move |_req, _token, _timeout| {
+                    counter_c.lock().unwrap().add_assign(1);
+                    Err(err)
+                });
+            let err = early_err.clone();
+            let counter_c = Arc::clone(&counter);
+            conn.expect_wait_synced()
+                .return_once(move |_req, _timeout| {
+                    assert!(id == 0, "wait synced should send to leader");
+                    counter_c.lock().unwrap().add_assign(1);
+                    Err(err)
+                });
+        });
+        let unary = init_unary_client(connects, None, Some(0), 1, 0, None);
+        let err = unary
+            .propose(&TestCommand::default(), None, true)
+            .await
+            .unwrap_err();
+        assert_eq!(err, early_err);
+        assert_eq!(*counter.lock().unwrap(), 1);
+    }
+}
+
+// Tests for retry layer
+
+#[traced_test]
+#[tokio::test]
+async fn test_retry_propose_return_no_retry_error() {
+    for early_err in [
+        CurpError::shutting_down(),
+        CurpError::invalid_config(),
+        CurpError::node_already_exists(),
+        CurpError::node_not_exist(),
+        CurpError::learner_not_catch_up(),
+    ] {
+        // record how many times rpc was invoked.
+        let counter = Arc::new(Mutex::new(0));
+        let connects = init_mocked_connects(5, |id, conn| {
+            let err = early_err.clone();
+            let counter_c = Arc::clone(&counter);
+            conn.expect_propose()
+                .return_once(move |_req, _token, _timeout| {
+                    counter_c.lock().unwrap().add_assign(1);
+                    Err(err)
+                });
+            let err = early_err.clone();
+            let counter_c = Arc::clone(&counter);
+            conn.expect_wait_synced()
+                .return_once(move |_req, _timeout| {
+                    assert!(id == 0, "wait synced should send to leader");
+                    counter_c.lock().unwrap().add_assign(1);
+                    Err(err)
+                });
+        });
+        let unary = init_unary_client(connects, None, Some(0), 1, 0, None);
+        let retry = Retry::new(
+            unary,
+            RetryConfig::new_fixed(Duration::from_millis(100), 5),
+            None,
+        );
+        let err = retry
+            .propose(&TestCommand::default(), None, false)
+            .await
+            .unwrap_err();
+        assert_eq!(err.message(), tonic::Status::from(early_err).message());
+        // fast path + slow path = 2
+        assert_eq!(*counter.lock().unwrap(), 2);
+    }
+}
+
+#[traced_test]
+#[tokio::test]
+async fn test_retry_propose_should_not_retry_deadline_exceeded() {
+    let connects = init_mocked_connects(5, |id, conn| {
+        conn.expect_propose()
+            .returning(move |_req, _token, _timeout| Err(CurpError::deadline_exceeded()));
+        if id == 0 {
+            conn.expect_wait_synced()
+                .times(1)
+                .returning(move |_req, _timeout| Err(CurpError::deadline_exceeded()));
+        }
+    });
+    let unary = init_unary_client(connects, None, Some(0), 1, 0, None);
+    let retry = Retry::new(
+        unary,
+        RetryConfig::new_fixed(Duration::from_millis(10), 5),
+        None,
+    );
+    let err = retry
+        .propose(&TestCommand::default(), None, false)
+        .await
+        .unwrap_err();
+    assert_eq!(err.code(), tonic::Code::DeadlineExceeded);
+    assert_eq!(
+        CurpError::from(err).internal_kind(),
+        Some(InternalErrorKind::DeadlineExceeded)
+    );
+}
+
+#[traced_test]
+#[tokio::test]
+async fn test_retry_propose_should_back_off_exponentially_when_rate_limited() {
+    let connects = init_mocked_connects(5, |_id, conn| {
+        conn.expect_propose()
+            .returning(move |_req, _token, _timeout| Err(CurpError::rate_limited()));
+        conn.expect_wait_synced()
+            .returning(move |_req, _timeout| Err(CurpError::rate_limited()));
+    });
+    let unary = init_unary_client(connects, None, Some(0), 1, 0, None);
+    let retry = Retry::new(
+        unary,
+        RetryConfig::new_fixed(Duration::from_millis(10), 4),
+        None,
+    );
+    let start = Instant::now();
+    let err = retry
+        .propose(&TestCommand::default(), None, false)
+        .await
+        .unwrap_err();
+    // a fixed backoff sleeps 40ms in total, the rate limited backoff 10 + 20 + 40 + 80ms
+    assert!(start.elapsed() >= Duration::from_millis(150));
+    assert_eq!(err.code(), tonic::Code::DeadlineExceeded);
+    assert!(err.message().contains("rate limited"));
+}
+
+#[traced_test]
+#[tokio::test]
+async fn test_retry_propose_return_retry_error() {
+    for early_err in [
+        CurpError::key_conflict(),
+        CurpError::RpcTransport(()),
+        CurpError::internal("No reason"),
+    ] {
+        let connects = init_mocked_connects(5, |id, conn| {
+            let err = early_err.clone();
+            conn.expect_fetch_cluster()
+                .returning(move |_req, _timeout| {
+                    Ok(tonic::Response::new(FetchClusterResponse {
+                        leader_id: Some(0),
+                        term: 2,
+                        cluster_id: 123,
+                        members: vec![
+                            Member::new(0, "S0", vec!["A0".to_owned()], [], false),
+                            Member::new(1, "S1", vec!["A1".to_owned()], [], false),
+                            Member::new(2, "S2", vec!["A2".to_owned()], [], false),
+                            Member::new(3, "S3", vec!["A3".to_owned()], [], false),
+                            Member::new(4, "S4", vec!["A4".to_owned()], [], false),
+                        ],
+                        cluster_version: 1,
+                    }))
+                });
+            conn.expect_propose()
+                .returning(move |_req, _token, _timeout| Err(err.clone()));
+            if id == 0 {
+                let err = early_err.clone();
+                conn.expect_wait_synced()
+                    .times(5) // wait synced should be retried in 5 times on leader
+                    .returning(move |_req, _timeout| Err(err.clone()));
+            }
+        });
+        let unary = init_unary_client(connects, None, Some(0), 1, 0, None);
+        let retry = Retry::new(
+            unary,
+            RetryConfig::new_fixed(Duration::from_millis(10), 5),
+            None,
+        );
+        let err = retry
+            .propose(&TestCommand::default(), None, false)
+            .await
+            .unwrap_err();
+        assert!(err.message().contains("request timeout"));
+    }
+}
+
+#[traced_test]
+#[tokio::test]
+async fn test_retry_propose_with_outcome_reports_attempts() {
+    let connects = init_mocked_connects(5, |id, conn| {
+        conn.expect_fetch_cluster()
+            .returning(move |_req, _timeout| {
+                Ok(tonic::Response::new(FetchClusterResponse {
+                    leader_id: Some(0),
+                    term: 1,
+                    cluster_id: 123,
+                    members: vec![
+                        Member::new(0, "S0", vec!["A0".to_owned()], [], false),
+                        Member::new(1, "S1", vec!["A1".to_owned()], [], false),
+                        Member::new(2, "S2", vec!["A2".to_owned()], [], false),
+                        Member::new(3, "S3", vec!["A3".to_owned()], [], false),
+                        Member::new(4, "S4", vec!["A4".to_owned()], [], false),
+                    ],
+                    cluster_version: 0,
+                }))
+            });
+        conn.expect_propose()
+            .returning(move |_req, _token, _timeout| {
+                Ok(tonic::Response::new(ProposeResponse::new_empty()))
+            });
+        if id == 0 {
+            let counter = Arc::new(Mutex::new(0));
+            conn.expect_wait_synced()
+                .times(2)
+                .returning(move |_req, _timeout| {
+                    counter.lock().unwrap().add_assign(1);
+                    // the first attempt fails with a transport error
+                    if *counter.lock().unwrap() == 1 {
+                        return Err(CurpError::RpcTransport(()));
+                    }
+                    Ok(tonic::Response::new(WaitSyncedResponse::new_from_result::<
+                        TestCommand,
+                    >(
+                        Ok(TestCommandResult::default()),
+                        Some(Ok(1.into())),
+                    )))
+                });
+        }
+    });
+    let unary = init_unary_client(connects, None, Some(0), 1, 0, None);
+    let retry = Retry::new(
+        unary,
+        RetryConfig::new_fixed(Duration::from_millis(10), 5),
+        None,
+    );
+    let outcome = retry
+        .propose_with_outcome(&TestCommand::default(), None, false)
+        .await
+        .unwrap();
+    assert_eq!(outcome.attempts, 2);
+    assert_eq!(
+        outcome.result.unwrap(),
+        (TestCommandResult::default(), Some(LogIndexResult::from(1)))
+    );
+}
+
+#[traced_test]
+#[tokio::test]
+async fn test_retry_propose_sends_dead_letter_after_retries_exhausted() {
+    let connects = init_mocked_connects(5, |id, conn| {
+        conn.expect_propose()
+            .returning(move |_req, _token, _timeout| Err(CurpError::key_conflict()));
+        if id == 0 {
+            conn.expect_wait_synced()
+                .times(3)
+                .returning(move |_req, _timeout| Err(CurpError::key_conflict()));
+        }
+    });
+    let unary = init_unary_client(connects, None, Some(0), 1, 0, None);
+    let (tx, mut rx) = tokio::sync::mpsc::channel(1);
+    let retry = Retry::new(
+        unary,
+        RetryConfig::new_fixed(Duration::from_millis(10), 3),
+        None,
+    )
+    .with_dead_letter(tx);
+    let cmd = TestCommand::new_put(vec![1], 1);
+    let err = retry.propose(&cmd, None, false).await.unwrap_err();
+    assert!(err.message().contains("request timeout"));
+    let letter = rx.try_recv().unwrap();
+    assert_eq!(letter.cmd, cmd);
+    assert_eq!(letter.err.code(), err.code());
+    assert_eq!(letter.err.message(), err.message());
+}
+
+#[traced_test]
+#[tokio::test]
+async fn test_retry_propose_does_not_send_dead_letter_on_server_deadline() {
+    let connects = init_mocked_connects(5, |id, conn| {
+        conn.expect_propose()
+            .returning(move |_req, _token, _timeout| {
+                Ok(tonic::Response::new(ProposeResponse::new_empty()))
+            });
+        if id == 0 {
+            conn.expect_wait_synced()
+                .times(1)
+                .returning(move |_req, _timeout| Err(CurpError::deadline_exceeded()));
+        }
+    });
+    let unary = init_unary_client(connects, None, Some(0), 1, 0, None);
+    let (tx, mut rx) = tokio::sync::mpsc::channel(1);
+    let retry = Retry::new(
+        unary,
+        RetryConfig::new_fixed(Duration::from_millis(10), 3),
+        None,
+    )
+    .with_dead_letter(tx);
+    let err = retry
+        .propose(&TestCommand::new_put(vec![1], 1), None, false)
+        .await
+        .unwrap_err();
+    // the command may still be applied, so it must not be replayed from the dead letters
+    assert_eq!(err.code(), tonic::Code::DeadlineExceeded);
+    assert!(rx.try_recv().is_err());
+}
+
+#[traced_test]
+#[tokio::test]
+async fn test_retry_hook_gives_up_on_retryable_error() {
+    let connects = init_mocked_connects(5, |id, conn| {
+        conn.expect_propose()
+            .returning(move |_req, _token, _timeout| Err(CurpError::key_conflict()));
+        if id == 0 {
+            conn.expect_wait_synced()
+                .times(1)
+                .returning(move |_req, _timeout| Err(CurpError::key_conflict()));
+        }
+    });
+    let unary = init_unary_client(connects, None, Some(0), 1, 0, None);
+    let attempts = Arc::new(Mutex::new(vec![]));
+    let attempts_c = Arc::clone(&attempts);
+    let hook = RetryHook(Arc::new(move |err: &CurpError, attempt| {
+        attempts_c.lock().unwrap().push(attempt);
+        if matches!(*err, CurpError::KeyConflict(_)) {
+            RetryDecision::GiveUp
+        } else {
+            RetryDecision::Default
+        }
+    }));
+    let retry = Retry::new(
+        unary,
+        RetryConfig::new_fixed(Duration::from_millis(10), 5).with_hook(hook),
+        None,
+    );
+    let err = retry
+        .propose(&TestCommand::default(), None, false)
+        .await
+        .unwrap_err();
+    // the error is returned as is instead of exhausting the retries
+    assert!(!err.message().contains("request timeout"));
+    assert_eq!(
+        err.code(),
+        tonic::Status::from(CurpError::key_conflict()).code()
+    );
+    assert_eq!(*attempts.lock().unwrap(), vec![1]);
+}
+
+#[traced_test]
+#[tokio::test]
+async fn test_retry_propose_records_traces() {
+    let connects = init_mocked_connects(3, |id, conn| {
+        conn.expect_propose()
+            .returning(move |_req, _token, _timeout| {
+                let resp = if id == 0 {
+                    ProposeResponse::new_result::<TestCommand>(&Ok(TestCommandResult::default()))
+                } else {
+                    ProposeResponse::new_empty()
                 };
                 Ok(tonic::Response::new(resp))
             });
-        conn.expect_wait_synced()
-            .return_once(move |_req, _timeout| {
-                assert!(id == 0, "wait synced should send to leader");
-                std::thread::sleep(Duration::from_millis(100));
+        if id == 0 {
+            let counter = Arc::new(Mutex::new(0));
+            conn.expect_wait_synced().returning(move |_req, _timeout| {
+                let mut counter = counter.lock().unwrap();
+                counter.add_assign(1);
+                // the third propose fails as the server is shutting down
+                if *counter == 3 {
+                    return Err(CurpError::shutting_down());
+                }
                 Ok(tonic::Response::new(WaitSyncedResponse::new_from_result::<
                     TestCommand,
                 >(
@@ -480,36 +1706,69 @@ async fn test_unary_propose_fast_path_works() {
                     Some(Ok(1.into())),
                 )))
             });
+        }
     });
     let unary = init_unary_client(connects, None, Some(0), 1, 0, None);
-    let res = unary
-        .propose(&TestCommand::default(), None, true)
-        .await
-        .unwrap()
-        .unwrap();
-    assert_eq!(res, (TestCommandResult::default(), None));
+    let retry = Retry::new(
+        unary,
+        RetryConfig::new_fixed(Duration::from_millis(10), 5),
+        None,
+    )
+    .with_trace_capacity(Some(2));
+    for key in 1..=3 {
+        let _ig = retry
+            .propose(&TestCommand::new_put(vec![key], key), None, false)
+            .await;
+    }
+
+    // only the latest 2 traces are kept
+    let traces = retry.export_trace();
+    assert_eq!(traces.len(), 2);
+    assert!(matches!(
+        traces[0].op,
+        TraceOp::Propose { ref cmd, use_fast_path: false } if *cmd == TestCommand::new_put(vec![2], 2)
+    ));
+    // the slow round is served by the leader
+    assert_eq!(traces[0].connect, Some(0));
+    assert!(matches!(traces[0].outcome, TraceOutcome::Succeeded));
+    assert_eq!(traces[0].attempts, 1);
+    assert!(matches!(
+        traces[1].op,
+        TraceOp::Propose { ref cmd, .. } if *cmd == TestCommand::new_put(vec![3], 3)
+    ));
+    // no server served the failed propose
+    assert_eq!(traces[1].connect, None);
+    assert!(matches!(
+        traces[1].outcome,
+        TraceOutcome::Failed(ref err) if err.code() == tonic::Code::FailedPrecondition
+    ));
+    assert!(traces[0].start <= traces[1].start);
 }
 
 #[traced_test]
 #[tokio::test]
-async fn test_unary_propose_slow_path_works() {
+async fn test_retry_propose_renews_expired_client_id() {
     let connects = init_mocked_connects(5, |id, conn| {
         conn.expect_propose()
-            .return_once(move |_req, _token, _timeout| {
+            .returning(move |req, _token, _timeout| {
+                if req.propose_id().0 == 0 {
+                    return Err(CurpError::expired_client_id());
+                }
+                assert_eq!(req.propose_id().0, 10, "retry should use the new client id");
                 let resp = match id {
                     0 => ProposeResponse::new_result::<TestCommand>(&Ok(
                         TestCommandResult::default(),
                     )),
-                    1 | 2 | 3 => ProposeResponse::new_empty(),
-                    4 => return Err(CurpError::key_conflict()),
-                    _ => unreachable!("there are only 5 nodes"),
+                    _ => ProposeResponse::new_empty(),
                 };
                 Ok(tonic::Response::new(resp))
             });
-        conn.expect_wait_synced()
-            .return_once(move |_req, _timeout| {
-                assert!(id == 0, "wait synced should send to leader");
-                std::thread::sleep(Duration::from_millis(100));
+        if id == 0 {
+            conn.expect_wait_synced().returning(move |req, _timeout| {
+                if req.propose_id().0 == 0 {
+                    return Err(CurpError::expired_client_id());
+                }
+                assert_eq!(req.propose_id().0, 10, "retry should use the new client id");
                 Ok(tonic::Response::new(WaitSyncedResponse::new_from_result::<
                     TestCommand,
                 >(
@@ -517,18 +1776,24 @@ async fn test_unary_propose_slow_path_works() {
                     Some(Ok(1.into())),
                 )))
             });
+            conn.expect_lease_keep_alive()
+                .times(1)
+                .returning(|client_id, _interval| {
+                    client_id.store(10, std::sync::atomic::Ordering::Relaxed);
+                    CurpError::RpcTransport(())
+                });
+        }
     });
     let unary = init_unary_client(connects, None, Some(0), 1, 0, None);
-    let start_at = Instant::now();
-    let res = unary
+    let retry = Retry::new(
+        unary,
+        RetryConfig::new_fixed(Duration::from_millis(10), 5),
+        None,
+    );
+    let res = retry
         .propose(&TestCommand::default(), None, false)
         .await
-        .unwrap()
         .unwrap();
-    assert!(
-        start_at.elapsed() > Duration::from_millis(100),
-        "slow round takes at least 100ms"
-    );
     assert_eq!(
         res,
         (TestCommandResult::default(), Some(LogIndexResult::from(1)))
@@ -537,157 +1802,114 @@ async fn test_unary_propose_slow_path_works() {
 
 #[traced_test]
 #[tokio::test]
-async fn test_unary_propose_fast_path_fallback_slow_path() {
-    // record how many times `handle_propose` was invoked.
-    let counter = Arc::new(Mutex::new(0));
+async fn test_retry_propose_returns_when_client_id_expires_again() {
     let connects = init_mocked_connects(5, |id, conn| {
-        let counter_c = Arc::clone(&counter);
         conn.expect_propose()
-            .return_once(move |_req, _token, _timeout| {
-                counter_c.lock().unwrap().add_assign(1);
-                // insufficient quorum
-                let resp = match id {
-                    0 => ProposeResponse::new_result::<TestCommand>(&Ok(
-                        TestCommandResult::default(),
-                    )),
-                    1 | 2 => ProposeResponse::new_empty(),
-                    3 | 4 => return Err(CurpError::key_conflict()),
-                    _ => unreachable!("there are only 5 nodes"),
-                };
-                Ok(tonic::Response::new(resp))
-            });
-        conn.expect_wait_synced()
-            .return_once(move |_req, _timeout| {
-                assert!(id == 0, "wait synced should send to leader");
-                std::thread::sleep(Duration::from_millis(100));
-                Ok(tonic::Response::new(WaitSyncedResponse::new_from_result::<
-                    TestCommand,
-                >(
-                    Ok(TestCommandResult::default()),
-                    Some(Ok(1.into())),
-                )))
-            });
+            .returning(move |_req, _token, _timeout| Err(CurpError::expired_client_id()));
+        if id == 0 {
+            conn.expect_wait_synced()
+                .returning(move |_req, _timeout| Err(CurpError::expired_client_id()));
+            conn.expect_lease_keep_alive()
+                .times(1)
+                .returning(|client_id, _interval| {
+                    client_id.store(10, std::sync::atomic::Ordering::Relaxed);
+                    CurpError::RpcTransport(())
+                });
+        }
     });
     let unary = init_unary_client(connects, None, Some(0), 1, 0, None);
-    let start_at = Instant::now();
-    let res = unary
-        .propose(&TestCommand::default(), None, true)
-        .await
-        .unwrap()
-        .unwrap();
-    assert!(
-        start_at.elapsed() > Duration::from_millis(100),
-        "slow round takes at least 100ms"
+    let retry = Retry::new(
+        unary,
+        RetryConfig::new_fixed(Duration::from_millis(10), 5),
+        None,
     );
-    // indicate that we actually run out of fast round
-    assert_eq!(*counter.lock().unwrap(), 5);
+    let err = retry
+        .propose(&TestCommand::default(), None, false)
+        .await
+        .unwrap_err();
     assert_eq!(
-        res,
-        (TestCommandResult::default(), Some(LogIndexResult::from(1)))
+        err.message(),
+        tonic::Status::from(CurpError::expired_client_id()).message()
     );
 }
 
 #[traced_test]
 #[tokio::test]
-async fn test_unary_propose_return_early_err() {
-    for early_err in [
-        CurpError::shutting_down(),
-        CurpError::invalid_config(),
-        CurpError::node_already_exists(),
-        CurpError::node_not_exist(),
-        CurpError::learner_not_catch_up(),
-        CurpError::expired_client_id(),
-        CurpError::redirect(Some(1), 0),
-    ] {
-        assert!(early_err.should_abort_fast_round());
-        // record how many times rpc was invoked.
-        let counter = Arc::new(Mutex::new(0));
-        let connects = init_mocked_connects(5, |id, conn| {
-            let err = early_err.clone();
-            let counter_c = Arc::clone(&counter);
-            conn.expect_propose()
-                .return_once(move |_req, _token, _timeout| {
-                    counter_c.lock().unwrap().add_assign(1);
-                    Err(err)
-                });
-            let err = early_err.clone();
-            let counter_c = Arc::clone(&counter);
-            conn.expect_wait_synced()
-                .return_once(move |_req, _timeout| {
-                    assert!(id == 0, "wait synced should send to leader");
-                    counter_c.lock().unwrap().add_assign(1);
-                    Err(err)
-                });
-        });
-        let unary = init_unary_client(connects, None, Some(0), 1, 0, None);
-        let err = unary
-            .propose(&TestCommand::default(), None, true)
-            .await
-            .unwrap_err();
-        assert_eq!(err, early_err);
-        assert_eq!(*counter.lock().unwrap(), 1);
-    }
+async fn test_connection_pool_reconnects_when_addrs_change() {
+    let pool = ConnectionPool::new();
+    let addrs = vec!["127.0.0.1:20000".to_owned(), "127.0.0.1:20010".to_owned()];
+    let conn0 = pool.connect(0, addrs.clone(), None).await.unwrap();
+    let reversed = addrs.into_iter().rev().collect();
+    let conn1 = pool.connect(0, reversed, None).await.unwrap();
+    let conn2 = pool
+        .connect(0, vec!["127.0.0.1:20020".to_owned()], None)
+        .await
+        .unwrap();
+    assert!(std::ptr::eq(
+        Arc::as_ptr(&conn0).cast::<()>(),
+        Arc::as_ptr(&conn1).cast::<()>()
+    ));
+    assert!(!std::ptr::eq(
+        Arc::as_ptr(&conn0).cast::<()>(),
+        Arc::as_ptr(&conn2).cast::<()>()
+    ));
+    assert_eq!(pool.len(), 2);
 }
 
-// Tests for retry layer
-
 #[traced_test]
 #[tokio::test]
-async fn test_retry_propose_return_no_retry_error() {
-    for early_err in [
-        CurpError::shutting_down(),
-        CurpError::invalid_config(),
-        CurpError::node_already_exists(),
-        CurpError::node_not_exist(),
-        CurpError::learner_not_catch_up(),
-    ] {
-        // record how many times rpc was invoked.
-        let counter = Arc::new(Mutex::new(0));
-        let connects = init_mocked_connects(5, |id, conn| {
-            let err = early_err.clone();
-            let counter_c = Arc::clone(&counter);
-            conn.expect_propose()
-                .return_once(move |_req, _token, _timeout| {
-                    counter_c.lock().unwrap().add_assign(1);
-                    Err(err)
-                });
-            let err = early_err.clone();
-            let counter_c = Arc::clone(&counter);
-            conn.expect_wait_synced()
-                .return_once(move |_req, _timeout| {
-                    assert!(id == 0, "wait synced should send to leader");
-                    counter_c.lock().unwrap().add_assign(1);
-                    Err(err)
-                });
-        });
-        let unary = init_unary_client(connects, None, Some(0), 1, 0, None);
-        let retry = Retry::new(
-            unary,
-            RetryConfig::new_fixed(Duration::from_millis(100), 5),
-            None,
-        );
-        let err = retry
-            .propose(&TestCommand::default(), None, false)
+async fn test_connection_pool_shares_connects_between_clients() {
+    let all_members: HashMap<ServerId, Vec<String>> = HashMap::from([
+        (0, vec!["127.0.0.1:20000".to_owned()]),
+        (1, vec!["127.0.0.1:20001".to_owned()]),
+        (2, vec!["127.0.0.1:20002".to_owned()]),
+    ]);
+    let pool = Arc::new(ConnectionPool::new());
+    let mut builder = StateBuilder::new(all_members, None);
+    builder.set_connection_pool(Arc::clone(&pool));
+    let state0 = Arc::new(builder.clone().build().await.unwrap());
+    let state1 = Arc::new(builder.build().await.unwrap());
+    // only one connection is established for each member
+    assert_eq!(pool.len(), 3);
+    for id in 0..3 {
+        let conn0 = state0
+            .map_server(id, |conn| async move { Ok(conn) })
             .await
-            .unwrap_err();
-        assert_eq!(err.message(), tonic::Status::from(early_err).message());
-        // fast path + slow path = 2
-        assert_eq!(*counter.lock().unwrap(), 2);
+            .unwrap();
+        let conn1 = state1
+            .map_server(id, |conn| async move { Ok(conn) })
+            .await
+            .unwrap();
+        assert!(std::ptr::eq(
+            Arc::as_ptr(&conn0).cast::<()>(),
+            Arc::as_ptr(&conn1).cast::<()>()
+        ));
     }
+    let unary0 = Unary::<TestCommand>::new(
+        state0,
+        UnaryConfig::new(Duration::from_secs(0), Duration::from_secs(0)),
+    );
+    let unary1 = Unary::<TestCommand>::new(
+        state1,
+        UnaryConfig::new(Duration::from_secs(0), Duration::from_secs(0)),
+    );
+    drop(unary0);
+    assert_eq!(pool.len(), 3);
+    // connections are closed after the last client is dropped
+    drop(unary1);
+    assert!(pool.is_empty());
 }
 
 #[traced_test]
 #[tokio::test]
-async fn test_retry_propose_return_retry_error() {
-    for early_err in [
-        CurpError::expired_client_id(),
-        CurpError::key_conflict(),
-        CurpError::RpcTransport(()),
-        CurpError::internal("No reason"),
+async fn test_retry_redirect_to_unknown_leader_rediscovers() {
+    for redirect in [
+        CurpError::redirect(None, 2),
+        CurpError::redirect(Some(9), 2),
     ] {
-        let connects = init_mocked_connects(5, |id, conn| {
-            let err = early_err.clone();
+        let connects = init_mocked_connects(3, |id, conn| {
             conn.expect_fetch_cluster()
+                .times(1)
                 .returning(move |_req, _timeout| {
                     Ok(tonic::Response::new(FetchClusterResponse {
                         leader_id: Some(0),
@@ -697,19 +1919,22 @@ async fn test_retry_propose_return_retry_error() {
                             Member::new(0, "S0", vec!["A0".to_owned()], [], false),
                             Member::new(1, "S1", vec!["A1".to_owned()], [], false),
                             Member::new(2, "S2", vec!["A2".to_owned()], [], false),
-                            Member::new(3, "S3", vec!["A3".to_owned()], [], false),
-                            Member::new(4, "S4", vec!["A4".to_owned()], [], false),
                         ],
                         cluster_version: 1,
                     }))
                 });
-            conn.expect_propose()
-                .returning(move |_req, _token, _timeout| Err(err.clone()));
             if id == 0 {
-                let err = early_err.clone();
-                conn.expect_wait_synced()
-                    .times(5) // wait synced should be retried in 5 times on leader
-                    .returning(move |_req, _timeout| Err(err.clone()));
+                let redirect = redirect.clone();
+                let mut redirected = false;
+                conn.expect_shutdown()
+                    .times(2) // only one retry after the rediscovery
+                    .returning(move |_req, _timeout| {
+                        if redirected {
+                            return Ok(tonic::Response::new(ShutdownResponse::default()));
+                        }
+                        redirected = true;
+                        Err(redirect.clone())
+                    });
             }
         });
         let unary = init_unary_client(connects, None, Some(0), 1, 0, None);
@@ -718,14 +1943,119 @@ async fn test_retry_propose_return_retry_error() {
             RetryConfig::new_fixed(Duration::from_millis(10), 5),
             None,
         );
-        let err = retry
-            .propose(&TestCommand::default(), None, false)
-            .await
-            .unwrap_err();
-        assert!(err.message().contains("request timeout"));
+        retry.propose_shutdown().await.unwrap();
     }
 }
 
+#[traced_test]
+#[tokio::test]
+async fn test_retry_redirect_to_known_leader_follows_it() {
+    let connects = init_mocked_connects(3, |id, conn| {
+        conn.expect_fetch_cluster().never();
+        match id {
+            0 => {
+                conn.expect_shutdown()
+                    .times(1)
+                    .returning(|_req, _timeout| Err(CurpError::redirect(Some(1), 2)));
+            }
+            1 => {
+                conn.expect_shutdown().times(1).returning(|_req, _timeout| {
+                    Ok(tonic::Response::new(ShutdownResponse::default()))
+                });
+            }
+            _ => {
+                conn.expect_shutdown().never();
+            }
+        }
+    });
+    let unary = init_unary_client(connects, None, Some(0), 1, 0, None);
+    let retry = Retry::new(
+        unary,
+        RetryConfig::new_fixed(Duration::from_millis(10), 5),
+        None,
+    );
+    // the retry goes to the new leader without rediscovery
+    retry.propose_shutdown().await.unwrap();
+}
+
+#[traced_test]
+#[tokio::test]
+async fn test_retry_conf_change_rejects_stale_cluster_version() {
+    let connects = init_mocked_connects(5, |id, conn| {
+        if id == 0 {
+            conn.expect_propose_conf_change()
+                .times(1)
+                .returning(|req, _timeout| {
+                    // the cluster has been reconfigured to version 2
+                    assert_eq!(req.cluster_version, 1);
+                    Err(CurpError::wrong_cluster_version())
+                });
+        }
+        // the client should not refresh the cluster and retry
+        conn.expect_fetch_cluster().never();
+    });
+    let unary = init_unary_client(connects, None, Some(0), 1, 2, None);
+    let retry = Retry::new(
+        unary,
+        RetryConfig::new_fixed(Duration::from_millis(10), 5),
+        None,
+    );
+    let err = retry
+        .propose_conf_change_if_version(vec![ConfChange::add(5, vec!["A5".to_owned()])], 1)
+        .await
+        .unwrap_err();
+    assert_eq!(
+        err.message(),
+        tonic::Status::from(CurpError::wrong_cluster_version()).message()
+    );
+}
+
+#[traced_test]
+#[tokio::test]
+async fn test_retry_rejects_conf_change_unsupported_by_server() {
+    let connects = init_mocked_connects(5, |_id, conn| {
+        conn.expect_fetch_cluster()
+            .returning(move |_req, _timeout| {
+                let mut resp = tonic::Response::new(FetchClusterResponse {
+                    leader_id: Some(0),
+                    term: 1,
+                    cluster_id: 123,
+                    members: vec![
+                        Member::new(0, "S0", vec!["A0".to_owned()], [], false),
+                        Member::new(1, "S1", vec!["A1".to_owned()], [], false),
+                        Member::new(2, "S2", vec!["A2".to_owned()], [], false),
+                        Member::new(3, "S3", vec!["A3".to_owned()], [], false),
+                        Member::new(4, "S4", vec!["A4".to_owned()], [], false),
+                    ],
+                    cluster_version: 1,
+                });
+                // an old server without membership changes
+                ServerFeatures::new("0.1.0", [ServerFeature::MoveLeader])
+                    .inject(resp.metadata_mut());
+                Ok(resp)
+            });
+        // the client should reject the change without sending it
+        conn.expect_propose_conf_change().never();
+    });
+    let unary = init_unary_client(connects, None, Some(0), 1, 1, None);
+    let retry = Retry::new(
+        unary,
+        RetryConfig::new_fixed(Duration::from_millis(10), 5),
+        None,
+    );
+    let _cluster = retry.fetch_cluster(true).await.unwrap();
+    let features = retry.server_features();
+    assert_eq!(features.version.as_deref(), Some("0.1.0"));
+    assert!(!features.supports(ServerFeature::ConfChange));
+
+    let err = retry
+        .propose_conf_change(vec![ConfChange::add(5, vec!["A5".to_owned()])])
+        .await
+        .unwrap_err();
+    assert_eq!(err.code(), tonic::Code::Unimplemented);
+    assert!(CurpError::from(err).is_unsupported_by_server());
+}
+
 // Tests for stream client
 
 struct MockedStreamConnectApi {
@@ -756,6 +2086,17 @@ impl ConnectApi for MockedStreamConnectApi {
         unreachable!("please use MockedConnectApi")
     }
 
+    /// Send `ProposeRequest` whose command is compressed by `codec`
+    async fn propose_compressed(
+        &self,
+        _request: ProposeRequest,
+        _codec: CompressionCodec,
+        _token: Option<String>,
+        _timeout: Duration,
+    ) -> Result<tonic::Response<ProposeResponse>, CurpError> {
+        unreachable!("please use MockedConnectApi")
+    }
+
     /// Send `ProposeConfChange`
     async fn propose_conf_change(
         &self,
@@ -954,3 +2295,58 @@ async fn test_stream_client_keep_alive_resume_on_leadership_changed() {
     }
     assert_eq!(stream.state.client_id(), 10);
 }
+
+#[traced_test]
+#[tokio::test]
+async fn test_leader_task_runs_only_while_local_server_is_leader() {
+    let leader = Arc::new(AtomicU64::new(0));
+    let term = Arc::new(AtomicU64::new(1));
+    let connects = init_mocked_connects(3, |_id, conn| {
+        let (leader, term) = (Arc::clone(&leader), Arc::clone(&term));
+        conn.expect_fetch_cluster()
+            .returning(move |_req, _timeout| {
+                let members = (0..3)
+                    .map(|i| Member::new(i, format!("S{i}"), vec![format!("A{i}")], [], false))
+                    .collect();
+                Ok(tonic::Response::new(FetchClusterResponse {
+                    leader_id: Some(leader.load(std::sync::atomic::Ordering::SeqCst)),
+                    term: term.load(std::sync::atomic::Ordering::SeqCst),
+                    cluster_id: 123,
+                    members,
+                    cluster_version: 0,
+                }))
+            });
+    });
+    let unary = init_unary_client(connects, Some(0), Some(0), 1, 0, None);
+    let retry = Arc::new(Retry::new(
+        unary,
+        RetryConfig::new_fixed(Duration::from_millis(10), 5),
+        None,
+    ));
+    let runs = Arc::new(AtomicU64::new(0));
+    let runs_c = Arc::clone(&runs);
+    let task = LeaderTask::spawn(retry, Duration::from_millis(10), move || {
+        let _prev = runs_c.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        futures::future::ready(())
+    });
+    let runs = move || runs.load(std::sync::atomic::Ordering::SeqCst);
+
+    // the local server is the leader
+    tokio::time::sleep(Duration::from_millis(300)).await;
+    assert!(runs() > 0);
+
+    // the leadership is lost, the task stops at the next step
+    leader.store(1, std::sync::atomic::Ordering::SeqCst);
+    term.store(2, std::sync::atomic::Ordering::SeqCst);
+    tokio::time::sleep(Duration::from_millis(20)).await;
+    let stopped = runs();
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    assert_eq!(runs(), stopped);
+
+    // the local server becomes the leader again
+    leader.store(0, std::sync::atomic::Ordering::SeqCst);
+    term.store(3, std::sync::atomic::Ordering::SeqCst);
+    tokio::time::sleep(Duration::from_millis(300)).await;
+    assert!(runs() > stopped);
+    task.stop();
+}
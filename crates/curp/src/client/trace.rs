@@ -0,0 +1,42 @@
+use std::collections::VecDeque;
+
+use curp_external_api::cmd::Command;
+use parking_lot::Mutex;
+
+use super::TraceEntry;
+
+/// A ring buffer of the latest operation traces of a client
+#[derive(Debug)]
+pub(super) struct Tracer<C: Command> {
+    /// Max number of traces kept
+    capacity: usize,
+    /// The latest traces, ordered by completion
+    entries: Mutex<VecDeque<TraceEntry<C>>>,
+}
+
+impl<C: Command> Tracer<C> {
+    /// Create a tracer which keeps at most `capacity` traces
+    pub(super) fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            entries: Mutex::new(VecDeque::with_capacity(capacity)),
+        }
+    }
+
+    /// Record a trace, the oldest one is dropped if the buffer is full
+    pub(super) fn record(&self, entry: TraceEntry<C>) {
+        if self.capacity == 0 {
+            return;
+        }
+        let mut entries = self.entries.lock();
+        if entries.len() >= self.capacity {
+            let _ig = entries.pop_front();
+        }
+        entries.push_back(entry);
+    }
+
+    /// Export the recorded traces, from the oldest to the latest
+    pub(super) fn export(&self) -> Vec<TraceEntry<C>> {
+        self.entries.lock().iter().cloned().collect()
+    }
+}
@@ -1,31 +1,52 @@
-use std::{cmp::Ordering, marker::PhantomData, ops::AddAssign, sync::Arc, time::Duration};
+use std::{
+    cmp::Ordering,
+    collections::HashMap,
+    marker::PhantomData,
+    ops::AddAssign,
+    sync::{atomic::AtomicU64, Arc},
+    time::{Duration, Instant},
+};
 
 use async_trait::async_trait;
 use curp_external_api::cmd::Command;
 use futures::{Future, StreamExt};
-use tonic::Response;
-use tracing::{debug, warn};
+use parking_lot::Mutex;
+use tokio::sync::oneshot;
+use tracing::{debug, info, warn};
 
-use super::{state::State, ClientApi, LeaderStateUpdate, ProposeResponse, RepeatableClientApi};
+use super::{
+    failure_detector::PhiAccrual, state::State, ClientApi, LeaderStateUpdate, ProposeCallback,
+    ProposeResponse, RepeatableClientApi,
+};
 use crate::{
     members::ServerId,
     quorum,
     rpc::{
-        connect::ConnectApi, ConfChange, CurpError, FetchClusterRequest, FetchClusterResponse,
-        FetchReadStateRequest, Member, MoveLeaderRequest, ProposeConfChangeRequest, ProposeId,
-        ProposeRequest, PublishRequest, ReadState, ShutdownRequest, WaitSyncedRequest,
+        connect::ConnectApi, ClientHints, CompressionCodec, ConfChange, CurpError,
+        FetchClusterRequest, FetchClusterResponse, FetchReadStateRequest, Member,
+        MoveLeaderRequest, ProposeCompression, ProposeConfChangeRequest, ProposeId, ProposeRequest,
+        PublishRequest, ReadState, ServerFeature, ServerFeatures, ServerLoad, ShutdownRequest,
+        WaitSyncedRequest,
     },
     super_quorum,
 };
 
 /// The unary client config
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub(super) struct UnaryConfig {
     /// The rpc timeout of a propose request
     propose_timeout: Duration,
     /// The rpc timeout of a 2-RTT request, usually takes longer than propose timeout
     /// The recommended the values is within (propose_timeout, 2 * propose_timeout].
     wait_synced_timeout: Duration,
+    /// The min number of reachable servers before the client is ready
+    min_ready_connects: usize,
+    /// The number of acks required by the fast path, `None` means the super quorum
+    fast_quorum: Option<usize>,
+    /// Compression of the commands of proposes, `None` means never compress
+    propose_compression: Option<ProposeCompression>,
+    /// Whether a batch of proposes is grouped by conflicts, otherwise it is proposed one by one
+    batch_by_conflicts: bool,
 }
 
 impl UnaryConfig {
@@ -34,8 +55,36 @@ impl UnaryConfig {
         Self {
             propose_timeout,
             wait_synced_timeout,
+            min_ready_connects: 1,
+            fast_quorum: None,
+            propose_compression: None,
+            batch_by_conflicts: true,
         }
     }
+
+    /// Set the min number of reachable servers before the client is ready
+    pub(super) fn with_min_ready_connects(mut self, min_ready_connects: usize) -> Self {
+        self.min_ready_connects = min_ready_connects;
+        self
+    }
+
+    /// Set the number of acks required by the fast path
+    pub(super) fn with_fast_quorum(mut self, fast_quorum: usize) -> Self {
+        self.fast_quorum = Some(fast_quorum);
+        self
+    }
+
+    /// Set the compression of the commands of proposes
+    pub(super) fn with_propose_compression(mut self, compression: ProposeCompression) -> Self {
+        self.propose_compression = Some(compression);
+        self
+    }
+
+    /// Set whether a batch of proposes is grouped by conflicts
+    pub(super) fn with_batch_by_conflicts(mut self, batch_by_conflicts: bool) -> Self {
+        self.batch_by_conflicts = batch_by_conflicts;
+        self
+    }
 }
 
 /// The unary client
@@ -45,6 +94,15 @@ pub(super) struct Unary<C: Command> {
     state: Arc<State>,
     /// Unary config
     config: UnaryConfig,
+    /// The latest load piggybacked on propose responses of each server, shared with the
+    /// detached copies of the client
+    loads: Arc<Mutex<HashMap<ServerId, ServerLoad>>>,
+    /// Failure detectors of each server, fed by successful responses
+    detectors: Arc<Mutex<HashMap<ServerId, PhiAccrual>>>,
+    /// Client settings recommended by servers, which take precedence over the config
+    hints: Arc<Mutex<ClientHints>>,
+    /// Version and features reported by the latest fetch cluster response which reports them
+    features: Arc<Mutex<ServerFeatures>>,
     /// marker
     phantom: PhantomData<C>,
 }
@@ -55,10 +113,125 @@ impl<C: Command> Unary<C> {
         Self {
             state,
             config,
+            loads: Arc::new(Mutex::new(HashMap::new())),
+            detectors: Arc::new(Mutex::new(HashMap::new())),
+            hints: Arc::new(Mutex::new(ClientHints::default())),
+            features: Arc::new(Mutex::new(ServerFeatures::default())),
             phantom: PhantomData,
         }
     }
 
+    /// Create a copy of the client sharing all its state, which can outlive a call of it
+    fn detach(&self) -> Self {
+        Self {
+            state: Arc::clone(&self.state),
+            config: self.config.clone(),
+            loads: Arc::clone(&self.loads),
+            detectors: Arc::clone(&self.detectors),
+            hints: Arc::clone(&self.hints),
+            features: Arc::clone(&self.features),
+            phantom: PhantomData,
+        }
+    }
+
+    /// Get the rpc timeout of a propose request
+    fn propose_timeout(&self) -> Duration {
+        self.hints
+            .lock()
+            .propose_timeout
+            .unwrap_or(self.config.propose_timeout)
+    }
+
+    /// Get the rpc timeout of a 2-RTT request
+    fn wait_synced_timeout(&self) -> Duration {
+        self.hints
+            .lock()
+            .wait_synced_timeout
+            .unwrap_or(self.config.wait_synced_timeout)
+    }
+
+    /// Get the number of acks required by the fast path in a cluster of `size`. Return
+    /// `InvalidConfig` if the configured value is out of `[super_quorum, size]`, as a smaller
+    /// one can not keep the recovery safe and a larger one can never be met.
+    fn fast_quorum(&self, size: usize) -> Result<usize, CurpError> {
+        let min = super_quorum(size);
+        let Some(n) = self.hints.lock().fast_quorum.or(self.config.fast_quorum) else {
+            return Ok(min);
+        };
+        if n < min || n > size {
+            warn!("fast quorum {n} is out of [{min}, {size}] for a cluster of {size}");
+            return Err(CurpError::invalid_config());
+        }
+        Ok(n)
+    }
+
+    /// Adopt the client settings recommended by the leader, which replace the previous ones
+    /// entirely. A response without hints clears them, so that the client falls back to its
+    /// own config once the operator removes the hints.
+    fn adopt_hints(&self, hints: ClientHints) {
+        let mut current = self.hints.lock();
+        if *current != hints {
+            info!("client adopts the settings recommended by the server: {hints:?}");
+            *current = hints;
+        }
+    }
+
+    /// Adopt the version and features reported by a server
+    fn adopt_features(&self, features: ServerFeatures) {
+        if !features.is_reported() {
+            return;
+        }
+        let mut current = self.features.lock();
+        if *current != features {
+            info!("client observes the server features: {features:?}");
+            *current = features;
+        }
+    }
+
+    /// Check that the servers support `feature` before sending a request requiring it. A
+    /// server which does not report its features is assumed to support it with a warning.
+    fn require_feature(&self, feature: ServerFeature) -> Result<(), CurpError> {
+        let features = self.features.lock();
+        if !features.is_reported() {
+            warn!("the server does not report its features, {feature:?} may be unsupported");
+            return Ok(());
+        }
+        if !features.supports(feature) {
+            return Err(CurpError::unsupported_by_server(feature));
+        }
+        Ok(())
+    }
+
+    /// Compress the command of `req` by the configured compression, return the codec if it is
+    /// compressed. Commands are never compressed before the servers report the support, since
+    /// a server predating the compression cannot decode them.
+    fn compress(&self, req: &mut ProposeRequest) -> Option<CompressionCodec> {
+        let compression = self.config.propose_compression?;
+        let features = self.features.lock();
+        if !features.is_reported() || !features.supports(ServerFeature::ProposeCompression) {
+            return None;
+        }
+        compression.compress(req)
+    }
+
+    /// Record a request sent to the server, the server is suspected if it keeps silent
+    fn expect_heartbeat(&self, id: ServerId) {
+        self.detectors
+            .lock()
+            .entry(id)
+            .or_default()
+            .request(Instant::now());
+    }
+
+    /// Record a successful response of the server
+    fn heartbeat(&self, id: ServerId) {
+        self.detectors
+            .lock()
+            .entry(id)
+            .or_default()
+            .heartbeat(Instant::now());
+    }
+
     /// Get a handle `f` and apply to the leader
     /// NOTICE:
     /// The leader might be outdate if the local state is stale.
@@ -79,32 +252,67 @@ impl<C: Command> Unary<C> {
     }
 
     /// Send proposal to all servers
+    ///
+    /// Responses are handled in arrival order, so the round returns as soon as the
+    /// earliest super quorum including the execution result is assembled, without
+    /// waiting for the remaining replicas.
     pub(super) async fn fast_round(
         &self,
         propose_id: ProposeId,
         cmd: &C,
         token: Option<&String>,
     ) -> Result<Result<C::ER, C::Error>, CurpError> {
-        let req = ProposeRequest::new(propose_id, cmd, self.state.cluster_version().await);
-        let timeout = self.config.propose_timeout;
+        self.fast_round_served(propose_id, cmd, token)
+            .await
+            .map(|(res, _id)| res)
+    }
+
+    /// Fast round like [`Self::fast_round`], also return the server which returned the
+    /// execution result
+    async fn fast_round_served(
+        &self,
+        propose_id: ProposeId,
+        cmd: &C,
+        token: Option<&String>,
+    ) -> Result<(Result<C::ER, C::Error>, ServerId), CurpError> {
+        let mut req = ProposeRequest::new(propose_id, cmd, self.state.cluster_version().await);
+        let codec = self.compress(&mut req);
+        let timeout = self.propose_timeout();
 
         let mut responses = self
             .state
             .for_each_server(|conn| {
+                self.expect_heartbeat(conn.id());
                 let req_c = req.clone();
                 let token_c = token.cloned();
-                async move { (conn.id(), conn.propose(req_c, token_c, timeout).await) }
+                async move {
+                    let resp = match codec {
+                        Some(codec) => {
+                            conn.propose_compressed(req_c, codec, token_c, timeout)
+                                .await
+                        }
+                        None => conn.propose(req_c, token_c, timeout).await,
+                    };
+                    (conn.id(), resp)
+                }
             })
             .await;
-        let super_quorum = super_quorum(responses.len());
+        // the requests are not sent until the responses are polled
+        let fast_quorum = self.fast_quorum(responses.len())?;
 
         let mut err: Option<CurpError> = None;
-        let mut execute_result: Option<C::ER> = None;
+        let mut execute_result: Option<(C::ER, ServerId)> = None;
         let mut ok_cnt = 0;
 
         while let Some((id, resp)) = responses.next().await {
             let resp = match resp {
-                Ok(resp) => resp.into_inner(),
+                Ok(resp) => {
+                    self.heartbeat(id);
+                    if let Some(load) = ServerLoad::extract(resp.metadata()) {
+                        let _ig = self.loads.lock().insert(id, load);
+                    }
+                    resp.into_inner()
+                }
                 Err(e) => {
                     warn!("propose cmd({propose_id}) to server({id}) error: {e:?}");
                     if e.should_abort_fast_round() {
@@ -127,7 +335,7 @@ impl<C: Command> Unary<C> {
                 };
                 if let Some(er) = er {
                     assert!(execute_result.is_none(), "should not set exe result twice");
-                    execute_result = Some(er);
+                    execute_result = Some((er, id));
                 }
                 ok_cnt.add_assign(1);
                 Ok(())
@@ -144,14 +352,14 @@ impl<C: Command> Unary<C> {
             };
             if let Err(cmd_err) = dr {
                 // got a command execution error early, abort the next requests and return the cmd error
-                return Ok(Err(cmd_err));
+                return Ok((Err(cmd_err), id));
             }
-            // if the propose meets the super quorum and we got the execute result,
+            // if the propose meets the fast quorum and we got the execute result,
             // that means we can safely abort the next requests
-            if ok_cnt >= super_quorum {
-                if let Some(er) = execute_result {
+            if ok_cnt >= fast_quorum {
+                if let Some((er, exe_id)) = execute_result {
                     debug!("fast round for cmd({}) succeed", propose_id);
-                    return Ok(Ok(er));
+                    return Ok((Ok(er), exe_id));
                 }
             }
         }
@@ -173,19 +381,34 @@ impl<C: Command> Unary<C> {
         &self,
         propose_id: ProposeId,
     ) -> Result<Result<(C::ASR, C::ER), C::Error>, CurpError> {
-        let timeout = self.config.wait_synced_timeout;
+        self.slow_round_served(propose_id)
+            .await
+            .map(|(res, _id)| res)
+    }
+
+    /// Slow round like [`Self::slow_round`], also return the leader which returned the result
+    async fn slow_round_served(
+        &self,
+        propose_id: ProposeId,
+    ) -> Result<(Result<(C::ASR, C::ER), C::Error>, ServerId), CurpError> {
+        let timeout = self.wait_synced_timeout();
         let req = WaitSyncedRequest::new(propose_id, self.state.cluster_version().await);
-        let resp = self
-            .map_leader(|conn| async move { conn.wait_synced(req, timeout).await })
-            .await?
-            .into_inner();
-        let synced_res = resp.map_result::<C, _, _>(|res| res).map_err(|ser_err| {
-            warn!("serialize error: {ser_err}");
-            // Same as fast round, we blame the server for the serializing error.
-            CurpError::from(ser_err)
-        })?;
+        let (resp, id) = self
+            .map_leader(|conn| {
+                let id = conn.id();
+                async move { conn.wait_synced(req, timeout).await.map(|resp| (resp, id)) }
+            })
+            .await?;
+        let synced_res = resp
+            .into_inner()
+            .map_result::<C, _, _>(|res| res)
+            .map_err(|ser_err| {
+                warn!("serialize error: {ser_err}");
+                // Same as fast round, we blame the server for the serializing error.
+                CurpError::from(ser_err)
+            })?;
         debug!("slow round for cmd({}) succeed", propose_id);
-        Ok(synced_res)
+        Ok((synced_res, id))
     }
 
     /// New a seq num and record it
@@ -212,7 +435,21 @@ impl<C: Command> ClientApi for Unary<C> {
         use_fast_path: bool,
     ) -> Result<ProposeResponse<C>, CurpError> {
         let propose_id = self.gen_propose_id()?;
-        RepeatableClientApi::propose(self, propose_id, cmd, token, use_fast_path).await
+        RepeatableClientApi::propose(self, propose_id, cmd, token, use_fast_path)
+            .await
+            .map(|(res, _id)| res)
+    }
+
+    /// Send propose and deliver the result to the callback after the command is applied
+    async fn propose_with_callback(
+        &self,
+        cmd: &C,
+        token: Option<&String>,
+        callback: ProposeCallback<C, CurpError>,
+    ) -> Result<ProposeId, CurpError> {
+        let propose_id = self.gen_propose_id()?;
+        RepeatableClientApi::propose_with_callback(self, propose_id, cmd, token, callback).await?;
+        Ok(propose_id)
     }
 
     /// Send propose configuration changes to the cluster
@@ -221,7 +458,23 @@ impl<C: Command> ClientApi for Unary<C> {
         changes: Vec<ConfChange>,
     ) -> Result<Vec<Member>, CurpError> {
         let propose_id = self.gen_propose_id()?;
-        RepeatableClientApi::propose_conf_change(self, propose_id, changes).await
+        RepeatableClientApi::propose_conf_change(self, propose_id, changes, None).await
+    }
+
+    /// Send propose configuration changes to the cluster if the cluster version matches
+    async fn propose_conf_change_if_version(
+        &self,
+        changes: Vec<ConfChange>,
+        expected_cluster_version: u64,
+    ) -> Result<Vec<Member>, CurpError> {
+        let propose_id = self.gen_propose_id()?;
+        RepeatableClientApi::propose_conf_change(
+            self,
+            propose_id,
+            changes,
+            Some(expected_cluster_version),
+        )
+        .await
     }
 
     /// Send propose to shutdown cluster
@@ -244,8 +497,9 @@ impl<C: Command> ClientApi for Unary<C> {
 
     /// Send move leader request
     async fn move_leader(&self, node_id: ServerId) -> Result<(), Self::Error> {
+        self.require_feature(ServerFeature::MoveLeader)?;
         let req = MoveLeaderRequest::new(node_id, self.state.cluster_version().await);
-        let timeout = self.config.wait_synced_timeout;
+        let timeout = self.wait_synced_timeout();
         let _ig = self
             .map_leader(|conn| async move { conn.move_leader(req, timeout).await })
             .await?;
@@ -262,7 +516,7 @@ impl<C: Command> ClientApi for Unary<C> {
                 CurpError::from(ser_err)
             },
         )?;
-        let timeout = self.config.wait_synced_timeout;
+        let timeout = self.wait_synced_timeout();
         let state = self
             .map_leader(|conn| async move { conn.fetch_read_state(req, timeout).await })
             .await?
@@ -275,7 +529,7 @@ impl<C: Command> ClientApi for Unary<C> {
     /// Send fetch cluster requests to all servers
     /// Note: The fetched cluster may still be outdated if `linearizable` is false
     async fn fetch_cluster(&self, linearizable: bool) -> Result<FetchClusterResponse, CurpError> {
-        let timeout = self.config.wait_synced_timeout;
+        let timeout = self.wait_synced_timeout();
         if !linearizable {
             // firstly, try to fetch the local server
             if let Some(connect) = self.state.local_connect().await {
@@ -289,8 +543,14 @@ impl<C: Command> ClientApi for Unary<C> {
                         unreachable!(
                             "fetch cluster from local connect should never failed, err {e:?}"
                         )
-                    })
-                    .into_inner();
+                    });
+                let hints = ClientHints::extract(resp.metadata());
+                self.adopt_features(ServerFeatures::extract(resp.metadata()));
+                let resp = resp.into_inner();
+                // the hints of a follower may be outdated, only the leader is trusted
+                if resp.leader_id == Some(connect.id()) {
+                    self.adopt_hints(hints);
+                }
                 debug!("fetch local cluster {resp:?}");
 
                 return Ok(resp);
@@ -299,25 +559,42 @@ impl<C: Command> ClientApi for Unary<C> {
         // then fetch the whole cluster
         let mut responses = self
             .state
-            .for_each_server(|conn| async move {
-                (
-                    conn.id(),
-                    conn.fetch_cluster(FetchClusterRequest { linearizable }, timeout)
-                        .await
-                        .map(Response::into_inner),
-                )
+            .for_each_server(|conn| {
+                self.expect_heartbeat(conn.id());
+                async move {
+                    (
+                        conn.id(),
+                        conn.fetch_cluster(FetchClusterRequest { linearizable }, timeout)
+                            .await
+                            .map(|resp| {
+                                let hints = ClientHints::extract(resp.metadata());
+                                let features = ServerFeatures::extract(resp.metadata());
+                                (resp.into_inner(), hints, features)
+                            }),
+                    )
+                }
             })
             .await;
         let quorum = quorum(responses.len());
 
         let mut max_term = 0;
+        // the leader reported in the max term, and whether the responses of the max term
+        // disagree on it
+        let mut max_term_leader = None;
+        let mut leader_conflict = false;
+        // the hints of the leader reported in the max term, the only source of the hints
+        let mut leader_hints = None;
         let mut res = None;
         let mut ok_cnt = 0;
         let mut err: Option<CurpError> = None;
 
         while let Some((id, resp)) = responses.next().await {
             let inner = match resp {
-                Ok(r) => r,
+                Ok((r, hints, features)) => {
+                    self.heartbeat(id);
+                    self.adopt_features(features);
+                    (r, hints)
+                }
                 Err(e) => {
                     warn!("fetch cluster from {} failed, {:?}", id, e);
                     // similar to fast round
@@ -334,12 +611,16 @@ impl<C: Command> ClientApi for Unary<C> {
                     continue;
                 }
             };
+            let (inner, hints) = inner;
             // Ignore the response of a node that doesn't know who the leader is.
             if inner.leader_id.is_some() {
                 #[allow(clippy::arithmetic_side_effects)]
                 match max_term.cmp(&inner.term) {
                     Ordering::Less => {
                         max_term = inner.term;
+                        max_term_leader = inner.leader_id;
+                        leader_conflict = false;
+                        leader_hints = (inner.leader_id == Some(id)).then_some(hints);
                         if !inner.members.is_empty() {
                             res = Some(inner);
                         }
@@ -347,6 +628,14 @@ impl<C: Command> ClientApi for Unary<C> {
                         ok_cnt = 1;
                     }
                     Ordering::Equal => {
+                        // only two distinct known leaders conflict
+                        match max_term_leader {
+                            Some(leader) => leader_conflict |= inner.leader_id != Some(leader),
+                            None => max_term_leader = inner.leader_id,
+                        }
+                        if inner.leader_id == Some(id) {
+                            leader_hints = Some(hints);
+                        }
                         if !inner.members.is_empty() {
                             res = Some(inner);
                         }
@@ -357,9 +646,19 @@ impl<C: Command> ClientApi for Unary<C> {
             }
             // first check quorum
             if ok_cnt >= quorum {
+                // a quorum of the same term but different leaders is unresolved, do not pick one
+                // of them arbitrarily and let the caller retry
+                if leader_conflict {
+                    warn!("fetch cluster got different leaders in term {max_term}, retry later");
+                    return Err(CurpError::leader_conflict());
+                }
                 // then check if we got the response
                 if let Some(res) = res {
                     debug!("fetch cluster succeeded, result: {res:?}");
+                    // keep the current hints if the leader does not respond in time
+                    if let Some(hints) = leader_hints {
+                        self.adopt_hints(hints);
+                    }
                     if let Err(e) = self.state.check_and_update(&res).await {
                         warn!("update to a new cluster state failed, error {e}");
                     }
@@ -377,6 +676,86 @@ impl<C: Command> ClientApi for Unary<C> {
         // It seems that the max term has not reached the majority here. Mock a transport error and return it to the external to retry.
         return Err(CurpError::RpcTransport(()));
     }
+
+    /// Wait until at least `min_ready_connects` servers respond to the fetch cluster
+    /// requests of the same round. Return `false` if not enough servers are reachable
+    /// before the timeout.
+    async fn wait_ready(&self, timeout: Duration) -> bool {
+        /// Interval between two rounds of fetches
+        const FETCH_INTERVAL: Duration = Duration::from_millis(100);
+
+        let rpc_timeout = self.propose_timeout();
+        let wait = async {
+            loop {
+                let mut responses = self
+                    .state
+                    .for_each_server(|conn| {
+                        self.expect_heartbeat(conn.id());
+                        async move {
+                            let req = FetchClusterRequest {
+                                linearizable: false,
+                            };
+                            (
+                                conn.id(),
+                                conn.fetch_cluster(req, rpc_timeout).await.is_ok(),
+                            )
+                        }
+                    })
+                    .await;
+                let mut ready: usize = 0;
+                while let Some((id, ok)) = responses.next().await {
+                    if ok {
+                        self.heartbeat(id);
+                        ready = ready.saturating_add(1);
+                    }
+                }
+                if ready >= self.config.min_ready_connects {
+                    debug!("client is ready with {ready} reachable servers");
+                    return;
+                }
+                tokio::time::sleep(FETCH_INTERVAL).await;
+            }
+        };
+        tokio::time::timeout(timeout, wait).await.is_ok()
+    }
+
+    /// Get the cached leader id
+    async fn cached_leader_id(&self) -> Option<ServerId> {
+        self.cached_leader().await
+    }
+
+    /// Get the id of the local server
+    fn local_server_id(&self) -> Option<ServerId> {
+        self.state.local_server()
+    }
+
+    /// Whether a batch of proposes is grouped by conflicts
+    fn batches_by_conflicts(&self) -> bool {
+        self.config.batch_by_conflicts
+    }
+
+    /// Get the heaviest server load piggybacked on the latest propose responses of each server
+    fn server_load(&self) -> Option<ServerLoad> {
+        self.loads
+            .lock()
+            .values()
+            .copied()
+            .reduce(ServerLoad::merge)
+    }
+
+    /// Get the version and features reported by the latest fetch cluster response
+    fn server_features(&self) -> ServerFeatures {
+        self.features.lock().clone()
+    }
+
+    /// Get the suspicion level of the server estimated by the phi accrual failure detector
+    /// fed by successful responses of the server
+    fn suspicion(&self, id: ServerId) -> f64 {
+        self.detectors
+            .lock()
+            .get(&id)
+            .map_or(0.0, |detector| detector.phi(Instant::now()))
+    }
 }
 
 #[async_trait]
@@ -388,6 +767,56 @@ impl<C: Command> RepeatableClientApi for Unary<C> {
         Ok(ProposeId(client_id, seq_num))
     }
 
+    /// Replace the client id of a propose id with the current one
+    fn refresh_propose_id(&self, propose_id: ProposeId) -> ProposeId {
+        ProposeId(self.state.client_id(), propose_id.1)
+    }
+
+    /// Register a new client id to the leader through a fresh keep alive handshake
+    #[allow(clippy::arithmetic_side_effects)] // introduced by tokio::select! macro
+    async fn register_client_id(&self) -> Result<u64, Self::Error> {
+        /// Interval to check whether the new client id is granted
+        const CHECK_INTERVAL: Duration = Duration::from_millis(10);
+
+        let local_server = self.state.local_server_id();
+        if local_server.is_some() && local_server == self.state.leader_id().await {
+            // the bypassed client generates its client id locally
+            let id = rand::random();
+            self.state.set_client_id(id);
+            return Ok(id);
+        }
+        let new_id = Arc::new(AtomicU64::new(0));
+        let new_id_c = Arc::clone(&new_id);
+        let keep_alive = self.map_leader(|conn| async move {
+            // keep alive only returns when an error occurs
+            Err::<(), _>(conn.lease_keep_alive(new_id_c, CHECK_INTERVAL).await)
+        });
+        let granted = async {
+            loop {
+                let id = new_id.load(std::sync::atomic::Ordering::Relaxed);
+                if id != 0 {
+                    break id;
+                }
+                tokio::time::sleep(CHECK_INTERVAL).await;
+            }
+        };
+        let id = tokio::select! {
+            res = keep_alive => {
+                let id = new_id.load(std::sync::atomic::Ordering::Relaxed);
+                if id == 0 {
+                    return Err(res
+                        .err()
+                        .unwrap_or_else(|| unreachable!("keep alive never returns ok")));
+                }
+                id
+            }
+            id = granted => id,
+        };
+        info!("client id is renewed to {id}");
+        self.state.set_client_id(id);
+        Ok(id)
+    }
+
     /// Send propose to the whole cluster, `use_fast_path` set to `false` to fallback into ordered
     /// requests (event the requests are commutative).
     async fn propose(
@@ -396,27 +825,30 @@ impl<C: Command> RepeatableClientApi for Unary<C> {
         cmd: &Self::Cmd,
         token: Option<&String>,
         use_fast_path: bool,
-    ) -> Result<ProposeResponse<Self::Cmd>, Self::Error> {
+    ) -> Result<(ProposeResponse<Self::Cmd>, ServerId), Self::Error> {
         tokio::pin! {
-            let fast_round = self.fast_round(propose_id, cmd, token);
-            let slow_round = self.slow_round(propose_id);
+            let fast_round = self.fast_round_served(propose_id, cmd, token);
+            let slow_round = self.slow_round_served(propose_id);
         }
 
-        let res: ProposeResponse<C> = if use_fast_path {
+        let res: (ProposeResponse<C>, ServerId) = if use_fast_path {
             match futures::future::select(fast_round, slow_round).await {
                 futures::future::Either::Left((fast_result, slow_round)) => match fast_result {
-                    Ok(er) => er.map(|e| {
-                        #[cfg(feature = "client-metrics")]
-                        super::metrics::get().client_fast_path_count.add(1, &[]);
+                    Ok((er, id)) => (
+                        er.map(|e| {
+                            #[cfg(feature = "client-metrics")]
+                            super::metrics::get().client_fast_path_count.add(1, &[]);
 
-                        (e, None)
-                    }),
+                            (e, None)
+                        }),
+                        id,
+                    ),
                     Err(fast_err) => {
                         if fast_err.should_abort_slow_round() {
                             return Err(fast_err);
                         }
                         // fallback to slow round if fast round failed
-                        let sr = match slow_round.await {
+                        let (sr, id) = match slow_round.await {
                             Ok(sr) => sr,
                             Err(slow_err) => {
                                 return Err(std::cmp::max_by_key(fast_err, slow_err, |err| {
@@ -424,7 +856,7 @@ impl<C: Command> RepeatableClientApi for Unary<C> {
                                 }))
                             }
                         };
-                        sr.map(|(asr, er)| {
+                        let sr = sr.map(|(asr, er)| {
                             #[cfg(feature = "client-metrics")]
                             {
                                 super::metrics::get().client_slow_path_count.add(1, &[]);
@@ -434,22 +866,26 @@ impl<C: Command> RepeatableClientApi for Unary<C> {
                             }
 
                             (er, Some(asr))
-                        })
+                        });
+                        (sr, id)
                     }
                 },
                 futures::future::Either::Right((slow_result, fast_round)) => match slow_result {
-                    Ok(er) => er.map(|(asr, e)| {
-                        #[cfg(feature = "client-metrics")]
-                        super::metrics::get().client_slow_path_count.add(1, &[]);
+                    Ok((er, id)) => (
+                        er.map(|(asr, e)| {
+                            #[cfg(feature = "client-metrics")]
+                            super::metrics::get().client_slow_path_count.add(1, &[]);
 
-                        (e, Some(asr))
-                    }),
+                            (e, Some(asr))
+                        }),
+                        id,
+                    ),
                     Err(slow_err) => {
                         if slow_err.should_abort_fast_round() {
                             return Err(slow_err);
                         }
                         // try to poll fast round
-                        let fr = match fast_round.await {
+                        let (fr, id) = match fast_round.await {
                             Ok(fr) => fr,
                             Err(fast_err) => {
                                 return Err(std::cmp::max_by_key(fast_err, slow_err, |err| {
@@ -457,23 +893,27 @@ impl<C: Command> RepeatableClientApi for Unary<C> {
                                 }))
                             }
                         };
-                        fr.map(|er| {
+                        let fr = fr.map(|er| {
                             #[cfg(feature = "client-metrics")]
                             super::metrics::get().client_fast_path_count.add(1, &[]);
 
                             (er, None)
-                        })
+                        });
+                        (fr, id)
                     }
                 },
             }
         } else {
             match futures::future::join(fast_round, slow_round).await {
-                (_, Ok(sr)) => sr.map(|(asr, er)| {
-                    #[cfg(feature = "client-metrics")]
-                    super::metrics::get().client_slow_path_count.add(1, &[]);
+                (_, Ok((sr, id))) => (
+                    sr.map(|(asr, er)| {
+                        #[cfg(feature = "client-metrics")]
+                        super::metrics::get().client_slow_path_count.add(1, &[]);
 
-                    (er, Some(asr))
-                }),
+                        (er, Some(asr))
+                    }),
+                    id,
+                ),
                 (Ok(_), Err(err)) => return Err(err),
                 (Err(fast_err), Err(slow_err)) => {
                     return Err(std::cmp::max_by_key(fast_err, slow_err, |err| {
@@ -486,15 +926,53 @@ impl<C: Command> RepeatableClientApi for Unary<C> {
         Ok(res)
     }
 
+    /// Send propose to the whole cluster and return once the command is committed, which is
+    /// either accepted by the super quorum in the fast round or synced in the slow round
+    async fn propose_with_callback(
+        &self,
+        propose_id: ProposeId,
+        cmd: &C,
+        token: Option<&String>,
+        callback: ProposeCallback<C, CurpError>,
+    ) -> Result<(), CurpError> {
+        let (synced_tx, synced_rx) = oneshot::channel();
+        // the slow round outlives this call, so it runs on a client sharing the same state
+        let detached = self.detach();
+        let _handle = tokio::spawn(async move {
+            let res = detached
+                .slow_round(propose_id)
+                .await
+                .map(|sr| sr.map(|(asr, er)| (er, Some(asr))));
+            let _ig = synced_tx.send(res.as_ref().err().cloned());
+            callback(res);
+        });
+        match self.fast_round(propose_id, cmd, token).await {
+            Ok(_er) => return Ok(()),
+            Err(fast_err) if fast_err.should_abort_slow_round() => return Err(fast_err),
+            Err(fast_err) => debug!("fast round for cmd({propose_id}) failed: {fast_err:?}"),
+        }
+        // the command is committed after the slow round succeeds
+        match synced_rx.await {
+            Ok(None) => Ok(()),
+            Ok(Some(slow_err)) => Err(slow_err),
+            Err(_) => Err(CurpError::internal("the slow round is cancelled")),
+        }
+    }
+
     /// Send propose configuration changes to the cluster
     async fn propose_conf_change(
         &self,
         propose_id: ProposeId,
         changes: Vec<ConfChange>,
+        cluster_version: Option<u64>,
     ) -> Result<Vec<Member>, Self::Error> {
-        let req =
-            ProposeConfChangeRequest::new(propose_id, changes, self.state.cluster_version().await);
-        let timeout = self.config.wait_synced_timeout;
+        self.require_feature(ServerFeature::ConfChange)?;
+        let cluster_version = match cluster_version {
+            Some(version) => version,
+            None => self.state.cluster_version().await,
+        };
+        let req = ProposeConfChangeRequest::new(propose_id, changes, cluster_version);
+        let timeout = self.wait_synced_timeout();
         let members = self
             .map_leader(|conn| async move { conn.propose_conf_change(req, timeout).await })
             .await?
@@ -506,7 +984,7 @@ impl<C: Command> RepeatableClientApi for Unary<C> {
     /// Send propose to shutdown cluster
     async fn propose_shutdown(&self, propose_id: ProposeId) -> Result<(), Self::Error> {
         let req = ShutdownRequest::new(propose_id, self.state.cluster_version().await);
-        let timeout = self.config.wait_synced_timeout;
+        let timeout = self.wait_synced_timeout();
         let _ig = self
             .map_leader(|conn| async move { conn.shutdown(req, timeout).await })
             .await?;
@@ -522,7 +1000,7 @@ impl<C: Command> RepeatableClientApi for Unary<C> {
         node_client_urls: Vec<String>,
     ) -> Result<(), Self::Error> {
         let req = PublishRequest::new(propose_id, node_id, node_name, node_client_urls);
-        let timeout = self.config.wait_synced_timeout;
+        let timeout = self.wait_synced_timeout();
         let _ig = self
             .map_leader(|conn| async move { conn.publish(req, timeout).await })
             .await?;
@@ -536,4 +1014,14 @@ impl<C: Command> LeaderStateUpdate for Unary<C> {
     async fn update_leader(&self, leader_id: Option<ServerId>, term: u64) -> bool {
         self.state.check_and_update_leader(leader_id, term).await
     }
+
+    /// Get the cached leader
+    async fn cached_leader(&self) -> Option<ServerId> {
+        self.state.leader_id().await
+    }
+
+    /// Whether the client has a connection to the server
+    async fn is_connected(&self, id: ServerId) -> bool {
+        self.state.contains_server(id).await
+    }
 }
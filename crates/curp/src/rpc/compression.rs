@@ -0,0 +1,158 @@
+use miniz_oxide::{deflate, inflate};
+use tonic::metadata::{MetadataMap, MetadataValue};
+
+use super::ProposeRequest;
+
+/// Metadata key of the codec of a compressed command in a propose request
+const COMMAND_CODEC_KEY: &str = "curp-command-codec";
+
+/// Compression level of the codecs, which balances the speed and the ratio
+const COMPRESSION_LEVEL: u8 = 6;
+
+/// Max bytes of a decompressed command, a command inflating beyond it is rejected
+const MAX_DECOMPRESSED_COMMAND_BYTES: usize = 64 * 1024 * 1024;
+
+/// Codec compressing the commands of proposes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[non_exhaustive]
+pub enum CompressionCodec {
+    /// Raw deflate
+    #[default]
+    Deflate,
+    /// Deflate with the zlib header and checksum
+    Zlib,
+}
+
+impl CompressionCodec {
+    /// All codecs supported by this build
+    const ALL: [Self; 2] = [Self::Deflate, Self::Zlib];
+
+    /// Name of the codec in metadata
+    fn name(self) -> &'static str {
+        match self {
+            Self::Deflate => "deflate",
+            Self::Zlib => "zlib",
+        }
+    }
+
+    /// Parse a codec from its name
+    fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|codec| codec.name() == name)
+    }
+
+    /// Compress `data`
+    fn compress(self, data: &[u8]) -> Vec<u8> {
+        match self {
+            Self::Deflate => deflate::compress_to_vec(data, COMPRESSION_LEVEL),
+            Self::Zlib => deflate::compress_to_vec_zlib(data, COMPRESSION_LEVEL),
+        }
+    }
+
+    /// Decompress `data`, return `None` if it is corrupted or inflates beyond the limit
+    fn decompress(self, data: &[u8]) -> Option<Vec<u8>> {
+        match self {
+            Self::Deflate => {
+                inflate::decompress_to_vec_with_limit(data, MAX_DECOMPRESSED_COMMAND_BYTES).ok()
+            }
+            Self::Zlib => {
+                inflate::decompress_to_vec_zlib_with_limit(data, MAX_DECOMPRESSED_COMMAND_BYTES)
+                    .ok()
+            }
+        }
+    }
+
+    /// Inject the codec into the metadata of a propose request
+    pub(crate) fn inject(self, metadata: &mut MetadataMap) {
+        let _ig = metadata.insert(COMMAND_CODEC_KEY, MetadataValue::from_static(self.name()));
+    }
+}
+
+/// Compression of the commands of proposes. A command is compressed only if its encoded size
+/// exceeds the threshold, so that small commands are not slowed down by the compression.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProposeCompression {
+    /// Commands larger than it in bytes are compressed
+    threshold: usize,
+    /// The codec compressing commands
+    codec: CompressionCodec,
+}
+
+impl ProposeCompression {
+    /// Create a new `ProposeCompression`
+    #[inline]
+    #[must_use]
+    pub fn new(threshold: usize, codec: CompressionCodec) -> Self {
+        Self { threshold, codec }
+    }
+
+    /// Compress the command of `req` if it exceeds the threshold, return the codec if it is
+    /// compressed. A command which the codec fails to shrink is sent uncompressed.
+    pub(crate) fn compress(&self, req: &mut ProposeRequest) -> Option<CompressionCodec> {
+        if req.command.len() <= self.threshold {
+            return None;
+        }
+        let compressed = self.codec.compress(&req.command);
+        if compressed.len() >= req.command.len() {
+            return None;
+        }
+        req.command = compressed;
+        Some(self.codec)
+    }
+}
+
+/// Decompress the command of a propose request compressed by the client, so that the command
+/// can be decoded. A request which is not compressed is left unchanged.
+///
+/// # Errors
+///
+/// Return `tonic::Status::invalid_argument` if the codec is unknown or the command is corrupted
+#[inline]
+pub fn decompress_propose(
+    request: &mut tonic::Request<ProposeRequest>,
+) -> Result<(), tonic::Status> {
+    let Some(value) = request.metadata_mut().remove(COMMAND_CODEC_KEY) else {
+        return Ok(());
+    };
+    let codec = value
+        .to_str()
+        .ok()
+        .and_then(CompressionCodec::from_name)
+        .ok_or_else(|| tonic::Status::invalid_argument("unknown codec of the command"))?;
+    let command = codec
+        .decompress(&request.get_ref().command)
+        .ok_or_else(|| tonic::Status::invalid_argument("failed to decompress the command"))?;
+    request.get_mut().command = command;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn command_should_be_compressed_only_beyond_the_threshold() {
+        let compression = ProposeCompression::new(64, CompressionCodec::Zlib);
+        let mut small = ProposeRequest {
+            command: vec![1; 64],
+            ..ProposeRequest::default()
+        };
+        assert_eq!(compression.compress(&mut small), None);
+        assert_eq!(small.command, vec![1; 64]);
+
+        let mut large = ProposeRequest {
+            command: vec![1; 1024],
+            ..ProposeRequest::default()
+        };
+        assert_eq!(
+            compression.compress(&mut large),
+            Some(CompressionCodec::Zlib)
+        );
+        assert!(large.command.len() < 1024);
+
+        let mut request = tonic::Request::new(large);
+        CompressionCodec::Zlib.inject(request.metadata_mut());
+        decompress_propose(&mut request).unwrap();
+        assert_eq!(request.get_ref().command, vec![1; 1024]);
+        assert!(request.metadata().get(COMMAND_CODEC_KEY).is_none());
+    }
+}
@@ -30,13 +30,13 @@ use crate::{
             commandpb::protocol_client::ProtocolClient,
             inner_messagepb::inner_protocol_client::InnerProtocolClient,
         },
-        AppendEntriesRequest, AppendEntriesResponse, CurpError, FetchClusterRequest,
-        FetchClusterResponse, FetchReadStateRequest, FetchReadStateResponse,
+        AppendEntriesRequest, AppendEntriesResponse, CompressionCodec, CurpError,
+        FetchClusterRequest, FetchClusterResponse, FetchReadStateRequest, FetchReadStateResponse,
         InstallSnapshotRequest, InstallSnapshotResponse, LeaseKeepAliveMsg, MoveLeaderRequest,
         MoveLeaderResponse, ProposeConfChangeRequest, ProposeConfChangeResponse, ProposeRequest,
         ProposeResponse, Protocol, PublishRequest, PublishResponse, ShutdownRequest,
         ShutdownResponse, TriggerShutdownRequest, TryBecomeLeaderNowRequest, VoteRequest,
-        VoteResponse, WaitSyncedRequest, WaitSyncedResponse,
+        VoteResponse, WaitSyncedRequest, WaitSyncedResponse, LEADER_HINT_KEY,
     },
     snapshot::Snapshot,
 };
@@ -165,6 +165,15 @@ pub(crate) trait ConnectApi: Send + Sync + 'static {
         timeout: Duration,
     ) -> Result<tonic::Response<ProposeResponse>, CurpError>;
 
+    /// Send `ProposeRequest` whose command is compressed by `codec`
+    async fn propose_compressed(
+        &self,
+        request: ProposeRequest,
+        codec: CompressionCodec,
+        token: Option<String>,
+        timeout: Duration,
+    ) -> Result<tonic::Response<ProposeResponse>, CurpError>;
+
     /// Send `ProposeRequest`
     async fn propose_conf_change(
         &self,
@@ -228,10 +237,11 @@ pub(crate) trait InnerConnectApi: Send + Sync + 'static {
     /// Update server addresses, the new addresses will override the old ones
     async fn update_addrs(&self, addrs: Vec<String>) -> Result<(), tonic::transport::Error>;
 
-    /// Send `AppendEntriesRequest`
+    /// Send `AppendEntriesRequest`, with the leader hint piggybacked
     async fn append_entries(
         &self,
         request: AppendEntriesRequest,
+        leader_hint: u64,
         timeout: Duration,
     ) -> Result<tonic::Response<AppendEntriesResponse>, tonic::Status>;
 
@@ -399,6 +409,26 @@ impl ConnectApi for Connect<ProtocolClient<Channel>> {
         client.propose(req).await.map_err(Into::into)
     }
 
+    /// Send `ProposeRequest` whose command is compressed by `codec`
+    #[instrument(skip(self), name = "client propose compressed")]
+    async fn propose_compressed(
+        &self,
+        request: ProposeRequest,
+        codec: CompressionCodec,
+        token: Option<String>,
+        timeout: Duration,
+    ) -> Result<tonic::Response<ProposeResponse>, CurpError> {
+        let mut client = self.rpc_connect.clone();
+        let mut req = tonic::Request::new(request);
+        req.set_timeout(timeout);
+        req.metadata_mut().inject_current();
+        codec.inject(req.metadata_mut());
+        if let Some(token) = token {
+            _ = req.metadata_mut().insert("token", token.parse()?);
+        }
+        client.propose(req).await.map_err(Into::into)
+    }
+
     /// Send `ShutdownRequest`
     #[instrument(skip(self), name = "client shutdown")]
     async fn shutdown(
@@ -523,10 +553,11 @@ impl InnerConnectApi for Connect<InnerProtocolClient<Channel>> {
         self.inner_update_addrs(addrs).await
     }
 
-    /// Send `AppendEntriesRequest`
+    /// Send `AppendEntriesRequest`, with the leader hint piggybacked
     async fn append_entries(
         &self,
         request: AppendEntriesRequest,
+        leader_hint: u64,
         timeout: Duration,
     ) -> Result<tonic::Response<AppendEntriesResponse>, tonic::Status> {
         #[cfg(feature = "client-metrics")]
@@ -535,6 +566,9 @@ impl InnerConnectApi for Connect<InnerProtocolClient<Channel>> {
         let mut client = self.rpc_connect.clone();
         let mut req = tonic::Request::new(request);
         req.set_timeout(timeout);
+        let _ig = req
+            .metadata_mut()
+            .insert(LEADER_HINT_KEY, leader_hint.into());
         let result = client.append_entries(req).await;
 
         #[cfg(feature = "client-metrics")]
@@ -678,9 +712,11 @@ where
         &self,
         request: ProposeRequest,
         token: Option<String>,
-        _timeout: Duration,
+        timeout: Duration,
     ) -> Result<tonic::Response<ProposeResponse>, CurpError> {
         let mut req = tonic::Request::new(request);
+        // nothing enforces the timeout of a bypassed request but the server itself
+        req.set_timeout(timeout);
         req.metadata_mut().inject_bypassed();
         req.metadata_mut().inject_current();
         if let Some(token) = token {
@@ -689,6 +725,26 @@ where
         self.server.propose(req).await.map_err(Into::into)
     }
 
+    /// Send `ProposeRequest` whose command is compressed by `codec`
+    async fn propose_compressed(
+        &self,
+        request: ProposeRequest,
+        codec: CompressionCodec,
+        token: Option<String>,
+        timeout: Duration,
+    ) -> Result<tonic::Response<ProposeResponse>, CurpError> {
+        let mut req = tonic::Request::new(request);
+        // nothing enforces the timeout of a bypassed request but the server itself
+        req.set_timeout(timeout);
+        req.metadata_mut().inject_bypassed();
+        req.metadata_mut().inject_current();
+        codec.inject(req.metadata_mut());
+        if let Some(token) = token {
+            _ = req.metadata_mut().insert("token", token.parse()?);
+        }
+        self.server.propose(req).await.map_err(Into::into)
+    }
+
     /// Send `PublishRequest`
     async fn publish(
         &self,
@@ -720,9 +776,10 @@ where
     async fn wait_synced(
         &self,
         request: WaitSyncedRequest,
-        _timeout: Duration,
+        timeout: Duration,
     ) -> Result<tonic::Response<WaitSyncedResponse>, CurpError> {
         let mut req = tonic::Request::new(request);
+        req.set_timeout(timeout);
         req.metadata_mut().inject_bypassed();
         req.metadata_mut().inject_current();
         self.server.wait_synced(req).await.map_err(Into::into)
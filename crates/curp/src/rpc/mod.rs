@@ -1,4 +1,8 @@
-use std::{collections::HashMap, sync::Arc};
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+    time::Duration,
+};
 
 use curp_external_api::{
     cmd::{ConflictCheck, PbCodec, PbSerializeError},
@@ -58,6 +62,10 @@ mod metrics;
 pub(crate) mod connect;
 pub(crate) use connect::{connect, connects, inner_connects};
 
+/// Compression of propose commands
+mod compression;
+pub use compression::{decompress_propose, CompressionCodec, ProposeCompression};
+
 // Skip for generated code
 #[allow(
     clippy::all,
@@ -618,6 +626,62 @@ impl PublishRequest {
     }
 }
 
+/// Metadata key of the kind of a `CurpError` carried by a `tonic::Status`
+const CURP_ERROR_KIND_KEY: &str = "curp-error-kind";
+
+/// Metadata key of the leader id carried by a redirect error
+const REDIRECT_LEADER_ID_KEY: &str = "curp-redirect-leader-id";
+
+/// Metadata key of the term carried by a redirect error
+const REDIRECT_TERM_KEY: &str = "curp-redirect-term";
+
+/// Reason prefix of the `Internal` error returned when the server lacks a required feature
+const UNSUPPORTED_BY_SERVER_REASON: &str = "unsupported by server: ";
+
+/// Kind of a `CurpError::Internal` error which has its own handling. The protocol has no
+/// dedicated errors for them and the protos can not be changed, so each kind is carried by
+/// an `Internal` error with a reserved reason.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum InternalErrorKind {
+    /// The deadline of the request expires before its result is ready, the command may
+    /// still be applied. It is never retried as the caller already gives up.
+    DeadlineExceeded,
+    /// The client exceeds its rate limit, the command is not applied. It is only retried
+    /// after an exponential backoff, which is not affected by the retry policy of the client.
+    RateLimited,
+    /// A quorum of servers report different leaders in the same term, so the leader is
+    /// unresolved. It is retried, as the servers converge once the election completes.
+    LeaderConflict,
+}
+
+impl InternalErrorKind {
+    /// All kinds
+    const ALL: [Self; 3] = [
+        Self::DeadlineExceeded,
+        Self::RateLimited,
+        Self::LeaderConflict,
+    ];
+
+    /// The reserved reason of `Internal` errors of this kind
+    fn reason(self) -> &'static str {
+        match self {
+            Self::DeadlineExceeded => "curp internal: deadline exceeded",
+            Self::RateLimited => "curp internal: rate limited",
+            Self::LeaderConflict => "curp internal: leader conflict",
+        }
+    }
+
+    /// The machine-readable name of this kind
+    fn name(self) -> &'static str {
+        match self {
+            Self::DeadlineExceeded => "deadline_exceeded",
+            Self::RateLimited => "rate_limited",
+            Self::LeaderConflict => "leader_conflict",
+        }
+    }
+}
+
 /// NOTICE:
 /// Please check test case `test_unary_fast_round_return_early_err` `test_unary_propose_return_early_err`
 /// `test_retry_propose_return_no_retry_error` `test_retry_propose_return_retry_error` if you added some
@@ -679,6 +743,113 @@ impl CurpError {
         Self::Internal(reason.into())
     }
 
+    /// Deadline exceeded error, returned when the server gives up waiting for the result
+    /// after the deadline of the request, see [`InternalErrorKind::DeadlineExceeded`]
+    pub(crate) fn deadline_exceeded() -> Self {
+        Self::Internal(InternalErrorKind::DeadlineExceeded.reason().to_owned())
+    }
+
+    /// Get the kind of an `Internal` error, `None` if it's not an `Internal` error or its
+    /// reason is not reserved by a kind
+    #[inline]
+    #[must_use]
+    pub fn internal_kind(&self) -> Option<InternalErrorKind> {
+        let Self::Internal(ref reason) = *self else {
+            return None;
+        };
+        InternalErrorKind::ALL
+            .into_iter()
+            .find(|kind| kind.reason() == reason)
+    }
+
+    /// Rate limited error, returned when a client exceeds its rate limit, see
+    /// [`InternalErrorKind::RateLimited`]
+    pub(crate) fn rate_limited() -> Self {
+        Self::Internal(InternalErrorKind::RateLimited.reason().to_owned())
+    }
+
+    /// Leader conflict error, returned by clients when servers report different leaders in
+    /// the same term, see [`InternalErrorKind::LeaderConflict`]
+    pub(crate) fn leader_conflict() -> Self {
+        Self::Internal(InternalErrorKind::LeaderConflict.reason().to_owned())
+    }
+
+    /// Unsupported by server error, returned by clients before sending a request which
+    /// requires a `feature` the server does not support. It is carried by an `Internal`
+    /// error as the protocol has no dedicated error for it, and it is never retried.
+    pub(crate) fn unsupported_by_server(feature: ServerFeature) -> Self {
+        Self::Internal(format!("{UNSUPPORTED_BY_SERVER_REASON}{}", feature.name()))
+    }
+
+    /// Whether the error is an unsupported by server error
+    #[inline]
+    #[must_use]
+    pub fn is_unsupported_by_server(&self) -> bool {
+        matches!(
+            *self,
+            Self::Internal(ref reason) if reason.starts_with(UNSUPPORTED_BY_SERVER_REASON)
+        )
+    }
+
+    /// The machine-readable kind of this error
+    fn kind(&self) -> &'static str {
+        match *self {
+            Self::KeyConflict(_) => "key_conflict",
+            Self::Duplicated(_) => "duplicated",
+            Self::ExpiredClientId(_) => "expired_client_id",
+            Self::InvalidConfig(_) => "invalid_config",
+            Self::NodeNotExists(_) => "node_not_exists",
+            Self::NodeAlreadyExists(_) => "node_already_exists",
+            Self::LearnerNotCatchUp(_) => "learner_not_catch_up",
+            Self::ShuttingDown(_) => "shutting_down",
+            Self::WrongClusterVersion(_) => "wrong_cluster_version",
+            Self::Redirect(_) => "redirect",
+            Self::Internal(_) if self.is_unsupported_by_server() => "unsupported_by_server",
+            Self::Internal(_) => self
+                .internal_kind()
+                .map_or("internal", InternalErrorKind::name),
+            Self::RpcTransport(_) => "rpc_transport",
+            Self::LeaderTransfer(_) => "leader_transfer",
+        }
+    }
+
+    /// Parse the error from the kind and the fields exposed in the metadata of a status, which
+    /// is used when the status carries no details, e.g. they are dropped by a proxy. `None` if
+    /// the metadata carries no kind or the kind can not be rebuilt from the metadata alone.
+    fn from_metadata(metadata: &tonic::metadata::MetadataMap) -> Option<Self> {
+        let kind = metadata.get(CURP_ERROR_KIND_KEY)?.to_str().ok()?;
+        if kind == "redirect" {
+            let leader_id = match metadata.get(REDIRECT_LEADER_ID_KEY) {
+                Some(value) => Some(value.to_str().ok()?.parse().ok()?),
+                None => None,
+            };
+            let term = metadata
+                .get(REDIRECT_TERM_KEY)?
+                .to_str()
+                .ok()?
+                .parse()
+                .ok()?;
+            return Some(Self::redirect(leader_id, term));
+        }
+        // errors carrying no fields
+        [
+            Self::key_conflict(),
+            Self::duplicated(),
+            Self::expired_client_id(),
+            Self::invalid_config(),
+            Self::node_not_exist(),
+            Self::node_already_exists(),
+            Self::learner_not_catch_up(),
+            Self::shutting_down(),
+            Self::wrong_cluster_version(),
+            Self::deadline_exceeded(),
+            Self::rate_limited(),
+            Self::RpcTransport(()),
+        ]
+        .into_iter()
+        .find(|err| err.kind() == kind)
+    }
+
     /// Whether to abort fast round early
     pub(crate) fn should_abort_fast_round(&self) -> bool {
         matches!(
@@ -763,6 +934,9 @@ impl<E: std::error::Error + 'static> From<E> for CurpError {
                     Err(dec_err) => Self::internal(dec_err.to_string()),
                 };
             }
+            if let Some(err) = Self::from_metadata(status.metadata()) {
+                return err;
+            }
         }
         // Errors that are not created manually by `CurpError::xxx()` are trivial,
         // and errors that need to be known to the client are best created manually.
@@ -814,6 +988,30 @@ impl From<CurpError> for tonic::Status {
                 tonic::Code::ResourceExhausted,
                 "Redirect error: The request should be redirected to another node.",
             ),
+            CurpError::Internal(_)
+                if err.internal_kind() == Some(InternalErrorKind::RateLimited) =>
+            (
+                tonic::Code::ResourceExhausted,
+                "Rate limited error: The client has exceeded its rate limit.",
+            ),
+            CurpError::Internal(_)
+                if err.internal_kind() == Some(InternalErrorKind::LeaderConflict) =>
+            (
+                tonic::Code::Aborted,
+                "Leader conflict error: Servers report different leaders in the same term.",
+            ),
+            CurpError::Internal(_) if err.is_unsupported_by_server() => (
+                tonic::Code::Unimplemented,
+                "Unsupported by server error: The server does not support the required feature.",
+            ),
+            CurpError::Internal(_)
+                if err.internal_kind() == Some(InternalErrorKind::DeadlineExceeded) =>
+            {
+                (
+                    tonic::Code::DeadlineExceeded,
+                    "Deadline exceeded error: The result is not ready before the deadline, the command may still be applied.",
+                )
+            }
             CurpError::Internal(_) => (
                 tonic::Code::Internal,
                 "Internal error: An internal error occurred.",
@@ -825,9 +1023,23 @@ impl From<CurpError> for tonic::Status {
             ),
         };
 
+        // expose the error kind and its machine-readable fields in metadata, so that clients
+        // without the curp protos can still tell what happened
+        let mut metadata = tonic::metadata::MetadataMap::new();
+        let _ig = metadata.insert(
+            CURP_ERROR_KIND_KEY,
+            tonic::metadata::MetadataValue::from_static(err.kind()),
+        );
+        if let CurpError::Redirect(ref redirect) = err {
+            if let Some(leader_id) = redirect.leader_id {
+                let _ig = metadata.insert(REDIRECT_LEADER_ID_KEY, leader_id.into());
+            }
+            let _ig = metadata.insert(REDIRECT_TERM_KEY, redirect.term.into());
+        }
+
         let details = CurpErrorWrapper { err: Some(err) }.encode_to_vec();
 
-        tonic::Status::with_details(code, msg, details.into())
+        tonic::Status::with_details_and_metadata(code, msg, details.into(), metadata)
     }
 }
 
@@ -913,3 +1125,358 @@ impl std::fmt::Display for ProposeId {
         write!(f, "{}#{}", self.0, self.1)
     }
 }
+
+/// Metadata key of the leader hint piggybacked on append entries requests
+pub(crate) const LEADER_HINT_KEY: &str = "curp-leader-hint";
+
+/// Metadata key of the propose queue depth piggybacked on propose responses
+const QUEUE_DEPTH_KEY: &str = "curp-queue-depth";
+
+/// Metadata key of the apply lag piggybacked on propose responses
+const APPLY_LAG_KEY: &str = "curp-apply-lag";
+
+/// The load of a server, piggybacked on propose responses so that clients can
+/// slow down when the server is falling behind
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[non_exhaustive]
+pub struct ServerLoad {
+    /// The number of log entries that are not committed yet
+    pub queue_depth: u64,
+    /// The number of committed log entries that are not applied yet, which is the commit
+    /// index minus the applied index
+    pub apply_lag: u64,
+}
+
+impl ServerLoad {
+    /// Create a new `ServerLoad`
+    #[inline]
+    #[must_use]
+    pub fn new(queue_depth: u64, apply_lag: u64) -> Self {
+        Self {
+            queue_depth,
+            apply_lag,
+        }
+    }
+
+    /// Inject the load into the metadata of a response
+    pub(crate) fn inject(self, metadata: &mut tonic::metadata::MetadataMap) {
+        let _ig1 = metadata.insert(QUEUE_DEPTH_KEY, self.queue_depth.into());
+        let _ig2 = metadata.insert(APPLY_LAG_KEY, self.apply_lag.into());
+    }
+
+    /// Extract the load from the metadata of a response, return `None` if it is absent
+    pub(crate) fn extract(metadata: &tonic::metadata::MetadataMap) -> Option<Self> {
+        let get = |key: &str| -> Option<u64> { metadata.get(key)?.to_str().ok()?.parse().ok() };
+        Some(Self {
+            queue_depth: get(QUEUE_DEPTH_KEY)?,
+            apply_lag: get(APPLY_LAG_KEY)?,
+        })
+    }
+
+    /// Merge with another load, keeping the heavier side of each field
+    pub(crate) fn merge(self, other: Self) -> Self {
+        Self {
+            queue_depth: self.queue_depth.max(other.queue_depth),
+            apply_lag: self.apply_lag.max(other.apply_lag),
+        }
+    }
+}
+
+/// Metadata key of the propose timeout hint piggybacked on fetch cluster responses
+const PROPOSE_TIMEOUT_HINT_KEY: &str = "curp-hint-propose-timeout-ms";
+
+/// Metadata key of the wait synced timeout hint piggybacked on fetch cluster responses
+const WAIT_SYNCED_TIMEOUT_HINT_KEY: &str = "curp-hint-wait-synced-timeout-ms";
+
+/// Metadata key of the fast quorum hint piggybacked on fetch cluster responses
+const FAST_QUORUM_HINT_KEY: &str = "curp-hint-fast-quorum";
+
+/// Client settings recommended by the server, piggybacked on fetch cluster responses so that
+/// operators can tune a fleet of clients without redeploying them. `None` means no hint.
+/// Clients adopt the hints of the leader only, and a leader response without any hint clears
+/// the hints adopted before.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[non_exhaustive]
+pub struct ClientHints {
+    /// The recommended rpc timeout of a propose request
+    pub propose_timeout: Option<Duration>,
+    /// The recommended rpc timeout of a 2-RTT request
+    pub wait_synced_timeout: Option<Duration>,
+    /// The recommended number of acks required by the fast path
+    pub fast_quorum: Option<usize>,
+}
+
+impl ClientHints {
+    /// Create a new `ClientHints`
+    #[inline]
+    #[must_use]
+    pub fn new(
+        propose_timeout: Option<Duration>,
+        wait_synced_timeout: Option<Duration>,
+        fast_quorum: Option<usize>,
+    ) -> Self {
+        Self {
+            propose_timeout,
+            wait_synced_timeout,
+            fast_quorum,
+        }
+    }
+
+    /// Inject the hints into the metadata of a response
+    pub(crate) fn inject(self, metadata: &mut tonic::metadata::MetadataMap) {
+        let millis = |timeout: Duration| u64::try_from(timeout.as_millis()).unwrap_or(u64::MAX);
+        if let Some(timeout) = self.propose_timeout {
+            let _ig = metadata.insert(PROPOSE_TIMEOUT_HINT_KEY, millis(timeout).into());
+        }
+        if let Some(timeout) = self.wait_synced_timeout {
+            let _ig = metadata.insert(WAIT_SYNCED_TIMEOUT_HINT_KEY, millis(timeout).into());
+        }
+        if let Some(fast_quorum) = self.fast_quorum {
+            let _ig = metadata.insert(FAST_QUORUM_HINT_KEY, fast_quorum.into());
+        }
+    }
+
+    /// Extract the hints from the metadata of a response, absent or invalid hints are ignored
+    pub(crate) fn extract(metadata: &tonic::metadata::MetadataMap) -> Self {
+        let get = |key: &str| -> Option<u64> { metadata.get(key)?.to_str().ok()?.parse().ok() };
+        Self {
+            propose_timeout: get(PROPOSE_TIMEOUT_HINT_KEY).map(Duration::from_millis),
+            wait_synced_timeout: get(WAIT_SYNCED_TIMEOUT_HINT_KEY).map(Duration::from_millis),
+            fast_quorum: get(FAST_QUORUM_HINT_KEY).and_then(|n| usize::try_from(n).ok()),
+        }
+    }
+}
+
+/// Metadata key of the server version piggybacked on fetch cluster responses
+const SERVER_VERSION_KEY: &str = "curp-server-version";
+
+/// Metadata key of the server features piggybacked on fetch cluster responses
+const SERVER_FEATURES_KEY: &str = "curp-server-features";
+
+/// A feature of the server which some client operations depend on
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ServerFeature {
+    /// Membership changes by `propose_conf_change`
+    ConfChange,
+    /// Leadership transfers by `move_leader`
+    MoveLeader,
+    /// Proposes whose commands are compressed
+    ProposeCompression,
+}
+
+impl ServerFeature {
+    /// All features supported by this build
+    const ALL: [Self; 3] = [Self::ConfChange, Self::MoveLeader, Self::ProposeCompression];
+
+    /// Name of the feature in metadata
+    fn name(self) -> &'static str {
+        match self {
+            Self::ConfChange => "conf_change",
+            Self::MoveLeader => "move_leader",
+            Self::ProposeCompression => "propose_compression",
+        }
+    }
+
+    /// Parse a feature from its name, features unknown to this build are ignored
+    fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|feature| feature.name() == name)
+    }
+}
+
+/// Build version and features of the server, piggybacked on fetch cluster responses so that
+/// clients can refuse operations the server does not support. A server predating the
+/// negotiation reports neither of them.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[non_exhaustive]
+pub struct ServerFeatures {
+    /// The build version of the server, `None` if it is not reported
+    pub version: Option<String>,
+    /// The features supported by the server, `None` if they are not reported
+    pub features: Option<HashSet<ServerFeature>>,
+}
+
+impl ServerFeatures {
+    /// Create a new `ServerFeatures`
+    #[inline]
+    #[must_use]
+    pub fn new(
+        version: impl Into<String>,
+        features: impl IntoIterator<Item = ServerFeature>,
+    ) -> Self {
+        Self {
+            version: Some(version.into()),
+            features: Some(features.into_iter().collect()),
+        }
+    }
+
+    /// The version and features of this build
+    pub(crate) fn current() -> Self {
+        Self::new(env!("CARGO_PKG_VERSION"), ServerFeature::ALL)
+    }
+
+    /// Whether the server reports its version or features
+    #[inline]
+    #[must_use]
+    pub fn is_reported(&self) -> bool {
+        self.version.is_some() || self.features.is_some()
+    }
+
+    /// Whether the server supports `feature`, features are assumed to be supported if they
+    /// are not reported
+    #[inline]
+    #[must_use]
+    pub fn supports(&self, feature: ServerFeature) -> bool {
+        self.features
+            .as_ref()
+            .map_or(true, |features| features.contains(&feature))
+    }
+
+    /// Inject the version and features into the metadata of a response
+    pub(crate) fn inject(&self, metadata: &mut tonic::metadata::MetadataMap) {
+        if let Some(Ok(version)) = self.version.as_deref().map(str::parse) {
+            let _ig = metadata.insert(SERVER_VERSION_KEY, version);
+        }
+        if let Some(ref features) = self.features {
+            let names = features
+                .iter()
+                .map(|feature| feature.name())
+                .collect::<Vec<_>>();
+            if let Ok(names) = names.join(",").parse() {
+                let _ig = metadata.insert(SERVER_FEATURES_KEY, names);
+            }
+        }
+    }
+
+    /// Extract the version and features from the metadata of a response
+    pub(crate) fn extract(metadata: &tonic::metadata::MetadataMap) -> Self {
+        let get = |key: &str| -> Option<&str> { metadata.get(key)?.to_str().ok() };
+        Self {
+            version: get(SERVER_VERSION_KEY).map(str::to_owned),
+            features: get(SERVER_FEATURES_KEY).map(|names| {
+                names
+                    .split(',')
+                    .filter_map(ServerFeature::from_name)
+                    .collect()
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn server_features_should_round_trip_through_metadata() {
+        let features = ServerFeatures::new("0.1.0", [ServerFeature::MoveLeader]);
+        let mut metadata = tonic::metadata::MetadataMap::new();
+        features.inject(&mut metadata);
+        let extracted = ServerFeatures::extract(&metadata);
+        assert_eq!(extracted, features);
+        assert!(extracted.supports(ServerFeature::MoveLeader));
+        assert!(!extracted.supports(ServerFeature::ConfChange));
+
+        // a server predating the negotiation is assumed to support everything
+        let extracted = ServerFeatures::extract(&tonic::metadata::MetadataMap::new());
+        assert!(!extracted.is_reported());
+        assert!(extracted.supports(ServerFeature::ConfChange));
+    }
+
+    #[test]
+    fn client_hints_should_round_trip_through_metadata() {
+        let hints = ClientHints::new(Some(Duration::from_millis(1500)), None, Some(4));
+        let mut metadata = tonic::metadata::MetadataMap::new();
+        hints.inject(&mut metadata);
+        assert_eq!(ClientHints::extract(&metadata), hints);
+        assert_eq!(
+            ClientHints::extract(&tonic::metadata::MetadataMap::new()),
+            ClientHints::default()
+        );
+    }
+
+    #[test]
+    fn redirect_error_should_round_trip_through_status() {
+        let status = tonic::Status::from(CurpError::redirect(Some(1), 0));
+        let metadata = status.metadata();
+        assert_eq!(
+            metadata.get(CURP_ERROR_KIND_KEY).unwrap().to_str().unwrap(),
+            "redirect"
+        );
+        assert_eq!(
+            metadata
+                .get(REDIRECT_LEADER_ID_KEY)
+                .unwrap()
+                .to_str()
+                .unwrap(),
+            "1"
+        );
+        assert_eq!(
+            metadata.get(REDIRECT_TERM_KEY).unwrap().to_str().unwrap(),
+            "0"
+        );
+
+        let err = CurpError::from(status);
+        assert!(matches!(
+            err,
+            CurpError::Redirect(Redirect {
+                leader_id: Some(1),
+                term: 0
+            })
+        ));
+    }
+
+    #[test]
+    fn error_should_be_parsed_from_status_metadata_without_details() {
+        for err in [
+            CurpError::redirect(Some(1), 3),
+            CurpError::redirect(None, 3),
+            CurpError::key_conflict(),
+            CurpError::wrong_cluster_version(),
+            CurpError::deadline_exceeded(),
+            CurpError::rate_limited(),
+        ] {
+            let status = tonic::Status::from(err.clone());
+            // the details are dropped, e.g. by a proxy
+            let stripped = tonic::Status::with_metadata(
+                status.code(),
+                status.message(),
+                status.metadata().clone(),
+            );
+            assert_eq!(CurpError::from(stripped), err);
+        }
+        // kinds which can not be rebuilt from the metadata are treated as before
+        let status = tonic::Status::from(CurpError::internal("reason"));
+        let stripped =
+            tonic::Status::with_metadata(status.code(), "reason", status.metadata().clone());
+        assert!(matches!(CurpError::from(stripped), CurpError::Internal(_)));
+    }
+
+    #[test]
+    fn rate_limited_error_should_round_trip_through_status() {
+        let status = tonic::Status::from(CurpError::rate_limited());
+        assert_eq!(status.code(), tonic::Code::ResourceExhausted);
+        assert_eq!(
+            status
+                .metadata()
+                .get(CURP_ERROR_KIND_KEY)
+                .unwrap()
+                .to_str()
+                .unwrap(),
+            "rate_limited"
+        );
+        assert_eq!(
+            CurpError::from(status).internal_kind(),
+            Some(InternalErrorKind::RateLimited)
+        );
+        assert_eq!(CurpError::internal("other").internal_kind(), None);
+    }
+
+    #[test]
+    fn server_load_should_round_trip_through_metadata() {
+        let mut metadata = tonic::metadata::MetadataMap::new();
+        assert_eq!(ServerLoad::extract(&metadata), None);
+        ServerLoad::new(3, 7).inject(&mut metadata);
+        assert_eq!(ServerLoad::extract(&metadata), Some(ServerLoad::new(3, 7)));
+    }
+}
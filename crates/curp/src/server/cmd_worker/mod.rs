@@ -87,12 +87,28 @@ async fn handle_task<C: Command, CE: CommandExecutor<C>, RC: RoleChange>(
     ce: &CE,
     curp: &RawCurp<C, RC>,
 ) {
-    let succeeded = match task.take() {
-        TaskType::SpecExe(entry, pre_err) => worker_exe(entry, pre_err, ce, curp).await,
-        TaskType::AS(entry, prepare) => worker_as(entry, prepare, ce, curp).await,
-        TaskType::Reset(snapshot, finish_tx) => worker_reset(snapshot, finish_tx, ce, curp).await,
-        TaskType::Snapshot(meta, tx) => worker_snapshot(meta, tx, ce, curp).await,
+    curp.set_worker_busy(true);
+    // an entry is applied once it is after synced, or it fails to execute as it will never
+    // be after synced then
+    let (succeeded, applied) = match task.take() {
+        TaskType::SpecExe(entry, pre_err) => {
+            let index = entry.index;
+            let succeeded = worker_exe(entry, pre_err, ce, curp).await;
+            (succeeded, (!succeeded).then_some(index))
+        }
+        TaskType::AS(entry, prepare) => {
+            let index = entry.index;
+            (worker_as(entry, prepare, ce, curp).await, Some(index))
+        }
+        TaskType::Reset(snapshot, finish_tx) => {
+            (worker_reset(snapshot, finish_tx, ce, curp).await, None)
+        }
+        TaskType::Snapshot(meta, tx) => (worker_snapshot(meta, tx, ce, curp).await, None),
     };
+    curp.set_worker_busy(false);
+    if let Some(index) = applied {
+        curp.mark_applied(index);
+    }
     if let Err(e) = done_tx.send((task, succeeded)) {
         if !curp.is_shutdown() {
             error!("can't mark a task done, the channel could be closed, {e}");
@@ -177,11 +193,13 @@ async fn worker_as<C: Command, CE: CommandExecutor<C>, RC: RoleChange>(
                 error!("failed to set last_applied, {e}");
                 return false;
             }
-            let change = conf_change.first().unwrap_or_else(|| {
-                unreachable!("conf change should always have at least one change")
-            });
-            let shutdown_self =
-                change.change_type() == ConfChangeType::Remove && change.node_id == id;
+            // an empty conf change leaves the joint config, removing the voters that only
+            // exist in the outgoing config, while a joint change never removes a node by itself
+            let shutdown_self = match conf_change.as_slice() {
+                [] => !curp.cluster().contains(id),
+                [change] => change.change_type() == ConfChangeType::Remove && change.node_id == id,
+                _ => false,
+            };
             cb.write().insert_conf(entry.propose_id);
             sp.lock().remove(&entry.propose_id);
             let _ig = ucp.lock().remove(&entry.propose_id);
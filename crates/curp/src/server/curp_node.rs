@@ -1,6 +1,7 @@
 use std::{
     collections::HashMap,
     fmt::Debug,
+    future::Future,
     sync::Arc,
     time::{Duration, Instant},
 };
@@ -30,6 +31,7 @@ use super::{
     cmd_worker::{conflict_checked_mpmc, start_cmd_workers},
     gc::{gc_cmd_board, gc_spec_pool},
     lease_manager::LeaseManager,
+    rate_limiter::ClientRateLimiter,
     raw_curp::{AppendEntries, RawCurp, UncommittedPool, Vote},
     spec_pool::{SpecPoolRef, SpeculativePool},
     storage::StorageApi,
@@ -42,14 +44,14 @@ use crate::{
     rpc::{
         self,
         connect::{InnerConnectApi, InnerConnectApiWrapper},
-        AppendEntriesRequest, AppendEntriesResponse, ConfChange, ConfChangeType, CurpError,
-        FetchClusterRequest, FetchClusterResponse, FetchReadStateRequest, FetchReadStateResponse,
-        InstallSnapshotRequest, InstallSnapshotResponse, LeaseKeepAliveMsg, MoveLeaderRequest,
-        MoveLeaderResponse, ProposeConfChangeRequest, ProposeConfChangeResponse, ProposeRequest,
-        ProposeResponse, PublishRequest, PublishResponse, ShutdownRequest, ShutdownResponse,
-        TriggerShutdownRequest, TriggerShutdownResponse, TryBecomeLeaderNowRequest,
-        TryBecomeLeaderNowResponse, VoteRequest, VoteResponse, WaitSyncedRequest,
-        WaitSyncedResponse,
+        AppendEntriesRequest, AppendEntriesResponse, ClientHints, ConfChange, ConfChangeType,
+        CurpError, FetchClusterRequest, FetchClusterResponse, FetchReadStateRequest,
+        FetchReadStateResponse, InstallSnapshotRequest, InstallSnapshotResponse, LeaseKeepAliveMsg,
+        MoveLeaderRequest, MoveLeaderResponse, ProposeConfChangeRequest, ProposeConfChangeResponse,
+        ProposeRequest, ProposeResponse, PublishRequest, PublishResponse, ServerLoad,
+        ShutdownRequest, ShutdownResponse, TriggerShutdownRequest, TriggerShutdownResponse,
+        TryBecomeLeaderNowRequest, TryBecomeLeaderNowResponse, VoteRequest, VoteResponse,
+        WaitSyncedRequest, WaitSyncedResponse,
     },
     server::{cmd_worker::CEEventTxApi, metrics, raw_curp::SyncAction, storage::db::DB},
     snapshot::{Snapshot, SnapshotMeta},
@@ -69,24 +71,64 @@ pub(super) struct CurpNode<C: Command, RC: RoleChange> {
     storage: Arc<dyn StorageApi<Command = C>>,
     /// Snapshot allocator
     snapshot_allocator: Box<dyn SnapshotAllocator>,
+    /// Rate limiter of proposes from each client id
+    rate_limiter: ClientRateLimiter,
+    /// Client settings recommended to clients, piggybacked on fetch cluster responses
+    client_hints: ClientHints,
+    /// Command executor, used to verify the determinism of proposed commands
+    #[cfg(feature = "verify-determinism")]
+    cmd_executor: Arc<dyn CommandExecutor<C>>,
 }
 
 /// Handlers for clients
 impl<C: Command, RC: RoleChange> CurpNode<C, RC> {
-    /// Handle `Propose` requests
-    pub(super) async fn propose(&self, req: ProposeRequest) -> Result<ProposeResponse, CurpError> {
+    /// Handle `Propose` requests, the wait for the execution result is bounded by the deadline
+    pub(super) async fn propose(
+        &self,
+        req: ProposeRequest,
+        deadline: Option<Instant>,
+    ) -> Result<ProposeResponse, CurpError> {
         if self.curp.is_shutdown() {
             return Err(CurpError::shutting_down());
         }
         let id = req.propose_id();
+        if !self.rate_limiter.try_acquire(id.0, Instant::now()) {
+            debug!(
+                "{} rejects propose {id} exceeding the rate limit",
+                self.curp.id()
+            );
+            return Err(CurpError::rate_limited());
+        }
         self.check_cluster_version(req.cluster_version)?;
         let cmd: Arc<C> = Arc::new(req.cmd()?);
+        #[cfg(feature = "verify-determinism")]
+        if let Err(err) = self.cmd_executor.verify_determinism(cmd.as_ref()).await {
+            error!(
+                "{} rejects cmd({id}) as it is not deterministic, {err}",
+                self.curp.id()
+            );
+            // the rejected command never enters the log, so every replica agrees on skipping
+            // it, and the rejection reaches both the propose and the wait synced of the client.
+            // Marking it as received also keeps a retried proposal out of the log.
+            {
+                let mut cb_w = self.cmd_board.write();
+                if cb_w.sync.insert(id) {
+                    cb_w.insert_er(id, Err(err));
+                }
+            }
+            let er_res = self
+                .wait_before(deadline, CommandBoard::wait_for_er(&self.cmd_board, id))
+                .await?;
+            return Ok(ProposeResponse::new_result::<C>(&er_res));
+        }
         // handle proposal
         let sp_exec = self.curp.handle_propose(id, Arc::clone(&cmd))?;
 
         // if speculatively executed, wait for the result and return
         if sp_exec {
-            let er_res = CommandBoard::wait_for_er(&self.cmd_board, id).await;
+            let er_res = self
+                .wait_before(deadline, CommandBoard::wait_for_er(&self.cmd_board, id))
+                .await?;
             return Ok(ProposeResponse::new_result::<C>(&er_res));
         }
 
@@ -151,10 +193,11 @@ impl<C: Command, RC: RoleChange> CurpNode<C, RC> {
 
 /// Handlers for peers
 impl<C: Command, RC: RoleChange> CurpNode<C, RC> {
-    /// Handle `AppendEntries` requests
+    /// Handle `AppendEntries` requests, the leader hint is recorded if the leader is accepted
     pub(super) fn append_entries(
         &self,
         req: &AppendEntriesRequest,
+        leader_hint: Option<u64>,
     ) -> Result<AppendEntriesResponse, CurpError> {
         let entries = req.entries()?;
 
@@ -167,7 +210,12 @@ impl<C: Command, RC: RoleChange> CurpNode<C, RC> {
             req.leader_commit,
         );
         let resp = match result {
-            Ok(term) => AppendEntriesResponse::new_accept(term),
+            Ok(term) => {
+                if let Some(hint) = leader_hint {
+                    self.curp.update_leader_hint(hint);
+                }
+                AppendEntriesResponse::new_accept(term)
+            }
             Err((term, hint)) => AppendEntriesResponse::new_reject(term, hint),
         };
 
@@ -215,10 +263,11 @@ impl<C: Command, RC: RoleChange> CurpNode<C, RC> {
         TriggerShutdownResponse::default()
     }
 
-    /// handle `WaitSynced` requests
+    /// handle `WaitSynced` requests, the wait for the results is bounded by the deadline
     pub(super) async fn wait_synced(
         &self,
         req: WaitSyncedRequest,
+        deadline: Option<Instant>,
     ) -> Result<WaitSyncedResponse, CurpError> {
         if self.curp.is_shutdown() {
             return Err(CurpError::shutting_down());
@@ -229,7 +278,9 @@ impl<C: Command, RC: RoleChange> CurpNode<C, RC> {
         if self.curp.get_transferee().is_some() {
             return Err(CurpError::leader_transfer("leader transferring"));
         }
-        let (er, asr) = CommandBoard::wait_for_er_asr(&self.cmd_board, id).await;
+        let (er, asr) = self
+            .wait_before(deadline, CommandBoard::wait_for_er_asr(&self.cmd_board, id))
+            .await?;
         debug!("{} wait synced for cmd({id}) finishes", self.curp.id());
         Ok(WaitSyncedResponse::new_from_result::<C>(er, asr))
     }
@@ -303,6 +354,7 @@ impl<C: Command, RC: RoleChange> CurpNode<C, RC> {
                     last_included_term: req.last_included_term,
                 };
                 let snapshot = Snapshot::new(meta, snapshot);
+                let snapshot_size = snapshot.size();
                 info!(
                     "{} successfully received a snapshot, {snapshot:?}",
                     self.curp.id(),
@@ -316,6 +368,7 @@ impl<C: Command, RC: RoleChange> CurpNode<C, RC> {
                             "failed to reset the command executor by snapshot, {err}"
                         ))
                     })?;
+                self.curp.record_snapshot(meta, snapshot_size);
                 metrics::get().apply_snapshot_in_progress.observe(0, &[]);
                 metrics::get()
                     .snapshot_install_total_duration_seconds
@@ -330,12 +383,15 @@ impl<C: Command, RC: RoleChange> CurpNode<C, RC> {
 
     /// Handle `FetchReadState` requests
     #[allow(clippy::needless_pass_by_value)] // To keep type consistent with other request handlers
-    pub(super) fn fetch_read_state(
+    pub(super) async fn fetch_read_state(
         &self,
         req: FetchReadStateRequest,
     ) -> Result<FetchReadStateResponse, CurpError> {
         self.check_cluster_version(req.cluster_version)?;
         let cmd = req.cmd()?;
+        if self.curp.cfg().enable_leader_lease && !self.curp.has_valid_leader_lease() {
+            self.curp.confirm_leadership().await?;
+        }
         let state = self.curp.handle_fetch_read_state(&cmd);
         Ok(FetchReadStateResponse::new(state))
     }
@@ -636,6 +692,7 @@ impl<C: Command, RC: RoleChange> CurpNode<C, RC> {
 
         // create curp state machine
         let (voted_for, entries) = storage.recover().await?;
+        let joint_config = storage.recover_joint_config()?;
         let curp = Arc::new(
             RawCurp::builder()
                 .cluster_info(Arc::clone(&cluster_info))
@@ -654,6 +711,7 @@ impl<C: Command, RC: RoleChange> CurpNode<C, RC> {
                 .last_applied(last_applied)
                 .voted_for(voted_for)
                 .entries(entries)
+                .joint_config(joint_config)
                 .curp_storage(Arc::clone(&storage))
                 .client_tls_config(client_tls_config)
                 .build_raw_curp()
@@ -662,6 +720,8 @@ impl<C: Command, RC: RoleChange> CurpNode<C, RC> {
 
         metrics::Metrics::register_callback(Arc::clone(&curp))?;
 
+        #[cfg(feature = "verify-determinism")]
+        let verifier: Arc<dyn CommandExecutor<C>> = Arc::<CE>::clone(&cmd_executor);
         start_cmd_workers(cmd_executor, Arc::clone(&curp), task_rx, done_tx);
 
         task_manager.spawn(TaskName::GcCmdBoard, |n| {
@@ -680,6 +740,14 @@ impl<C: Command, RC: RoleChange> CurpNode<C, RC> {
             ce_event_tx,
             storage,
             snapshot_allocator,
+            rate_limiter: ClientRateLimiter::new(curp_cfg.client_qps_limit),
+            client_hints: ClientHints::new(
+                Some(curp_cfg.client_hint_propose_timeout).filter(|t| !t.is_zero()),
+                Some(curp_cfg.client_hint_wait_synced_timeout).filter(|t| !t.is_zero()),
+                Some(curp_cfg.client_hint_fast_quorum).filter(|n| *n != 0),
+            ),
+            #[cfg(feature = "verify-determinism")]
+            cmd_executor: verifier,
         })
     }
 
@@ -820,8 +888,9 @@ impl<C: Command, RC: RoleChange> CurpNode<C, RC> {
             debug!("{} send append_entries to {}", curp.id(), connect.id());
         }
 
+        let sent_at = Instant::now();
         let resp = connect
-            .append_entries(req, curp.cfg().rpc_timeout)
+            .append_entries(req, curp.local_leader_hint(), curp.cfg().rpc_timeout)
             .await?
             .into_inner();
 
@@ -834,6 +903,8 @@ impl<C: Command, RC: RoleChange> CurpNode<C, RC> {
         ) else {
             return Ok((true, false));
         };
+        // the follower has accepted us as the leader of current term, whether the ae succeeded or not
+        curp.record_ae_ack(connect.id(), sent_at);
 
         Ok((false, ae_succeed))
     }
@@ -856,6 +927,27 @@ impl<C: Command, RC: RoleChange> CurpNode<C, RC> {
             .is_err())
     }
 
+    /// Wait for a command result until the deadline of its request if the deadline is enabled.
+    /// The command keeps being executed and applied after the deadline, only the wait is given up.
+    async fn wait_before<F: Future>(
+        &self,
+        deadline: Option<Instant>,
+        fut: F,
+    ) -> Result<F::Output, CurpError> {
+        let Some(deadline) = deadline.filter(|_| self.curp.cfg().enable_propose_deadline) else {
+            return Ok(fut.await);
+        };
+        tokio::time::timeout(deadline.saturating_duration_since(Instant::now()), fut)
+            .await
+            .map_err(|_elapsed| {
+                debug!(
+                    "{} gives up waiting for the result after the deadline",
+                    self.curp.id()
+                );
+                CurpError::deadline_exceeded()
+            })
+    }
+
     /// Check cluster version and return new cluster
     fn check_cluster_version(&self, client_cluster_version: u64) -> Result<(), CurpError> {
         let server_cluster_version = self.curp.cluster().cluster_version();
@@ -869,6 +961,17 @@ impl<C: Command, RC: RoleChange> CurpNode<C, RC> {
         Ok(())
     }
 
+    /// Get the load of this server, piggybacked on propose responses
+    pub(super) fn load(&self) -> ServerLoad {
+        self.curp.load()
+    }
+
+    /// Get the client settings recommended by this server, piggybacked on fetch cluster
+    /// responses
+    pub(super) fn client_hints(&self) -> ClientHints {
+        self.client_hints
+    }
+
     /// Get `RawCurp`
     pub(super) fn raw_curp(&self) -> Arc<RawCurp<C, RC>> {
         Arc::clone(&self.curp)
@@ -957,11 +1060,14 @@ impl<C: Command, RC: RoleChange> CurpNode<C, RC> {
                 }
             }
             SyncAction::Snapshot(rx) => match rx.await {
-                Ok(snapshot) => match Self::send_snapshot(connect, curp, snapshot).await {
-                    Ok(true) => return true,
-                    Err(err) => warn!("snapshot to {} failed, {err:?}", connect.id()),
-                    Ok(false) => {}
-                },
+                Ok(snapshot) => {
+                    curp.record_snapshot(snapshot.meta, snapshot.size());
+                    match Self::send_snapshot(connect, curp, snapshot).await {
+                        Ok(true) => return true,
+                        Err(err) => warn!("snapshot to {} failed, {err:?}", connect.id()),
+                        Ok(false) => {}
+                    }
+                }
                 Err(err) => {
                     warn!("failed to receive snapshot result, {err}");
                 }
@@ -1006,7 +1112,7 @@ mod tests {
         mock_connect1
             .expect_append_entries()
             .times(1..)
-            .returning(|_, _| Ok(tonic::Response::new(AppendEntriesResponse::new_accept(0))));
+            .returning(|_, _, _| Ok(tonic::Response::new(AppendEntriesResponse::new_accept(0))));
         let s1_id = curp.cluster().get_id_by_name("S1").unwrap();
         mock_connect1.expect_id().return_const(s1_id);
         let remove_event = Arc::new(Event::new());
@@ -1,4 +1,8 @@
-use std::{fmt::Debug, sync::Arc};
+use std::{
+    fmt::Debug,
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
 use engine::SnapshotAllocator;
 use tokio::sync::broadcast;
@@ -10,19 +14,24 @@ use utils::ClientTlsConfig;
 use utils::{config::CurpConfig, task_manager::TaskManager, tracing::Extract};
 
 use self::curp_node::CurpNode;
-pub use self::raw_curp::RawCurp;
+pub use self::{
+    observer::{LogEvent, Observer, ReplicateError, Replicator},
+    raw_curp::RawCurp,
+};
+pub use crate::snapshot::SnapshotInfo;
 use crate::{
     cmd::{Command, CommandExecutor},
     members::{ClusterInfo, ServerId},
     role_change::RoleChange,
     rpc::{
-        AppendEntriesRequest, AppendEntriesResponse, FetchClusterRequest, FetchClusterResponse,
-        FetchReadStateRequest, FetchReadStateResponse, InstallSnapshotRequest,
-        InstallSnapshotResponse, LeaseKeepAliveMsg, MoveLeaderRequest, MoveLeaderResponse,
-        ProposeConfChangeRequest, ProposeConfChangeResponse, ProposeRequest, ProposeResponse,
-        PublishRequest, PublishResponse, ShutdownRequest, ShutdownResponse, TriggerShutdownRequest,
-        TriggerShutdownResponse, TryBecomeLeaderNowRequest, TryBecomeLeaderNowResponse,
-        VoteRequest, VoteResponse, WaitSyncedRequest, WaitSyncedResponse,
+        decompress_propose, AppendEntriesRequest, AppendEntriesResponse, FetchClusterRequest,
+        FetchClusterResponse, FetchReadStateRequest, FetchReadStateResponse,
+        InstallSnapshotRequest, InstallSnapshotResponse, LeaseKeepAliveMsg, MoveLeaderRequest,
+        MoveLeaderResponse, ProposeConfChangeRequest, ProposeConfChangeResponse, ProposeRequest,
+        ProposeResponse, PublishRequest, PublishResponse, ServerFeatures, ShutdownRequest,
+        ShutdownResponse, TriggerShutdownRequest, TriggerShutdownResponse,
+        TryBecomeLeaderNowRequest, TryBecomeLeaderNowResponse, VoteRequest, VoteResponse,
+        WaitSyncedRequest, WaitSyncedResponse, LEADER_HINT_KEY,
     },
 };
 
@@ -53,8 +62,35 @@ mod lease_manager;
 /// Curp metrics
 mod metrics;
 
+/// Observer of the committed log
+mod observer;
+
+/// Per-client rate limiter
+mod rate_limiter;
+
 pub use storage::{db::DB, StorageApi, StorageError};
 
+/// Metadata key of the timeout set by the client of a request
+const GRPC_TIMEOUT_KEY: &str = "grpc-timeout";
+
+/// Get the deadline of a request from its `grpc-timeout` metadata, which is in the format of
+/// a positive integer followed by a unit, e.g. `500m` means 500 milliseconds
+fn request_deadline(metadata: &tonic::metadata::MetadataMap) -> Option<Instant> {
+    let timeout = metadata.get(GRPC_TIMEOUT_KEY)?.to_str().ok()?;
+    let (value, unit) = timeout.split_at(timeout.len().checked_sub(1)?);
+    let value: u64 = value.parse().ok()?;
+    let timeout = match unit {
+        "H" => Duration::from_secs(value.checked_mul(3600)?),
+        "M" => Duration::from_secs(value.checked_mul(60)?),
+        "S" => Duration::from_secs(value),
+        "m" => Duration::from_millis(value),
+        "u" => Duration::from_micros(value),
+        "n" => Duration::from_nanos(value),
+        _ => return None,
+    };
+    Instant::now().checked_add(timeout)
+}
+
 /// The Rpc Server to handle rpc requests
 /// This Wrapper is introduced due to the `MadSim` rpc lib
 #[derive(Debug)]
@@ -77,12 +113,15 @@ impl<C: Command, RC: RoleChange> crate::rpc::Protocol for Rpc<C, RC> {
     #[instrument(skip_all, name = "curp_propose")]
     async fn propose(
         &self,
-        request: tonic::Request<ProposeRequest>,
+        mut request: tonic::Request<ProposeRequest>,
     ) -> Result<tonic::Response<ProposeResponse>, tonic::Status> {
         request.metadata().extract_span();
-        Ok(tonic::Response::new(
-            self.inner.propose(request.into_inner()).await?,
-        ))
+        decompress_propose(&mut request)?;
+        let deadline = request_deadline(request.metadata());
+        let mut resp =
+            tonic::Response::new(self.inner.propose(request.into_inner(), deadline).await?);
+        self.inner.load().inject(resp.metadata_mut());
+        Ok(resp)
     }
 
     #[instrument(skip_all, name = "curp_shutdown")]
@@ -124,8 +163,11 @@ impl<C: Command, RC: RoleChange> crate::rpc::Protocol for Rpc<C, RC> {
         request: tonic::Request<WaitSyncedRequest>,
     ) -> Result<tonic::Response<WaitSyncedResponse>, tonic::Status> {
         request.metadata().extract_span();
+        let deadline = request_deadline(request.metadata());
         Ok(tonic::Response::new(
-            self.inner.wait_synced(request.into_inner()).await?,
+            self.inner
+                .wait_synced(request.into_inner(), deadline)
+                .await?,
         ))
     }
 
@@ -134,9 +176,10 @@ impl<C: Command, RC: RoleChange> crate::rpc::Protocol for Rpc<C, RC> {
         &self,
         request: tonic::Request<FetchClusterRequest>,
     ) -> Result<tonic::Response<FetchClusterResponse>, tonic::Status> {
-        Ok(tonic::Response::new(
-            self.inner.fetch_cluster(request.into_inner())?,
-        ))
+        let mut resp = tonic::Response::new(self.inner.fetch_cluster(request.into_inner())?);
+        self.inner.client_hints().inject(resp.metadata_mut());
+        ServerFeatures::current().inject(resp.metadata_mut());
+        Ok(resp)
     }
 
     #[instrument(skip_all, name = "curp_fetch_read_state")]
@@ -145,7 +188,7 @@ impl<C: Command, RC: RoleChange> crate::rpc::Protocol for Rpc<C, RC> {
         request: tonic::Request<FetchReadStateRequest>,
     ) -> Result<tonic::Response<FetchReadStateResponse>, tonic::Status> {
         Ok(tonic::Response::new(
-            self.inner.fetch_read_state(request.into_inner())?,
+            self.inner.fetch_read_state(request.into_inner()).await?,
         ))
     }
 
@@ -179,8 +222,12 @@ impl<C: Command, RC: RoleChange> crate::rpc::InnerProtocol for Rpc<C, RC> {
         &self,
         request: tonic::Request<AppendEntriesRequest>,
     ) -> Result<tonic::Response<AppendEntriesResponse>, tonic::Status> {
+        let leader_hint = request
+            .metadata()
+            .get(LEADER_HINT_KEY)
+            .and_then(|hint| hint.to_str().ok()?.parse().ok());
         Ok(tonic::Response::new(
-            self.inner.append_entries(request.get_ref())?,
+            self.inner.append_entries(request.get_ref(), leader_hint)?,
         ))
     }
 
@@ -330,4 +377,11 @@ impl<C: Command, RC: RoleChange> Rpc<C, RC> {
     pub fn raw_curp(&self) -> Arc<RawCurp<C, RC>> {
         self.inner.raw_curp()
     }
+
+    /// Get an observer of the committed log
+    #[inline]
+    #[must_use]
+    pub fn observer(&self) -> Observer<C, RC> {
+        Observer::new(self.inner.raw_curp())
+    }
 }
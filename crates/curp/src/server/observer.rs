@@ -0,0 +1,236 @@
+use std::{
+    future::Future,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+};
+
+use async_stream::stream;
+use clippy_utilities::OverflowArithmetic;
+use futures::{Stream, StreamExt};
+
+use super::{RawCurp, StorageApi, StorageError, DB};
+use crate::{cmd::Command, log_entry::EntryData, role_change::RoleChange, LogIndex};
+
+/// An event of the committed log stream
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub enum LogEvent<C> {
+    /// A committed command and its log index
+    Command(LogIndex, Arc<C>),
+    /// The entries up to the index have been compacted into a snapshot. The observer should
+    /// install a snapshot including the index, and the stream resumes right after it.
+    SnapshotNeeded(LogIndex),
+    /// All commands up to the index have been yielded, only by `Observer::replication_stream`
+    Watermark(LogIndex),
+}
+
+/// Observer of the committed log of a curp server, used for change data capture
+///
+/// Only the entries applied by the local server are observed. They are never rolled back,
+/// so the stream is not affected by leader changes.
+pub struct Observer<C: Command, RC: RoleChange> {
+    /// The curp state machine
+    curp: Arc<RawCurp<C, RC>>,
+}
+
+impl<C: Command, RC: RoleChange> std::fmt::Debug for Observer<C, RC> {
+    #[inline]
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Observer").finish()
+    }
+}
+
+impl<C: Command, RC: RoleChange> Observer<C, RC> {
+    /// Create a new `Observer`
+    pub(super) fn new(curp: Arc<RawCurp<C, RC>>) -> Self {
+        Self { curp }
+    }
+
+    /// Get the committed watermark, which is the index of the last entry applied by the local
+    /// server. All commands up to it can be read from the log stream.
+    #[inline]
+    #[must_use]
+    pub fn committed_watermark(&self) -> LogIndex {
+        self.curp.last_applied()
+    }
+
+    /// Get a stream of the committed commands starting from `from_index`. Entries that are
+    /// not commands are skipped, and a `LogEvent::SnapshotNeeded` is yielded instead of a gap
+    /// when the requested entries have been compacted.
+    #[inline]
+    pub fn log_stream(&self, from_index: LogIndex) -> impl Stream<Item = LogEvent<C>> {
+        self.stream(from_index, false)
+    }
+
+    /// Get a stream like `log_stream`, which also yields a `LogEvent::Watermark` after each
+    /// batch of entries, so that a replica knows how far it has caught up even if the last
+    /// entries are not commands
+    #[inline]
+    pub fn replication_stream(&self, from_index: LogIndex) -> impl Stream<Item = LogEvent<C>> {
+        self.stream(from_index, true)
+    }
+
+    /// Get a stream of the committed log, yields watermarks if `with_watermarks` is true
+    fn stream(
+        &self,
+        from_index: LogIndex,
+        with_watermarks: bool,
+    ) -> impl Stream<Item = LogEvent<C>> {
+        let curp = Arc::clone(&self.curp);
+        stream! {
+            let mut next = from_index.max(1);
+            loop {
+                // listen before reading the log so that no notification is lost
+                let listener = curp.apply_listener();
+                match curp.applied_entries_from(next) {
+                    Err(base_index) => {
+                        yield LogEvent::SnapshotNeeded(base_index);
+                        next = base_index.overflow_add(1);
+                    }
+                    Ok(entries) if entries.is_empty() => listener.await,
+                    Ok(entries) => {
+                        for entry in entries {
+                            next = entry.index.overflow_add(1);
+                            if let EntryData::Command(ref cmd) = entry.entry_data {
+                                yield LogEvent::Command(entry.index, Arc::clone(cmd));
+                            }
+                        }
+                        if with_watermarks {
+                            yield LogEvent::Watermark(next.overflow_sub(1));
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Error of the replication
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum ReplicateError<E> {
+    /// The source has compacted the entries to replicate, the target should install a
+    /// snapshot including the index of the source
+    #[error("the source log is compacted, a snapshot including {0} is needed")]
+    SnapshotNeeded(LogIndex),
+    /// Failed to apply a command to the target
+    #[error("failed to apply to the target: {0}")]
+    Apply(E),
+    /// Failed to persist the watermark of the target
+    #[error("failed to persist the watermark: {0}")]
+    Storage(StorageError),
+}
+
+/// Replicates the committed commands of a source cluster to a passive target cluster, and
+/// tracks how far the target has applied to report the replication lag. The watermark is
+/// persisted in the curp storage of the target, so that the replication resumes from it after
+/// a restart, and the target reports it in its status.
+#[derive(Debug)]
+pub struct Replicator<C> {
+    /// All commands of the source up to this index have been applied to the target
+    watermark: AtomicU64,
+    /// The latest known committed watermark of the source
+    source_watermark: AtomicU64,
+    /// The curp storage of the target
+    storage: Arc<DB<C>>,
+}
+
+impl<C: Command> Replicator<C> {
+    /// Create a new `Replicator`, which resumes from the watermark persisted in the curp
+    /// storage of the target, or from the beginning if the target has never replicated
+    ///
+    /// # Errors
+    ///
+    /// Return `StorageError` if the watermark can not be recovered
+    #[inline]
+    pub fn recover(storage: Arc<DB<C>>) -> Result<Self, StorageError> {
+        let watermark = storage.recover_replication_watermark()?.unwrap_or(0);
+        Ok(Self {
+            watermark: AtomicU64::new(watermark),
+            source_watermark: AtomicU64::new(watermark),
+            storage,
+        })
+    }
+
+    /// Get the watermark of the target, the replication stream of the source should start
+    /// right after it
+    #[inline]
+    #[must_use]
+    pub fn watermark(&self) -> LogIndex {
+        self.watermark.load(Ordering::Acquire)
+    }
+
+    /// Get the replication lag, the number of source log entries not applied to the target
+    #[inline]
+    #[must_use]
+    pub fn lag(&self) -> u64 {
+        self.source_watermark
+            .load(Ordering::Acquire)
+            .saturating_sub(self.watermark())
+    }
+
+    /// Advance the watermark of the target and persist it
+    fn advance<E>(&self, index: LogIndex) -> Result<(), ReplicateError<E>> {
+        if index <= self.watermark() {
+            return Ok(());
+        }
+        self.storage
+            .put_replication_watermark(index)
+            .map_err(ReplicateError::Storage)?;
+        let _prev = self.watermark.fetch_max(index, Ordering::AcqRel);
+        Ok(())
+    }
+
+    /// Apply the commands from a replication stream of the source to the target with `apply`,
+    /// until the target catches up with `source_watermark` or the stream ends. Commands at or
+    /// below the watermark of the target are skipped, so a stream can be safely resumed. The
+    /// watermark is persisted after each applied command, a command applied right before a
+    /// crash may be applied again after the restart.
+    ///
+    /// # Errors
+    ///
+    /// Return `ReplicateError::SnapshotNeeded` if the source has compacted the entries to
+    /// replicate, `ReplicateError::Apply` if `apply` fails, or `ReplicateError::Storage` if
+    /// the watermark can not be persisted
+    #[inline]
+    pub async fn replicate_to<S, F, Fut, E>(
+        &self,
+        source_watermark: LogIndex,
+        events: S,
+        mut apply: F,
+    ) -> Result<(), ReplicateError<E>>
+    where
+        S: Stream<Item = LogEvent<C>>,
+        F: FnMut(LogIndex, Arc<C>) -> Fut,
+        Fut: Future<Output = Result<(), E>>,
+    {
+        let _prev = self
+            .source_watermark
+            .fetch_max(source_watermark, Ordering::AcqRel);
+        let mut events = Box::pin(events);
+        while self.watermark() < source_watermark {
+            let Some(event) = events.next().await else {
+                break;
+            };
+            match event {
+                LogEvent::Command(index, cmd) => {
+                    if index <= self.watermark() {
+                        continue;
+                    }
+                    apply(index, cmd).await.map_err(ReplicateError::Apply)?;
+                    self.advance(index)?;
+                }
+                LogEvent::Watermark(index) => {
+                    let _prev = self.source_watermark.fetch_max(index, Ordering::AcqRel);
+                    self.advance(index)?;
+                }
+                LogEvent::SnapshotNeeded(index) => {
+                    return Err(ReplicateError::SnapshotNeeded(index));
+                }
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,99 @@
+use std::{collections::HashMap, time::Instant};
+
+use parking_lot::Mutex;
+
+/// Number of tracked clients above which idle buckets are dropped
+const GC_THRESHOLD: usize = 1024;
+
+/// Per-client-id rate limiter of proposes, each client id owns a token bucket which holds
+/// at most one second of its quota, so that short bursts are tolerated
+#[derive(Debug)]
+pub(super) struct ClientRateLimiter {
+    /// Max proposes per second of a client id, 0 means unlimited
+    qps: u64,
+    /// Token buckets of client ids
+    buckets: Mutex<HashMap<u64, Bucket>>,
+}
+
+/// Token bucket of a client id
+#[derive(Debug, Clone, Copy)]
+struct Bucket {
+    /// Available tokens
+    tokens: f64,
+    /// Time of the last refill
+    last_refill: Instant,
+}
+
+impl ClientRateLimiter {
+    /// Create a new rate limiter
+    pub(super) fn new(qps: u64) -> Self {
+        Self {
+            qps,
+            buckets: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Try to take a token of the client id at `now`, return `false` if the client id has
+    /// exceeded its quota
+    #[allow(clippy::arithmetic_side_effects, clippy::float_arithmetic)] // float calculation
+    #[allow(clippy::as_conversions, clippy::cast_precision_loss)] // qps is not that large
+    pub(super) fn try_acquire(&self, client_id: u64, now: Instant) -> bool {
+        if self.qps == 0 {
+            return true;
+        }
+        let capacity = self.qps as f64;
+        let refill = |bucket: &mut Bucket| {
+            let elapsed = now
+                .saturating_duration_since(bucket.last_refill)
+                .as_secs_f64();
+            bucket.tokens = (bucket.tokens + elapsed * capacity).min(capacity);
+            bucket.last_refill = now;
+        };
+        let mut buckets = self.buckets.lock();
+        if buckets.len() >= GC_THRESHOLD && !buckets.contains_key(&client_id) {
+            // a full bucket behaves the same as a new one
+            buckets.retain(|_id, bucket| {
+                refill(bucket);
+                bucket.tokens < capacity
+            });
+        }
+        let bucket = buckets.entry(client_id).or_insert(Bucket {
+            tokens: capacity,
+            last_refill: now,
+        });
+        refill(bucket);
+        if bucket.tokens < 1.0 {
+            return false;
+        }
+        bucket.tokens -= 1.0;
+        true
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[test]
+    fn flooding_client_should_be_limited_while_others_proceed() {
+        let limiter = ClientRateLimiter::new(10);
+        let now = Instant::now();
+        let accepted = (0..100).filter(|_| limiter.try_acquire(1, now)).count();
+        assert_eq!(accepted, 10);
+        assert!(!limiter.try_acquire(1, now));
+        // another client id is not affected
+        assert!(limiter.try_acquire(2, now));
+        // the quota is refilled over time
+        assert!(limiter.try_acquire(1, now + Duration::from_millis(100)));
+        assert!(!limiter.try_acquire(1, now + Duration::from_millis(100)));
+    }
+
+    #[test]
+    fn zero_qps_should_be_unlimited() {
+        let limiter = ClientRateLimiter::new(0);
+        let now = Instant::now();
+        assert!((0..1000).all(|_| limiter.try_acquire(1, now)));
+    }
+}
@@ -17,7 +17,8 @@ use tracing::error;
 use crate::{
     cmd::Command,
     log_entry::{EntryData, LogEntry},
-    rpc::ProposeId,
+    members::ServerId,
+    rpc::{Member, ProposeId},
     server::metrics,
     snapshot::SnapshotMeta,
     LogIndex,
@@ -58,6 +59,10 @@ pub(super) struct FallbackContext<C: Command> {
     pub(super) name: String,
     /// Whether the old config is a learner
     pub(super) is_learner: bool,
+    /// The outgoing voters of the joint config left by the origin entry
+    pub(super) outgoing: HashSet<ServerId>,
+    /// The members removed when leaving the joint config
+    pub(super) removed: Vec<Member>,
 }
 
 impl<C: Command> FallbackContext<C> {
@@ -73,8 +78,21 @@ impl<C: Command> FallbackContext<C> {
             addrs,
             name,
             is_learner,
+            outgoing: HashSet::new(),
+            removed: Vec::new(),
         }
     }
+
+    /// Attach the joint config left by the origin entry
+    pub(super) fn with_left_joint(
+        mut self,
+        outgoing: HashSet<ServerId>,
+        removed: Vec<Member>,
+    ) -> Self {
+        self.outgoing = outgoing;
+        self.removed = removed;
+        self
+    }
 }
 
 /// That's a struct to store log entries and calculate batch of log
@@ -14,16 +14,17 @@ use std::{
     collections::{HashMap, HashSet},
     fmt::Debug,
     sync::{
-        atomic::{AtomicU64, AtomicU8, Ordering},
+        atomic::{AtomicU64, AtomicU8, AtomicUsize, Ordering},
         Arc,
     },
+    time::{Duration, Instant},
 };
 
 use clippy_utilities::{NumericCast, OverflowArithmetic};
 use curp_external_api::cmd::ConflictCheck;
 use dashmap::DashMap;
 use derive_builder::Builder;
-use event_listener::Event;
+use event_listener::{Event, EventListener};
 use itertools::Itertools;
 use opentelemetry::KeyValue;
 use parking_lot::{Mutex, RwLock, RwLockUpgradableReadGuard, RwLockWriteGuard};
@@ -45,19 +46,19 @@ use utils::{
 
 use self::{
     log::Log,
-    state::{CandidateState, LeaderState, State},
+    state::{AppliedState, CandidateState, Config, LeaderState, State},
 };
 use super::{cmd_worker::CEEventTxApi, lease_manager::LeaseManagerRef, storage::StorageApi, DB};
 use crate::{
     cmd::Command,
     log_entry::{EntryData, LogEntry},
     members::{ClusterInfo, ServerId},
-    quorum, recover_quorum,
+    recover_quorum,
     role_change::RoleChange,
     rpc::{
         connect::{InnerConnectApi, InnerConnectApiWrapper},
         ConfChange, ConfChangeType, CurpError, IdSet, Member, PoolEntry, PoolEntryInner, ProposeId,
-        PublishRequest, ReadState,
+        PublishRequest, ReadState, ServerLoad,
     },
     server::{
         cmd_board::CmdBoardRef,
@@ -65,7 +66,7 @@ use crate::{
         raw_curp::{log::FallbackContext, state::VoteResult},
         spec_pool::SpecPoolRef,
     },
-    snapshot::{Snapshot, SnapshotMeta},
+    snapshot::{Snapshot, SnapshotInfo, SnapshotMeta},
     LogIndex,
 };
 
@@ -151,6 +152,9 @@ pub(super) struct RawCurpArgs<C: Command, RC: RoleChange> {
     /// Log entries
     #[builder(default)]
     entries: Vec<LogEntry<C>>,
+    /// The incoming and the outgoing voters if the cluster was in a joint config
+    #[builder(default)]
+    joint_config: Option<(HashSet<ServerId>, HashSet<ServerId>)>,
 }
 
 impl<C: Command, RC: RoleChange> RawCurpBuilder<C, RC> {
@@ -163,7 +167,11 @@ impl<C: Command, RC: RoleChange> RawCurpBuilder<C, RC> {
             args.cfg.candidate_timeout_ticks,
         ));
         let lst = LeaderState::new(&args.cluster_info.peers_ids());
-        let cst = Mutex::new(CandidateState::new(args.cluster_info.all_ids().into_iter()));
+        let mut candidate_state = CandidateState::new(args.cluster_info.all_ids().into_iter());
+        if let Some((incoming, outgoing)) = args.joint_config {
+            candidate_state.config.recover_joint(incoming, outgoing);
+        }
+        let cst = Mutex::new(candidate_state);
         let log = RwLock::new(Log::new(
             args.log_tx,
             args.cfg.batch_max_size,
@@ -221,6 +229,7 @@ impl<C: Command, RC: RoleChange> RawCurpBuilder<C, RC> {
             log_w.last_exe = last_applied;
             log_w.commit_index = last_applied;
             log_w.restore_entries(args.entries);
+            *raw_curp.ctx.applied.lock() = AppliedState::new(last_applied);
         }
 
         Ok(raw_curp)
@@ -324,6 +333,12 @@ struct Context<C: Command, RC: RoleChange> {
     /// Become leader event
     #[builder(setter(skip))]
     leader_event: Arc<Event>,
+    /// Event triggered when log entries are applied or the log is reset by a snapshot
+    #[builder(setter(skip))]
+    apply_event: Event,
+    /// Applied state
+    #[builder(setter(skip))]
+    applied: Mutex<AppliedState>,
     /// Leader change callback
     role_change: RC,
     /// Conf change tx, used to update sync tasks
@@ -339,6 +354,18 @@ struct Context<C: Command, RC: RoleChange> {
     last_conf_change_idx: AtomicU64,
     /// Curp storage
     curp_storage: Arc<DB<C>>,
+    /// Source of the hint piggybacked on append entries while this node is the leader
+    #[builder(setter(skip))]
+    hint_source: RwLock<Option<Box<dyn Fn() -> u64 + Send + Sync>>>,
+    /// The latest hint piggybacked by the leader
+    #[builder(setter(skip))]
+    leader_hint: AtomicU64,
+    /// Information of the latest snapshot taken or installed
+    #[builder(setter(skip))]
+    last_snapshot: Mutex<Option<SnapshotInfo>>,
+    /// Number of command execute workers handling a task
+    #[builder(setter(skip))]
+    busy_workers: AtomicUsize,
 }
 
 impl<C: Command, RC: RoleChange> Context<C, RC> {
@@ -388,6 +415,8 @@ impl<C: Command, RC: RoleChange> ContextBuilder<C, RC> {
                 None => return Err(ContextBuilderError::UninitializedField("sync_events")),
             },
             leader_event: Arc::new(Event::new()),
+            apply_event: Event::new(),
+            applied: Mutex::new(AppliedState::default()),
             role_change: match self.role_change.take() {
                 Some(value) => value,
                 None => return Err(ContextBuilderError::UninitializedField("role_change")),
@@ -407,6 +436,10 @@ impl<C: Command, RC: RoleChange> ContextBuilder<C, RC> {
                 Some(value) => value,
                 None => return Err(ContextBuilderError::UninitializedField("client_tls_config")),
             },
+            hint_source: RwLock::new(None),
+            leader_hint: AtomicU64::new(0),
+            last_snapshot: Mutex::new(None),
+            busy_workers: AtomicUsize::new(0),
         })
     }
 }
@@ -688,6 +721,10 @@ impl<C: Command, RC: RoleChange> RawCurp<C, RC> {
                 unreachable!("the entry in the fallback_info should be conf change entry");
             };
             let changes = conf_change.clone();
+            if changes.is_empty() {
+                self.fallback_leave_joint(info.outgoing, info.removed);
+                continue;
+            }
             self.fallback_conf_change(changes, info.addrs, info.name, info.is_learner);
         }
         // apply conf change entries
@@ -695,11 +732,17 @@ impl<C: Command, RC: RoleChange> RawCurp<C, RC> {
             let EntryData::ConfChange(ref cc) = e.entry_data else {
                 unreachable!("cc_entry should be conf change entry");
             };
-            let (addrs, name, is_learner) = self.apply_conf_change(cc.clone());
-            let _ig = log_w.fallback_contexts.insert(
-                e.index,
-                FallbackContext::new(Arc::clone(&e), addrs, name, is_learner),
-            );
+            // an empty conf change is appended by the leader to leave the joint config
+            let fallback_context = if cc.is_empty() {
+                // a node accepting the entries of the leader is not the leader
+                let (outgoing, removed) = self.leave_joint(false);
+                FallbackContext::new(Arc::clone(&e), vec![], String::new(), false)
+                    .with_left_joint(outgoing, removed)
+            } else {
+                let (addrs, name, is_learner) = self.apply_conf_change(cc.clone());
+                FallbackContext::new(Arc::clone(&e), addrs, name, is_learner)
+            };
+            let _ig = log_w.fallback_contexts.insert(e.index, fallback_context);
         }
         // update commit index
         let prev_commit_index = log_w.commit_index;
@@ -763,6 +806,7 @@ impl<C: Command, RC: RoleChange> RawCurp<C, RC> {
                     .proposals_pending
                     .observe(log_w.last_log_index().overflow_sub(last_sent_index), &[]);
                 self.apply(&mut *log_w);
+                self.leave_joint_if_committed(&mut log_w, cur_term);
             }
         }
 
@@ -1139,6 +1183,72 @@ impl<C: Command, RC: RoleChange> RawCurp<C, RC> {
         self.log.read().commit_index
     }
 
+    /// Set the source of the hint piggybacked on append entries while this node is the
+    /// leader, so that followers learn an application state of the leader, e.g. its latest
+    /// revision, with heartbeats
+    #[inline]
+    pub fn set_leader_hint_source(&self, source: impl Fn() -> u64 + Send + Sync + 'static) {
+        *self.ctx.hint_source.write() = Some(Box::new(source));
+    }
+
+    /// Get the hint of the leader, which is read from the hint source if this node is the
+    /// leader, or piggybacked by the latest accepted append entries otherwise
+    #[inline]
+    pub fn leader_hint(&self) -> u64 {
+        if self.is_leader() {
+            self.local_leader_hint()
+        } else {
+            self.ctx.leader_hint.load(Ordering::Acquire)
+        }
+    }
+
+    /// Get the watermark of the replication from a source cluster persisted by a
+    /// `Replicator`, `None` if this node has never replicated from a source
+    #[inline]
+    pub fn replication_watermark(&self) -> Option<LogIndex> {
+        self.ctx
+            .curp_storage
+            .recover_replication_watermark()
+            .unwrap_or_else(|e| {
+                warn!("failed to recover the replication watermark, {e}");
+                None
+            })
+    }
+
+    /// Get the information of the latest snapshot taken to calibrate a follower or installed
+    /// from the leader, `None` if no snapshot has been made since this node started
+    #[inline]
+    pub fn snapshot_info(&self) -> Option<SnapshotInfo> {
+        *self.ctx.last_snapshot.lock()
+    }
+
+    /// Record a snapshot taken or installed now
+    pub(super) fn record_snapshot(&self, meta: SnapshotMeta, size: u64) {
+        *self.ctx.last_snapshot.lock() = Some(SnapshotInfo::new(meta, size));
+    }
+
+    /// Get the size of the command execution pool
+    #[inline]
+    pub fn execution_pool_size(&self) -> usize {
+        self.cfg().cmd_workers.numeric_cast()
+    }
+
+    /// Get the number of command execute workers handling a task, the utilization of the
+    /// execution pool is it divided by the pool size
+    #[inline]
+    pub fn busy_workers(&self) -> usize {
+        self.ctx.busy_workers.load(Ordering::Relaxed)
+    }
+
+    /// Mark a command execute worker busy or idle
+    pub(super) fn set_worker_busy(&self, busy: bool) {
+        if busy {
+            let _prev = self.ctx.busy_workers.fetch_add(1, Ordering::Relaxed);
+        } else {
+            let _prev = self.ctx.busy_workers.fetch_sub(1, Ordering::Relaxed);
+        }
+    }
+
     /// Get cluster info
     pub(super) fn cluster(&self) -> &ClusterInfo {
         self.ctx.cluster_info.as_ref()
@@ -1221,6 +1331,34 @@ impl<C: Command, RC: RoleChange> RawCurp<C, RC> {
     pub(super) fn reset_by_snapshot(&self, meta: SnapshotMeta) {
         let mut log_w = self.log.write();
         log_w.reset_by_snapshot_meta(meta);
+        self.ctx.applied.lock().reset(log_w.last_as);
+        self.ctx.apply_event.notify(usize::MAX);
+    }
+
+    /// Get a listener notified when log entries are applied or the log is reset by a snapshot
+    pub(super) fn apply_listener(&self) -> EventListener {
+        self.ctx.apply_event.listen()
+    }
+
+    /// Get the index of the last applied log entry, all entries up to it are after synced
+    pub(super) fn last_applied(&self) -> LogIndex {
+        self.ctx.applied.lock().index()
+    }
+
+    /// Get the applied log entries starting from `from`, returns the base index of the log
+    /// instead if some of the entries have been compacted
+    pub(super) fn applied_entries_from(
+        &self,
+        from: LogIndex,
+    ) -> Result<Vec<Arc<LogEntry<C>>>, LogIndex> {
+        let log_r = self.log.read();
+        if from <= log_r.base_index {
+            return Err(log_r.base_index);
+        }
+        let applied = self.ctx.applied.lock().index();
+        Ok((from..=applied)
+            .filter_map(|i| log_r.get(i).map(Arc::clone))
+            .collect())
     }
 
     /// Get current term
@@ -1279,11 +1417,21 @@ impl<C: Command, RC: RoleChange> RawCurp<C, RC> {
     }
 
     /// Check if the new config is valid
+    ///
+    /// More than one change will be committed atomically through a joint config,
+    /// no other conf change is accepted until the cluster leaves that joint config.
     pub(super) fn check_new_config(&self, changes: &[ConfChange]) -> Result<(), CurpError> {
-        assert_eq!(changes.len(), 1, "Joint consensus is not supported yet");
-        let Some(conf_change) = changes.iter().next() else {
-            unreachable!("conf change is empty");
-        };
+        let mut config = self.cst.map_lock(|cst_l| cst_l.config.clone());
+        if changes.is_empty() || config.is_joint() {
+            return Err(CurpError::invalid_config());
+        }
+        let joint = changes.len() > 1;
+        // entering a joint config requires all previous conf changes to be committed
+        if joint
+            && self.ctx.last_conf_change_idx.load(Ordering::Acquire) > self.log.read().commit_index
+        {
+            return Err(CurpError::invalid_config());
+        }
         let mut statuses_ids = self
             .lst
             .get_all_statuses()
@@ -1291,7 +1439,29 @@ impl<C: Command, RC: RoleChange> RawCurp<C, RC> {
             .copied()
             .chain([self.id()])
             .collect::<HashSet<_>>();
-        let mut config = self.cst.map_lock(|cst_l| cst_l.config.clone());
+        for conf_change in changes {
+            self.check_conf_change(conf_change, joint, &mut statuses_ids, &mut config)?;
+        }
+        let mut all_nodes = HashSet::new();
+        all_nodes.extend(config.voters());
+        all_nodes.extend(&config.learners);
+        if all_nodes != statuses_ids
+            || !config.voters().is_disjoint(&config.learners)
+            || (joint && config.voters().is_empty())
+        {
+            return Err(CurpError::invalid_config());
+        }
+        Ok(())
+    }
+
+    /// Check a single conf change against the config it will be applied to
+    fn check_conf_change(
+        &self,
+        conf_change: &ConfChange,
+        joint: bool,
+        statuses_ids: &mut HashSet<ServerId>,
+        config: &mut Config,
+    ) -> Result<(), CurpError> {
         let node_id = conf_change.node_id;
         match conf_change.change_type() {
             ConfChangeType::Add => {
@@ -1300,11 +1470,18 @@ impl<C: Command, RC: RoleChange> RawCurp<C, RC> {
                 }
             }
             ConfChangeType::Remove => {
+                // a joint config can only defer the removal of voters
+                if joint && config.learners.contains(&node_id) {
+                    return Err(CurpError::invalid_config());
+                }
                 if !statuses_ids.remove(&node_id) || !config.remove(node_id) {
                     return Err(CurpError::node_not_exist());
                 }
             }
             ConfChangeType::Update => {
+                if joint {
+                    return Err(CurpError::invalid_config());
+                }
                 if statuses_ids.get(&node_id).is_none() || !config.contains(node_id) {
                     return Err(CurpError::node_not_exist());
                 }
@@ -1321,10 +1498,13 @@ impl<C: Command, RC: RoleChange> RawCurp<C, RC> {
                         .add(1, &[KeyValue::new("reason", "learner not exist")]);
                     return Err(CurpError::node_not_exist());
                 }
-                let learner_index = self
-                    .lst
-                    .get_match_index(node_id)
-                    .unwrap_or_else(|| unreachable!("learner should exist here"));
+                // a learner added by the same joint change has not caught up yet
+                let Some(learner_index) = self.lst.get_match_index(node_id) else {
+                    if joint {
+                        return Err(CurpError::learner_not_catch_up());
+                    }
+                    unreachable!("learner should exist here");
+                };
                 let leader_index = self.log.read().last_log_index();
                 if leader_index.overflow_sub(learner_index) > MAX_PROMOTE_GAP {
                     metrics::get()
@@ -1334,21 +1514,25 @@ impl<C: Command, RC: RoleChange> RawCurp<C, RC> {
                 }
             }
         }
-        let mut all_nodes = HashSet::new();
-        all_nodes.extend(config.voters());
-        all_nodes.extend(&config.learners);
-        if all_nodes != statuses_ids || !config.voters().is_disjoint(&config.learners) {
-            return Err(CurpError::invalid_config());
-        }
         Ok(())
     }
 
     /// Apply conf changes and return true if self node is removed
+    ///
+    /// More than one change enters a joint config, see `enter_joint`
     pub(super) fn apply_conf_change(
         &self,
         changes: Vec<ConfChange>,
     ) -> (Vec<String>, String, bool) {
-        assert_eq!(changes.len(), 1, "Joint consensus is not supported yet");
+        if changes.len() > 1 {
+            debug!(
+                "{} enters joint config with changes {:?}",
+                self.id(),
+                changes
+            );
+            self.enter_joint(changes);
+            return (vec![], String::new(), false);
+        }
         let Some(conf_change) = changes.into_iter().next() else {
             unreachable!("conf change is empty");
         };
@@ -1364,7 +1548,10 @@ impl<C: Command, RC: RoleChange> RawCurp<C, RC> {
         name: String,
         is_learner: bool,
     ) {
-        assert_eq!(changes.len(), 1, "Joint consensus is not supported yet");
+        if changes.len() > 1 {
+            self.fallback_joint(changes);
+            return;
+        }
         if is_learner {
             metrics::get().learner_promote_failed.add(
                 1,
@@ -1464,10 +1651,9 @@ impl<C: Command, RC: RoleChange> RawCurp<C, RC> {
     /// Get voters connects
     pub(super) fn voters_connects(&self) -> Vec<Arc<dyn InnerConnectApi>> {
         let cst_r = self.cst.lock();
-        let voters = cst_r.config.voters();
         self.connects()
             .iter()
-            .filter(|c| voters.contains(c.key()))
+            .filter(|c| cst_r.config.is_voter(*c.key()))
             .map(|c| Arc::clone(c.value()))
             .collect()
     }
@@ -1487,6 +1673,42 @@ impl<C: Command, RC: RoleChange> RawCurp<C, RC> {
         self.log.read().last_log_index()
     }
 
+    /// Get the hint to piggyback on append entries, which is 0 if there is no hint source
+    pub(super) fn local_leader_hint(&self) -> u64 {
+        self.ctx
+            .hint_source
+            .read()
+            .as_ref()
+            .map_or(0, |source| source())
+    }
+
+    /// Record the hint piggybacked by the leader, hints never go backwards
+    pub(super) fn update_leader_hint(&self, hint: u64) {
+        let _prev = self.ctx.leader_hint.fetch_max(hint, Ordering::AcqRel);
+    }
+
+    /// Get the load of this server, which includes the number of uncommitted log entries
+    /// and the number of committed log entries not applied yet
+    pub(super) fn load(&self) -> ServerLoad {
+        let log_r = self.log.read();
+        let applied = self.ctx.applied.lock().index();
+        ServerLoad::new(
+            log_r.last_log_index().overflow_sub(log_r.commit_index),
+            log_r.commit_index.saturating_sub(applied),
+        )
+    }
+
+    /// Mark a log entry as applied, called by the cmd workers when an entry is after synced
+    /// or fails to execute
+    pub(super) fn mark_applied(&self, index: LogIndex) {
+        let log_r = self.log.read();
+        let mut applied_l = self.ctx.applied.lock();
+        applied_l.finish(index);
+        if applied_l.advance(log_r.last_as) {
+            self.ctx.apply_event.notify(usize::MAX);
+        }
+    }
+
     /// Pick a node that has the same log as the current node
     pub(super) fn pick_new_leader(&self) -> Option<ServerId> {
         let last_idx = self.log.read().last_log_index();
@@ -1502,6 +1724,67 @@ impl<C: Command, RC: RoleChange> RawCurp<C, RC> {
     pub(super) fn client_tls_config(&self) -> Option<&ClientTlsConfig> {
         self.ctx.client_tls_config.as_ref()
     }
+
+    /// Record that a follower has acknowledged an append entries sent at `sent_at`,
+    /// a quorum of such acknowledgements renews the leader lease
+    pub(super) fn record_ae_ack(&self, follower_id: ServerId, sent_at: Instant) {
+        self.lst.record_ack(follower_id, sent_at);
+    }
+
+    /// Check whether a quorum of voters has acknowledged append entries sent no earlier than `since`
+    pub(super) fn quorum_acked_since(&self, since: Instant) -> bool {
+        self.cst.map_lock(|cst_l| {
+            cst_l
+                .config
+                .has_quorum(|id| id == self.id() || self.lst.acked_since(id, since))
+        })
+    }
+
+    /// Check whether the leader holds a valid leader lease, in which case it can serve
+    /// linearizable reads without confirming its leadership with a quorum
+    pub(super) fn has_valid_leader_lease(&self) -> bool {
+        if !self.cfg().enable_leader_lease || !self.is_leader() || self.get_transferee().is_some() {
+            return false;
+        }
+        let Some(since) = Instant::now().checked_sub(self.leader_lease_duration()) else {
+            return false;
+        };
+        self.quorum_acked_since(since)
+    }
+
+    /// Get a listener for follower acknowledgements
+    pub(super) fn ack_listener(&self) -> EventListener {
+        self.lst.ack_listener()
+    }
+
+    /// Confirm that current node is still the leader by waiting for a quorum of followers
+    /// to acknowledge append entries sent after this call, used when the leader lease expires.
+    /// The wait is bounded by the linearizable read timeout, after which an `Internal` error
+    /// is returned and the read is retried by the client.
+    pub(super) async fn confirm_leadership(&self) -> Result<(), CurpError> {
+        let since = Instant::now();
+        let wait_quorum = async {
+            loop {
+                let listener = self.ack_listener();
+                if !self.is_leader() {
+                    let (leader_id, term, _) = self.leader();
+                    return Err(CurpError::redirect(leader_id, term));
+                }
+                if self.quorum_acked_since(since) {
+                    return Ok(());
+                }
+                listener.await;
+            }
+        };
+        tokio::time::timeout(self.linearizable_read_timeout(), wait_quorum)
+            .await
+            .unwrap_or_else(|_elapsed| {
+                debug!("{} failed to confirm leadership in time", self.id());
+                Err(CurpError::internal(
+                    "failed to confirm leadership for linearizable read".to_owned(),
+                ))
+            })
+    }
 }
 
 // Utils
@@ -1609,6 +1892,7 @@ impl<C: Command, RC: RoleChange> RawCurp<C, RC> {
         metrics::get().leader_changes.add(1, &[]);
         st.role = Role::Leader;
         st.leader_id = Some(self.id());
+        self.lst.reset_acks();
         let _ig = self.ctx.leader_tx.send(Some(self.id())).ok();
         self.ctx.leader_event.notify(usize::MAX);
         self.ctx.role_change.on_election_win();
@@ -1648,6 +1932,25 @@ impl<C: Command, RC: RoleChange> RawCurp<C, RC> {
         self.ctx.election_tick.store(0, Ordering::Relaxed);
     }
 
+    /// Length of the leader lease. Followers that have heard from the leader will not grant
+    /// pre votes within `heartbeat_interval * follower_timeout_ticks`, the clock drift margin
+    /// is subtracted from it to stay safe
+    fn leader_lease_duration(&self) -> Duration {
+        (self.cfg().heartbeat_interval * u32::from(self.cfg().follower_timeout_ticks))
+            .saturating_sub(self.cfg().leader_lease_clock_drift)
+    }
+
+    /// Timeout of confirming the leadership for a linearizable read, it defaults to the time
+    /// for a heartbeat round if not configured
+    fn linearizable_read_timeout(&self) -> Duration {
+        let cfg = self.cfg();
+        if cfg.linearizable_read_timeout.is_zero() {
+            cfg.heartbeat_interval.saturating_add(cfg.rpc_timeout)
+        } else {
+            cfg.linearizable_read_timeout
+        }
+    }
+
     /// Check whether `commit_index` can be updated to i
     fn can_update_commit_index_to(&self, log: &Log<C>, i: LogIndex, cur_term: u64) -> bool {
         if log.commit_index >= i {
@@ -1659,12 +1962,15 @@ impl<C: Command, RC: RoleChange> RawCurp<C, RC> {
             return false;
         }
 
-        let replicated_cnt = self
-            .lst
-            .iter()
-            .filter(|f| !f.is_learner && f.match_index >= i)
-            .count();
-        replicated_cnt + 1 >= quorum(self.ctx.cluster_info.voters_len())
+        self.cst.map_lock(|cst_l| {
+            cst_l.config.has_quorum(|id| {
+                id == self.id()
+                    || self
+                        .lst
+                        .get_match_index(id)
+                        .is_some_and(|match_index| match_index >= i)
+            })
+        })
     }
 
     /// Recover from all voter's spec pools
@@ -1754,6 +2060,7 @@ impl<C: Command, RC: RoleChange> RawCurp<C, RC> {
 
     /// Apply new logs
     fn apply(&self, log: &mut Log<C>) {
+        let notify = log.last_as < log.commit_index;
         for i in (log.last_as + 1)..=log.commit_index {
             metrics::get().proposals_applied.observe(i, &[]);
             let entry = log.get(i).unwrap_or_else(|| {
@@ -1775,6 +2082,11 @@ impl<C: Command, RC: RoleChange> RawCurp<C, RC> {
             );
         }
         log.compact();
+        // entries that failed to execute may have finished before they are committed
+        let advanced = self.ctx.applied.lock().advance(log.last_as);
+        if notify || advanced {
+            self.ctx.apply_event.notify(usize::MAX);
+        }
     }
 
     /// When leader retires, it should reset state
@@ -1863,6 +2175,160 @@ impl<C: Command, RC: RoleChange> RawCurp<C, RC> {
         fallback_info
     }
 
+    /// Enter a joint config made of the current voters and the voters after `changes`
+    ///
+    /// The removed voters stay in the outgoing config until the cluster leaves the
+    /// joint config, so they can still take part in elections and replication.
+    fn enter_joint(&self, changes: Vec<ConfChange>) {
+        self.cst.map_lock(|mut cst_l| cst_l.config.enter_joint());
+        for change in changes {
+            if matches!(change.change_type(), ConfChangeType::Remove) {
+                self.cst
+                    .map_lock(|mut cst_l| _ = cst_l.config.remove(change.node_id));
+            } else {
+                let _ig = self.switch_config(change);
+            }
+        }
+        self.cst
+            .map_lock(|cst_l| self.persist_joint_config(&cst_l.config));
+    }
+
+    /// Fallback a joint conf change, the outgoing voters become the current voters again
+    fn fallback_joint(&self, changes: Vec<ConfChange>) {
+        for change in changes.into_iter().rev() {
+            match change.change_type() {
+                ConfChangeType::Add | ConfChangeType::AddLearner | ConfChangeType::Promote => {
+                    self.fallback_conf_change(vec![change], vec![], String::new(), false);
+                }
+                // removed voters are only dropped when leaving the joint config
+                ConfChangeType::Remove | ConfChangeType::Update => {}
+            }
+        }
+        self.cst.map_lock(|mut cst_l| {
+            cst_l.config.revert_joint();
+            self.persist_joint_config(&cst_l.config);
+        });
+    }
+
+    /// Persist the joint config, so that a restarted node still requires the quorum of both
+    /// the incoming and the outgoing voters
+    fn persist_joint_config(&self, config: &Config) {
+        if let Err(e) = self
+            .ctx
+            .curp_storage
+            .put_joint_config(config.joint_voters())
+        {
+            error!("failed to persist the joint config, {e}");
+        }
+    }
+
+    /// Leave the joint config and remove the voters that only exist in the outgoing config,
+    /// return the outgoing voters and the removed members for fallback
+    ///
+    /// The role is passed in by the caller because it may hold `st` already, and `st` must
+    /// not be acquired while holding `cst`
+    fn leave_joint(&self, is_leader: bool) -> (HashSet<ServerId>, Vec<Member>) {
+        let mut cst_l = self.cst.lock();
+        let outgoing = cst_l.config.leave_joint();
+        self.persist_joint_config(&cst_l.config);
+        let mut removed_ids = outgoing
+            .iter()
+            .copied()
+            .filter(|id| !cst_l.config.contains(*id))
+            .collect_vec();
+        // the conf change task of the leader stops once it sees its own removal
+        removed_ids.sort_by_key(|id| *id == self.id());
+        let mut removed = Vec::with_capacity(removed_ids.len());
+        for id in removed_ids {
+            self.lst.remove(id);
+            _ = self.ctx.sync_events.remove(&id);
+            _ = self.ctx.connects.remove(&id);
+            let _ig = self.ctx.curp_storage.remove_member(id);
+            if let Some(member) = self.ctx.cluster_info.remove(&id) {
+                removed.push(member);
+            }
+            if is_leader {
+                self.ctx
+                    .change_tx
+                    .send(ConfChange::remove(id))
+                    .unwrap_or_else(|_e| unreachable!("change_rx should not be dropped"));
+            }
+        }
+        if !removed.is_empty() {
+            self.ctx.cluster_info.cluster_version_update();
+        }
+        if is_leader
+            && self
+                .lst
+                .get_transferee()
+                .is_some_and(|transferee| !cst_l.config.voters().contains(&transferee))
+        {
+            self.lst.reset_transferee();
+        }
+        (outgoing, removed)
+    }
+
+    /// Fallback leaving the joint config, the removed members are restored
+    fn fallback_leave_joint(&self, outgoing: HashSet<ServerId>, removed: Vec<Member>) {
+        self.cst.map_lock(|mut cst_l| {
+            cst_l.config.restore_joint(outgoing);
+            self.persist_joint_config(&cst_l.config);
+        });
+        let modified = !removed.is_empty();
+        for member in removed {
+            let node_id = member.id;
+            let _ig1 = self.ctx.curp_storage.put_member(&member);
+            let change = ConfChange::add(node_id, member.peer_urls.clone());
+            let _ig2 = self.ctx.cluster_info.insert(member);
+            if node_id == self.id() {
+                continue;
+            }
+            self.lst.insert(node_id, false);
+            _ = self.ctx.sync_events.insert(node_id, Arc::new(Event::new()));
+            self.ctx
+                .change_tx
+                .send(change)
+                .unwrap_or_else(|_e| unreachable!("change_rx should not be dropped"));
+        }
+        if modified {
+            self.ctx.cluster_info.cluster_version_update();
+        }
+    }
+
+    /// Append an entry to leave the joint config once the entry entering it is committed,
+    /// must be called by the leader
+    fn leave_joint_if_committed(&self, log_w: &mut RwLockWriteGuard<'_, Log<C>>, term: u64) {
+        if !self.cst.map_lock(|cst_l| cst_l.config.is_joint())
+            || self.ctx.last_conf_change_idx.load(Ordering::Acquire) > log_w.commit_index
+        {
+            return;
+        }
+        // TODO: Generate client id in the same way as client
+        let propose_id = ProposeId(rand::random(), 0);
+        let entry = match log_w.push(term, propose_id, Vec::<ConfChange>::new()) {
+            Ok(entry) => entry,
+            Err(e) => {
+                error!("failed to append the entry leaving the joint config, {e}");
+                return;
+            }
+        };
+        debug!(
+            "{} leaves the joint config at log[{}]",
+            self.id(),
+            entry.index
+        );
+        let (outgoing, removed) = self.leave_joint(true);
+        self.ctx
+            .last_conf_change_idx
+            .store(entry.index, Ordering::Release);
+        let _ig = log_w.fallback_contexts.insert(
+            entry.index,
+            FallbackContext::new(Arc::clone(&entry), vec![], String::new(), false)
+                .with_left_joint(outgoing, removed),
+        );
+        self.entry_process(log_w, entry, false, term);
+    }
+
     /// Entry process shared by `handle_xxx`
     fn entry_process(
         &self,
@@ -1893,6 +2359,7 @@ impl<C: Command, RC: RoleChange> RawCurp<C, RC> {
                 .observe(log_w.last_log_index().overflow_sub(index), &[]);
             debug!("{} updates commit index to {index}", self.id());
             self.apply(&mut *log_w);
+            self.leave_joint_if_committed(log_w, term);
         }
     }
 
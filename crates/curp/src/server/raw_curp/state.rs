@@ -1,8 +1,10 @@
 use std::{
-    collections::{HashMap, HashSet},
+    collections::{BTreeSet, HashMap, HashSet},
     sync::atomic::{AtomicU64, Ordering},
+    time::Instant,
 };
 
+use clippy_utilities::OverflowArithmetic;
 use dashmap::{
     mapref::{
         multiple::RefMulti,
@@ -10,6 +12,7 @@ use dashmap::{
     },
     DashMap,
 };
+use event_listener::{Event, EventListener};
 use madsim::rand::{thread_rng, Rng};
 use tracing::{debug, warn};
 
@@ -62,6 +65,8 @@ pub(super) struct FollowerStatus {
     pub(super) match_index: LogIndex,
     /// This node is a learner or not
     pub(super) is_learner: bool,
+    /// Send time of the latest append entries acknowledged by that follower
+    pub(super) last_ack: Option<Instant>,
 }
 
 impl Default for FollowerStatus {
@@ -70,6 +75,7 @@ impl Default for FollowerStatus {
             next_index: 1,
             match_index: 0,
             is_learner: false,
+            last_ack: None,
         }
     }
 }
@@ -81,6 +87,7 @@ impl FollowerStatus {
             next_index,
             match_index,
             is_learner,
+            last_ack: None,
         }
     }
 }
@@ -92,6 +99,8 @@ pub(super) struct LeaderState {
     statuses: DashMap<ServerId, FollowerStatus>,
     /// Leader Transferee
     leader_transferee: AtomicU64,
+    /// Event notified when a follower acknowledges an append entries
+    ack_event: Event,
 }
 
 impl State {
@@ -130,6 +139,7 @@ impl LeaderState {
                 .map(|o| (*o, FollowerStatus::default()))
                 .collect(),
             leader_transferee: AtomicU64::new(0),
+            ack_event: Event::new(),
         }
     }
 
@@ -231,6 +241,38 @@ impl LeaderState {
         let val = self.leader_transferee.swap(node_id, Ordering::SeqCst);
         (val != 0).then_some(val)
     }
+
+    /// Record that a follower has acknowledged an append entries sent at `sent_at`
+    pub(super) fn record_ack(&self, id: ServerId, sent_at: Instant) {
+        {
+            let Some(mut status) = self.get_status_mut(id) else {
+                return;
+            };
+            if status.last_ack.is_some_and(|last| last >= sent_at) {
+                return;
+            }
+            status.last_ack = Some(sent_at);
+        }
+        self.ack_event.notify(usize::MAX);
+    }
+
+    /// Forget all acknowledgements, used when a new leadership begins
+    pub(super) fn reset_acks(&self) {
+        for mut status in self.statuses.iter_mut() {
+            status.last_ack = None;
+        }
+    }
+
+    /// Check if a follower has acknowledged an append entries sent no earlier than `since`
+    pub(super) fn acked_since(&self, id: ServerId, since: Instant) -> bool {
+        self.get_status(id)
+            .is_some_and(|s| s.last_ack.is_some_and(|last| last >= since))
+    }
+
+    /// Get a listener for follower acknowledgements
+    pub(super) fn ack_listener(&self) -> EventListener {
+        self.ack_event.listen()
+    }
 }
 
 impl<C> CandidateState<C> {
@@ -245,7 +287,7 @@ impl<C> CandidateState<C> {
 
     /// Check if the candidate has won the election
     pub(super) fn check_vote(&self) -> VoteResult {
-        self.config.majority_config.check_vote(&self.votes_received)
+        self.config.check_vote(&self.votes_received)
     }
 }
 
@@ -267,6 +309,8 @@ pub(super) struct MajorityConfig {
 pub(super) struct Config {
     /// The majority config
     pub(super) majority_config: MajorityConfig,
+    /// The outgoing majority config, only exists while the cluster is in a joint config
+    pub(super) outgoing: Option<MajorityConfig>,
     /// The learners in the cluster
     pub(super) learners: HashSet<ServerId>,
 }
@@ -276,6 +320,7 @@ impl Config {
     pub(super) fn new(voters: impl Iterator<Item = ServerId>) -> Self {
         Self {
             majority_config: MajorityConfig::new(voters),
+            outgoing: None,
             learners: HashSet::new(),
         }
     }
@@ -309,6 +354,87 @@ impl Config {
     pub(super) fn contains(&self, id: ServerId) -> bool {
         self.majority_config.voters.contains(&id) || self.learners.contains(&id)
     }
+
+    /// Check if a server is a voter of the current or the outgoing config
+    pub(super) fn is_voter(&self, id: ServerId) -> bool {
+        self.majority_config.voters.contains(&id)
+            || self
+                .outgoing
+                .as_ref()
+                .is_some_and(|outgoing| outgoing.voters.contains(&id))
+    }
+
+    /// Check if the cluster is in a joint config
+    pub(super) fn is_joint(&self) -> bool {
+        self.outgoing.is_some()
+    }
+
+    /// Enter the joint config, the current voters become the outgoing config
+    pub(super) fn enter_joint(&mut self) {
+        debug_assert!(!self.is_joint(), "the cluster is already in a joint config");
+        self.outgoing = Some(self.majority_config.clone());
+    }
+
+    /// Leave the joint config and return the outgoing voters
+    pub(super) fn leave_joint(&mut self) -> HashSet<ServerId> {
+        self.outgoing
+            .take()
+            .map(|outgoing| outgoing.voters)
+            .unwrap_or_default()
+    }
+
+    /// Get the incoming and the outgoing voters if the cluster is in a joint config
+    pub(super) fn joint_voters(&self) -> Option<(HashSet<ServerId>, HashSet<ServerId>)> {
+        self.outgoing
+            .as_ref()
+            .map(|outgoing| (self.voters().clone(), outgoing.voters.clone()))
+    }
+
+    /// Recover the joint config made of the `incoming` and the `outgoing` voters
+    pub(super) fn recover_joint(
+        &mut self,
+        incoming: HashSet<ServerId>,
+        outgoing: HashSet<ServerId>,
+    ) {
+        self.majority_config = MajorityConfig { voters: incoming };
+        self.outgoing = Some(MajorityConfig { voters: outgoing });
+    }
+
+    /// Restore the joint config whose outgoing voters are `outgoing`
+    pub(super) fn restore_joint(&mut self, outgoing: HashSet<ServerId>) {
+        self.outgoing = Some(MajorityConfig { voters: outgoing });
+    }
+
+    /// Revert the joint config, the outgoing voters become the current voters again
+    pub(super) fn revert_joint(&mut self) {
+        if let Some(outgoing) = self.outgoing.take() {
+            self.majority_config = outgoing;
+        }
+    }
+
+    /// Check if the servers that satisfy `acked` form a quorum, a joint config
+    /// requires a majority of both the incoming and the outgoing voters
+    pub(super) fn has_quorum(&self, acked: impl Fn(ServerId) -> bool) -> bool {
+        self.majority_config.has_quorum(&acked)
+            && self
+                .outgoing
+                .as_ref()
+                .map_or(true, |outgoing| outgoing.has_quorum(&acked))
+    }
+}
+
+impl ClusterConfig for Config {
+    fn check_vote(&self, votes_received: &HashMap<ServerId, bool>) -> VoteResult {
+        let incoming = self.majority_config.check_vote(votes_received);
+        let Some(ref outgoing) = self.outgoing else {
+            return incoming;
+        };
+        match (incoming, outgoing.check_vote(votes_received)) {
+            (VoteResult::Won, VoteResult::Won) => VoteResult::Won,
+            (VoteResult::Lost, _) | (_, VoteResult::Lost) => VoteResult::Lost,
+            _ => VoteResult::Pending,
+        }
+    }
 }
 
 impl MajorityConfig {
@@ -318,6 +444,12 @@ impl MajorityConfig {
             voters: voters.collect(),
         }
     }
+
+    /// Check if the voters that satisfy `acked` form a majority
+    fn has_quorum(&self, acked: impl Fn(ServerId) -> bool) -> bool {
+        self.voters.is_empty()
+            || self.voters.iter().filter(|id| acked(**id)).count() >= quorum(self.voters.len())
+    }
 }
 
 impl ClusterConfig for MajorityConfig {
@@ -347,6 +479,57 @@ impl ClusterConfig for MajorityConfig {
     }
 }
 
+/// Applied state, tracks the index of the last applied log entry
+///
+/// Non-conflicting commands are applied concurrently, so entries may finish out of order.
+/// The applied index only advances when all entries up to it have finished.
+#[derive(Debug, Default)]
+pub(super) struct AppliedState {
+    /// All entries up to this index are applied
+    index: LogIndex,
+    /// Finished entries after `index`
+    finished: BTreeSet<LogIndex>,
+}
+
+impl AppliedState {
+    /// Create a new `AppliedState`
+    pub(super) fn new(index: LogIndex) -> Self {
+        Self {
+            index,
+            finished: BTreeSet::new(),
+        }
+    }
+
+    /// Get the applied index
+    pub(super) fn index(&self) -> LogIndex {
+        self.index
+    }
+
+    /// Mark an entry as finished
+    pub(super) fn finish(&mut self, index: LogIndex) {
+        if index > self.index {
+            let _ig = self.finished.insert(index);
+        }
+    }
+
+    /// Advance the applied index over the finished entries, the applied index never exceeds
+    /// `last_as` as only the entries sent to after sync are applied. Return true if the applied
+    /// index advances.
+    pub(super) fn advance(&mut self, last_as: LogIndex) -> bool {
+        let prev = self.index;
+        while self.index < last_as && self.finished.remove(&self.index.overflow_add(1)) {
+            self.index = self.index.overflow_add(1);
+        }
+        self.index != prev
+    }
+
+    /// Reset the applied index, used when the log is reset by a snapshot
+    pub(super) fn reset(&mut self, index: LogIndex) {
+        self.index = index;
+        self.finished.retain(|i| *i > index);
+    }
+}
+
 /// Result of a vote
 #[derive(Debug, PartialEq)]
 pub(super) enum VoteResult {
@@ -381,4 +564,49 @@ mod test {
         cst.votes_received = HashMap::from([(1, true), (2, true), (3, false), (4, false)]);
         assert_eq!(cst.check_vote(), VoteResult::Pending);
     }
+
+    #[test]
+    fn check_vote_should_require_both_majorities_in_joint_config() {
+        let mut cst = CandidateState::<TestCommand>::new([1, 2, 3].into_iter());
+        cst.config.enter_joint();
+        for id in [1, 2, 3] {
+            assert!(cst.config.remove(id));
+        }
+        for id in [4, 5, 6] {
+            assert!(cst.config.insert(id, false));
+        }
+
+        cst.votes_received = HashMap::from([(1, true), (2, true), (4, true), (5, true)]);
+        assert_eq!(cst.check_vote(), VoteResult::Won);
+
+        cst.votes_received = HashMap::from([(1, true), (2, true), (3, true), (4, true)]);
+        assert_eq!(cst.check_vote(), VoteResult::Pending);
+
+        cst.votes_received = HashMap::from([(1, true), (2, true), (4, false), (5, false)]);
+        assert_eq!(cst.check_vote(), VoteResult::Lost);
+
+        let outgoing = cst.config.leave_joint();
+        assert_eq!(outgoing, HashSet::from([1, 2, 3]));
+        cst.votes_received = HashMap::from([(4, true), (5, true)]);
+        assert_eq!(cst.check_vote(), VoteResult::Won);
+    }
+
+    #[test]
+    fn applied_index_should_only_advance_over_contiguous_finished_entries() {
+        let mut applied = AppliedState::new(1);
+        applied.finish(3);
+        applied.finish(4);
+        assert!(!applied.advance(4));
+        assert_eq!(applied.index(), 1);
+        applied.finish(2);
+        assert!(applied.advance(3));
+        assert_eq!(applied.index(), 3);
+        assert!(applied.advance(4));
+        assert_eq!(applied.index(), 4);
+        applied.finish(6);
+        applied.reset(5);
+        applied.finish(7);
+        assert!(applied.advance(7));
+        assert_eq!(applied.index(), 7);
+    }
 }
@@ -1,6 +1,7 @@
 use std::{cmp::Reverse, ops::Add, time::Duration};
 
 use curp_test_utils::{mock_role_change, test_cmd::TestCommand, TEST_CLIENT_ID};
+use futures::StreamExt;
 use test_macros::abort_on_panic;
 use tokio::{
     sync::oneshot,
@@ -9,7 +10,7 @@ use tokio::{
 use tracing_test::traced_test;
 use utils::config::{
     default_candidate_timeout_ticks, default_follower_timeout_ticks, default_heartbeat_interval,
-    CurpConfigBuilder,
+    CurpConfigBuilder, EngineConfig,
 };
 
 use super::*;
@@ -21,6 +22,7 @@ use crate::{
         lease_manager::LeaseManager,
         raw_curp::UncommittedPool,
         spec_pool::SpeculativePool,
+        LogEvent, Observer, Replicator,
     },
     LogIndex,
 };
@@ -43,6 +45,20 @@ impl<C: Command, RC: RoleChange> RawCurp<C, RC> {
         exe_tx: Tx,
         role_change: RC,
         task_manager: Arc<TaskManager>,
+    ) -> Self {
+        let curp_config = CurpConfigBuilder::default()
+            .log_entries_cap(10)
+            .build()
+            .unwrap();
+        Self::new_test_with_cfg(n, exe_tx, role_change, task_manager, curp_config)
+    }
+
+    pub(crate) fn new_test_with_cfg<Tx: CEEventTxApi<C>>(
+        n: u64,
+        exe_tx: Tx,
+        role_change: RC,
+        task_manager: Arc<TaskManager>,
+        curp_config: CurpConfig,
     ) -> Self {
         let all_members: HashMap<_, _> = (0..n)
             .map(|i| (format!("S{i}"), vec![format!("S{i}")]))
@@ -70,10 +86,6 @@ impl<C: Command, RC: RoleChange> RawCurp<C, RC> {
                 )
             })
             .collect();
-        let curp_config = CurpConfigBuilder::default()
-            .log_entries_cap(10)
-            .build()
-            .unwrap();
         let curp_storage = Arc::new(DB::open(&curp_config.engine_cfg).unwrap());
 
         // grant a infinity expiry lease for test client id
@@ -311,6 +323,35 @@ fn handle_ae_will_calibrate_term() {
     assert_eq!(st_r.leader_id, Some(s2_id));
 }
 
+#[traced_test]
+#[test]
+fn leader_hint_should_be_piggybacked_by_leader() {
+    let task_manager = Arc::new(TaskManager::new());
+    let curp = {
+        let mut exe_tx = MockCEEventTxApi::<TestCommand>::default();
+        exe_tx
+            .expect_send_reset()
+            .returning(|_| oneshot::channel().1);
+        Arc::new(RawCurp::new_test(
+            3,
+            exe_tx,
+            mock_role_change(),
+            task_manager,
+        ))
+    };
+    curp.set_leader_hint_source(|| 10);
+    assert!(curp.is_leader());
+    assert_eq!(curp.leader_hint(), 10);
+
+    curp.update_to_term_and_become_follower(&mut *curp.st.write(), 2);
+    assert_eq!(curp.leader_hint(), 0);
+    curp.update_leader_hint(5);
+    assert_eq!(curp.leader_hint(), 5);
+    // a delayed heartbeat does not move the hint backwards
+    curp.update_leader_hint(3);
+    assert_eq!(curp.leader_hint(), 5);
+}
+
 #[traced_test]
 #[test]
 fn handle_ae_will_set_leader_id() {
@@ -1081,6 +1122,78 @@ fn leader_handle_propose_conf_change() {
         .unwrap();
 }
 
+#[traced_test]
+#[test]
+fn joint_consensus_should_keep_quorum_of_both_configs() {
+    let task_manager = Arc::new(TaskManager::new());
+    let curp = {
+        let mut exe_tx = MockCEEventTxApi::<TestCommand>::default();
+        exe_tx.expect_send_sp_exe().returning(|_| {});
+        exe_tx.expect_send_after_sync().returning(|_| {});
+        Arc::new(RawCurp::new_test(
+            3,
+            exe_tx,
+            mock_role_change(),
+            task_manager,
+        ))
+    };
+    let term = curp.term();
+    let old_ids = ["S0", "S1", "S2"].map(|name| curp.cluster().get_id_by_name(name).unwrap());
+    let new_ids = [10, 11, 12];
+    let changes = new_ids
+        .iter()
+        .map(|id| ConfChange::add(*id, vec![format!("http://127.0.0.1:{id}")]))
+        .chain(old_ids.iter().map(|id| ConfChange::remove(*id)))
+        .collect_vec();
+    assert!(curp.check_new_config(&changes).is_ok());
+    curp.handle_propose_conf_change(ProposeId(TEST_CLIENT_ID, 0), changes)
+        .unwrap();
+    let joint_index = curp.last_log_index();
+    assert!(curp.cst.lock().config.is_joint());
+    // the removed voters are kept until the cluster leaves the joint config
+    assert!(old_ids
+        .iter()
+        .chain(&new_ids)
+        .all(|id| curp.cluster().contains(*id)));
+    // no other conf change is accepted in a joint config
+    let resp =
+        curp.check_new_config(&[ConfChange::add(13, vec!["http://127.0.0.1:13".to_owned()])]);
+    assert!(matches!(resp, Err(CurpError::InvalidConfig(()))));
+
+    // a majority of the new config alone can't commit the joint entry
+    for id in &new_ids[..2] {
+        assert!(curp
+            .handle_append_entries_resp(*id, Some(joint_index), term, true, joint_index + 1)
+            .unwrap());
+    }
+    assert!(curp.commit_index() < joint_index);
+    // it is committed once a majority of the old config acknowledges as well
+    assert!(curp
+        .handle_append_entries_resp(old_ids[1], Some(joint_index), term, true, joint_index + 1)
+        .unwrap());
+    assert_eq!(curp.commit_index(), joint_index);
+
+    // the leader leaves the joint config right after the joint entry is committed
+    let leave_index = curp.last_log_index();
+    assert_eq!(leave_index, joint_index + 1);
+    assert!(!curp.cst.lock().config.is_joint());
+    assert!(old_ids.iter().all(|id| !curp.cluster().contains(*id)));
+    assert!(new_ids.iter().all(|id| curp.cluster().contains(*id)));
+
+    // only the new config counts now, and the leader is not part of it
+    assert!(curp
+        .handle_append_entries_resp(old_ids[2], Some(leave_index), term, true, leave_index + 1)
+        .unwrap());
+    assert!(curp
+        .handle_append_entries_resp(new_ids[0], Some(leave_index), term, true, leave_index + 1)
+        .unwrap());
+    assert_eq!(curp.commit_index(), joint_index);
+    assert!(curp
+        .handle_append_entries_resp(new_ids[1], Some(leave_index), term, true, leave_index + 1)
+        .unwrap());
+    assert_eq!(curp.commit_index(), leave_index);
+}
+
 #[traced_test]
 #[test]
 fn follower_handle_propose_conf_change() {
@@ -1235,3 +1348,263 @@ fn leader_will_reset_transferee_after_it_become_follower() {
     curp.update_to_term_and_become_follower(&mut *curp.st.write(), 2);
     assert!(curp.get_transferee().is_none());
 }
+
+/*************** tests for leader lease **************/
+
+#[traced_test]
+#[test]
+fn leader_lease_is_valid_after_quorum_acked() {
+    let task_manager = Arc::new(TaskManager::new());
+    let curp_config = CurpConfigBuilder::default()
+        .log_entries_cap(10)
+        .enable_leader_lease(true)
+        .build()
+        .unwrap();
+    let curp = RawCurp::new_test_with_cfg(
+        3,
+        MockCEEventTxApi::<TestCommand>::default(),
+        mock_role_change(),
+        task_manager,
+        curp_config,
+    );
+    // no follower has acked yet, a quorum round is needed
+    assert!(!curp.has_valid_leader_lease());
+
+    let s1_id = curp.cluster().get_id_by_name("S1").unwrap();
+    curp.record_ae_ack(s1_id, std::time::Instant::now());
+    // within the lease, the read can be served locally
+    assert!(curp.has_valid_leader_lease());
+}
+
+#[traced_test]
+#[test]
+fn leader_lease_will_expire_without_acks() {
+    let task_manager = Arc::new(TaskManager::new());
+    let curp_config = CurpConfigBuilder::default()
+        .log_entries_cap(10)
+        .enable_leader_lease(true)
+        .build()
+        .unwrap();
+    let curp = RawCurp::new_test_with_cfg(
+        3,
+        MockCEEventTxApi::<TestCommand>::default(),
+        mock_role_change(),
+        task_manager,
+        curp_config,
+    );
+    let s1_id = curp.cluster().get_id_by_name("S1").unwrap();
+    let sent_at = std::time::Instant::now()
+        .checked_sub(curp.leader_lease_duration() + Duration::from_millis(1))
+        .unwrap();
+    curp.record_ae_ack(s1_id, sent_at);
+    // out of the lease, fall back to confirming leadership with a quorum
+    assert!(!curp.has_valid_leader_lease());
+    assert!(curp.quorum_acked_since(sent_at));
+    assert!(!curp.quorum_acked_since(std::time::Instant::now()));
+}
+
+#[traced_test]
+#[test]
+fn leader_lease_is_invalid_when_disabled() {
+    let task_manager = Arc::new(TaskManager::new());
+    let curp = RawCurp::new_test(
+        3,
+        MockCEEventTxApi::<TestCommand>::default(),
+        mock_role_change(),
+        task_manager,
+    );
+    let s1_id = curp.cluster().get_id_by_name("S1").unwrap();
+    let s2_id = curp.cluster().get_id_by_name("S2").unwrap();
+    curp.record_ae_ack(s1_id, std::time::Instant::now());
+    curp.record_ae_ack(s2_id, std::time::Instant::now());
+    assert!(!curp.has_valid_leader_lease());
+}
+
+#[traced_test]
+#[tokio::test]
+async fn confirm_leadership_should_time_out_at_linearizable_read_timeout() {
+    let task_manager = Arc::new(TaskManager::new());
+    let curp_config = CurpConfigBuilder::default()
+        .log_entries_cap(10)
+        .enable_leader_lease(true)
+        .linearizable_read_timeout(Duration::from_secs(1))
+        .build()
+        .unwrap();
+    let curp = Arc::new(RawCurp::new_test_with_cfg(
+        3,
+        MockCEEventTxApi::<TestCommand>::default(),
+        mock_role_change(),
+        task_manager,
+        curp_config,
+    ));
+    let s1_id = curp.cluster().get_id_by_name("S1").unwrap();
+
+    // the follower does not confirm before the timeout, the upper bound is loose so that a
+    // loaded machine does not fail the test
+    let start = Instant::now();
+    let err = curp.confirm_leadership().await.unwrap_err();
+    let elapsed = start.elapsed();
+    assert!(elapsed >= Duration::from_secs(1));
+    assert!(elapsed < Duration::from_secs(30), "{elapsed:?}");
+    // the client retries the read
+    assert!(matches!(err, CurpError::Internal(_)));
+    assert!(!err.should_abort_slow_round());
+
+    // the retry is confirmed once the follower acknowledges again
+    let acks = {
+        let curp = Arc::clone(&curp);
+        tokio::spawn(async move {
+            loop {
+                sleep(Duration::from_millis(10)).await;
+                curp.record_ae_ack(s1_id, std::time::Instant::now());
+            }
+        })
+    };
+    curp.confirm_leadership().await.unwrap();
+    acks.abort();
+}
+
+#[traced_test]
+#[tokio::test]
+async fn log_stream_should_yield_committed_commands_and_signal_snapshot() {
+    let task_manager = Arc::new(TaskManager::new());
+    let curp = {
+        let mut exe_tx = MockCEEventTxApi::<TestCommand>::default();
+        exe_tx.expect_send_sp_exe().returning(|_| {});
+        exe_tx.expect_send_after_sync().returning(|_| {});
+        Arc::new(RawCurp::new_test(
+            3,
+            exe_tx,
+            mock_role_change(),
+            task_manager,
+        ))
+    };
+    let term = curp.term();
+    let s1_id = curp.cluster().get_id_by_name("S1").unwrap();
+    let mut indexes = Vec::new();
+    for i in 0..3 {
+        let cmd = Arc::new(TestCommand::new_put(vec![i], i));
+        assert!(curp
+            .handle_propose(ProposeId(TEST_CLIENT_ID, i.into()), cmd)
+            .unwrap());
+        indexes.push(curp.last_log_index());
+    }
+    let last = curp.last_log_index();
+
+    let observer = Observer::new(Arc::clone(&curp));
+    let mut stream = Box::pin(observer.log_stream(indexes[1]));
+    // nothing is committed yet
+    assert!(
+        tokio::time::timeout(Duration::from_millis(100), stream.next())
+            .await
+            .is_err()
+    );
+
+    assert!(curp
+        .handle_append_entries_resp(s1_id, Some(last), term, true, last + 1)
+        .unwrap());
+    // committed entries are not observed until they are applied
+    assert!(
+        tokio::time::timeout(Duration::from_millis(100), stream.next())
+            .await
+            .is_err()
+    );
+    for index in (1..=last).rev() {
+        curp.mark_applied(index);
+    }
+    assert_eq!(observer.committed_watermark(), last);
+    for (i, index) in indexes.iter().enumerate().skip(1) {
+        let Some(LogEvent::Command(idx, cmd)) = stream.next().await else {
+            panic!("expect a committed command");
+        };
+        assert_eq!(idx, *index);
+        assert_eq!(*cmd, TestCommand::new_put(vec![i as u32], i as u32));
+    }
+
+    // the observer falls behind a snapshot
+    curp.reset_by_snapshot(SnapshotMeta {
+        last_included_index: last + 10,
+        last_included_term: term,
+    });
+    assert!(matches!(
+        stream.next().await,
+        Some(LogEvent::SnapshotNeeded(idx)) if idx == last + 10
+    ));
+    let mut stream = Box::pin(observer.log_stream(1));
+    assert!(matches!(
+        stream.next().await,
+        Some(LogEvent::SnapshotNeeded(idx)) if idx == last + 10
+    ));
+}
+
+#[traced_test]
+#[tokio::test]
+async fn replicator_should_catch_up_with_source_watermark() {
+    let task_manager = Arc::new(TaskManager::new());
+    let curp = {
+        let mut exe_tx = MockCEEventTxApi::<TestCommand>::default();
+        exe_tx.expect_send_sp_exe().returning(|_| {});
+        exe_tx.expect_send_after_sync().returning(|_| {});
+        Arc::new(RawCurp::new_test(
+            3,
+            exe_tx,
+            mock_role_change(),
+            task_manager,
+        ))
+    };
+    let term = curp.term();
+    let s1_id = curp.cluster().get_id_by_name("S1").unwrap();
+    for i in 0..5 {
+        let cmd = Arc::new(TestCommand::new_put(vec![i], i));
+        assert!(curp
+            .handle_propose(ProposeId(TEST_CLIENT_ID, i.into()), cmd)
+            .unwrap());
+    }
+    let last = curp.last_log_index();
+    assert!(curp
+        .handle_append_entries_resp(s1_id, Some(last), term, true, last + 1)
+        .unwrap());
+    for index in 1..=last {
+        curp.mark_applied(index);
+    }
+
+    // the source cluster exposes its committed watermark
+    let source = Observer::new(Arc::clone(&curp));
+    let source_watermark = source.committed_watermark();
+    assert_eq!(source_watermark, last);
+
+    // the target cluster applies the commands up to the watermark
+    let storage = Arc::new(DB::<TestCommand>::open(&EngineConfig::Memory).unwrap());
+    let replicator = Replicator::recover(Arc::clone(&storage)).unwrap();
+    assert_eq!(replicator.watermark(), 0);
+    let mut target = Vec::new();
+    let mut lags = Vec::new();
+    replicator
+        .replicate_to(
+            source_watermark,
+            source.replication_stream(replicator.watermark() + 1),
+            |index, cmd| {
+                lags.push(replicator.lag());
+                target.push((index, cmd));
+                futures::future::ready(Ok::<(), ()>(()))
+            },
+        )
+        .await
+        .unwrap();
+    assert_eq!(replicator.watermark(), source_watermark);
+    assert_eq!(replicator.lag(), 0);
+    assert_eq!(target.len(), 5);
+    for (i, (_index, cmd)) in target.iter().enumerate() {
+        assert_eq!(**cmd, TestCommand::new_put(vec![i as u32], i as u32));
+    }
+    assert!(lags.windows(2).all(|w| w[0] > w[1]));
+    assert_eq!(lags[0], source_watermark);
+
+    // the watermark is persisted, a restarted replicator resumes from it
+    assert_eq!(
+        storage.recover_replication_watermark().unwrap(),
+        Some(source_watermark)
+    );
+    let resumed = Replicator::recover(storage).unwrap();
+    assert_eq!(resumed.watermark(), source_watermark);
+}
@@ -1,4 +1,4 @@
-use std::marker::PhantomData;
+use std::{collections::HashSet, marker::PhantomData};
 
 use async_trait::async_trait;
 use engine::{Engine, EngineType, StorageEngine, WriteOperation};
@@ -11,6 +11,7 @@ use crate::{
     log_entry::LogEntry,
     members::{ClusterInfo, ServerId},
     rpc::Member,
+    LogIndex,
 };
 
 /// Key for persisted state
@@ -19,6 +20,10 @@ const VOTE_FOR: &[u8] = b"VoteFor";
 const CLUSTER_ID: &[u8] = b"ClusterId";
 /// Key for member id
 const MEMBER_ID: &[u8] = b"MemberId";
+/// Key for joint config
+const JOINT_CONFIG: &[u8] = b"JointConfig";
+/// Key for the watermark of the replication from a source cluster
+const REPLICATION_WATERMARK: &[u8] = b"ReplicationWatermark";
 
 /// Column family name for curp storage
 const CF: &str = "curp";
@@ -134,6 +139,55 @@ impl<C: Command> StorageApi for DB<C> {
         Ok(cluster_info)
     }
 
+    #[inline]
+    fn put_joint_config(
+        &self,
+        joint: Option<(HashSet<ServerId>, HashSet<ServerId>)>,
+    ) -> Result<(), StorageError> {
+        let op = match joint {
+            Some(voters) => {
+                WriteOperation::new_put(CF, JOINT_CONFIG.to_vec(), bincode::serialize(&voters)?)
+            }
+            None => WriteOperation::new_delete(CF, JOINT_CONFIG),
+        };
+        self.db.write_batch(vec![op], true)?;
+        Ok(())
+    }
+
+    #[inline]
+    fn recover_joint_config(
+        &self,
+    ) -> Result<Option<(HashSet<ServerId>, HashSet<ServerId>)>, StorageError> {
+        Ok(self
+            .db
+            .get(CF, JOINT_CONFIG)?
+            .map(|bytes| bincode::deserialize(&bytes))
+            .transpose()?)
+    }
+
+    #[inline]
+    fn put_replication_watermark(&self, watermark: LogIndex) -> Result<(), StorageError> {
+        let op = WriteOperation::new_put(
+            CF,
+            REPLICATION_WATERMARK.to_vec(),
+            watermark.to_be_bytes().to_vec(),
+        );
+        self.db.write_batch(vec![op], true)?;
+        Ok(())
+    }
+
+    #[inline]
+    fn recover_replication_watermark(&self) -> Result<Option<LogIndex>, StorageError> {
+        Ok(self.db.get(CF, REPLICATION_WATERMARK)?.map(|bytes| {
+            LogIndex::from_be_bytes(
+                bytes
+                    .as_slice()
+                    .try_into()
+                    .unwrap_or_else(|e| unreachable!("cannot decode index from backend, {e:?}")),
+            )
+        }))
+    }
+
     #[inline]
     async fn recover(
         &self,
@@ -170,6 +224,7 @@ impl<C> DB<C> {
         let engine_type = match *config {
             EngineConfig::Memory => EngineType::Memory,
             EngineConfig::RocksDB(ref path) => EngineType::Rocks(path.clone()),
+            EngineConfig::Sled(ref path) => EngineType::Sled(path.clone()),
             _ => unreachable!("Not supported storage type"),
         };
         let db = Engine::new(engine_type, &[CF, LOGS_CF, MEMBERS_CF])?;
@@ -222,4 +277,27 @@ mod tests {
 
         Ok(())
     }
+
+    #[tokio::test]
+    #[abort_on_panic]
+    async fn put_and_recover_joint_config() -> Result<(), Box<dyn Error>> {
+        let db_dir = tempfile::tempdir().unwrap().into_path();
+        let storage_cfg = EngineConfig::RocksDB(db_dir.clone());
+        let joint = (HashSet::from([1, 2, 4]), HashSet::from([1, 2, 3]));
+        {
+            let s = DB::<TestCommand>::open(&storage_cfg)?;
+            assert!(s.recover_joint_config()?.is_none());
+            s.put_joint_config(Some(joint.clone()))?;
+        }
+        {
+            let s = DB::<TestCommand>::open(&storage_cfg)?;
+            assert_eq!(s.recover_joint_config()?, Some(joint));
+            s.put_joint_config(None)?;
+            assert!(s.recover_joint_config()?.is_none());
+        }
+
+        remove_dir_all(db_dir).await?;
+
+        Ok(())
+    }
 }
@@ -1,3 +1,5 @@
+use std::collections::HashSet;
+
 use async_trait::async_trait;
 use engine::EngineError;
 use thiserror::Error;
@@ -7,6 +9,7 @@ use crate::{
     log_entry::LogEntry,
     members::{ClusterInfo, ServerId},
     rpc::Member,
+    LogIndex,
 };
 
 /// Storage layer error
@@ -58,6 +61,26 @@ pub trait StorageApi: Send + Sync {
     /// Recover `ClusterInfo` from storage
     fn recover_cluster_info(&self) -> Result<Option<ClusterInfo>, StorageError>;
 
+    /// Put the incoming and the outgoing voters of the joint config into storage,
+    /// `None` means the cluster is not in a joint config
+    fn put_joint_config(
+        &self,
+        joint: Option<(HashSet<ServerId>, HashSet<ServerId>)>,
+    ) -> Result<(), StorageError>;
+
+    /// Recover the incoming and the outgoing voters of the joint config from storage
+    fn recover_joint_config(
+        &self,
+    ) -> Result<Option<(HashSet<ServerId>, HashSet<ServerId>)>, StorageError>;
+
+    /// Put the watermark of the replication from a source cluster into storage, must be
+    /// flushed on disk before returning
+    fn put_replication_watermark(&self, watermark: LogIndex) -> Result<(), StorageError>;
+
+    /// Recover the watermark of the replication from a source cluster, `None` if this node
+    /// has never replicated from a source
+    fn recover_replication_watermark(&self) -> Result<Option<LogIndex>, StorageError>;
+
     /// Put log entries in storage
     async fn put_log_entry(&self, entry: &LogEntry<Self::Command>) -> Result<(), StorageError>;
 
@@ -1,4 +1,4 @@
-use std::fmt::Debug;
+use std::{fmt::Debug, time::SystemTime};
 
 use engine::Snapshot as EngineSnapshot;
 
@@ -22,6 +22,11 @@ impl Snapshot {
         self.inner
     }
 
+    /// Get the size of the snapshot
+    pub(crate) fn size(&self) -> u64 {
+        self.inner.size()
+    }
+
     /// Get the inner snapshot ref
     #[cfg(feature = "client-metrics")]
     pub(crate) fn inner(&self) -> &EngineSnapshot {
@@ -37,3 +42,29 @@ pub(crate) struct SnapshotMeta {
     /// Last included term
     pub(crate) last_included_term: u64,
 }
+
+/// Information of the latest snapshot taken or installed by a server
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct SnapshotInfo {
+    /// Last log index included by the snapshot
+    pub last_included_index: u64,
+    /// Term of the last log entry included by the snapshot
+    pub last_included_term: u64,
+    /// Size of the snapshot in bytes
+    pub size: u64,
+    /// Time when the snapshot was taken or installed
+    pub created_at: SystemTime,
+}
+
+impl SnapshotInfo {
+    /// Create the information of a snapshot created now
+    pub(crate) fn new(meta: SnapshotMeta, size: u64) -> Self {
+        Self {
+            last_included_index: meta.last_included_index,
+            last_included_term: meta.last_included_term,
+            size,
+            created_at: SystemTime::now(),
+        }
+    }
+}
@@ -160,6 +160,40 @@ async fn fast_round_is_slower_than_slow_round() {
     assert!(resp.result.is_none());
 }
 
+#[tokio::test(flavor = "multi_thread")]
+#[abort_on_panic]
+async fn propose_should_time_out_at_deadline_while_cmd_is_still_applied() {
+    init_logger();
+
+    let mut group = CurpGroup::new(3).await;
+    let leader = group.get_leader().await.0;
+    let mut leader_connect = group.get_connect(&leader).await;
+
+    // the execution result is delayed past the deadline
+    let cmd = TestCommand::new_put(vec![0], 0).set_exe_dur(Duration::from_secs(2));
+    let mut req = tonic::Request::new(ProposeRequest {
+        propose_id: Some(ProposeId {
+            client_id: 0,
+            seq_num: 0,
+        }),
+        command: bincode::serialize(&cmd).unwrap(),
+        cluster_version: 0,
+    });
+    req.set_timeout(Duration::from_millis(500));
+    let err = tokio::time::timeout(Duration::from_secs(1), leader_connect.propose(req))
+        .await
+        .expect("the propose should not wait for the result after the deadline")
+        .unwrap_err();
+    assert_eq!(err.code(), tonic::Code::DeadlineExceeded);
+
+    // the command is still committed and applied on each node
+    for as_rx in group.as_rxs() {
+        let (cmd1, index) = as_rx.recv().await.unwrap();
+        assert_eq!(cmd1, cmd);
+        assert_eq!(index, 1);
+    }
+}
+
 #[tokio::test(flavor = "multi_thread")]
 #[abort_on_panic]
 async fn concurrent_cmd_order() {
@@ -627,3 +661,33 @@ async fn move_leader_should_move_leadership_to_target_node() {
     assert_eq!(target, new_leader);
     assert_ne!(old_leader, new_leader);
 }
+
+#[cfg(feature = "verify-determinism")]
+#[tokio::test(flavor = "multi_thread")]
+#[abort_on_panic]
+async fn non_deterministic_cmd_should_be_rejected_by_all_nodes() {
+    init_logger();
+
+    let mut group = CurpGroup::new(3).await;
+    let client = group.new_client().await;
+    let cmd = TestCommand::new_non_deterministic(vec![0]);
+
+    let err = client.propose(&cmd, None, true).await.unwrap().unwrap_err();
+    assert!(err.0.contains("not deterministic"));
+
+    // the rejected command never enters the log, so no node executes or after syncs it
+    for exe_rx in group.exe_rxs() {
+        assert!(
+            tokio::time::timeout(Duration::from_millis(100), exe_rx.recv())
+                .await
+                .is_err()
+        );
+    }
+    for as_rx in group.as_rxs() {
+        assert!(
+            tokio::time::timeout(Duration::from_millis(100), as_rx.recv())
+                .await
+                .is_err()
+        );
+    }
+}
@@ -178,6 +178,9 @@ mod proxy;
 /// `RocksDB` Storage Engine
 #[cfg(not(madsim))]
 mod rocksdb_engine;
+/// `Sled` Storage Engine
+#[cfg(not(madsim))]
+mod sled_engine;
 /// Snapshot Allocator
 mod snapshot_allocator;
 
@@ -188,5 +191,5 @@ pub use crate::{
     },
     error::EngineError,
     proxy::{Engine, EngineType, Snapshot},
-    snapshot_allocator::{MemorySnapshotAllocator, RocksSnapshotAllocator},
+    snapshot_allocator::{MemorySnapshotAllocator, RocksSnapshotAllocator, SledSnapshotAllocator},
 };
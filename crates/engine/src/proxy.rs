@@ -2,15 +2,22 @@ use std::path::{Path, PathBuf};
 
 use bytes::{Bytes, BytesMut};
 
+// sled runs its own threads and file io which the simulation can not control, it is mocked
+// by the memory engine in the same way as `RocksDB`
 #[cfg(madsim)]
-use crate::mock_rocksdb_engine::{RocksEngine, RocksSnapshot};
-#[cfg(not(madsim))]
-use crate::rocksdb_engine::{RocksEngine, RocksSnapshot};
+use crate::mock_rocksdb_engine::{
+    RocksEngine, RocksEngine as SledEngine, RocksSnapshot, RocksSnapshot as SledSnapshot,
+};
 use crate::{
     error::EngineError,
     memory_engine::{MemoryEngine, MemorySnapshot},
     metrics, SnapshotApi, StorageEngine, WriteOperation,
 };
+#[cfg(not(madsim))]
+use crate::{
+    rocksdb_engine::{RocksEngine, RocksSnapshot},
+    sled_engine::{SledEngine, SledSnapshot},
+};
 
 #[derive(Debug)]
 #[non_exhaustive]
@@ -20,10 +27,12 @@ pub enum EngineType {
     Memory,
     /// Rocks engine, the inner path is path of `Engine` or `Snapshot`
     Rocks(PathBuf),
+    /// Sled engine, the inner path is path of `Engine` or `Snapshot`
+    Sled(PathBuf),
 }
 
-/// `Engine` is designed to mask the different type of `MemoryEngine` and `RocksEngine`
-/// and provides an uniform type to the upper layer.
+/// `Engine` is designed to mask the different type of `MemoryEngine`, `RocksEngine` and
+/// `SledEngine` and provides an uniform type to the upper layer.
 #[derive(Debug)]
 #[non_exhaustive]
 pub enum Engine {
@@ -31,6 +40,8 @@ pub enum Engine {
     Memory(MemoryEngine),
     /// Rocks engine
     Rocks(metrics::Layer<RocksEngine>),
+    /// Sled engine
+    Sled(SledEngine),
 }
 
 impl Engine {
@@ -44,6 +55,7 @@ impl Engine {
             EngineType::Rocks(path) => Ok(Engine::Rocks(metrics::Layer::new(RocksEngine::new(
                 path, tables,
             )?))),
+            EngineType::Sled(path) => Ok(Engine::Sled(SledEngine::new(path, tables)?)),
         }
     }
 
@@ -61,6 +73,9 @@ impl Engine {
             Engine::Memory(ref _e) => {
                 unreachable!("Memory engine does not support apply snapshot from file")
             }
+            Engine::Sled(ref _e) => {
+                unreachable!("Sled engine does not support apply snapshot from file")
+            }
         }
     }
 }
@@ -74,6 +89,7 @@ impl StorageEngine for Engine {
         match *self {
             Engine::Memory(ref e) => e.get(table, key),
             Engine::Rocks(ref e) => e.get(table, key),
+            Engine::Sled(ref e) => e.get(table, key),
         }
     }
 
@@ -86,6 +102,7 @@ impl StorageEngine for Engine {
         match *self {
             Engine::Memory(ref e) => e.get_multi(table, keys),
             Engine::Rocks(ref e) => e.get_multi(table, keys),
+            Engine::Sled(ref e) => e.get_multi(table, keys),
         }
     }
 
@@ -94,6 +111,7 @@ impl StorageEngine for Engine {
         match *self {
             Engine::Memory(ref e) => e.get_all(table),
             Engine::Rocks(ref e) => e.get_all(table),
+            Engine::Sled(ref e) => e.get_all(table),
         }
     }
 
@@ -102,6 +120,7 @@ impl StorageEngine for Engine {
         match *self {
             Engine::Memory(ref e) => e.write_batch(wr_ops, sync),
             Engine::Rocks(ref e) => e.write_batch(wr_ops, sync),
+            Engine::Sled(ref e) => e.write_batch(wr_ops, sync),
         }
     }
 
@@ -114,6 +133,7 @@ impl StorageEngine for Engine {
         match *self {
             Engine::Memory(ref e) => e.get_snapshot(path, tables).map(Snapshot::Memory),
             Engine::Rocks(ref e) => e.get_snapshot(path, tables).map(Snapshot::Rocks),
+            Engine::Sled(ref e) => e.get_snapshot(path, tables).map(Snapshot::Sled),
         }
     }
 
@@ -126,11 +146,15 @@ impl StorageEngine for Engine {
         match *self {
             Engine::Memory(ref e) => match snapshot {
                 Snapshot::Memory(s) => e.apply_snapshot(s, tables).await,
-                Snapshot::Rocks(_) => Err(EngineError::InvalidSnapshot),
+                Snapshot::Rocks(_) | Snapshot::Sled(_) => Err(EngineError::InvalidSnapshot),
             },
             Engine::Rocks(ref e) => match snapshot {
-                Snapshot::Memory(_) => Err(EngineError::InvalidSnapshot),
                 Snapshot::Rocks(s) => e.apply_snapshot(s, tables).await,
+                Snapshot::Memory(_) | Snapshot::Sled(_) => Err(EngineError::InvalidSnapshot),
+            },
+            Engine::Sled(ref e) => match snapshot {
+                Snapshot::Sled(s) => e.apply_snapshot(s, tables).await,
+                Snapshot::Memory(_) | Snapshot::Rocks(_) => Err(EngineError::InvalidSnapshot),
             },
         }
     }
@@ -140,6 +164,7 @@ impl StorageEngine for Engine {
         match *self {
             Engine::Memory(ref e) => e.estimated_file_size(),
             Engine::Rocks(ref e) => e.estimated_file_size(),
+            Engine::Sled(ref e) => e.estimated_file_size(),
         }
     }
 
@@ -148,12 +173,13 @@ impl StorageEngine for Engine {
         match *self {
             Engine::Memory(ref e) => e.file_size(),
             Engine::Rocks(ref e) => e.file_size(),
+            Engine::Sled(ref e) => e.file_size(),
         }
     }
 }
 
-/// `Snapshot` is designed to mask the different type of `MemorySnapshot` and `RocksSnapshot`
-/// and provides an uniform type to the upper layer.
+/// `Snapshot` is designed to mask the different type of `MemorySnapshot`, `RocksSnapshot` and
+/// `SledSnapshot` and provides an uniform type to the upper layer.
 #[derive(Debug)]
 #[non_exhaustive]
 pub enum Snapshot {
@@ -161,6 +187,8 @@ pub enum Snapshot {
     Memory(MemorySnapshot),
     /// Rocks snapshot
     Rocks(metrics::Layer<RocksSnapshot>),
+    /// Sled snapshot
+    Sled(SledSnapshot),
 }
 
 impl Snapshot {
@@ -174,6 +202,7 @@ impl Snapshot {
             EngineType::Rocks(path) => Ok(Self::Rocks(metrics::Layer::new(
                 RocksSnapshot::new_for_receiving(path)?,
             ))),
+            EngineType::Sled(path) => Ok(Self::Sled(SledSnapshot::new_for_receiving(path)?)),
         }
     }
 }
@@ -185,6 +214,7 @@ impl SnapshotApi for Snapshot {
         match *self {
             Snapshot::Memory(ref s) => s.size(),
             Snapshot::Rocks(ref s) => s.size(),
+            Snapshot::Sled(ref s) => s.size(),
         }
     }
 
@@ -193,6 +223,7 @@ impl SnapshotApi for Snapshot {
         match *self {
             Snapshot::Memory(ref mut s) => s.rewind(),
             Snapshot::Rocks(ref mut s) => s.rewind(),
+            Snapshot::Sled(ref mut s) => s.rewind(),
         }
     }
 
@@ -201,6 +232,7 @@ impl SnapshotApi for Snapshot {
         match *self {
             Snapshot::Memory(ref mut s) => s.read_buf(buf).await,
             Snapshot::Rocks(ref mut s) => s.read_buf(buf).await,
+            Snapshot::Sled(ref mut s) => s.read_buf(buf).await,
         }
     }
 
@@ -209,6 +241,7 @@ impl SnapshotApi for Snapshot {
         match *self {
             Snapshot::Memory(ref mut s) => s.read_buf_exact(buf).await,
             Snapshot::Rocks(ref mut s) => s.read_buf_exact(buf).await,
+            Snapshot::Sled(ref mut s) => s.read_buf_exact(buf).await,
         }
     }
 
@@ -217,6 +250,7 @@ impl SnapshotApi for Snapshot {
         match *self {
             Snapshot::Memory(ref mut s) => s.write_all(buf).await,
             Snapshot::Rocks(ref mut s) => s.write_all(buf).await,
+            Snapshot::Sled(ref mut s) => s.write_all(buf).await,
         }
     }
 
@@ -225,6 +259,7 @@ impl SnapshotApi for Snapshot {
         match *self {
             Snapshot::Memory(ref mut s) => s.clean().await,
             Snapshot::Rocks(ref mut s) => s.clean().await,
+            Snapshot::Sled(ref mut s) => s.clean().await,
         }
     }
 }
@@ -313,6 +348,72 @@ mod test {
         std::fs::remove_dir_all(dir).unwrap();
     }
 
+    #[tokio::test]
+    #[abort_on_panic]
+    async fn rocks_and_sled_engines_should_end_in_the_same_state() {
+        let dir = PathBuf::from("/tmp/rocks_and_sled_engines_should_end_in_the_same_state");
+        let engines = vec![
+            Engine::new(EngineType::Rocks(dir.join("rocks_engine")), &TESTTABLES).unwrap(),
+            Engine::new(EngineType::Sled(dir.join("sled_engine")), &TESTTABLES).unwrap(),
+        ];
+        let mut states = Vec::new();
+        for engine in engines {
+            let puts = (1u8..=10u8)
+                .map(|val| WriteOperation::new_put("kv", vec![val; 4], vec![val]))
+                .chain([WriteOperation::new_put(
+                    "lease",
+                    b"id".to_vec(),
+                    b"lease".to_vec(),
+                )])
+                .collect();
+            engine.write_batch(puts, false).unwrap();
+            // a batch across tables, whose range deletion also covers keys put earlier in it
+            let batch = vec![
+                WriteOperation::new_put("kv", vec![3, 3, 3, 3, 3], b"new".to_vec()),
+                WriteOperation::new_put("auth", b"user".to_vec(), b"root".to_vec()),
+                WriteOperation::new_delete_range("kv", &[2, 2, 2, 2], &[5, 5, 5, 5]),
+                WriteOperation::new_delete("kv", &[7, 7, 7, 7]),
+                WriteOperation::new_delete("lease", b"id"),
+                WriteOperation::new_put("kv", vec![4, 4, 4, 4], b"back".to_vec()),
+            ];
+            engine.write_batch(batch, true).unwrap();
+
+            // the state survives a snapshot round trip
+            let mut snapshot = engine
+                .get_snapshot(dir.join("snapshot"), &TESTTABLES)
+                .unwrap();
+            let mut buf = BytesMut::with_capacity(snapshot.size().numeric_cast());
+            snapshot.read_buf_exact(&mut buf).await.unwrap();
+            engine
+                .write_batch(vec![WriteOperation::new_delete("auth", b"user")], false)
+                .unwrap();
+            let mut received = match engine {
+                Engine::Rocks(_) => {
+                    Snapshot::new_for_receiving(EngineType::Rocks(dir.join("snapshot_bak")))
+                        .unwrap()
+                }
+                _ => Snapshot::new_for_receiving(EngineType::Sled(dir.join("snapshot_bak_sled")))
+                    .unwrap(),
+            };
+            received.write_all(buf.freeze()).await.unwrap();
+            engine.apply_snapshot(received, &TESTTABLES).await.unwrap();
+
+            let state: Vec<_> = TESTTABLES
+                .iter()
+                .map(|table| engine.get_all(table).unwrap())
+                .collect();
+            states.push(state);
+        }
+        assert_eq!(states[0], states[1]);
+        assert_eq!(
+            states[1][0].iter().map(|kv| kv.0[0]).collect::<Vec<_>>(),
+            [1, 4, 5, 6, 8, 9, 10]
+        );
+        assert!(states[1][1].is_empty());
+        assert_eq!(states[1][2], [(b"user".to_vec(), b"root".to_vec())]);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
     #[test]
     fn get_operation_should_success() {
         let dir = PathBuf::from("/tmp/get_operation_should_success");
@@ -0,0 +1,368 @@
+use std::{
+    collections::BTreeMap,
+    fs::{self, File},
+    io::{self, BufRead, BufReader, BufWriter, Read, Write},
+    path::{Path, PathBuf},
+};
+
+use bytes::{Bytes, BytesMut};
+use clippy_utilities::NumericCast;
+use parking_lot::Mutex;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+use crate::{
+    api::{
+        engine_api::{StorageEngine, WriteOperation},
+        snapshot_api::SnapshotApi,
+    },
+    error::EngineError,
+};
+
+/// Separator between the table name and the key, table names never contain it
+const TABLE_SEPARATOR: u8 = 0;
+
+/// Name of the snapshot file in the snapshot directory
+const SNAPSHOT_FILE: &str = "sled.snap";
+
+/// Max number of key-values written by one sled batch when a snapshot is applied
+const APPLY_BATCH_SIZE: usize = 4096;
+
+/// `Sled` Storage Engine Implementation, which is written in pure Rust and avoids the C++
+/// dependency of `RocksDB`.
+///
+/// All tables share one sled tree, a key is stored with its table name as the prefix, so
+/// that a batch across tables is applied atomically by a single sled batch. Snapshots are
+/// files of [`SledSnapshot`].
+#[derive(Debug)]
+pub struct SledEngine {
+    /// The inner sled db
+    db: sled::Db,
+    /// Names of the tables
+    tables: Vec<String>,
+    /// Serializes batches, a range deletion reads the keys to delete before the batch is
+    /// applied
+    write_lock: Mutex<()>,
+}
+
+/// Map the sled error to an `EngineError`
+#[allow(clippy::needless_pass_by_value)] // used in `map_err`
+fn sled_error(err: sled::Error) -> EngineError {
+    EngineError::UnderlyingError(format!("sled error: {err}"))
+}
+
+impl SledEngine {
+    /// New `SledEngine`
+    ///
+    /// # Errors
+    ///
+    /// Return `EngineError` when the db failed to open
+    #[inline]
+    pub fn new(data_dir: impl AsRef<Path>, tables: &[&'static str]) -> Result<Self, EngineError> {
+        let db = sled::open(data_dir).map_err(sled_error)?;
+        Ok(Self {
+            db,
+            tables: tables.iter().map(|table| (*table).to_owned()).collect(),
+            write_lock: Mutex::new(()),
+        })
+    }
+
+    /// Get the key prefix of the table
+    fn prefix(&self, table: &str) -> Result<Vec<u8>, EngineError> {
+        if !self.tables.iter().any(|t| t == table) {
+            return Err(EngineError::TableNotFound(table.to_owned()));
+        }
+        let mut prefix = table.as_bytes().to_vec();
+        prefix.push(TABLE_SEPARATOR);
+        Ok(prefix)
+    }
+
+    /// Get the full key of the key in the table
+    fn full_key(&self, table: &str, key: &[u8]) -> Result<Vec<u8>, EngineError> {
+        let mut full_key = self.prefix(table)?;
+        full_key.extend_from_slice(key);
+        Ok(full_key)
+    }
+
+    /// Get all key-values of the table without the prefix, ordered by key
+    #[allow(clippy::type_complexity)] // it's clear that (Vec<u8>, Vec<u8>) is a key-value pair
+    fn scan_table(&self, table: &str) -> Result<Vec<(Vec<u8>, Vec<u8>)>, EngineError> {
+        let prefix = self.prefix(table)?;
+        self.db
+            .scan_prefix(&prefix)
+            .map(|res| {
+                let (key, value) = res.map_err(sled_error)?;
+                let key = key.get(prefix.len()..).unwrap_or_default().to_vec();
+                Ok((key, value.to_vec()))
+            })
+            .collect()
+    }
+
+    /// Apply the batch when it is full or `force` is set, and start a new one
+    fn flush_batch(
+        &self,
+        batch: &mut sled::Batch,
+        len: &mut usize,
+        force: bool,
+    ) -> Result<(), EngineError> {
+        if *len == 0 || (!force && *len < APPLY_BATCH_SIZE) {
+            return Ok(());
+        }
+        self.db
+            .apply_batch(std::mem::take(batch))
+            .map_err(sled_error)?;
+        *len = 0;
+        Ok(())
+    }
+}
+
+#[async_trait::async_trait]
+impl StorageEngine for SledEngine {
+    type Snapshot = SledSnapshot;
+
+    #[inline]
+    fn get(&self, table: &str, key: impl AsRef<[u8]>) -> Result<Option<Vec<u8>>, EngineError> {
+        let full_key = self.full_key(table, key.as_ref())?;
+        Ok(self
+            .db
+            .get(full_key)
+            .map_err(sled_error)?
+            .map(|value| value.to_vec()))
+    }
+
+    #[inline]
+    fn get_multi(
+        &self,
+        table: &str,
+        keys: &[impl AsRef<[u8]>],
+    ) -> Result<Vec<Option<Vec<u8>>>, EngineError> {
+        keys.iter().map(|key| self.get(table, key)).collect()
+    }
+
+    #[inline]
+    fn get_all(&self, table: &str) -> Result<Vec<(Vec<u8>, Vec<u8>)>, EngineError> {
+        self.scan_table(table)
+    }
+
+    #[inline]
+    fn write_batch(&self, wr_ops: Vec<WriteOperation<'_>>, sync: bool) -> Result<(), EngineError> {
+        let _guard = self.write_lock.lock();
+        // writes of this batch, `None` means a deletion
+        let mut writes: BTreeMap<Vec<u8>, Option<Vec<u8>>> = BTreeMap::new();
+        for op in wr_ops {
+            match op {
+                WriteOperation::Put { table, key, value } => {
+                    let _prev = writes.insert(self.full_key(table, &key)?, Some(value));
+                }
+                WriteOperation::Delete { table, key } => {
+                    let _prev = writes.insert(self.full_key(table, key)?, None);
+                }
+                WriteOperation::DeleteRange { table, from, to } => {
+                    let (from, to) = (self.full_key(table, from)?, self.full_key(table, to)?);
+                    if from >= to {
+                        continue;
+                    }
+                    let mut keys = Vec::new();
+                    for res in self.db.range(from.as_slice()..to.as_slice()) {
+                        keys.push(res.map_err(sled_error)?.0.to_vec());
+                    }
+                    // keys put earlier in this batch are deleted as well
+                    keys.extend(writes.range(from..to).map(|(key, _value)| key.clone()));
+                    for key in keys {
+                        let _prev = writes.insert(key, None);
+                    }
+                }
+            }
+        }
+        let mut batch = sled::Batch::default();
+        for (key, value) in writes {
+            match value {
+                Some(value) => batch.insert(key, value),
+                None => batch.remove(key),
+            }
+        }
+        self.db.apply_batch(batch).map_err(sled_error)?;
+        if sync {
+            let _bytes = self.db.flush().map_err(sled_error)?;
+        }
+        Ok(())
+    }
+
+    #[inline]
+    fn get_snapshot(
+        &self,
+        path: impl AsRef<Path>,
+        tables: &[&'static str],
+    ) -> Result<Self::Snapshot, EngineError> {
+        let _guard = self.write_lock.lock();
+        fs::create_dir_all(path.as_ref())?;
+        let file_path = path.as_ref().join(SNAPSHOT_FILE);
+        let mut writer = BufWriter::new(File::create(&file_path)?);
+        for table in tables {
+            let prefix = self.prefix(table)?;
+            for res in self.db.scan_prefix(&prefix) {
+                let (key, value) = res.map_err(sled_error)?;
+                let key = key.get(prefix.len()..).unwrap_or_default();
+                for field in [table.as_bytes(), key, value.as_ref()] {
+                    write_field(&mut writer, field)?;
+                }
+            }
+        }
+        writer.flush()?;
+        let size = fs::metadata(&file_path)?.len();
+        Ok(SledSnapshot::new(file_path, size))
+    }
+
+    #[inline]
+    async fn apply_snapshot(
+        &self,
+        snapshot: Self::Snapshot,
+        tables: &[&'static str],
+    ) -> Result<(), EngineError> {
+        let mut reader = BufReader::new(File::open(&snapshot.path)?);
+        let _guard = self.write_lock.lock();
+        // the snapshot is applied in bounded batches, so that it is never held in memory as
+        // a whole, a snapshot interrupted by a crash is applied again after the restart
+        let mut batch = sled::Batch::default();
+        let mut len = 0;
+        for table in tables {
+            let prefix = self.prefix(table)?;
+            for res in self.db.scan_prefix(&prefix) {
+                batch.remove(res.map_err(sled_error)?.0);
+                len = len.saturating_add(1);
+                self.flush_batch(&mut batch, &mut len, false)?;
+            }
+        }
+        while !reader.fill_buf()?.is_empty() {
+            let (table, key, value) = (
+                read_field(&mut reader)?,
+                read_field(&mut reader)?,
+                read_field(&mut reader)?,
+            );
+            let Some(table) = tables.iter().find(|t| t.as_bytes() == table) else {
+                continue;
+            };
+            let mut full_key = self.prefix(table)?;
+            full_key.extend(key);
+            batch.insert(full_key, value);
+            len = len.saturating_add(1);
+            self.flush_batch(&mut batch, &mut len, false)?;
+        }
+        self.flush_batch(&mut batch, &mut len, true)?;
+        let _bytes = self.db.flush().map_err(sled_error)?;
+        Ok(())
+    }
+
+    #[inline]
+    fn estimated_file_size(&self) -> u64 {
+        self.db.size_on_disk().unwrap_or_default()
+    }
+
+    #[inline]
+    fn file_size(&self) -> Result<u64, EngineError> {
+        self.db.size_on_disk().map_err(sled_error)
+    }
+}
+
+/// Write a field of a snapshot record, prefixed by its length
+fn write_field(writer: &mut impl Write, field: &[u8]) -> io::Result<()> {
+    let len: u64 = field.len().numeric_cast();
+    writer.write_all(&len.to_be_bytes())?;
+    writer.write_all(field)
+}
+
+/// Read a field of a snapshot record written by `write_field`
+fn read_field(reader: &mut impl Read) -> io::Result<Vec<u8>> {
+    let mut len = [0; 8];
+    reader.read_exact(&mut len)?;
+    let mut field = vec![0; u64::from_be_bytes(len).numeric_cast()];
+    reader.read_exact(&mut field)?;
+    Ok(field)
+}
+
+/// Snapshot of the `SledEngine`, a file of the key-values of the snapshotted tables. It is
+/// written and read piece by piece, so that a snapshot is never held in memory as a whole.
+///
+/// Each key-value is a record of the table name, the key and the value, each one prefixed by
+/// its length as a big-endian `u64`.
+#[derive(Debug)]
+pub struct SledSnapshot {
+    /// Path of the snapshot file
+    path: PathBuf,
+    /// Size of the snapshot file
+    size: u64,
+    /// The file being read or written, opened by the first read or write after a rewind
+    file: Option<tokio::fs::File>,
+}
+
+impl SledSnapshot {
+    /// New `SledSnapshot` of an existing snapshot file
+    fn new(path: PathBuf, size: u64) -> Self {
+        Self {
+            path,
+            size,
+            file: None,
+        }
+    }
+
+    /// Create a new empty snapshot in the directory for receiving
+    ///
+    /// # Errors
+    ///
+    /// Return `EngineError` when the snapshot file failed to create.
+    #[inline]
+    pub fn new_for_receiving(dir: impl AsRef<Path>) -> Result<Self, EngineError> {
+        fs::create_dir_all(dir.as_ref())?;
+        let path = dir.as_ref().join(SNAPSHOT_FILE);
+        let _file = File::create(&path)?;
+        Ok(Self::new(path, 0))
+    }
+}
+
+#[async_trait::async_trait]
+impl SnapshotApi for SledSnapshot {
+    #[inline]
+    fn size(&self) -> u64 {
+        self.size
+    }
+
+    #[inline]
+    fn rewind(&mut self) -> io::Result<()> {
+        self.file = None;
+        Ok(())
+    }
+
+    #[inline]
+    async fn read_buf(&mut self, buf: &mut BytesMut) -> io::Result<()> {
+        if self.file.is_none() {
+            self.file = Some(tokio::fs::File::open(&self.path).await?);
+        }
+        if let Some(ref mut file) = self.file {
+            let _n = file.read_buf(buf).await?;
+        }
+        Ok(())
+    }
+
+    #[inline]
+    async fn write_all(&mut self, buf: Bytes) -> io::Result<()> {
+        if self.file.is_none() {
+            let file = tokio::fs::OpenOptions::new()
+                .append(true)
+                .open(&self.path)
+                .await?;
+            self.file = Some(file);
+        }
+        if let Some(ref mut file) = self.file {
+            file.write_all(&buf).await?;
+            // the file is read by a separate handle when the snapshot is applied
+            file.flush().await?;
+            self.size = self.size.saturating_add(buf.len().numeric_cast());
+        }
+        Ok(())
+    }
+
+    #[inline]
+    async fn clean(&mut self) -> io::Result<()> {
+        self.file = None;
+        tokio::fs::remove_file(&self.path).await
+    }
+}
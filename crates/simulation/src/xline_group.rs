@@ -14,8 +14,8 @@ use xline_client::{
     types::{
         cluster::{MemberAddRequest, MemberAddResponse, MemberListRequest, MemberListResponse},
         kv::{
-            CompactionRequest, CompactionResponse, PutRequest, PutResponse, RangeRequest,
-            RangeResponse,
+            CompactionRequest, CompactionResponse, Consistency, PutRequest, PutResponse,
+            RangeRequest, RangeResponse,
         },
         watch::{WatchRequest, WatchStreaming, Watcher},
     },
@@ -60,6 +60,9 @@ impl XlineGroup {
                     ClientConfig::default(),
                     ServerTimeout::default(),
                     InitialClusterState::New,
+                    false,
+                    0,
+                    0,
                 );
 
                 let handle = handle
@@ -115,6 +118,10 @@ impl XlineGroup {
     }
 
     pub async fn client(&self) -> SimClient {
+        self.client_with_options(ClientOptions::default()).await
+    }
+
+    pub async fn client_with_options(&self, options: ClientOptions) -> SimClient {
         let all_members = self
             .nodes
             .values()
@@ -122,11 +129,7 @@ impl XlineGroup {
             .collect_vec();
         let client = self
             .client_handle
-            .spawn(async move {
-                Client::connect(all_members, ClientOptions::default())
-                    .await
-                    .unwrap()
-            })
+            .spawn(async move { Client::connect(all_members, options).await.unwrap() })
             .await
             .unwrap();
         SimClient {
@@ -152,6 +155,33 @@ impl XlineGroup {
         let handle = madsim::runtime::Handle::current();
         handle.restart(name);
     }
+
+    /// Disconnect the network link between two nodes
+    pub fn clog_link_nodes(&self, fst: &str, snd: &str) {
+        let net = madsim::net::NetSim::current();
+        let id_fst = self.get_node(fst).handle.id();
+        let id_snd = self.get_node(snd).handle.id();
+        net.clog_link(id_fst, id_snd);
+        net.clog_link(id_snd, id_fst);
+    }
+
+    /// Disconnect the network link between the client and a node
+    pub fn clog_link_client_node(&self, name: &str) {
+        let net = madsim::net::NetSim::current();
+        let id_client = self.client_handle.id();
+        let id_node = self.get_node(name).handle.id();
+        net.clog_link(id_client, id_node);
+        net.clog_link(id_node, id_client);
+    }
+
+    /// Reconnect the network link between two nodes
+    pub fn unclog_link_nodes(&self, fst: &str, snd: &str) {
+        let net = madsim::net::NetSim::current();
+        let id_fst = self.get_node(fst).handle.id();
+        let id_snd = self.get_node(snd).handle.id();
+        net.unclog_link(id_fst, id_snd);
+        net.unclog_link(id_snd, id_fst);
+    }
 }
 
 pub struct SimClient {
@@ -180,6 +210,35 @@ impl SimClient {
     impl_client_method!(compact, kv_client, CompactionRequest, CompactionResponse);
 
     impl_client_method!(watch, watch_client, WatchRequest, (Watcher, WatchStreaming));
+
+    pub async fn range_with_consistency(
+        &self,
+        request: RangeRequest,
+        consistency: Consistency,
+    ) -> Result<RangeResponse, XlineClientError<Command>> {
+        let client = self.inner.clone();
+        self.handle
+            .spawn(async move {
+                client
+                    .kv_client()
+                    .range_with_consistency(request, consistency)
+                    .await
+            })
+            .await
+            .unwrap()
+    }
+
+    pub async fn count_prefix_all_replicas(
+        &self,
+        prefix: &str,
+    ) -> Result<HashMap<u64, i64>, XlineClientError<Command>> {
+        let client = self.inner.clone();
+        let prefix = prefix.to_owned();
+        self.handle
+            .spawn(async move { client.kv_client().count_prefix_all_replicas(prefix).await })
+            .await
+            .unwrap()
+    }
 }
 
 impl Drop for XlineGroup {
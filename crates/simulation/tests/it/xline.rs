@@ -3,10 +3,14 @@ use std::time::Duration;
 use curp_test_utils::init_logger;
 use madsim::time::sleep;
 use simulation::xline_group::{SimEtcdClient, XlineGroup};
-use xline_client::types::{
-    cluster::{MemberAddRequest, MemberListRequest},
-    kv::{CompactionRequest, PutRequest},
-    watch::WatchRequest,
+use xline_client::{
+    error::XlineClientError,
+    types::{
+        cluster::{MemberAddRequest, MemberListRequest},
+        kv::{CompactionRequest, Consistency, PutRequest, RangeRequest, ReadSelectionPolicy},
+        watch::WatchRequest,
+    },
+    ClientOptions,
 };
 
 // TODO: Add more tests if needed
@@ -48,6 +52,131 @@ async fn watch_compacted_revision_should_receive_canceled_response() {
     assert!(r.canceled);
 }
 
+#[madsim::test]
+async fn count_prefix_all_replicas_should_report_the_lagging_member() {
+    init_logger();
+    let group = XlineGroup::new(3).await;
+    let client = group.client().await;
+
+    // S2 stops replicating, but it still serves the client
+    group.clog_link_nodes("S2", "S0");
+    group.clog_link_nodes("S2", "S1");
+    // wait for a new leader if S2 was the leader
+    sleep(Duration::from_secs(10)).await;
+    for i in 0..4 {
+        client
+            .put(PutRequest::new(format!("count/{i}"), "v"))
+            .await
+            .unwrap();
+    }
+    sleep(Duration::from_secs(1)).await;
+    let mut counts: Vec<_> = client
+        .count_prefix_all_replicas("count/")
+        .await
+        .unwrap()
+        .into_values()
+        .collect();
+    counts.sort_unstable();
+    assert_eq!(counts, [0, 4, 4]);
+
+    // the lagging member catches up once it is reconnected
+    group.unclog_link_nodes("S2", "S0");
+    group.unclog_link_nodes("S2", "S1");
+    sleep(Duration::from_secs(10)).await;
+    let counts = client.count_prefix_all_replicas("count/").await.unwrap();
+    assert_eq!(counts.len(), 3);
+    assert!(counts.values().all(|&count| count == 4));
+}
+
+#[madsim::test]
+async fn serializable_reads_should_be_routed_away_from_the_unreachable_member() {
+    init_logger();
+    let group = XlineGroup::new(3).await;
+    let client = group
+        .client_with_options(
+            ClientOptions::default()
+                .with_read_selection(ReadSelectionPolicy::SerializablePreferLeader),
+        )
+        .await;
+    client.put(PutRequest::new("key", "value")).await.unwrap();
+    let members = SimEtcdClient::new(
+        group.get_node("S0").client_url.clone(),
+        group.client_handle.clone(),
+    )
+    .await
+    .member_list(MemberListRequest::new(false))
+    .await
+    .unwrap()
+    .members;
+    let leader_id = client
+        .range_with_consistency(RangeRequest::new("key"), Consistency::Linearizable)
+        .await
+        .unwrap()
+        .header
+        .unwrap()
+        .member_id;
+    // the client can not reach a follower, while the follower is still in the cluster
+    let unreachable = members.iter().find(|m| m.id != leader_id).unwrap();
+    group.clog_link_client_node(&unreachable.name);
+
+    let mut served_by = Vec::new();
+    for _ in 0..10 {
+        let start = madsim::time::Instant::now();
+        let resp = client
+            .range_with_consistency(RangeRequest::new("key"), Consistency::Serializable)
+            .await
+            .unwrap();
+        assert_eq!(resp.kvs[0].value, b"value");
+        served_by.push((resp.header.unwrap().member_id, start.elapsed()));
+    }
+    // the unreachable member is tried once at most, then the reads never wait for it again
+    for &(id, latency) in &served_by[3..] {
+        assert_ne!(id, unreachable.id);
+        assert!(
+            latency < Duration::from_millis(500),
+            "slow read: {latency:?}"
+        );
+    }
+}
+
+#[madsim::test]
+async fn writes_should_fail_fast_while_serializable_reads_work_on_quorum_loss() {
+    init_logger();
+    let mut group = XlineGroup::new(3).await;
+    let client = group
+        .client_with_options(ClientOptions::default().with_read_only_on_quorum_loss(2))
+        .await;
+    client.put(PutRequest::new("key", "value")).await.unwrap();
+
+    group.crash("S1").await;
+    group.crash("S2").await;
+    // two failures switch the client to the read-only mode, then the probing write fails
+    for _ in 0..3 {
+        assert!(client.put(PutRequest::new("key", "value1")).await.is_err());
+    }
+
+    let start = madsim::time::Instant::now();
+    let err = client
+        .put(PutRequest::new("key", "value2"))
+        .await
+        .unwrap_err();
+    assert!(matches!(err, XlineClientError::ClusterDegraded), "{err:?}");
+    assert!(start.elapsed() < Duration::from_millis(100));
+
+    // the balanced channel may pick a crashed server, which is skipped on the next read
+    let mut read = None;
+    for _ in 0..3 {
+        if let Ok(resp) = client
+            .range_with_consistency(RangeRequest::new("key"), Consistency::Serializable)
+            .await
+        {
+            read = Some(resp);
+            break;
+        }
+    }
+    assert_eq!(read.unwrap().kvs[0].value, b"value");
+}
+
 #[madsim::test]
 async fn xline_members_restore() {
     init_logger();
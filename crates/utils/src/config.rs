@@ -119,6 +119,20 @@ pub struct ClusterConfig {
     #[getset(get = "pub")]
     #[serde(with = "state_format", default = "InitialClusterState::default")]
     initial_cluster_state: InitialClusterState,
+    /// Format responses exactly as etcd does where xline diverges
+    #[getset(get = "pub")]
+    #[serde(default)]
+    etcd_compat: bool,
+    /// Max bytes of responses buffered for one watch, a watch exceeding it is canceled as a
+    /// slow consumer, 0 means unlimited
+    #[getset(get = "pub")]
+    #[serde(default)]
+    max_watch_buffer_bytes: u64,
+    /// Max active watches of a client connection, a watch creation beyond it is rejected,
+    /// 0 means unlimited
+    #[getset(get = "pub")]
+    #[serde(default)]
+    max_watches_per_client: usize,
 }
 
 impl Default for ClusterConfig {
@@ -139,6 +153,9 @@ impl Default for ClusterConfig {
             client_config: ClientConfig::default(),
             server_timeout: ServerTimeout::default(),
             initial_cluster_state: InitialClusterState::default(),
+            etcd_compat: false,
+            max_watch_buffer_bytes: 0,
+            max_watches_per_client: 0,
         }
     }
 }
@@ -189,6 +206,9 @@ impl ClusterConfig {
         client_config: ClientConfig,
         server_timeout: ServerTimeout,
         initial_cluster_state: InitialClusterState,
+        etcd_compat: bool,
+        max_watch_buffer_bytes: u64,
+        max_watches_per_client: usize,
     ) -> Self {
         Self {
             name,
@@ -202,6 +222,39 @@ impl ClusterConfig {
             client_config,
             server_timeout,
             initial_cluster_state,
+            etcd_compat,
+            max_watch_buffer_bytes,
+            max_watches_per_client,
+        }
+    }
+
+    /// Set the curp server settings
+    #[must_use]
+    #[inline]
+    pub fn with_curp_config(self, curp_config: CurpConfig) -> Self {
+        Self {
+            curp_config,
+            ..self
+        }
+    }
+
+    /// Set whether to format responses exactly as etcd does
+    #[must_use]
+    #[inline]
+    pub fn with_etcd_compat(self, etcd_compat: bool) -> Self {
+        Self {
+            etcd_compat,
+            ..self
+        }
+    }
+
+    /// Set the max active watches of a client connection
+    #[must_use]
+    #[inline]
+    pub fn with_max_watches_per_client(self, max_watches_per_client: usize) -> Self {
+        Self {
+            max_watches_per_client,
+            ..self
         }
     }
 }
@@ -210,10 +263,16 @@ impl ClusterConfig {
 #[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq, Getters)]
 #[allow(clippy::module_name_repetitions)]
 pub struct CompactConfig {
-    /// The max number of historical versions processed in a single compact operation
+    /// The max number of historical versions processed in a single compact operation, which
+    /// is the chunk size of the incremental compaction
     #[getset(get = "pub")]
     #[serde(default = "default_compact_batch_size")]
     compact_batch_size: usize,
+    /// Whether to reclaim the space in chunks with yields between them, otherwise all
+    /// historical versions are removed at once, which may cause a long write stall
+    #[getset(get = "pub")]
+    #[serde(default = "default_incremental_compaction")]
+    incremental_compaction: bool,
     /// The interval between two compaction batches
     #[getset(get = "pub")]
     #[serde(with = "duration_format", default = "default_compact_sleep_interval")]
@@ -228,6 +287,7 @@ impl Default for CompactConfig {
     fn default() -> Self {
         Self {
             compact_batch_size: default_compact_batch_size(),
+            incremental_compaction: default_incremental_compaction(),
             compact_sleep_interval: default_compact_sleep_interval(),
             auto_compact_config: None,
         }
@@ -245,10 +305,21 @@ impl CompactConfig {
     ) -> Self {
         Self {
             compact_batch_size,
+            incremental_compaction: default_incremental_compaction(),
             compact_sleep_interval,
             auto_compact_config,
         }
     }
+
+    /// Set whether to reclaim the space in chunks of `compact_batch_size`
+    #[must_use]
+    #[inline]
+    pub fn with_incremental_compaction(self, incremental_compaction: bool) -> Self {
+        Self {
+            incremental_compaction,
+            ..self
+        }
+    }
 }
 
 /// default compact batch size
@@ -258,6 +329,13 @@ pub const fn default_compact_batch_size() -> usize {
     1000
 }
 
+/// default compaction mode
+#[must_use]
+#[inline]
+pub const fn default_incremental_compaction() -> bool {
+    true
+}
+
 /// default compact interval
 #[must_use]
 #[inline]
@@ -322,9 +400,10 @@ pub struct CurpConfig {
     #[serde(default = "EngineConfig::default")]
     pub engine_cfg: EngineConfig,
 
-    /// Number of command execute workers
+    /// Number of command execute workers, i.e. the size of the command execution pool, which
+    /// must be greater than 0
     #[builder(default = "default_cmd_workers()")]
-    #[serde(default = "default_cmd_workers")]
+    #[serde(default = "default_cmd_workers", alias = "execution_pool_size")]
     pub cmd_workers: u8,
 
     /// How often should the gc task run
@@ -336,6 +415,50 @@ pub struct CurpConfig {
     #[builder(default = "default_log_entries_cap()")]
     #[serde(default = "default_log_entries_cap")]
     pub log_entries_cap: usize,
+
+    /// Whether the leader serves linearizable reads locally while it holds a valid leader lease
+    #[builder(default = "default_enable_leader_lease()")]
+    #[serde(default = "default_enable_leader_lease")]
+    pub enable_leader_lease: bool,
+
+    /// Safety margin subtracted from the leader lease to tolerate clock drift between nodes
+    #[builder(default = "default_leader_lease_clock_drift()")]
+    #[serde(with = "duration_format", default = "default_leader_lease_clock_drift")]
+    pub leader_lease_clock_drift: Duration,
+
+    /// Timeout of confirming the leadership with a quorum for a linearizable read, 0 means
+    /// `heartbeat_interval + rpc_timeout`
+    #[builder(default = "default_linearizable_read_timeout()")]
+    #[serde(
+        with = "duration_format",
+        default = "default_linearizable_read_timeout"
+    )]
+    pub linearizable_read_timeout: Duration,
+
+    /// Whether the deadline of a propose also bounds how long the server waits for its result
+    #[builder(default = "default_enable_propose_deadline()")]
+    #[serde(default = "default_enable_propose_deadline")]
+    pub enable_propose_deadline: bool,
+
+    /// Max proposes per second accepted from a single client id, 0 means unlimited
+    #[builder(default = "default_client_qps_limit()")]
+    #[serde(default = "default_client_qps_limit")]
+    pub client_qps_limit: u64,
+
+    /// Propose timeout recommended to clients in fetch cluster responses, 0 means no hint
+    #[builder(default = "default_client_hint_timeout()")]
+    #[serde(with = "duration_format", default = "default_client_hint_timeout")]
+    pub client_hint_propose_timeout: Duration,
+
+    /// Wait synced timeout recommended to clients in fetch cluster responses, 0 means no hint
+    #[builder(default = "default_client_hint_timeout()")]
+    #[serde(with = "duration_format", default = "default_client_hint_timeout")]
+    pub client_hint_wait_synced_timeout: Duration,
+
+    /// Fast path quorum recommended to clients in fetch cluster responses, 0 means no hint
+    #[builder(default = "default_client_hint_fast_quorum()")]
+    #[serde(default = "default_client_hint_fast_quorum")]
+    pub client_hint_fast_quorum: usize,
 }
 
 /// default heartbeat interval
@@ -487,6 +610,55 @@ pub const fn default_log_entries_cap() -> usize {
     5000
 }
 
+/// default enable leader lease
+#[must_use]
+#[inline]
+pub const fn default_enable_leader_lease() -> bool {
+    false
+}
+
+/// default leader lease clock drift
+#[must_use]
+#[inline]
+pub const fn default_leader_lease_clock_drift() -> Duration {
+    Duration::from_millis(100)
+}
+
+/// default linearizable read timeout, derived from the heartbeat interval and rpc timeout
+#[must_use]
+#[inline]
+pub const fn default_linearizable_read_timeout() -> Duration {
+    Duration::ZERO
+}
+
+/// default enable propose deadline
+#[must_use]
+#[inline]
+pub const fn default_enable_propose_deadline() -> bool {
+    true
+}
+
+/// default max proposes per second of a client id
+#[must_use]
+#[inline]
+pub const fn default_client_qps_limit() -> u64 {
+    0
+}
+
+/// default timeout recommended to clients, no hint
+#[must_use]
+#[inline]
+pub const fn default_client_hint_timeout() -> Duration {
+    Duration::ZERO
+}
+
+/// default fast path quorum recommended to clients, no hint
+#[must_use]
+#[inline]
+pub const fn default_client_hint_fast_quorum() -> usize {
+    0
+}
+
 /// default watch progress notify interval
 #[must_use]
 #[inline]
@@ -510,6 +682,14 @@ impl Default for CurpConfig {
             cmd_workers: default_cmd_workers(),
             gc_interval: default_gc_interval(),
             log_entries_cap: default_log_entries_cap(),
+            enable_leader_lease: default_enable_leader_lease(),
+            leader_lease_clock_drift: default_leader_lease_clock_drift(),
+            linearizable_read_timeout: default_linearizable_read_timeout(),
+            enable_propose_deadline: default_enable_propose_deadline(),
+            client_qps_limit: default_client_qps_limit(),
+            client_hint_propose_timeout: default_client_hint_timeout(),
+            client_hint_wait_synced_timeout: default_client_hint_timeout(),
+            client_hint_fast_quorum: default_client_hint_fast_quorum(),
         }
     }
 }
@@ -694,6 +874,8 @@ pub enum EngineConfig {
     Memory,
     /// RocksDB Storage Engine
     RocksDB(PathBuf),
+    /// Sled Storage Engine, which avoids the C++ dependency of RocksDB
+    Sled(PathBuf),
 }
 
 impl Default for EngineConfig {
@@ -1233,6 +1415,7 @@ mod tests {
 
             [compact]
             compact_batch_size = 123
+            incremental_compaction = false
             compact_sleep_interval = '5ms'
 
             [compact.auto_compact_config]
@@ -1314,7 +1497,10 @@ mod tests {
                 curp_config,
                 client_config,
                 server_timeout,
-                InitialClusterState::New
+                InitialClusterState::New,
+                false,
+                0,
+                0
             )
         );
 
@@ -1345,6 +1531,7 @@ mod tests {
             config.compact,
             CompactConfig {
                 compact_batch_size: 123,
+                incremental_compaction: false,
                 compact_sleep_interval: Duration::from_millis(5),
                 auto_compact_config: Some(AutoCompactConfig::Periodic(Duration::from_secs(
                     10 * 60 * 60
@@ -1439,7 +1626,10 @@ mod tests {
                 CurpConfigBuilder::default().build().unwrap(),
                 ClientConfig::default(),
                 ServerTimeout::default(),
-                InitialClusterState::default()
+                InitialClusterState::default(),
+                false,
+                0,
+                0
             )
         );
 
@@ -46,6 +46,7 @@ enum_with_iter! {
     RevokeExpiredLeases,
     SyncVictims,
     AutoCompactor,
+    SyncTerm,
 }
 
 /// All edges of task graph, the first item in each pair must be shut down before the second item
@@ -0,0 +1,248 @@
+use std::{
+    collections::BTreeMap,
+    fmt::Debug,
+    sync::{Arc, PoisonError, RwLock},
+};
+
+use clippy_utilities::OverflowArithmetic;
+use tokio::task::JoinHandle;
+use xlineapi::{EventType, WatchResponse};
+
+use crate::{
+    error::{Result, XlineClientError},
+    types::{kv::RangeRequest, watch::WatchRequest},
+    Client,
+};
+
+/// A read-only local cache of a key range, which is loaded by a range and kept fresh by a
+/// background watch. Reads are served from memory, and each of them reflects the state
+/// of the key range at the returned revision.
+///
+/// The view becomes stale once the watch is canceled or disconnected, e.g. the revisions
+/// it requires have been compacted, and all reads of a stale view fail. A new view should
+/// be created to resync the key range then.
+pub struct CachedView {
+    /// The cached state
+    state: Arc<RwLock<ViewState>>,
+    /// The background watch task
+    task: JoinHandle<()>,
+}
+
+impl Debug for CachedView {
+    #[inline]
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let state = self.state.read().unwrap_or_else(PoisonError::into_inner);
+        f.debug_struct("CachedView")
+            .field("keys", &state.kvs.len())
+            .field("revision", &state.revision)
+            .field("stale", &state.stale)
+            .finish()
+    }
+}
+
+/// State of a `CachedView`
+#[derive(Debug, Default)]
+struct ViewState {
+    /// Cached key-values
+    kvs: BTreeMap<Vec<u8>, Vec<u8>>,
+    /// The revision reflected by the cached key-values
+    revision: i64,
+    /// Whether the view is stale
+    stale: bool,
+}
+
+impl ViewState {
+    /// Apply a watch response to the view, return false if the view becomes stale
+    fn apply(&mut self, resp: &WatchResponse) -> bool {
+        if resp.canceled || resp.compact_revision != 0 {
+            self.stale = true;
+            return false;
+        }
+        for event in &resp.events {
+            let Some(ref kv) = event.kv else {
+                continue;
+            };
+            if event.r#type() == EventType::Delete {
+                let _prev = self.kvs.remove(&kv.key);
+            } else {
+                let _prev = self.kvs.insert(kv.key.clone(), kv.value.clone());
+            }
+        }
+        // progress notifications also advance the revision, all events before it are received
+        if let Some(revision) = resp.header.as_ref().map(|h| h.revision) {
+            self.revision = self.revision.max(revision);
+        }
+        true
+    }
+}
+
+impl CachedView {
+    /// Create a cached view of the key range `[key, range_end)`, the view is loaded by a
+    /// linearizable range and then kept fresh by a watch from the next revision. An empty
+    /// `range_end` caches the single `key`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the initial range or watch fails
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use xline_client::{clients::CachedView, Client, ClientOptions};
+    /// use anyhow::Result;
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<()> {
+    ///     let curp_members = ["10.0.0.1:2379", "10.0.0.2:2379", "10.0.0.3:2379"];
+    ///
+    ///     let client = Client::connect(curp_members, ClientOptions::default()).await?;
+    ///     let view = CachedView::new(&client, "config/", "config0").await?;
+    ///
+    ///     let (value, revision) = view.get("config/key1")?;
+    ///     println!("value: {value:?}, revision: {revision}");
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    #[inline]
+    pub async fn new(
+        client: &Client,
+        key: impl Into<Vec<u8>>,
+        range_end: impl Into<Vec<u8>>,
+    ) -> Result<Self> {
+        let (key, range_end) = (key.into(), range_end.into());
+        let resp = client
+            .kv_client()
+            .range(RangeRequest::new(key.clone()).with_range_end(range_end.clone()))
+            .await?;
+        let revision = resp.header.as_ref().map_or(0, |h| h.revision);
+        let state = Arc::new(RwLock::new(ViewState {
+            kvs: resp.kvs.into_iter().map(|kv| (kv.key, kv.value)).collect(),
+            revision,
+            stale: false,
+        }));
+        let (watcher, mut stream) = client
+            .watch_client()
+            .watch(
+                WatchRequest::new(key)
+                    .with_range_end(range_end)
+                    .with_start_revision(revision.overflow_add(1)),
+            )
+            .await?;
+        let state_c = Arc::clone(&state);
+        let task = tokio::spawn(async move {
+            // the watch is canceled once the watcher is dropped
+            let _watcher = watcher;
+            loop {
+                let Ok(Some(resp)) = stream.message().await else {
+                    state_c
+                        .write()
+                        .unwrap_or_else(PoisonError::into_inner)
+                        .stale = true;
+                    break;
+                };
+                if !state_c
+                    .write()
+                    .unwrap_or_else(PoisonError::into_inner)
+                    .apply(&resp)
+                {
+                    break;
+                }
+            }
+        });
+        Ok(Self { state, task })
+    }
+
+    /// Get the cached value of the key and the revision the view reflects, the value is
+    /// `None` if the key is absent at that revision
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the view is stale
+    #[inline]
+    pub fn get(&self, key: impl AsRef<[u8]>) -> Result<(Option<Vec<u8>>, i64)> {
+        let state = self.state.read().unwrap_or_else(PoisonError::into_inner);
+        if state.stale {
+            return Err(XlineClientError::WatchError(
+                "the cached view is stale".to_owned(),
+            ));
+        }
+        Ok((state.kvs.get(key.as_ref()).cloned(), state.revision))
+    }
+
+    /// Get the revision the view reflects
+    #[inline]
+    #[must_use]
+    pub fn revision(&self) -> i64 {
+        self.state
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .revision
+    }
+
+    /// Whether the view is stale, a stale view is no longer updated
+    #[inline]
+    #[must_use]
+    pub fn is_stale(&self) -> bool {
+        self.state
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .stale
+    }
+}
+
+impl Drop for CachedView {
+    #[inline]
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use xlineapi::{Event, KeyValue, ResponseHeader};
+
+    use super::*;
+
+    fn put_response(revision: i64, key: &str, value: &str) -> WatchResponse {
+        WatchResponse {
+            header: Some(ResponseHeader {
+                revision,
+                ..ResponseHeader::default()
+            }),
+            events: vec![Event {
+                r#type: EventType::Put.into(),
+                kv: Some(KeyValue {
+                    key: key.into(),
+                    value: value.into(),
+                    mod_revision: revision,
+                    ..KeyValue::default()
+                }),
+                prev_kv: None,
+            }],
+            ..WatchResponse::default()
+        }
+    }
+
+    #[test]
+    fn view_should_apply_events_and_go_stale_on_compaction() {
+        let mut state = ViewState::default();
+        assert!(state.apply(&put_response(2, "foo", "bar")));
+        assert_eq!(state.kvs.get(b"foo".as_slice()), Some(&b"bar".to_vec()));
+        assert_eq!(state.revision, 2);
+
+        let mut delete = put_response(3, "foo", "");
+        delete.events[0].r#type = EventType::Delete.into();
+        assert!(state.apply(&delete));
+        assert!(state.kvs.is_empty());
+        assert_eq!(state.revision, 3);
+
+        let compacted = WatchResponse {
+            canceled: true,
+            compact_revision: 5,
+            ..WatchResponse::default()
+        };
+        assert!(!state.apply(&compacted));
+        assert!(state.stale);
+    }
+}
@@ -1,27 +1,50 @@
-use std::sync::Arc;
+use std::{fmt::Debug, sync::Arc, time::Duration};
 
+use futures::future::join_all;
 use tonic::transport::Channel;
+#[cfg(not(madsim))]
+use tonic::transport::ClientTlsConfig;
+use utils::build_endpoint;
+#[cfg(madsim)]
+use utils::ClientTlsConfig;
 
 use crate::{
-    error::Result,
+    error::{Result, XlineClientError},
     types::cluster::{
-        MemberAddRequest, MemberAddResponse, MemberListRequest, MemberListResponse,
-        MemberPromoteRequest, MemberPromoteResponse, MemberRemoveRequest, MemberRemoveResponse,
-        MemberUpdateRequest, MemberUpdateResponse,
+        DetailedMember, Member, MemberAddRequest, MemberAddResponse, MemberListRequest,
+        MemberListResponse, MemberPromoteRequest, MemberPromoteResponse, MemberRemoveRequest,
+        MemberRemoveResponse, MemberUpdateRequest, MemberUpdateResponse,
     },
-    AuthService,
+    AuthService, CurpClient,
 };
 
+/// Timeout of probing whether a member is reachable
+const PROBE_TIMEOUT: Duration = Duration::from_secs(1);
+
 /// Client for Cluster operations.
-#[derive(Clone, Debug)]
+#[derive(Clone)]
 #[non_exhaustive]
 pub struct ClusterClient {
+    /// The client running the CURP protocol, used to observe the leader
+    curp_client: Option<Arc<CurpClient>>,
     /// Inner client
     #[cfg(not(madsim))]
     inner: xlineapi::ClusterClient<AuthService<Channel>>,
     /// Inner client
     #[cfg(madsim)]
     inner: xlineapi::ClusterClient<Channel>,
+    /// Client tls config, used to probe members
+    tls_config: Option<ClientTlsConfig>,
+}
+
+impl Debug for ClusterClient {
+    #[inline]
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ClusterClient")
+            .field("inner", &self.inner)
+            .field("tls_config", &self.tls_config)
+            .finish()
+    }
 }
 
 impl ClusterClient {
@@ -30,13 +53,31 @@ impl ClusterClient {
     #[must_use]
     pub fn new(channel: Channel, token: Option<String>) -> Self {
         Self {
+            curp_client: None,
             inner: xlineapi::ClusterClient::new(AuthService::new(
                 channel,
                 token.and_then(|t| t.parse().ok().map(Arc::new)),
             )),
+            tls_config: None,
+        }
+    }
+
+    /// Set the client running the CURP protocol, which is required to wait for the leader
+    #[inline]
+    #[must_use]
+    pub fn with_curp_client(self, curp_client: Arc<CurpClient>) -> Self {
+        Self {
+            curp_client: Some(curp_client),
+            ..self
         }
     }
 
+    /// Set the tls config used to probe members
+    #[inline]
+    pub(crate) fn with_tls_config(self, tls_config: Option<ClientTlsConfig>) -> Self {
+        Self { tls_config, ..self }
+    }
+
     /// Add a new member to the cluster.
     ///
     /// # Errors
@@ -228,4 +269,133 @@ impl ClusterClient {
             .await?
             .into_inner())
     }
+
+    /// List all members of the cluster with their learner flags, urls and reachability.
+    /// A member is reachable if it answers a status request on any of its client urls.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the member list request could not be sent or if the response is invalid.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use xline_client::{Client, ClientOptions};
+    /// use anyhow::Result;
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<()> {
+    ///     let curp_members = ["10.0.0.1:2379", "10.0.0.2:2379", "10.0.0.3:2379"];
+    ///
+    ///     let mut client = Client::connect(curp_members, ClientOptions::default())
+    ///         .await?
+    ///         .cluster_client();
+    ///
+    ///     for member in client.member_list_detailed(true).await? {
+    ///         println!(
+    ///             "member: {}, learner: {}, reachable: {}",
+    ///             member.name(),
+    ///             member.is_learner(),
+    ///             member.is_reachable()
+    ///         );
+    ///     }
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    #[inline]
+    pub async fn member_list_detailed(
+        &mut self,
+        linearizable: bool,
+    ) -> Result<Vec<DetailedMember>> {
+        let members = self
+            .member_list(MemberListRequest::new(linearizable))
+            .await?
+            .members;
+        let probes = members.iter().map(|member| self.probe(member));
+        let reachable = join_all(probes).await;
+        Ok(members
+            .into_iter()
+            .zip(reachable)
+            .map(|(member, reachable)| DetailedMember::new(member, reachable))
+            .collect())
+    }
+
+    /// Wait until a stable leader is elected, which is useful right after the cluster starts.
+    /// The leader is stable after consecutive fetches observe it with the same term.
+    ///
+    /// # Errors
+    ///
+    /// Returns `XlineClientError::Timeout` if no stable leader is elected before the timeout,
+    /// or `XlineClientError::InvalidArgs` if the client is not built with a CURP client, see
+    /// [`ClusterClient::with_curp_client`].
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use std::time::Duration;
+    ///
+    /// use xline_client::{Client, ClientOptions};
+    /// use anyhow::Result;
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<()> {
+    ///     let curp_members = ["10.0.0.1:2379", "10.0.0.2:2379", "10.0.0.3:2379"];
+    ///
+    ///     let client = Client::connect(curp_members, ClientOptions::default())
+    ///         .await?
+    ///         .cluster_client();
+    ///     let leader = client.wait_for_leader(Duration::from_secs(10)).await?;
+    ///
+    ///     println!("leader: {leader}");
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    #[inline]
+    pub async fn wait_for_leader(&self, timeout: Duration) -> Result<u64> {
+        let Some(ref curp_client) = self.curp_client else {
+            return Err(XlineClientError::InvalidArgs(
+                "waiting for the leader requires a curp client".to_owned(),
+            ));
+        };
+        curp_client
+            .wait_for_leader(timeout)
+            .await
+            .ok_or(XlineClientError::Timeout)
+    }
+
+    /// Probe whether the member answers on any of its client urls
+    async fn probe(&self, member: &Member) -> bool {
+        for url in &member.client_ur_ls {
+            let Ok(endpoint) = build_endpoint(url, self.tls_config.as_ref()) else {
+                continue;
+            };
+            let Ok(channel) = endpoint
+                .connect_timeout(PROBE_TIMEOUT)
+                .timeout(PROBE_TIMEOUT)
+                .connect()
+                .await
+            else {
+                continue;
+            };
+            let mut client = xlineapi::MaintenanceClient::new(channel);
+            match client.status(xlineapi::StatusRequest::default()).await {
+                Ok(_) => return true,
+                // the server answered, even if it rejects the request
+                Err(status)
+                    if !matches!(
+                        status.code(),
+                        tonic::Code::Unavailable
+                            | tonic::Code::DeadlineExceeded
+                            | tonic::Code::Cancelled
+                    ) =>
+                {
+                    return true;
+                }
+                Err(_) => {}
+            }
+        }
+        false
+    }
 }
@@ -0,0 +1,342 @@
+use std::time::Duration;
+
+use clippy_utilities::OverflowArithmetic;
+use xlineapi::{execute_error::ExecuteError, EventType, RangeResponse, WatchResponse};
+
+use super::KvClient;
+use crate::{
+    error::{Result, XlineClientError},
+    types::{
+        kv::RangeRequest,
+        watch::{WatchRequest, WatchStreaming, Watcher},
+    },
+};
+
+#[allow(clippy::multiple_inherent_impl)] // the operations of the client are split by feature
+impl KvClient {
+    /// Get the history of a key between two revisions, which lists the revision and value of
+    /// each version of the key in `[from_rev, to_rev]`, the value is `None` if the key is deleted
+    /// at that revision. The history is read from the MVCC store by a watch from `from_rev`, so
+    /// `from_rev` must be above the compacted revision.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the revisions are invalid, `from_rev` has been
+    /// compacted, or the inner CURP client or the watch fails
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use xline_client::{Client, ClientOptions};
+    /// use anyhow::Result;
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<()> {
+    ///     let curp_members = ["10.0.0.1:2379", "10.0.0.2:2379", "10.0.0.3:2379"];
+    ///
+    ///     let client = Client::connect(curp_members, ClientOptions::default())
+    ///         .await?
+    ///         .kv_client();
+    ///
+    ///     for (revision, value) in client.key_history("key1", 1, 100).await? {
+    ///         println!("revision {revision}: {value:?}");
+    ///     }
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    #[inline]
+    pub async fn key_history(
+        &self,
+        key: impl Into<Vec<u8>>,
+        from_rev: i64,
+        to_rev: i64,
+    ) -> Result<Vec<(i64, Option<Vec<u8>>)>> {
+        if from_rev <= 0 || to_rev < from_rev {
+            return Err(XlineClientError::InvalidArgs(format!(
+                "invalid revisions [{from_rev}, {to_rev}] of the history"
+            )));
+        }
+        let key = key.into();
+        // revisions after the current one have no history yet
+        let current = self
+            .range(RangeRequest::new(key.clone()).with_count_only(true))
+            .await?
+            .header
+            .map_or(0, |h| h.revision);
+        let to_rev = to_rev.min(current);
+        let mut history = Vec::new();
+        if from_rev > to_rev {
+            return Ok(history);
+        }
+
+        let mut watch_client = self.watch_client.clone();
+        let (mut watcher, mut stream) = watch_client
+            .watch(WatchRequest::new(key).with_start_revision(from_rev))
+            .await?;
+        // the progress response arrives after all events before its revision
+        watcher.request_progress()?;
+        let result = loop {
+            let Some(resp) = stream.message().await? else {
+                break Err(XlineClientError::WatchError(
+                    "watch stream closed before the history is read".to_owned(),
+                ));
+            };
+            match Self::collect_history(&mut history, &resp, from_rev, to_rev) {
+                Ok(false) => {}
+                Ok(true) => break Ok(history),
+                Err(e) => break Err(e),
+            }
+        };
+        // the stream may already be closed, in which case there is nothing to cancel
+        let _ig = watcher.cancel();
+        result
+    }
+
+    /// Collect versions of a key in `[from_rev, to_rev]` from a watch response, return true if
+    /// the history reaches `to_rev`
+    fn collect_history(
+        history: &mut Vec<(i64, Option<Vec<u8>>)>,
+        resp: &WatchResponse,
+        from_rev: i64,
+        to_rev: i64,
+    ) -> Result<bool> {
+        if resp.compact_revision != 0 {
+            return Err(XlineClientError::ExecuteError(
+                ExecuteError::RevisionCompacted(from_rev, resp.compact_revision),
+            ));
+        }
+        if resp.canceled {
+            return Err(XlineClientError::WatchError(format!(
+                "watch canceled before the history is read: {}",
+                resp.cancel_reason
+            )));
+        }
+        for event in &resp.events {
+            let Some(ref kv) = event.kv else {
+                continue;
+            };
+            if kv.mod_revision > to_rev {
+                return Ok(true);
+            }
+            let value = (event.r#type() == EventType::Put).then(|| kv.value.clone());
+            history.push((kv.mod_revision, value));
+        }
+        let reached = history.last().is_some_and(|&(rev, _)| rev >= to_rev)
+            || (resp.events.is_empty()
+                && resp.header.as_ref().is_some_and(|h| h.revision >= to_rev));
+        Ok(reached)
+    }
+
+    /// Wait until the value of the key satisfies the `predicate`, and return the value. The
+    /// `predicate` gets `None` if the key is absent. The current value is checked first, so
+    /// this returns immediately if it already satisfies the `predicate`, otherwise each later
+    /// change of the key is checked by a watch until one satisfies it or the `timeout` expires.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the inner CURP client or the watch fails, or
+    /// `XlineClientError::Timeout` if no value satisfies the `predicate` in time
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use std::time::Duration;
+    ///
+    /// use xline_client::{Client, ClientOptions};
+    /// use anyhow::Result;
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<()> {
+    ///     let curp_members = ["10.0.0.1:2379", "10.0.0.2:2379", "10.0.0.3:2379"];
+    ///
+    ///     let client = Client::connect(curp_members, ClientOptions::default())
+    ///         .await?
+    ///         .kv_client();
+    ///
+    ///     let value = client
+    ///         .wait_value(
+    ///             "job1/state",
+    ///             |value| value == Some(b"done".as_slice()),
+    ///             Some(Duration::from_secs(10)),
+    ///         )
+    ///         .await?;
+    ///     println!("job1 finished: {value:?}");
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    #[inline]
+    pub async fn wait_value(
+        &self,
+        key: impl Into<Vec<u8>>,
+        predicate: impl Fn(Option<&[u8]>) -> bool,
+        timeout: Option<Duration>,
+    ) -> Result<Option<Vec<u8>>> {
+        let wait = self.wait_value_inner(key.into(), predicate);
+        match timeout {
+            Some(timeout) => tokio::time::timeout(timeout, wait)
+                .await
+                .unwrap_or(Err(XlineClientError::Timeout)),
+            None => wait.await,
+        }
+    }
+
+    /// Wait until the value of the key satisfies the `predicate` without a timeout
+    async fn wait_value_inner(
+        &self,
+        key: Vec<u8>,
+        predicate: impl Fn(Option<&[u8]>) -> bool,
+    ) -> Result<Option<Vec<u8>>> {
+        let resp = self.range(RangeRequest::new(key.clone())).await?;
+        let revision = resp.header.as_ref().map_or(0, |h| h.revision);
+        let value = resp.kvs.into_iter().next().map(|kv| kv.value);
+        // the value may already match, in which case no event would come
+        if predicate(value.as_deref()) {
+            return Ok(value);
+        }
+
+        let mut watch_client = self.watch_client.clone();
+        let (mut watcher, mut stream) = watch_client
+            .watch(WatchRequest::new(key).with_start_revision(revision.overflow_add(1)))
+            .await?;
+        let result = loop {
+            let Some(resp) = stream.message().await? else {
+                break Err(XlineClientError::WatchError(
+                    "watch stream closed before the value matches".to_owned(),
+                ));
+            };
+            if resp.canceled {
+                break Err(XlineClientError::WatchError(format!(
+                    "watch canceled before the value matches: {}",
+                    resp.cancel_reason
+                )));
+            }
+            let matched = resp.events.into_iter().find_map(|event| {
+                let value = (event.r#type() == EventType::Put)
+                    .then(|| event.kv.map(|kv| kv.value))
+                    .flatten();
+                predicate(value.as_deref()).then_some(value)
+            });
+            if let Some(value) = matched {
+                break Ok(value);
+            }
+        };
+        // the stream may already be closed, in which case there is nothing to cancel
+        let _ig = watcher.cancel();
+        result
+    }
+
+    /// Read all keys with the prefix and watch the prefix from the next revision of the read,
+    /// which is useful to initialize a cache consistently. The watch starts right after the
+    /// revision of the snapshot, so every change after the snapshot is delivered exactly once
+    /// by the watch, and no change before it is delivered again.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the range or the watch creation fails
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use xline_client::{Client, ClientOptions};
+    /// use anyhow::Result;
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<()> {
+    ///     let curp_members = ["10.0.0.1:2379", "10.0.0.2:2379", "10.0.0.3:2379"];
+    ///
+    ///     let client = Client::connect(curp_members, ClientOptions::default())
+    ///         .await?
+    ///         .kv_client();
+    ///
+    ///     let (snapshot, _watcher, mut stream) = client.range_and_watch("job/").await?;
+    ///     println!("{} jobs at start", snapshot.kvs.len());
+    ///     while let Some(resp) = stream.message().await? {
+    ///         println!("{} jobs changed", resp.events.len());
+    ///     }
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    #[inline]
+    pub async fn range_and_watch(
+        &self,
+        prefix: impl Into<Vec<u8>>,
+    ) -> Result<(RangeResponse, Watcher, WatchStreaming)> {
+        let prefix = prefix.into();
+        let snapshot = self
+            .range(RangeRequest::new(prefix.clone()).with_prefix())
+            .await?;
+        let revision = snapshot.header.as_ref().map_or(0, |h| h.revision);
+        let mut watch_client = self.watch_client.clone();
+        let (watcher, stream) = watch_client
+            .watch(
+                WatchRequest::new(prefix)
+                    .with_prefix()
+                    .with_start_revision(revision.overflow_add(1)),
+            )
+            .await?;
+        Ok((snapshot, watcher, stream))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use xlineapi::ResponseHeader;
+
+    use super::*;
+
+    #[test]
+    fn history_should_stop_at_to_rev() {
+        let event = |r#type: EventType, revision: i64, value: &str| xlineapi::Event {
+            r#type: r#type.into(),
+            kv: Some(xlineapi::KeyValue {
+                key: b"key".to_vec(),
+                value: value.into(),
+                mod_revision: revision,
+                ..xlineapi::KeyValue::default()
+            }),
+            prev_kv: None,
+        };
+        let mut history = Vec::new();
+        let resp = WatchResponse {
+            events: vec![
+                event(EventType::Put, 2, "v1"),
+                event(EventType::Delete, 3, ""),
+            ],
+            ..WatchResponse::default()
+        };
+        assert!(!KvClient::collect_history(&mut history, &resp, 1, 5).unwrap());
+        assert_eq!(history, vec![(2, Some(b"v1".to_vec())), (3, None)]);
+
+        // a progress response after the events ends the history
+        let progress = WatchResponse {
+            header: Some(ResponseHeader {
+                revision: 6,
+                ..ResponseHeader::default()
+            }),
+            watch_id: -1,
+            ..WatchResponse::default()
+        };
+        assert!(KvClient::collect_history(&mut history, &progress, 1, 5).unwrap());
+
+        // events after to_rev are not in the history
+        let resp = WatchResponse {
+            events: vec![
+                event(EventType::Put, 4, "v2"),
+                event(EventType::Put, 6, "v3"),
+            ],
+            ..WatchResponse::default()
+        };
+        assert!(KvClient::collect_history(&mut history, &resp, 1, 5).unwrap());
+        assert_eq!(history.last(), Some(&(4, Some(b"v2".to_vec()))));
+
+        let compacted = WatchResponse {
+            canceled: true,
+            compact_revision: 3,
+            ..WatchResponse::default()
+        };
+        assert!(KvClient::collect_history(&mut history, &compacted, 1, 5).is_err());
+    }
+}
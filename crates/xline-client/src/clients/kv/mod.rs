@@ -0,0 +1,1043 @@
+use std::{
+    fmt::Debug,
+    sync::{
+        atomic::{AtomicI64, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+#[cfg(not(madsim))]
+use tonic::transport::ClientTlsConfig;
+use tonic::{metadata::MetadataValue, transport::Channel};
+#[cfg(madsim)]
+use utils::ClientTlsConfig;
+use xlineapi::{
+    command::{Command, CommandResponse, SyncResponse},
+    execute_error::ExecuteError,
+    CompactionResponse, DeleteRangeResponse, LeaseGrantResponse, PutResponse, RangeResponse,
+    RequestOp, RequestWrapper, ResponseHeader, ResponseOp, ResponseWrapper, TxnResponse,
+    COMPACTED_REVISION_KEY, RESPECT_WATCHERS_KEY,
+};
+
+use crate::{
+    clients::{OrderedProposeQueue, WatchClient},
+    coalesce::WriteCoalescer,
+    degrade::DegradeState,
+    error::{Result, XlineClientError},
+    key_codec::KeyCodec,
+    latency::LatencyTracker,
+    lease_gen::LeaseIdGenerator,
+    lease_pool::LeasePool,
+    member_channels::MemberChannels,
+    response_cache::ResponseCache,
+    speculative::SpeculativeWrites,
+    types::kv::{
+        CompactionRequest, Compare, Consistency, DeleteRangeRequest, PutRequest, RangeRequest,
+        ReadSelectionPolicy, TxnOp, TxnRequest,
+    },
+    AuthService, CurpClient,
+};
+
+/// Reads of the history of keys and waits on their changes
+mod history;
+/// Reads of the serializable, bounded staleness and quorum consistencies
+mod reads;
+/// Conditional writes and counters built on transactions
+mod recipes;
+/// Reads served by chosen members of the cluster
+mod replicas;
+
+/// Timeout of reading from one member in a quorum read
+const QUORUM_READ_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Client for KV operations.
+#[derive(Clone)]
+pub struct KvClient {
+    /// The client running the CURP protocol, communicate with all servers.
+    curp_client: Arc<CurpClient>,
+    /// The lease RPC client, only communicate with one server at a time
+    #[cfg(not(madsim))]
+    kv_client: xlineapi::KvClient<AuthService<Channel>>,
+    /// The lease RPC client, only communicate with one server at a time
+    #[cfg(madsim)]
+    kv_client: xlineapi::KvClient<Channel>,
+    /// The auth token
+    token: Option<String>,
+    /// Limits of the key and value size of puts
+    size_limits: SizeLimits,
+    /// The latest revision of the leader observed from responses of the CURP client
+    leader_revision: Arc<AtomicI64>,
+    /// Lease id generator
+    id_gen: Arc<LeaseIdGenerator>,
+    /// Leases shared by keys put with a ttl
+    lease_pool: Arc<LeasePool>,
+    /// Client tls config, used to connect to members in quorum reads
+    tls_config: Option<ClientTlsConfig>,
+    /// Channels to members serving the reads sent to a specific member
+    member_channels: Arc<MemberChannels>,
+    /// Whether to write the freshest value back if a quorum read finds stale members
+    read_repair: bool,
+    /// The watch client, used to read the history of keys
+    watch_client: WatchClient,
+    /// Policy selecting the server which serves serializable reads
+    read_selection: ReadSelectionPolicy,
+    /// EWMA latencies of members measured by serializable reads
+    latency: Arc<LatencyTracker>,
+    /// Tracks the sustained quorum loss, writes fail fast in the read-only mode
+    degrade: Arc<DegradeState>,
+    /// Cache of serializable reads with the stale-while-revalidate policy, no cache if not set
+    response_cache: Option<Arc<ResponseCache<RangeCacheKey, RangeResponse>>>,
+    /// Values of puts in flight served to reads of this client, no speculation if not set
+    speculative_writes: Option<Arc<SpeculativeWrites>>,
+    /// Codec of keys applied before sending them to the cluster, no codec if not set
+    key_codec: Option<KeyCodec>,
+    /// Coalescer of puts of the same key within a window, no coalescing if not set
+    coalescer: Option<Arc<WriteCoalescer>>,
+}
+
+/// Limits of the key and value size, checked before sending a put to the cluster
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) struct SizeLimits {
+    /// Max key size in bytes
+    max_key_size: Option<usize>,
+    /// Max value size in bytes
+    max_value_size: Option<usize>,
+}
+
+impl SizeLimits {
+    /// New `SizeLimits`
+    pub(crate) fn new(max_key_size: Option<usize>, max_value_size: Option<usize>) -> Self {
+        Self {
+            max_key_size,
+            max_value_size,
+        }
+    }
+
+    /// Check whether the key and value are within the limits
+    fn check(&self, key: &[u8], value: &[u8]) -> Result<()> {
+        if let Some(limit) = self.max_key_size {
+            if key.len() > limit {
+                return Err(XlineClientError::KeyTooLarge(key.len(), limit));
+            }
+        }
+        if let Some(limit) = self.max_value_size {
+            if value.len() > limit {
+                return Err(XlineClientError::ValueTooLarge(value.len(), limit));
+            }
+        }
+        Ok(())
+    }
+
+    /// Check whether the keys and values of all puts of a txn, including the puts of its nested
+    /// txns, are within the limits
+    fn check_txn(&self, txn: &xlineapi::TxnRequest) -> Result<()> {
+        for op in txn.success.iter().chain(txn.failure.iter()) {
+            if let Some(xlineapi::Request::RequestPut(ref put)) = op.request {
+                self.check(&put.key, &put.value)?;
+            }
+            if let Some(xlineapi::Request::RequestTxn(ref nested)) = op.request {
+                self.check_txn(nested)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Key of a cached range response, which consists of all fields of the range request
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+struct RangeCacheKey {
+    /// The first key of the range
+    key: Vec<u8>,
+    /// The end of the range
+    range_end: Vec<u8>,
+    /// Max number of keys returned
+    limit: i64,
+    /// The revision to read at
+    revision: i64,
+    /// Order of sorting
+    sort_order: i32,
+    /// Field to sort by
+    sort_target: i32,
+    /// Whether to return only the keys
+    keys_only: bool,
+    /// Whether to return only the count
+    count_only: bool,
+    /// Lower bound of the mod revision of keys
+    min_mod_revision: i64,
+    /// Upper bound of the mod revision of keys
+    max_mod_revision: i64,
+    /// Lower bound of the create revision of keys
+    min_create_revision: i64,
+    /// Upper bound of the create revision of keys
+    max_create_revision: i64,
+}
+
+impl From<&xlineapi::RangeRequest> for RangeCacheKey {
+    #[inline]
+    fn from(req: &xlineapi::RangeRequest) -> Self {
+        Self {
+            key: req.key.clone(),
+            range_end: req.range_end.clone(),
+            limit: req.limit,
+            revision: req.revision,
+            sort_order: req.sort_order,
+            sort_target: req.sort_target,
+            keys_only: req.keys_only,
+            count_only: req.count_only,
+            min_mod_revision: req.min_mod_revision,
+            max_mod_revision: req.max_mod_revision,
+            min_create_revision: req.min_create_revision,
+            max_create_revision: req.max_create_revision,
+        }
+    }
+}
+
+impl Debug for KvClient {
+    #[inline]
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("KvClient")
+            .field("kv_client", &self.kv_client)
+            .field("kv_client", &self.kv_client)
+            .field("token", &self.token)
+            .field("size_limits", &self.size_limits)
+            .field("leader_revision", &self.leader_revision)
+            .field("lease_pool", &self.lease_pool)
+            .field("tls_config", &self.tls_config)
+            .field("member_channels", &self.member_channels)
+            .field("read_repair", &self.read_repair)
+            .field("watch_client", &self.watch_client)
+            .field("read_selection", &self.read_selection)
+            .field("latency", &self.latency)
+            .field("degrade", &self.degrade)
+            .field("response_cache", &self.response_cache)
+            .field("speculative_writes", &self.speculative_writes)
+            .field("key_codec", &self.key_codec)
+            .finish()
+    }
+}
+
+#[allow(clippy::multiple_inherent_impl)] // the operations of the client are split by feature
+impl KvClient {
+    /// New `KvClient`
+    #[inline]
+    pub(crate) fn new(
+        curp_client: Arc<CurpClient>,
+        channel: Channel,
+        token: Option<String>,
+        id_gen: Arc<LeaseIdGenerator>,
+    ) -> Self {
+        Self {
+            curp_client,
+            kv_client: xlineapi::KvClient::new(AuthService::new(
+                channel.clone(),
+                token.as_ref().and_then(|t| t.parse().ok().map(Arc::new)),
+            )),
+            watch_client: WatchClient::new(channel, token.clone()),
+            token,
+            size_limits: SizeLimits::default(),
+            leader_revision: Arc::new(AtomicI64::new(0)),
+            id_gen,
+            lease_pool: Arc::new(LeasePool::default()),
+            tls_config: None,
+            member_channels: Arc::new(MemberChannels::new(QUORUM_READ_TIMEOUT)),
+            read_repair: false,
+            read_selection: ReadSelectionPolicy::default(),
+            latency: Arc::new(LatencyTracker::default()),
+            degrade: Arc::new(DegradeState::default()),
+            response_cache: None,
+            speculative_writes: None,
+            key_codec: None,
+            coalescer: None,
+        }
+    }
+
+    /// Set the limits of the key and value size
+    #[inline]
+    pub(crate) fn with_size_limits(self, size_limits: SizeLimits) -> Self {
+        Self {
+            size_limits,
+            ..self
+        }
+    }
+
+    /// Set the tls config and whether to repair stale members of quorum reads
+    #[inline]
+    pub(crate) fn with_quorum_read(
+        self,
+        tls_config: Option<ClientTlsConfig>,
+        read_repair: bool,
+    ) -> Self {
+        Self {
+            tls_config,
+            read_repair,
+            ..self
+        }
+    }
+
+    /// Set the policy selecting the server which serves serializable reads
+    #[inline]
+    pub(crate) fn with_read_selection(self, read_selection: ReadSelectionPolicy) -> Self {
+        Self {
+            read_selection,
+            ..self
+        }
+    }
+
+    /// Set the state of the degraded mode shared with the `Client`
+    #[inline]
+    pub(crate) fn with_degrade_state(self, degrade: Arc<DegradeState>) -> Self {
+        Self { degrade, ..self }
+    }
+
+    /// Enable the cache of serializable reads with the stale-while-revalidate window
+    #[inline]
+    pub(crate) fn with_stale_while_revalidate(self, window: Option<Duration>) -> Self {
+        Self {
+            response_cache: window.map(|w| Arc::new(ResponseCache::new(w))),
+            ..self
+        }
+    }
+
+    /// Set whether to serve the values of puts in flight to reads of this client
+    #[inline]
+    pub(crate) fn with_speculative_local_reads(self, enabled: bool) -> Self {
+        Self {
+            speculative_writes: enabled.then(|| Arc::new(SpeculativeWrites::default())),
+            ..self
+        }
+    }
+
+    /// Coalesce the puts of the same key within the window into the latest one
+    #[inline]
+    pub(crate) fn with_coalesce_same_key(self, window: Option<Duration>) -> Self {
+        Self {
+            coalescer: window.map(|w| Arc::new(WriteCoalescer::new(w))),
+            ..self
+        }
+    }
+
+    /// Set the codec of keys, which is applied to the watches of this client as well
+    #[inline]
+    pub(crate) fn with_key_codec(self, key_codec: Option<KeyCodec>) -> Self {
+        Self {
+            watch_client: self.watch_client.clone().with_key_codec(key_codec),
+            key_codec,
+            ..self
+        }
+    }
+
+    /// Put a key-value into the store. If `ClientOptions::with_coalesce_same_key` is set, the
+    /// put is proposed after the window, and earlier puts of the same key with identical
+    /// options within the window are superseded by it and resolve with its result.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the inner CURP client encountered a propose failure,
+    /// or the key or value exceeds the size limits set in `ClientOptions`
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use xline_client::{types::kv::PutRequest, Client, ClientOptions};
+    /// use anyhow::Result;
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<()> {
+    ///     let curp_members = ["10.0.0.1:2379", "10.0.0.2:2379", "10.0.0.3:2379"];
+    ///
+    ///     let mut client = Client::connect(curp_members, ClientOptions::default())
+    ///         .await?
+    ///         .kv_client();
+    ///
+    ///     client.put(PutRequest::new("key1", "value1")).await?;
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    #[inline]
+    pub async fn put(&self, request: PutRequest) -> Result<PutResponse> {
+        self.size_limits.check(request.key(), request.value())?;
+        if let Some(ref coalescer) = self.coalescer {
+            let client = self.clone();
+            return coalescer
+                .put(request, move |latest| {
+                    let client = client.clone();
+                    async move { client.put_uncoalesced(latest).await }
+                })
+                .await;
+        }
+        self.put_uncoalesced(request).await
+    }
+
+    /// Put a key-value without coalescing, served speculatively if enabled
+    async fn put_uncoalesced(&self, request: PutRequest) -> Result<PutResponse> {
+        match self.speculative_writes {
+            Some(ref writes) if !request.ignore_value() => {
+                let (key, value) = (request.key().to_vec(), request.value().to_vec());
+                writes
+                    .speculate(key, value, self.propose_put(request))
+                    .await
+            }
+            Some(_) | None => self.propose_put(request).await,
+        }
+    }
+
+    /// Propose a put through the CURP client
+    async fn propose_put(&self, request: PutRequest) -> Result<PutResponse> {
+        let delta_encode = request.delta_encode();
+        let request = RequestWrapper::from(xlineapi::PutRequest::from(request));
+        let cmd = self.command(request)?.with_delta_encode(delta_encode);
+        let (cmd_res, _sync_res) = self.propose_write(&cmd, true).await??;
+        let resp: PutResponse = self.response(cmd_res).into();
+        self.observe_leader_revision(resp.header.as_ref());
+        Ok(resp)
+    }
+
+    /// Put a key-value that expires after at least `ttl`, without managing a lease. Keys with
+    /// similar ttl share a pooled lease, which is granted on demand and expires together with
+    /// all keys attached to it.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the inner CURP client encountered a propose failure,
+    /// or the key or value exceeds the size limits set in `ClientOptions`
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use std::time::Duration;
+    ///
+    /// use xline_client::{Client, ClientOptions};
+    /// use anyhow::Result;
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<()> {
+    ///     let curp_members = ["10.0.0.1:2379", "10.0.0.2:2379", "10.0.0.3:2379"];
+    ///
+    ///     let client = Client::connect(curp_members, ClientOptions::default())
+    ///         .await?
+    ///         .kv_client();
+    ///
+    ///     client
+    ///         .put_with_ttl("key1", "value1", Duration::from_secs(10))
+    ///         .await?;
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    #[inline]
+    pub async fn put_with_ttl(
+        &self,
+        key: impl Into<Vec<u8>>,
+        value: impl Into<Vec<u8>>,
+        ttl: Duration,
+    ) -> Result<PutResponse> {
+        let (key, value) = (key.into(), value.into());
+        self.size_limits.check(&key, &value)?;
+        let lease_id = match self.lease_pool.get(ttl, Instant::now()) {
+            Some(id) => id,
+            None => self.grant_pooled_lease(ttl).await?,
+        };
+        self.put(PutRequest::new(key, value).with_lease(lease_id))
+            .await
+    }
+
+    /// Grant a new lease for the ttl bucket of `ttl` and add it to the pool
+    async fn grant_pooled_lease(&self, ttl: Duration) -> Result<i64> {
+        let granted_at = Instant::now();
+        let request = RequestWrapper::from(xlineapi::LeaseGrantRequest {
+            ttl: LeasePool::grant_ttl(ttl),
+            id: self.id_gen.next(),
+        });
+        let cmd = Command::new(request.keys(), request);
+        let (cmd_res, _sync_res) = self.propose_write(&cmd, true).await??;
+        let resp: LeaseGrantResponse = cmd_res.into_inner().into();
+        self.lease_pool.insert(ttl, resp.id, granted_at);
+        Ok(resp.id)
+    }
+
+    /// Get a range of keys from the store
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the inner CURP client encountered a propose failure
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use xline_client::{types::kv::RangeRequest, Client, ClientOptions};
+    /// use anyhow::Result;
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<()> {
+    ///     let curp_members = ["10.0.0.1:2379", "10.0.0.2:2379", "10.0.0.3:2379"];
+    ///
+    ///     let mut client = Client::connect(curp_members, ClientOptions::default())
+    ///         .await?
+    ///         .kv_client();
+    ///
+    ///     let resp = client.range(RangeRequest::new("key1")).await?;
+    ///
+    ///     if let Some(kv) = resp.kvs.first() {
+    ///         println!(
+    ///             "got key: {}, value: {}",
+    ///             String::from_utf8_lossy(&kv.key),
+    ///             String::from_utf8_lossy(&kv.value)
+    ///         );
+    ///     }
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    #[inline]
+    pub async fn range(&self, request: RangeRequest) -> Result<RangeResponse> {
+        if let Some(resp) = self.speculative_range(&request) {
+            return Ok(resp);
+        }
+        let projection = request.projection();
+        let mut resp = self
+            .linearizable_range(xlineapi::RangeRequest::from(request))
+            .await?;
+        projection.apply(&mut resp);
+        Ok(resp)
+    }
+
+    /// Starts a fluent range request of the key, which is sent by `GetBuilder::send`. The
+    /// options of the request and its consistency can be composed in any order.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use xline_client::{types::kv::Consistency, Client, ClientOptions};
+    /// use anyhow::Result;
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<()> {
+    ///     let curp_members = ["10.0.0.1:2379", "10.0.0.2:2379", "10.0.0.3:2379"];
+    ///
+    ///     let client = Client::connect(curp_members, ClientOptions::default())
+    ///         .await?
+    ///         .kv_client();
+    ///
+    ///     let resp = client
+    ///         .get("job/")
+    ///         .with_prefix()
+    ///         .consistency(Consistency::Serializable)
+    ///         .count_only()
+    ///         .send()
+    ///         .await?;
+    ///     println!("jobs: {}", resp.count);
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    #[inline]
+    pub fn get(&self, key: impl Into<Vec<u8>>) -> GetBuilder<'_> {
+        GetBuilder {
+            client: self,
+            request: RangeRequest::new(key),
+            consistency: Consistency::default(),
+        }
+    }
+
+    /// Propose a write through the CURP client, which is tracked by the degraded mode, see
+    /// `DegradeState::track`
+    async fn propose_write(
+        &self,
+        cmd: &Command,
+        use_fast_path: bool,
+    ) -> Result<std::result::Result<(CommandResponse, Option<SyncResponse>), ExecuteError>> {
+        let res = self
+            .degrade
+            .propose_write(&*self.curp_client, cmd, self.token.as_ref(), use_fast_path)
+            .await;
+        if let Some(ref cache) = self.response_cache {
+            cache.clear();
+        }
+        res
+    }
+
+    /// Build the command of a request, whose keys are encoded by the key codec
+    fn command(&self, request: RequestWrapper) -> Result<Command> {
+        let mut request = request;
+        if let Some(codec) = self.key_codec {
+            codec.encode_request(&mut request)?;
+        }
+        Ok(Command::new(request.keys(), request))
+    }
+
+    /// Take the response of a command, whose keys are decoded by the key codec
+    fn response(&self, cmd_res: CommandResponse) -> ResponseWrapper {
+        let mut resp = cmd_res.into_inner();
+        if let Some(codec) = self.key_codec {
+            codec.decode_response(&mut resp);
+        }
+        resp
+    }
+
+    /// Send the range request through the CURP client, which is executed by the leader
+    async fn leader_range(&self, request: xlineapi::RangeRequest) -> Result<RangeResponse> {
+        let request = RequestWrapper::from(request);
+        let cmd = self.command(request)?;
+        let (cmd_res, _sync_res) = self
+            .curp_client
+            .propose(&cmd, self.token.as_ref(), true)
+            .await??;
+        let resp: RangeResponse = self.response(cmd_res).into();
+        self.observe_leader_revision(resp.header.as_ref());
+        Ok(resp)
+    }
+
+    /// Record the revision of a response executed by the leader
+    fn observe_leader_revision(&self, header: Option<&ResponseHeader>) {
+        if let Some(header) = header {
+            let _prev = self
+                .leader_revision
+                .fetch_max(header.revision, Ordering::Relaxed);
+        }
+    }
+
+    /// Delete a range of keys from the store
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the inner CURP client encountered a propose failure
+    ///
+    /// # Examples
+    /// ```no_run
+    /// use xline_client::{types::kv::DeleteRangeRequest, Client, ClientOptions};
+    /// use anyhow::Result;
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<()> {
+    ///     let curp_members = ["10.0.0.1:2379", "10.0.0.2:2379", "10.0.0.3:2379"];
+    ///
+    ///     let mut client = Client::connect(curp_members, ClientOptions::default())
+    ///         .await?
+    ///         .kv_client();
+    ///
+    ///     client
+    ///         .delete(DeleteRangeRequest::new("key1").with_prev_kv(true))
+    ///         .await?;
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    #[inline]
+    pub async fn delete(&self, request: DeleteRangeRequest) -> Result<DeleteRangeResponse> {
+        let request = RequestWrapper::from(xlineapi::DeleteRangeRequest::from(request));
+        let cmd = self.command(request)?;
+        let (cmd_res, _sync_res) = self.propose_write(&cmd, true).await??;
+        let resp: DeleteRangeResponse = self.response(cmd_res).into();
+        self.observe_leader_revision(resp.header.as_ref());
+        Ok(resp)
+    }
+
+    /// Creates a transaction, which can provide serializable writes
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the inner CURP client encountered a propose failure
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use xline_client::{
+    ///     types::kv::{Compare, PutRequest, RangeRequest, TxnOp, TxnRequest, CompareResult},
+    ///     Client, ClientOptions,
+    /// };
+    /// use anyhow::Result;
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<()> {
+    ///     let curp_members = ["10.0.0.1:2379", "10.0.0.2:2379", "10.0.0.3:2379"];
+    ///
+    ///     let mut client = Client::connect(curp_members, ClientOptions::default())
+    ///         .await?
+    ///         .kv_client();
+    ///
+    ///     let txn_req = TxnRequest::new()
+    ///         .when(&[Compare::value("key2", CompareResult::Equal, "value2")][..])
+    ///         .and_then(
+    ///             &[TxnOp::put(
+    ///                 PutRequest::new("key2", "value3").with_prev_kv(true),
+    ///             )][..],
+    ///         )
+    ///         .or_else(&[TxnOp::range(RangeRequest::new("key2"))][..]);
+    ///
+    ///     let _resp = client.txn(txn_req).await?;
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    #[inline]
+    pub async fn txn(&self, request: TxnRequest) -> Result<TxnResponse> {
+        let request = xlineapi::TxnRequest::from(request);
+        self.size_limits.check_txn(&request)?;
+        let request = RequestWrapper::from(request);
+        let cmd = self.command(request)?;
+        let (cmd_res, Some(sync_res)) = self.propose_write(&cmd, false).await?? else {
+            unreachable!("sync_res is always Some when use_fast_path is false");
+        };
+        let mut res_wrapper = self.response(cmd_res);
+        res_wrapper.update_revision(sync_res.revision());
+        let resp: TxnResponse = res_wrapper.into();
+        self.observe_leader_revision(resp.header.as_ref());
+        Ok(resp)
+    }
+
+    /// Open a client-side queue of operations which are proposed one after another in the exact
+    /// order they are pushed, for workloads requiring a strict total order of the writes of this
+    /// client. See [`OrderedProposeQueue`] for details.
+    #[inline]
+    #[must_use]
+    pub fn ordered_propose_queue(&self) -> OrderedProposeQueue {
+        OrderedProposeQueue::new(self.clone())
+    }
+
+    /// Propose an operation of an ordered propose queue through the slow path, so that it is
+    /// synced before the next operation is proposed
+    pub(crate) async fn propose_op(&self, op: xlineapi::Request) -> Result<ResponseOp> {
+        if let xlineapi::Request::RequestPut(ref put) = op {
+            self.size_limits.check(&put.key, &put.value)?;
+        }
+        let cmd = self.command(RequestWrapper::from(RequestOp { request: Some(op) }))?;
+        let (cmd_res, sync_res) = self.propose_write(&cmd, false).await??;
+        let mut res_wrapper = self.response(cmd_res);
+        if let Some(sync_res) = sync_res {
+            res_wrapper.update_revision(sync_res.revision());
+        }
+        Ok(res_wrapper.into())
+    }
+
+    /// Starts a transaction scope, which accumulates compares and operations and writes
+    /// nothing until it is committed. Dropping the scope without committing discards them.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use xline_client::{
+    ///     types::kv::{Compare, CompareResult, PutRequest},
+    ///     Client, ClientOptions,
+    /// };
+    /// use anyhow::Result;
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<()> {
+    ///     let curp_members = ["10.0.0.1:2379", "10.0.0.2:2379", "10.0.0.3:2379"];
+    ///
+    ///     let client = Client::connect(curp_members, ClientOptions::default())
+    ///         .await?
+    ///         .kv_client();
+    ///
+    ///     let mut scope = client.scope();
+    ///     scope
+    ///         .when(Compare::version("key1", CompareResult::Equal, 0))
+    ///         .put(PutRequest::new("key1", "value1"));
+    ///     let _resp = scope.commit().await?;
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    #[inline]
+    #[must_use]
+    pub fn scope(&self) -> TxnScope<'_> {
+        TxnScope {
+            client: self,
+            compares: Vec::new(),
+            success: Vec::new(),
+            failure: Vec::new(),
+        }
+    }
+
+    /// Compacts the key-value store up to a given revision.
+    /// All keys with revisions less than the given revision will be compacted.
+    /// The compaction process will remove all historical versions of these keys, except for the most recent one.
+    /// For example, here is a revision list: [(A, 1), (A, 2), (A, 3), (A, 4), (A, 5)].
+    /// We compact at revision 3. After the compaction, the revision list will become [(A, 3), (A, 4), (A, 5)].
+    /// All revisions less than 3 are deleted. The latest revision, 3, will be kept.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the inner CURP client encountered a propose failure
+    ///
+    /// # Examples
+    ///
+    ///```no_run
+    /// use xline_client::{
+    ///     types::kv::{CompactionRequest, PutRequest},
+    ///     Client, ClientOptions,
+    /// };
+    /// use anyhow::Result;
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<()> {
+    ///     let curp_members = ["10.0.0.1:2379", "10.0.0.2:2379", "10.0.0.3:2379"];
+    ///
+    ///     let client = Client::connect(curp_members, ClientOptions::default())
+    ///         .await?
+    ///         .kv_client();
+    ///
+    ///     let resp_put = client.put(PutRequest::new("key", "val")).await?;
+    ///     let rev = resp_put.header.unwrap().revision;
+    ///
+    ///     let _resp = client.compact(CompactionRequest::new(rev)).await?;
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    #[inline]
+    pub async fn compact(&self, request: CompactionRequest) -> Result<CompactionResponse> {
+        self.compact_with_revision(request)
+            .await
+            .map(|(resp, _revision)| resp)
+    }
+
+    /// Compacts the key-value store like [`KvClient::compact`], and returns the revision
+    /// actually compacted to as well. It is lower than the requested revision only if
+    /// `respect_watchers` of the request is set and an active watcher on the server watches
+    /// from a lower revision.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the inner CURP client encountered a propose failure
+    ///
+    /// # Examples
+    ///
+    ///```no_run
+    /// use xline_client::{types::kv::CompactionRequest, Client, ClientOptions};
+    /// use anyhow::Result;
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<()> {
+    ///     let curp_members = ["10.0.0.1:2379", "10.0.0.2:2379", "10.0.0.3:2379"];
+    ///
+    ///     let client = Client::connect(curp_members, ClientOptions::default())
+    ///         .await?
+    ///         .kv_client();
+    ///
+    ///     let (_resp, revision) = client
+    ///         .compact_with_revision(CompactionRequest::new(100).with_respect_watchers(true))
+    ///         .await?;
+    ///     println!("compacted to revision {revision}");
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    #[inline]
+    pub async fn compact_with_revision(
+        &self,
+        request: CompactionRequest,
+    ) -> Result<(CompactionResponse, i64)> {
+        let revision = request.revision();
+        if request.respect_watchers() {
+            // only the server knows its watchers, so the request is not proposed directly
+            let mut req = tonic::Request::new(xlineapi::CompactionRequest::from(request));
+            let _prev = req
+                .metadata_mut()
+                .insert(RESPECT_WATCHERS_KEY, MetadataValue::from_static("true"));
+            let mut kv_client = self.kv_client.clone();
+            let resp = kv_client.compact(req).await?;
+            let revision = resp
+                .metadata()
+                .get(COMPACTED_REVISION_KEY)
+                .and_then(|v| v.to_str().ok())
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(revision);
+            return Ok((resp.into_inner(), revision));
+        }
+        if request.physical() {
+            let mut kv_client = self.kv_client.clone();
+            let resp = kv_client
+                .compact(xlineapi::CompactionRequest::from(request))
+                .await?;
+            return Ok((resp.into_inner(), revision));
+        }
+        let request = RequestWrapper::from(xlineapi::CompactionRequest::from(request));
+        let cmd = Command::new(request.keys(), request);
+        let (cmd_res, _sync_res) = self.propose_write(&cmd, true).await??;
+        Ok((cmd_res.into_inner().into(), revision))
+    }
+}
+
+/// A transaction scope of a `KvClient`, the accumulated compares and operations are committed
+/// atomically in one transaction by `commit`. Nothing is sent to the server before that, so
+/// dropping the scope without committing discards all of them.
+#[derive(Debug)]
+#[must_use = "the operations are discarded if the scope is not committed"]
+pub struct TxnScope<'a> {
+    /// The client to commit the transaction
+    client: &'a KvClient,
+    /// Compares of the transaction
+    compares: Vec<Compare>,
+    /// Operations executed if all compares succeed
+    success: Vec<TxnOp>,
+    /// Operations executed if any compare fails
+    failure: Vec<TxnOp>,
+}
+
+impl TxnScope<'_> {
+    /// Adds a compare, the success operations are executed only if all compares succeed.
+    #[inline]
+    pub fn when(&mut self, compare: Compare) -> &mut Self {
+        self.compares.push(compare);
+        self
+    }
+
+    /// Adds a put operation executed if all compares succeed.
+    #[inline]
+    pub fn put(&mut self, request: PutRequest) -> &mut Self {
+        self.success.push(TxnOp::put(request));
+        self
+    }
+
+    /// Adds a delete operation executed if all compares succeed.
+    #[inline]
+    pub fn delete(&mut self, request: DeleteRangeRequest) -> &mut Self {
+        self.success.push(TxnOp::delete(request));
+        self
+    }
+
+    /// Adds an operation executed if all compares succeed.
+    #[inline]
+    pub fn then(&mut self, op: TxnOp) -> &mut Self {
+        self.success.push(op);
+        self
+    }
+
+    /// Adds an operation executed if any compare fails.
+    #[inline]
+    pub fn or_else(&mut self, op: TxnOp) -> &mut Self {
+        self.failure.push(op);
+        self
+    }
+
+    /// Returns true if no operations are accumulated.
+    #[inline]
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.success.is_empty() && self.failure.is_empty()
+    }
+
+    /// Commits the accumulated compares and operations atomically in one transaction.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the inner CURP client encountered a propose failure
+    #[inline]
+    pub async fn commit(self) -> Result<TxnResponse> {
+        let request = TxnRequest::new()
+            .when(self.compares)
+            .and_then(self.success)
+            .or_else(self.failure);
+        self.client.txn(request).await
+    }
+
+    /// Discards the accumulated compares and operations. Nothing is sent until `commit`, so
+    /// this is the same as dropping the scope.
+    #[inline]
+    pub fn discard(self) {
+        drop(self);
+    }
+}
+
+/// A fluent range request of a `KvClient`, nothing is sent to the server until `send`
+#[derive(Debug)]
+#[must_use = "the request is not sent until `send` is called"]
+pub struct GetBuilder<'a> {
+    /// The client to send the request
+    client: &'a KvClient,
+    /// The request
+    request: RangeRequest,
+    /// Consistency of the read, linearizable by default
+    consistency: Consistency,
+}
+
+impl GetBuilder<'_> {
+    /// Sets the consistency of the read.
+    #[inline]
+    pub fn consistency(mut self, consistency: Consistency) -> Self {
+        self.consistency = consistency;
+        self
+    }
+
+    /// Gets all keys prefixed with the key.
+    #[inline]
+    pub fn with_prefix(mut self) -> Self {
+        self.request = self.request.with_prefix();
+        self
+    }
+
+    /// Gets all keys in the range `[key, range_end)`.
+    #[inline]
+    pub fn with_range_end(mut self, range_end: impl Into<Vec<u8>>) -> Self {
+        self.request = self.request.with_range_end(range_end);
+        self
+    }
+
+    /// Limits the number of keys returned.
+    #[inline]
+    pub fn with_limit(mut self, limit: i64) -> Self {
+        self.request = self.request.with_limit(limit);
+        self
+    }
+
+    /// Reads at the revision, a revision of zero or less reads the latest revision.
+    #[inline]
+    pub fn with_revision(mut self, revision: i64) -> Self {
+        self.request = self.request.with_revision(revision);
+        self
+    }
+
+    /// Returns only the keys without the values.
+    #[inline]
+    pub fn keys_only(mut self) -> Self {
+        self.request = self.request.with_keys_only(true);
+        self
+    }
+
+    /// Returns only the count of the keys.
+    #[inline]
+    pub fn count_only(mut self) -> Self {
+        self.request = self.request.with_count_only(true);
+        self
+    }
+
+    /// Sends the request, same as `KvClient::range_with_consistency`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the inner CURP client encountered a propose failure
+    #[inline]
+    pub async fn send(self) -> Result<RangeResponse> {
+        self.client
+            .range_with_consistency(self.request, self.consistency)
+            .await
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn oversized_key_should_be_rejected() {
+        let limits = SizeLimits::new(Some(4), None);
+        assert!(limits.check(b"abcd", b"value").is_ok());
+        assert!(matches!(
+            limits.check(b"abcde", b"value"),
+            Err(XlineClientError::KeyTooLarge(5, 4))
+        ));
+    }
+
+    #[test]
+    fn oversized_value_should_be_rejected() {
+        let limits = SizeLimits::new(None, Some(4));
+        assert!(limits.check(b"key", b"abcd").is_ok());
+        assert!(matches!(
+            limits.check(b"key", b"abcde"),
+            Err(XlineClientError::ValueTooLarge(5, 4))
+        ));
+    }
+}
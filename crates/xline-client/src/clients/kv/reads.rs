@@ -0,0 +1,369 @@
+use std::sync::atomic::Ordering;
+
+use curp::rpc::FetchClusterResponse;
+use xlineapi::{command::Command, RangeResponse, ResponseHeader, LEADER_REVISION_KEY};
+
+use super::{KvClient, RangeCacheKey};
+use crate::{
+    degrade::ClientMode,
+    error::{Result, XlineClientError},
+    types::kv::{Consistency, RangeRequest, ReadSelectionPolicy, SerializableRead},
+};
+
+#[allow(clippy::multiple_inherent_impl)] // the operations of the client are split by feature
+impl KvClient {
+    /// Get a range of keys from the store with the given consistency
+    ///
+    /// A [`Consistency::BoundedStaleness`] read is served by the local state of a server, and
+    /// it falls back to the leader if the revision of that server lags behind the latest
+    /// leader revision observed by this client for more than the bound.
+    ///
+    /// A [`Consistency::Quorum`] read is served by the local state of every member, and the
+    /// freshest response is returned once a majority of them respond. If read repair is enabled
+    /// in `ClientOptions`, the freshest values are written back through the leader when some
+    /// members return stale values, so that they converge.
+    ///
+    /// A [`Consistency::Serializable`] read is sent to the server selected by the
+    /// `ReadSelectionPolicy` set in `ClientOptions`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the inner CURP client encountered a propose failure,
+    /// the server serving the local read is unavailable, or less than a majority of members
+    /// respond to a quorum read
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use xline_client::{
+    ///     types::kv::{Consistency, RangeRequest},
+    ///     Client, ClientOptions,
+    /// };
+    /// use anyhow::Result;
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<()> {
+    ///     let curp_members = ["10.0.0.1:2379", "10.0.0.2:2379", "10.0.0.3:2379"];
+    ///
+    ///     let client = Client::connect(curp_members, ClientOptions::default())
+    ///         .await?
+    ///         .kv_client();
+    ///
+    ///     let resp = client
+    ///         .range_with_consistency(RangeRequest::new("key1"), Consistency::BoundedStaleness(10))
+    ///         .await?;
+    ///     println!("got {} keys", resp.kvs.len());
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    #[inline]
+    pub async fn range_with_consistency(
+        &self,
+        request: RangeRequest,
+        consistency: Consistency,
+    ) -> Result<RangeResponse> {
+        if let Some(resp) = self.speculative_range(&request) {
+            return Ok(resp);
+        }
+        let serializable = !matches!(consistency, Consistency::Linearizable);
+        let projection = request.projection();
+        let request = xlineapi::RangeRequest::from(request.with_serializable(serializable));
+        let mut resp = match consistency {
+            Consistency::BoundedStaleness(max_lag) => {
+                let (resp, hint) = self.local_range(request.clone()).await?;
+                let revision = resp.header.as_ref().map_or(0, |h| h.revision);
+                let leader_revision = self.leader_revision.load(Ordering::Relaxed).max(hint);
+                if Self::within_staleness(revision, leader_revision, max_lag) {
+                    resp
+                } else {
+                    // the fallback read is linearizable
+                    let request = xlineapi::RangeRequest {
+                        serializable: false,
+                        ..request
+                    };
+                    self.leader_range(request).await?
+                }
+            }
+            Consistency::Quorum => self.quorum_range(request).await?,
+            Consistency::Serializable => match self.response_cache {
+                Some(ref cache) => {
+                    let client = self.clone();
+                    cache
+                        .get_or_fetch(RangeCacheKey::from(&request), move || async move {
+                            client.uncached_serializable_range(request).await
+                        })
+                        .await?
+                }
+                None => self.uncached_serializable_range(request).await?,
+            },
+            Consistency::Linearizable => self.linearizable_range(request).await?,
+        };
+        projection.apply(&mut resp);
+        Ok(resp)
+    }
+
+    /// Serve a read of a single key by the value of a put of this client in flight. The value is
+    /// speculative, its revisions are unknown before the put is confirmed, so reads of ranges,
+    /// at a revision or filtered by revisions are always sent to the cluster.
+    pub(super) fn speculative_range(&self, request: &RangeRequest) -> Option<RangeResponse> {
+        let writes = self.speculative_writes.as_ref()?;
+        let req = &request.inner;
+        let plain = req.range_end.is_empty()
+            && req.revision == 0
+            && req.min_mod_revision == 0
+            && req.max_mod_revision == 0
+            && req.min_create_revision == 0
+            && req.max_create_revision == 0;
+        if !plain {
+            return None;
+        }
+        let value = writes.get(&req.key)?;
+        let kvs = if req.count_only {
+            vec![]
+        } else {
+            vec![xlineapi::KeyValue {
+                key: req.key.clone(),
+                value: if req.keys_only { vec![] } else { value },
+                ..xlineapi::KeyValue::default()
+            }]
+        };
+        let mut resp = RangeResponse {
+            header: Some(ResponseHeader::default()),
+            kvs,
+            count: 1,
+            ..RangeResponse::default()
+        };
+        request.projection().apply(&mut resp);
+        Some(resp)
+    }
+
+    /// Get a range of keys with the serializable consistency, bypassing the response cache
+    async fn uncached_serializable_range(
+        &self,
+        request: xlineapi::RangeRequest,
+    ) -> Result<RangeResponse> {
+        Ok(self.marked_serializable_range(request).await?.0)
+    }
+
+    /// Get a range of keys with the serializable consistency, together with whether it is
+    /// served while no leader is elected. During an election, the read is served by the most
+    /// caught-up member instead of failing.
+    async fn marked_serializable_range(
+        &self,
+        request: xlineapi::RangeRequest,
+    ) -> Result<(RangeResponse, bool)> {
+        // the leader may be unreachable, serve the read by an available server
+        if self.degrade.mode() == ClientMode::ReadOnly {
+            return Ok((self.local_range(request).await?.0, false));
+        }
+        if self.curp_client.cached_leader_id().await.is_none() {
+            if let Some(resp) = self.leaderless_range(&request).await {
+                return Ok((resp, true));
+            }
+        }
+        let res = if self.read_selection == ReadSelectionPolicy::SerializablePreferLeader {
+            self.selected_range(request.clone()).await
+        } else {
+            self.leader_range(request.clone()).await
+        };
+        match res {
+            Ok(resp) => Ok((resp, false)),
+            Err(err) => match self.leaderless_range(&request).await {
+                Some(resp) => Ok((resp, true)),
+                None => Err(err),
+            },
+        }
+    }
+
+    /// Send the range request through the CURP client, and fail with
+    /// `XlineClientError::LeaderElection` if it fails while no leader is elected
+    pub(super) async fn linearizable_range(
+        &self,
+        request: xlineapi::RangeRequest,
+    ) -> Result<RangeResponse> {
+        match self.leader_range(request).await {
+            Ok(resp) => Ok(resp),
+            Err(err) => {
+                let cluster = self.curp_client.fetch_cluster(false).await.ok();
+                Err(Self::linearizable_error(err, cluster.as_ref()))
+            }
+        }
+    }
+
+    /// Get the error of a failed linearizable read by the cluster state fetched after it
+    fn linearizable_error(
+        err: XlineClientError<Command>,
+        cluster: Option<&FetchClusterResponse>,
+    ) -> XlineClientError<Command> {
+        if cluster.and_then(Self::leaderless_members).is_some() {
+            XlineClientError::LeaderElection
+        } else {
+            err
+        }
+    }
+
+    /// Get a range of keys with the [`Consistency::Serializable`] consistency, together with
+    /// whether it is served while no leader is elected. During a leaderless window, e.g. an
+    /// election, linearizable reads fail with `XlineClientError::LeaderElection`, while this
+    /// read is served by the most caught-up member and marked as potentially stale.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if neither the cluster nor any member serves the read
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use xline_client::{types::kv::RangeRequest, Client, ClientOptions};
+    /// use anyhow::Result;
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<()> {
+    ///     let curp_members = ["10.0.0.1:2379", "10.0.0.2:2379", "10.0.0.3:2379"];
+    ///
+    ///     let client = Client::connect(curp_members, ClientOptions::default())
+    ///         .await?
+    ///         .kv_client();
+    ///
+    ///     let read = client.serializable_read(RangeRequest::new("key1")).await?;
+    ///     if read.leaderless {
+    ///         println!("no leader is elected, the read may be stale");
+    ///     }
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    #[inline]
+    pub async fn serializable_read(&self, request: RangeRequest) -> Result<SerializableRead> {
+        if let Some(response) = self.speculative_range(&request) {
+            return Ok(SerializableRead {
+                response,
+                leaderless: false,
+            });
+        }
+        let projection = request.projection();
+        let request = xlineapi::RangeRequest::from(request.with_serializable(true));
+        let (mut response, leaderless) = self.marked_serializable_range(request).await?;
+        projection.apply(&mut response);
+        Ok(SerializableRead {
+            response,
+            leaderless,
+        })
+    }
+
+    /// Get a range of keys from the local state of a server, together with the latest revision
+    /// of the leader known by that server, which is piggybacked on the heartbeats of the
+    /// leader. The staleness of the read is the difference between the leader revision and
+    /// the revision in the response header, and it is already known without asking the leader.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the server serving the local read is unavailable
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use xline_client::{types::kv::RangeRequest, Client, ClientOptions};
+    /// use anyhow::Result;
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<()> {
+    ///     let curp_members = ["10.0.0.1:2379", "10.0.0.2:2379", "10.0.0.3:2379"];
+    ///
+    ///     let client = Client::connect(curp_members, ClientOptions::default())
+    ///         .await?
+    ///         .kv_client();
+    ///
+    ///     let (resp, leader_revision) = client
+    ///         .serializable_range(RangeRequest::new("key1"))
+    ///         .await?;
+    ///     let revision = resp.header.map_or(0, |h| h.revision);
+    ///     println!("the read lags {} revisions behind", leader_revision - revision);
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    #[inline]
+    pub async fn serializable_range(&self, request: RangeRequest) -> Result<(RangeResponse, i64)> {
+        let projection = request.projection();
+        let request = xlineapi::RangeRequest::from(request.with_serializable(true));
+        let (mut resp, leader_revision) = self.local_range(request).await?;
+        projection.apply(&mut resp);
+        Ok((resp, leader_revision))
+    }
+
+    /// Send the range request to the server directly, which is served by its local state, and
+    /// return the response with the leader revision hint of the server
+    async fn local_range(&self, request: xlineapi::RangeRequest) -> Result<(RangeResponse, i64)> {
+        let mut request = request;
+        if let Some(codec) = self.key_codec {
+            codec.encode_range_request(&mut request)?;
+        }
+        let mut kv_client = self.kv_client.clone();
+        let resp = kv_client.range(request).await?;
+        let leader_revision = resp
+            .metadata()
+            .get(LEADER_REVISION_KEY)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(0);
+        let mut resp = resp.into_inner();
+        if let Some(codec) = self.key_codec {
+            codec.decode_range_response(&mut resp);
+        }
+        Ok((resp, leader_revision))
+    }
+
+    /// Check whether a local read at `revision` lags behind `leader_revision` within `max_lag`
+    fn within_staleness(revision: i64, leader_revision: i64, max_lag: i64) -> bool {
+        leader_revision.saturating_sub(revision) <= max_lag
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use curp::rpc::Member;
+
+    use super::*;
+
+    #[test]
+    fn bounded_staleness_should_fall_back_to_leader_when_lagging() {
+        // the local revision is close enough to the leader
+        assert!(KvClient::within_staleness(95, 100, 10));
+        assert!(KvClient::within_staleness(100, 100, 0));
+        // the follower lags behind for too many revisions, read from the leader instead
+        assert!(!KvClient::within_staleness(80, 100, 10));
+        assert!(!KvClient::within_staleness(99, 100, 0));
+        // no leader revision is observed yet
+        assert!(KvClient::within_staleness(5, 0, 0));
+    }
+
+    #[test]
+    fn linearizable_reads_should_fail_with_leader_election_while_leaderless() {
+        let cluster = FetchClusterResponse {
+            leader_id: None,
+            term: 2,
+            members: vec![Member::default()],
+            ..FetchClusterResponse::default()
+        };
+        let err = XlineClientError::RpcError("timeout".to_owned());
+        assert!(matches!(
+            KvClient::linearizable_error(err, Some(&cluster)),
+            XlineClientError::LeaderElection
+        ));
+
+        // a cluster with a leader serves the reads as usual
+        let elected = FetchClusterResponse {
+            leader_id: Some(1),
+            ..cluster
+        };
+        for cluster in [Some(&elected), None] {
+            let err = XlineClientError::RpcError("timeout".to_owned());
+            assert!(matches!(
+                KvClient::linearizable_error(err, cluster),
+                XlineClientError::RpcError(_)
+            ));
+        }
+    }
+}
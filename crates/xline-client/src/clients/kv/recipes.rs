@@ -0,0 +1,484 @@
+use std::collections::HashSet;
+
+use xlineapi::TxnResponse;
+
+use super::KvClient;
+use crate::{
+    error::{Result, XlineClientError},
+    types::kv::{
+        Compare, CompareResult, Consistency, DeleteRangeRequest, PutRequest, RangeRequest,
+        SortOrder, SortTarget, TxnOp, TxnRequest,
+    },
+};
+
+/// Prefix of the claim markers of work items claimed by `claim_one`, the marker of an item is
+/// this prefix followed by the key of the item, so markers are not listed as items
+const CLAIM_MARKER_PREFIX: &[u8] = b"__claim__/";
+
+#[allow(clippy::multiple_inherent_impl)] // the operations of the client are split by feature
+impl KvClient {
+    /// Check whether a key exists in the store with the `consistency`, see
+    /// [`KvClient::range_with_consistency`]. It is implemented with a `count_only` range, so
+    /// the value of the key will not be transferred.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the inner CURP client encountered a propose failure
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use xline_client::{types::kv::Consistency, Client, ClientOptions};
+    /// use anyhow::Result;
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<()> {
+    ///     let curp_members = ["10.0.0.1:2379", "10.0.0.2:2379", "10.0.0.3:2379"];
+    ///
+    ///     let client = Client::connect(curp_members, ClientOptions::default())
+    ///         .await?
+    ///         .kv_client();
+    ///
+    ///     if client.exists("key1", Consistency::Linearizable).await? {
+    ///         println!("key1 exists");
+    ///     }
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    #[inline]
+    pub async fn exists(&self, key: impl Into<Vec<u8>>, consistency: Consistency) -> Result<bool> {
+        let request = RangeRequest::new(key).with_count_only(true);
+        let resp = self.range_with_consistency(request, consistency).await?;
+        Ok(resp.count > 0)
+    }
+
+    /// Create a key-value only if the key is absent, the existing value will never be
+    /// overwritten. It is implemented with a txn comparing `create_revision == 0`.
+    /// Returns whether the key was created.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the inner CURP client encountered a propose failure,
+    /// or the key or value exceeds the size limits set in `ClientOptions`
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use xline_client::{Client, ClientOptions};
+    /// use anyhow::Result;
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<()> {
+    ///     let curp_members = ["10.0.0.1:2379", "10.0.0.2:2379", "10.0.0.3:2379"];
+    ///
+    ///     let client = Client::connect(curp_members, ClientOptions::default())
+    ///         .await?
+    ///         .kv_client();
+    ///
+    ///     if !client.create("key1", "value1", None).await? {
+    ///         println!("key1 already exists");
+    ///     }
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    #[inline]
+    pub async fn create(
+        &self,
+        key: impl Into<Vec<u8>>,
+        value: impl Into<Vec<u8>>,
+        lease: Option<i64>,
+    ) -> Result<bool> {
+        let (key, value) = (key.into(), value.into());
+        self.size_limits.check(&key, &value)?;
+        let mut put = PutRequest::new(key.clone(), value);
+        if let Some(lease) = lease {
+            put = put.with_lease(lease);
+        }
+        let request = TxnRequest::new()
+            .when(&[Compare::create_revision(key, CompareResult::Equal, 0)][..])
+            .and_then(&[TxnOp::put(put)][..]);
+        let resp = self.txn(request).await?;
+        Ok(resp.succeeded)
+    }
+
+    /// Put many key-value pairs attached to the same lease in one txn, so that either all
+    /// of them are attached to the lease or none of them is
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the inner CURP client encountered a propose failure,
+    /// or any key or value exceeds the size limits set in `ClientOptions`
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use xline_client::{types::lease::LeaseGrantRequest, Client, ClientOptions};
+    /// use anyhow::Result;
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<()> {
+    ///     let curp_members = ["10.0.0.1:2379", "10.0.0.2:2379", "10.0.0.3:2379"];
+    ///
+    ///     let client = Client::connect(curp_members, ClientOptions::default()).await?;
+    ///     let lease_id = client
+    ///         .lease_client()
+    ///         .grant(LeaseGrantRequest::new(60))
+    ///         .await?
+    ///         .id;
+    ///
+    ///     client
+    ///         .kv_client()
+    ///         .put_many_with_lease(&[("key1", "value1"), ("key2", "value2")], lease_id)
+    ///         .await?;
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    #[inline]
+    pub async fn put_many_with_lease<K, V>(
+        &self,
+        kvs: &[(K, V)],
+        lease_id: i64,
+    ) -> Result<TxnResponse>
+    where
+        K: AsRef<[u8]>,
+        V: AsRef<[u8]>,
+    {
+        for (key, value) in kvs {
+            self.size_limits.check(key.as_ref(), value.as_ref())?;
+        }
+        let puts = kvs
+            .iter()
+            .map(|(key, value)| {
+                TxnOp::put(PutRequest::new(key.as_ref(), value.as_ref()).with_lease(lease_id))
+            })
+            .collect::<Vec<_>>();
+        self.txn(TxnRequest::new().and_then(puts)).await
+    }
+
+    /// Delete a key only if its current value equals `expected_value`. It is implemented with
+    /// a txn comparing the value of the key. Returns whether the key was deleted, an absent
+    /// key is never deleted.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the inner CURP client encountered a propose failure
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use xline_client::{Client, ClientOptions};
+    /// use anyhow::Result;
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<()> {
+    ///     let curp_members = ["10.0.0.1:2379", "10.0.0.2:2379", "10.0.0.3:2379"];
+    ///
+    ///     let client = Client::connect(curp_members, ClientOptions::default())
+    ///         .await?
+    ///         .kv_client();
+    ///
+    ///     if !client.compare_and_delete("lock1", "owner1").await? {
+    ///         println!("lock1 is not owned by owner1");
+    ///     }
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    #[inline]
+    pub async fn compare_and_delete(
+        &self,
+        key: impl Into<Vec<u8>>,
+        expected_value: impl Into<Vec<u8>>,
+    ) -> Result<bool> {
+        let key = key.into();
+        let request = TxnRequest::new()
+            .when(
+                &[Compare::value(
+                    key.clone(),
+                    CompareResult::Equal,
+                    expected_value,
+                )][..],
+            )
+            .and_then(&[TxnOp::delete(DeleteRangeRequest::new(key))][..]);
+        let resp = self.txn(request).await?;
+        Ok(resp.succeeded)
+    }
+
+    /// Move a key from its current lease to `new_lease_id` atomically, so that the old lease
+    /// no longer governs the key. It reads the current value, then puts it with the new lease
+    /// in a txn comparing the mod revision of the key. Returns whether the key was moved, it
+    /// is not moved if it is absent or modified concurrently between the read and the txn.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the inner CURP client encountered a propose failure
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use xline_client::{types::lease::LeaseGrantRequest, Client, ClientOptions};
+    /// use anyhow::Result;
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<()> {
+    ///     let curp_members = ["10.0.0.1:2379", "10.0.0.2:2379", "10.0.0.3:2379"];
+    ///
+    ///     let client = Client::connect(curp_members, ClientOptions::default()).await?;
+    ///     let new_lease = client.lease_client().grant(LeaseGrantRequest::new(60)).await?.id;
+    ///
+    ///     if !client.kv_client().reattach_lease("key1", new_lease).await? {
+    ///         println!("key1 is absent or modified concurrently");
+    ///     }
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    #[inline]
+    pub async fn reattach_lease(&self, key: impl Into<Vec<u8>>, new_lease_id: i64) -> Result<bool> {
+        let resp = self.range(RangeRequest::new(key)).await?;
+        let Some(kv) = resp.kvs.first() else {
+            return Ok(false);
+        };
+        let compare = Compare::mod_revision(kv.key.clone(), CompareResult::Equal, kv.mod_revision);
+        let put = PutRequest::new(kv.key.clone(), kv.value.clone()).with_lease(new_lease_id);
+        let request = TxnRequest::new()
+            .when(&[compare][..])
+            .and_then(&[TxnOp::put(put)][..]);
+        let resp = self.txn(request).await?;
+        Ok(resp.succeeded)
+    }
+
+    /// Claim the unclaimed work item with the lowest create revision under `queue_prefix` for
+    /// `claimant`, returning its key and value, or `None` if all items are claimed or the
+    /// queue is empty. An item is claimed by putting a claim marker attached to `lease_id` in
+    /// a txn, which succeeds only if the item is neither claimed nor modified since it was
+    /// read, so every item is claimed by exactly one claimant. The claim is released once the
+    /// lease expires, the claimant should delete the item when it is done.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the inner CURP client encountered a propose failure
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use xline_client::{types::lease::LeaseGrantRequest, Client, ClientOptions};
+    /// use anyhow::Result;
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<()> {
+    ///     let curp_members = ["10.0.0.1:2379", "10.0.0.2:2379", "10.0.0.3:2379"];
+    ///
+    ///     let client = Client::connect(curp_members, ClientOptions::default()).await?;
+    ///     let lease_id = client.lease_client().grant(LeaseGrantRequest::new(60)).await?.id;
+    ///
+    ///     if let Some((key, value)) = client
+    ///         .kv_client()
+    ///         .claim_one("jobs/", "worker1", lease_id)
+    ///         .await?
+    ///     {
+    ///         println!("claimed {:?}: {:?}", key, value);
+    ///     }
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    #[inline]
+    pub async fn claim_one(
+        &self,
+        queue_prefix: impl Into<Vec<u8>>,
+        claimant: impl Into<Vec<u8>>,
+        lease_id: i64,
+    ) -> Result<Option<(Vec<u8>, Vec<u8>)>> {
+        let queue_prefix = queue_prefix.into();
+        let claimant = claimant.into();
+        let items = self
+            .range(
+                RangeRequest::new(queue_prefix.clone())
+                    .with_prefix()
+                    .with_sort_target(SortTarget::Create)
+                    .with_sort_order(SortOrder::Ascend),
+            )
+            .await?;
+        let markers = self
+            .range(
+                RangeRequest::new(Self::claim_marker_key(&queue_prefix))
+                    .with_prefix()
+                    .with_keys_only(true),
+            )
+            .await?;
+        let claimed: HashSet<_> = markers.kvs.into_iter().map(|kv| kv.key).collect();
+        for kv in items.kvs {
+            let marker = Self::claim_marker_key(&kv.key);
+            if claimed.contains(&marker) {
+                continue;
+            }
+            let unclaimed = Compare::create_revision(marker.clone(), CompareResult::Equal, 0);
+            let unmodified =
+                Compare::mod_revision(kv.key.clone(), CompareResult::Equal, kv.mod_revision);
+            let put = PutRequest::new(marker, claimant.clone()).with_lease(lease_id);
+            let request = TxnRequest::new()
+                .when(&[unclaimed, unmodified][..])
+                .and_then(&[TxnOp::put(put)][..]);
+            // the item may be claimed by another claimant or deleted since it was read
+            if self.txn(request).await?.succeeded {
+                return Ok(Some((kv.key, kv.value)));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Get the key of the claim marker of the item `key`
+    fn claim_marker_key(key: &[u8]) -> Vec<u8> {
+        [CLAIM_MARKER_PREFIX, key].concat()
+    }
+
+    /// Delete a set of keys atomically only if the current value of the `guard_key` equals
+    /// `expected_value`, e.g. to tear down all keys of a resource only if it is still owned.
+    /// It is implemented with a txn comparing the value of the guard key and deleting the keys
+    /// in the success branch. Returns whether the keys were deleted, nothing is deleted if the
+    /// guard key is absent. The guard key itself is kept unless it is one of the `keys`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the inner CURP client encountered a propose failure
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use xline_client::{Client, ClientOptions};
+    /// use anyhow::Result;
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<()> {
+    ///     let curp_members = ["10.0.0.1:2379", "10.0.0.2:2379", "10.0.0.3:2379"];
+    ///
+    ///     let client = Client::connect(curp_members, ClientOptions::default())
+    ///         .await?
+    ///         .kv_client();
+    ///
+    ///     let keys = ["job1/config", "job1/status", "job1/owner"];
+    ///     if !client.delete_many_if("job1/owner", "worker1", keys).await? {
+    ///         println!("job1 is not owned by worker1");
+    ///     }
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    #[inline]
+    pub async fn delete_many_if<K: Into<Vec<u8>>>(
+        &self,
+        guard_key: impl Into<Vec<u8>>,
+        expected_value: impl Into<Vec<u8>>,
+        keys: impl IntoIterator<Item = K>,
+    ) -> Result<bool> {
+        let mut keys: Vec<Vec<u8>> = keys.into_iter().map(Into::into).collect();
+        // a txn can not modify the same key twice
+        keys.sort();
+        keys.dedup();
+        let deletes: Vec<_> = keys
+            .into_iter()
+            .map(|key| TxnOp::delete(DeleteRangeRequest::new(key)))
+            .collect();
+        let request = TxnRequest::new()
+            .when(
+                &[Compare::value(
+                    guard_key,
+                    CompareResult::Equal,
+                    expected_value,
+                )][..],
+            )
+            .and_then(deletes);
+        let resp = self.txn(request).await?;
+        Ok(resp.succeeded)
+    }
+
+    /// Atomically increment the counter stored in `counter_key` and return the new value. The
+    /// counter is stored as a decimal string and starts from 0 if the key is absent, so the
+    /// first value returned is 1. It is implemented with a txn comparing the mod revision of
+    /// the key, and retried if another client bumps the counter concurrently, so every value
+    /// is returned to exactly one caller and values increase monotonically.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the inner CURP client encountered a propose failure,
+    /// or the stored value is not a valid counter
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use xline_client::{Client, ClientOptions};
+    /// use anyhow::Result;
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<()> {
+    ///     let curp_members = ["10.0.0.1:2379", "10.0.0.2:2379", "10.0.0.3:2379"];
+    ///
+    ///     let client = Client::connect(curp_members, ClientOptions::default())
+    ///         .await?
+    ///         .kv_client();
+    ///
+    ///     let id = client.next_sequence("order/id").await?;
+    ///     println!("new order id: {id}");
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    #[inline]
+    pub async fn next_sequence(&self, counter_key: impl Into<Vec<u8>>) -> Result<i64> {
+        let key = counter_key.into();
+        loop {
+            let resp = self.range(RangeRequest::new(key.clone())).await?;
+            let (current, mod_revision) = match resp.kvs.first() {
+                Some(kv) => (Self::parse_sequence(&kv.value)?, kv.mod_revision),
+                None => (0, 0),
+            };
+            let next = current
+                .checked_add(1)
+                .ok_or_else(|| XlineClientError::InvalidArgs("sequence overflows".to_owned()))?;
+            // 0 means the counter is absent
+            let request = TxnRequest::new()
+                .when(
+                    &[Compare::mod_revision(
+                        key.clone(),
+                        CompareResult::Equal,
+                        mod_revision,
+                    )][..],
+                )
+                .and_then(&[TxnOp::put(PutRequest::new(key.clone(), next.to_string()))][..]);
+            let resp = self.txn(request).await?;
+            if resp.succeeded {
+                return Ok(next);
+            }
+            // the counter is bumped by another client, retry with the new value
+        }
+    }
+
+    /// Parse the value of a sequence counter
+    fn parse_sequence(value: &[u8]) -> Result<i64> {
+        std::str::from_utf8(value)
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .ok_or_else(|| {
+                XlineClientError::InvalidArgs(format!(
+                    "invalid sequence counter: {}",
+                    String::from_utf8_lossy(value)
+                ))
+            })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn sequence_should_be_parsed_from_decimal_counter() {
+        assert_eq!(KvClient::parse_sequence(b"42").unwrap(), 42);
+        assert!(KvClient::parse_sequence(b"abc").is_err());
+        assert!(KvClient::parse_sequence(b"").is_err());
+    }
+}
@@ -0,0 +1,354 @@
+use std::{collections::HashMap, sync::Arc, time::Instant};
+
+use clippy_utilities::OverflowArithmetic;
+use curp::rpc::{FetchClusterResponse, Member};
+use futures::future::join_all;
+use xlineapi::RangeResponse;
+
+use super::{KvClient, QUORUM_READ_TIMEOUT};
+use crate::{
+    error::{Result, XlineClientError},
+    types::kv::{Compare, CompareResult, PutRequest, RangeRequest, TxnOp, TxnRequest},
+    AuthService,
+};
+
+#[allow(clippy::multiple_inherent_impl)] // the operations of the client are split by feature
+impl KvClient {
+    /// Serve the range request by the most caught-up member if no leader is elected, `None`
+    /// if a leader is elected or no member responds
+    pub(super) async fn leaderless_range(
+        &self,
+        request: &xlineapi::RangeRequest,
+    ) -> Option<RangeResponse> {
+        let cluster = self.curp_client.fetch_cluster(false).await.ok()?;
+        let members = Self::leaderless_members(&cluster)?;
+        let reads = members
+            .iter()
+            .filter(|member| !member.is_learner)
+            .map(|member| self.member_range(member, request.clone()));
+        Self::freshest(join_all(reads).await.into_iter().flatten())
+    }
+
+    /// Get the members to serve reads if no leader is elected in the cluster
+    pub(super) fn leaderless_members(cluster: &FetchClusterResponse) -> Option<&[Member]> {
+        cluster
+            .leader_id
+            .is_none()
+            .then_some(cluster.members.as_slice())
+    }
+
+    /// Get the freshest response, whose revision is the highest
+    fn freshest(resps: impl IntoIterator<Item = RangeResponse>) -> Option<RangeResponse> {
+        resps
+            .into_iter()
+            .max_by_key(|resp| resp.header.as_ref().map_or(0, |h| h.revision))
+    }
+
+    /// Send the serializable range request to every voter, and return the freshest response
+    /// if a majority of voters respond. Learners are not counted in the quorum, so they are
+    /// not asked.
+    pub(super) async fn quorum_range(
+        &self,
+        request: xlineapi::RangeRequest,
+    ) -> Result<RangeResponse> {
+        let members: Vec<_> = self
+            .curp_client
+            .fetch_cluster(false)
+            .await?
+            .members
+            .into_iter()
+            .filter(|member| !member.is_learner)
+            .collect();
+        let reads = members
+            .iter()
+            .map(|member| self.member_range(member, request.clone()));
+        let resps: Vec<_> = join_all(reads).await.into_iter().flatten().collect();
+        let quorum = members.len().overflow_div(2).overflow_add(1);
+        if resps.len() < quorum {
+            return Err(XlineClientError::RpcError(format!(
+                "only {} of {} members respond to the quorum read",
+                resps.len(),
+                members.len()
+            )));
+        }
+        // values are incomplete in keys only or count only responses
+        if self.read_repair && !request.keys_only && !request.count_only {
+            if let Some(repair) = Self::read_repair_request(&resps) {
+                // the repair is best effort, a failed one does not fail the read
+                let _ignore = self.txn(repair).await;
+            }
+        }
+        Ok(Self::freshest(resps)
+            .unwrap_or_else(|| unreachable!("a quorum is at least one response")))
+    }
+
+    /// Send the serializable range request to the leader if it is the fastest member measured
+    /// by this client, otherwise to the fastest follower. It falls back to the CURP client if
+    /// the selected member fails to respond.
+    pub(super) async fn selected_range(
+        &self,
+        request: xlineapi::RangeRequest,
+    ) -> Result<RangeResponse> {
+        let cluster = self.curp_client.fetch_cluster(false).await?;
+        let ids: Vec<_> = cluster.members.iter().map(|member| member.id).collect();
+        let selected = self
+            .latency
+            .select(cluster.leader_id, &ids)
+            .and_then(|id| cluster.members.iter().find(|member| member.id == id));
+        if let Some(member) = selected {
+            if let Some(resp) = self.member_range(member, request.clone()).await {
+                return Ok(resp);
+            }
+        }
+        self.leader_range(request).await
+    }
+
+    /// Send the range request to a member through the pooled channel to it. The latency of
+    /// the request on the pooled channel is measured, so connecting to the member is not
+    /// counted, while a member failing to respond is deprioritized as if it timed out.
+    async fn member_range(
+        &self,
+        member: &Member,
+        request: xlineapi::RangeRequest,
+    ) -> Option<RangeResponse> {
+        let mut request = request;
+        if let Some(codec) = self.key_codec {
+            codec.encode_range_request(&mut request).ok()?;
+        }
+        let Some(channel) = self
+            .member_channels
+            .get(member.id, &member.client_urls, self.tls_config.as_ref())
+            .await
+        else {
+            self.latency.observe(member.id, QUORUM_READ_TIMEOUT);
+            return None;
+        };
+        let mut kv_client = xlineapi::KvClient::new(AuthService::new(
+            channel,
+            self.token
+                .as_ref()
+                .and_then(|t| t.parse().ok().map(Arc::new)),
+        ));
+        let start = Instant::now();
+        let Ok(resp) = kv_client.range(request).await else {
+            // the member may have moved, reconnect on the next read
+            self.member_channels.remove(member.id);
+            self.latency.observe(member.id, QUORUM_READ_TIMEOUT);
+            return None;
+        };
+        self.latency.observe(member.id, start.elapsed());
+        let mut resp = resp.into_inner();
+        if let Some(codec) = self.key_codec {
+            codec.decode_range_response(&mut resp);
+        }
+        Some(resp)
+    }
+
+    /// Build the txn writing the freshest values back, return `None` if no member is stale.
+    /// A value is only written back if it is not modified since the read.
+    fn read_repair_request(resps: &[RangeResponse]) -> Option<TxnRequest> {
+        let freshest = resps
+            .iter()
+            .max_by_key(|resp| resp.header.as_ref().map_or(0, |h| h.revision))?;
+        let (compares, puts): (Vec<_>, Vec<_>) = freshest
+            .kvs
+            .iter()
+            .filter(|kv| {
+                resps.iter().any(|resp| {
+                    !resp
+                        .kvs
+                        .iter()
+                        .any(|other| other.key == kv.key && other.mod_revision >= kv.mod_revision)
+                })
+            })
+            .map(|kv| {
+                let compare =
+                    Compare::mod_revision(kv.key.clone(), CompareResult::Equal, kv.mod_revision);
+                let mut put = PutRequest::new(kv.key.clone(), kv.value.clone());
+                if kv.lease != 0 {
+                    put = put.with_lease(kv.lease);
+                }
+                (compare, TxnOp::put(put))
+            })
+            .unzip();
+        if puts.is_empty() {
+            return None;
+        }
+        Some(TxnRequest::new().when(compares).and_then(puts))
+    }
+
+    /// Count the keys with the prefix on every member of the cluster, each member counts by
+    /// its local state. It is useful for approximate cluster-wide metrics, or for checking
+    /// whether the replicas converge, a member reporting a different count from the others
+    /// may lag behind or diverge. Members failing to respond are absent from the result.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the members of the cluster cannot be fetched
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use xline_client::{Client, ClientOptions};
+    /// use anyhow::Result;
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<()> {
+    ///     let curp_members = ["10.0.0.1:2379", "10.0.0.2:2379", "10.0.0.3:2379"];
+    ///
+    ///     let client = Client::connect(curp_members, ClientOptions::default())
+    ///         .await?
+    ///         .kv_client();
+    ///
+    ///     let counts = client.count_prefix_all_replicas("job/").await?;
+    ///     for (member_id, count) in counts {
+    ///         println!("member {member_id} has {count} jobs");
+    ///     }
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    #[inline]
+    pub async fn count_prefix_all_replicas(
+        &self,
+        prefix: impl Into<Vec<u8>>,
+    ) -> Result<HashMap<u64, i64>> {
+        let request = xlineapi::RangeRequest::from(
+            RangeRequest::new(prefix)
+                .with_prefix()
+                .with_count_only(true)
+                .with_serializable(true),
+        );
+        let request = &request;
+        let members = self.curp_client.fetch_cluster(false).await?.members;
+        let counts = members.iter().map(|member| async move {
+            let resp = self.member_range(member, request.clone()).await;
+            (member.id, resp)
+        });
+        Ok(Self::replica_counts(join_all(counts).await))
+    }
+
+    /// Collect the counts reported by members, members without a response are skipped
+    fn replica_counts(resps: Vec<(u64, Option<RangeResponse>)>) -> HashMap<u64, i64> {
+        resps
+            .into_iter()
+            .filter_map(|(id, resp)| resp.map(|r| (id, r.count)))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use xlineapi::ResponseHeader;
+
+    use super::*;
+
+    #[test]
+    fn replica_counts_should_reflect_divergent_members() {
+        let count = |count| {
+            Some(RangeResponse {
+                count,
+                ..RangeResponse::default()
+            })
+        };
+        let counts =
+            KvClient::replica_counts(vec![(1, count(4)), (2, count(4)), (3, count(3)), (4, None)]);
+        assert_eq!(
+            counts.len(),
+            3,
+            "members without a response should be skipped"
+        );
+        assert_eq!(counts[&1], 4);
+        assert_eq!(counts[&2], 4);
+        assert_eq!(counts[&3], 3, "the divergent count should be reported");
+    }
+
+    #[test]
+    fn leaderless_cluster_should_serve_serializable_reads_by_the_freshest_follower() {
+        let member = |id| Member {
+            id,
+            client_urls: vec![format!("http://10.0.0.{id}:2379")],
+            ..Member::default()
+        };
+        let cluster = FetchClusterResponse {
+            leader_id: None,
+            term: 2,
+            members: vec![member(1), member(2), member(3)],
+            ..FetchClusterResponse::default()
+        };
+        let at = |revision| RangeResponse {
+            header: Some(ResponseHeader {
+                revision,
+                ..ResponseHeader::default()
+            }),
+            ..RangeResponse::default()
+        };
+
+        // serializable reads are served by the most caught-up follower
+        let members = KvClient::leaderless_members(&cluster).unwrap();
+        assert_eq!(members.len(), 3);
+        assert_eq!(KvClient::freshest([at(5), at(7), at(6)]), Some(at(7)));
+        assert_eq!(KvClient::freshest([]), None);
+
+        // a cluster with a leader serves the reads as usual
+        let elected = FetchClusterResponse {
+            leader_id: Some(1),
+            ..cluster
+        };
+        assert!(KvClient::leaderless_members(&elected).is_none());
+    }
+
+    fn range_response(revision: i64, kvs: &[(&str, &str, i64)]) -> RangeResponse {
+        RangeResponse {
+            header: Some(ResponseHeader {
+                revision,
+                ..ResponseHeader::default()
+            }),
+            kvs: kvs
+                .iter()
+                .map(|&(key, value, mod_revision)| xlineapi::KeyValue {
+                    key: key.into(),
+                    value: value.into(),
+                    mod_revision,
+                    ..xlineapi::KeyValue::default()
+                })
+                .collect(),
+            ..RangeResponse::default()
+        }
+    }
+
+    #[test]
+    fn read_repair_should_write_back_the_freshest_value() {
+        let resps = [
+            range_response(5, &[("a", "new", 5), ("b", "b", 2)]),
+            range_response(5, &[("a", "new", 5), ("b", "b", 2)]),
+            // the straggler misses the latest put of `a`
+            range_response(3, &[("a", "old", 3), ("b", "b", 2)]),
+        ];
+        let req = xlineapi::TxnRequest::from(KvClient::read_repair_request(&resps).unwrap());
+        assert_eq!(req.compare.len(), 1);
+        let cmp = &req.compare[0];
+        assert_eq!(cmp.key, b"a");
+        assert_eq!(cmp.target, xlineapi::CompareTarget::Mod as i32);
+        assert_eq!(
+            cmp.target_union,
+            Some(xlineapi::TargetUnion::ModRevision(5))
+        );
+        assert_eq!(req.success.len(), 1);
+        let Some(xlineapi::Request::RequestPut(ref put)) = req.success[0].request else {
+            panic!("the success branch should be a put");
+        };
+        assert_eq!(put.key, b"a");
+        assert_eq!(put.value, b"new");
+        assert!(req.failure.is_empty());
+    }
+
+    #[test]
+    fn read_repair_should_skip_converged_members() {
+        let resps = [
+            range_response(5, &[("a", "new", 5)]),
+            range_response(5, &[("a", "new", 5)]),
+        ];
+        assert!(KvClient::read_repair_request(&resps).is_none());
+    }
+}
@@ -8,6 +8,8 @@ use xlineapi::{
 };
 
 use crate::{
+    clients::traced,
+    degrade::DegradeState,
     error::{Result, XlineClientError},
     lease_gen::LeaseIdGenerator,
     types::lease::{
@@ -32,6 +34,8 @@ pub struct LeaseClient {
     token: Option<String>,
     /// Lease Id generator
     id_gen: Arc<LeaseIdGenerator>,
+    /// State of the degraded mode, which tracks the grants and revokes
+    degrade: Arc<DegradeState>,
 }
 
 impl Debug for LeaseClient {
@@ -42,6 +46,7 @@ impl Debug for LeaseClient {
             .field("lease_client", &self.lease_client)
             .field("token", &self.token)
             .field("id_gen", &self.id_gen)
+            .field("degrade", &self.degrade)
             .finish()
     }
 }
@@ -63,9 +68,15 @@ impl LeaseClient {
             )),
             token,
             id_gen,
+            degrade: Arc::new(DegradeState::default()),
         }
     }
 
+    /// Set the state of the degraded mode shared with the `Client`
+    pub(crate) fn with_degrade_state(self, degrade: Arc<DegradeState>) -> Self {
+        Self { degrade, ..self }
+    }
+
     /// Creates a lease which expires if the server does not receive a keepAlive
     /// within a given time to live period. All keys attached to the lease will be expired and
     /// deleted if the lease expires. Each expired key generates a delete event in the event history.
@@ -102,8 +113,8 @@ impl LeaseClient {
         let request = RequestWrapper::from(xlineapi::LeaseGrantRequest::from(request));
         let cmd = Command::new(request.keys(), request);
         let (cmd_res, _sync_res) = self
-            .curp_client
-            .propose(&cmd, self.token.as_ref(), true)
+            .degrade
+            .propose_write(&*self.curp_client, &cmd, self.token.as_ref(), true)
             .await??;
         Ok(cmd_res.into_inner().into())
     }
@@ -137,7 +148,10 @@ impl LeaseClient {
     /// ```
     #[inline]
     pub async fn revoke(&mut self, request: LeaseRevokeRequest) -> Result<LeaseRevokeResponse> {
-        let res = self.lease_client.lease_revoke(request.inner).await?;
+        let res = self
+            .degrade
+            .track(self.lease_client.lease_revoke(request.inner))
+            .await?;
         Ok(res.into_inner())
     }
 
@@ -179,6 +193,20 @@ impl LeaseClient {
     pub async fn keep_alive(
         &mut self,
         request: LeaseKeepAliveRequest,
+    ) -> Result<(LeaseKeeper, Streaming<LeaseKeepAliveResponse>)> {
+        let curp_client = Arc::clone(&self.curp_client);
+        traced(
+            &*curp_client,
+            "lease_keep_alive",
+            self.start_keep_alive(request),
+        )
+        .await
+    }
+
+    /// Start the keep alive stream of the lease
+    async fn start_keep_alive(
+        &mut self,
+        request: LeaseKeepAliveRequest,
     ) -> Result<(LeaseKeeper, Streaming<LeaseKeepAliveResponse>)> {
         let (mut sender, receiver) = channel::<xlineapi::LeaseKeepAliveRequest>(100);
 
@@ -20,11 +20,12 @@ use xlineapi::{
 
 use crate::{
     clients::{lease::LeaseClient, watch::WatchClient},
+    degrade::DegradeState,
     error::{Result, XlineClientError},
     lease_gen::LeaseIdGenerator,
     types::{
-        lease::LeaseGrantRequest,
-        lock::{LockRequest, UnlockRequest},
+        lease::{LeaseGrantRequest, LeaseRevokeRequest},
+        lock::{LockRequest, UnlockRequest, DEFAULT_SESSION_TTL},
         watch::WatchRequest,
     },
     CurpClient,
@@ -41,6 +42,8 @@ pub struct LockClient {
     watch_client: WatchClient,
     /// Auth token
     token: Option<String>,
+    /// The state of the degraded mode shared with the `Client`
+    degrade: Arc<DegradeState>,
 }
 
 impl Debug for LockClient {
@@ -50,6 +53,7 @@ impl Debug for LockClient {
             .field("lease_client", &self.lease_client)
             .field("watch_client", &self.watch_client)
             .field("token", &self.token)
+            .field("degrade", &self.degrade)
             .finish()
     }
 }
@@ -70,6 +74,17 @@ impl LockClient {
             lease_client: LeaseClient::new(curp_client, channel.clone(), token.clone(), id_gen),
             watch_client: WatchClient::new(channel, token.clone()),
             token,
+            degrade: Arc::new(DegradeState::default()),
+        }
+    }
+
+    /// Set the state of the degraded mode shared with the `Client`, which is shared with the
+    /// inner lease client too
+    pub(crate) fn with_degrade_state(self, degrade: Arc<DegradeState>) -> Self {
+        Self {
+            lease_client: self.lease_client.with_degrade_state(Arc::clone(&degrade)),
+            degrade,
+            ..self
         }
     }
 
@@ -151,7 +166,7 @@ impl LockClient {
         lock_success: &AtomicBool,
     ) -> Result<LockResponse> {
         let txn = Self::create_acquire_txn(&prefix, lease_id);
-        let (cmd_res, sync_res) = self.propose(txn, false).await?;
+        let (cmd_res, sync_res) = self.propose_write(txn, false).await?;
         let mut txn_res = Into::<TxnResponse>::into(cmd_res.into_inner());
         let my_rev = sync_res
             .unwrap_or_else(|| unreachable!("sync_res always has value when use slow path"))
@@ -245,6 +260,81 @@ impl LockClient {
         Ok(UnlockResponse { header })
     }
 
+    /// Acquires all the named locks with the lease. The names are acquired in the sorted
+    /// order, which is a global order among all callers, so that two callers acquiring
+    /// overlapping sets of locks never deadlock. If `lease_id` is 0, a lease with the default
+    /// session TTL is granted for all the locks, which is revoked once the locks are released.
+    ///
+    /// All locks are released when the returned guard is dropped. If an acquisition fails or
+    /// the returned future is cancelled, e.g. by a timeout, the locks already held are
+    /// released as well.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the inner CURP client encountered a propose failure
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use xline_client::{Client, ClientOptions};
+    /// use anyhow::Result;
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<()> {
+    ///     // the name and address of all curp members
+    ///     let curp_members = ["10.0.0.1:2379", "10.0.0.2:2379", "10.0.0.3:2379"];
+    ///
+    ///     let client = Client::connect(curp_members, ClientOptions::default())
+    ///         .await?
+    ///         .lock_client();
+    ///
+    ///     let names = ["account-b".to_owned(), "account-a".to_owned()];
+    ///     let guard = client.lock_all(&names, 0).await?;
+    ///     // both accounts are locked here
+    ///     guard.unlock().await?;
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    #[inline]
+    pub async fn lock_all(&self, names: &[String], mut lease_id: i64) -> Result<MultiLockGuard> {
+        let mut names: Vec<&String> = names.iter().collect();
+        names.sort_unstable();
+        names.dedup();
+        let mut owned_lease = None;
+        if lease_id == 0 {
+            lease_id = self
+                .lease_client
+                .grant(LeaseGrantRequest::new(DEFAULT_SESSION_TTL))
+                .await?
+                .id;
+            owned_lease = Some(lease_id);
+        }
+        // the guard releases the locks already held if this future returns early or is dropped
+        let mut guard = MultiLockGuard {
+            lock_client: self.clone(),
+            keys: Vec::with_capacity(names.len()),
+            owned_lease,
+        };
+        for name in names {
+            let resp = self
+                .lock(LockRequest::new(name.as_str()).with_lease(lease_id))
+                .await?;
+            guard.keys.push(resp.key);
+        }
+        Ok(guard)
+    }
+
+    /// Revoke the lease of `lease_id`
+    async fn revoke_lease(&self, lease_id: i64) -> Result<()> {
+        let _resp = self
+            .lease_client
+            .clone()
+            .revoke(LeaseRevokeRequest::new(lease_id))
+            .await?;
+        Ok(())
+    }
+
     /// Propose request and get result with fast/slow path
     async fn propose<T>(
         &self,
@@ -262,6 +352,23 @@ impl LockClient {
             .map_err(Into::into)
     }
 
+    /// Propose a write request, which is tracked by the degraded mode
+    async fn propose_write<T>(
+        &self,
+        request: T,
+        use_fast_path: bool,
+    ) -> Result<(CommandResponse, Option<SyncResponse>)>
+    where
+        T: Into<RequestWrapper>,
+    {
+        let request = request.into();
+        let cmd = Command::new(request.keys(), request);
+        self.degrade
+            .propose_write(&*self.curp_client, &cmd, self.token.as_ref(), use_fast_path)
+            .await?
+            .map_err(Into::into)
+    }
+
     /// Create txn for try acquire lock
     fn create_acquire_txn(prefix: &str, lease_id: i64) -> TxnRequest {
         let key = format!("{prefix}{lease_id:x}");
@@ -349,12 +456,81 @@ impl LockClient {
             key: key.into(),
             ..Default::default()
         };
-        let (cmd_res, _sync_res) = self.propose(del_req, true).await?;
+        let (cmd_res, _sync_res) = self.propose_write(del_req, true).await?;
         let res = Into::<DeleteRangeResponse>::into(cmd_res.into_inner());
         Ok(res.header)
     }
 }
 
+/// Guard of the locks acquired by `LockClient::lock_all`, all locks are released on drop
+pub struct MultiLockGuard {
+    /// The lock client
+    lock_client: LockClient,
+    /// The keys of the held locks, in the acquisition order
+    keys: Vec<Vec<u8>>,
+    /// The lease granted by `LockClient::lock_all` for the locks, which is revoked after
+    /// the releases, `None` if the lease is given by the caller
+    owned_lease: Option<i64>,
+}
+
+impl Debug for MultiLockGuard {
+    #[inline]
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MultiLockGuard")
+            .field("keys", &self.keys)
+            .field("owned_lease", &self.owned_lease)
+            .finish()
+    }
+}
+
+impl MultiLockGuard {
+    /// Get the keys of the held locks, in the acquisition order
+    #[inline]
+    #[must_use]
+    pub fn keys(&self) -> &[Vec<u8>] {
+        &self.keys
+    }
+
+    /// Release all locks and wait for the releases, the lease granted for the locks is
+    /// revoked afterwards
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the inner CURP client encountered a propose failure
+    #[inline]
+    pub async fn unlock(mut self) -> Result<()> {
+        let keys = std::mem::take(&mut self.keys);
+        // release in the reverse order of the acquisition
+        for key in keys.iter().rev() {
+            let _header = self.lock_client.delete_key(key).await?;
+        }
+        if let Some(lease_id) = self.owned_lease.take() {
+            self.lock_client.revoke_lease(lease_id).await?;
+        }
+        Ok(())
+    }
+}
+
+impl Drop for MultiLockGuard {
+    #[inline]
+    fn drop(&mut self) {
+        if self.keys.is_empty() && self.owned_lease.is_none() {
+            return;
+        }
+        let lock_client = self.lock_client.clone();
+        let keys = std::mem::take(&mut self.keys);
+        let owned_lease = self.owned_lease.take();
+        let _ignore = tokio::spawn(async move {
+            for key in keys.iter().rev() {
+                let _ignore = lock_client.delete_key(key).await;
+            }
+            if let Some(lease_id) = owned_lease {
+                let _ignore = lock_client.revoke_lease(lease_id).await;
+            }
+        });
+    }
+}
+
 /// The future that will do the lock operation
 /// This exists because we need to do some clean up after the lock operation has failed or being cancelled
 struct LockFuture<'a> {
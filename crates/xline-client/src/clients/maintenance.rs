@@ -1,11 +1,20 @@
-use std::{fmt::Debug, sync::Arc};
+use std::{collections::VecDeque, fmt::Debug, sync::Arc, time::Duration};
 
+use futures::{stream, Stream};
 use tonic::{transport::Channel, Streaming};
 use xlineapi::{
-    AlarmRequest, AlarmResponse, SnapshotRequest, SnapshotResponse, StatusRequest, StatusResponse,
+    command::Command, AlarmAction, AlarmRequest, AlarmResponse, AlarmType, HashKvRequest,
+    SnapshotRequest, SnapshotResponse, StatusRequest, StatusResponse,
 };
 
-use crate::{error::Result, AuthService};
+use crate::{
+    error::{Result, XlineClientError},
+    types::maintenance::{
+        replication_watermark, AlarmChange, CompactionProgress, ExecutionPoolStatus,
+        HashKvVerification, SnapshotInfo,
+    },
+    AuthService,
+};
 
 /// Client for Maintenance operations.
 #[derive(Clone, Debug)]
@@ -105,6 +114,69 @@ impl MaintenanceClient {
         Ok(self.inner.alarm(request).await?.into_inner())
     }
 
+    /// Subscribes to the alarms of the cluster. The stream emits an `AlarmRaised` for each
+    /// alarm active at the subscription or raised later, and an `AlarmDisarmed` when an alarm
+    /// is disarmed. The alarms are checked every `interval`. A failed check is yielded as an
+    /// error, and the stream keeps checking after a transient error, such as an unreachable
+    /// member or a timeout, while it ends after other errors.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use std::time::Duration;
+    ///
+    /// use futures::StreamExt;
+    /// use xline_client::{types::maintenance::AlarmChange, Client, ClientOptions};
+    /// use anyhow::Result;
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<()> {
+    ///     // the name and address of all curp members
+    ///     let curp_members = ["10.0.0.1:2379", "10.0.0.2:2379", "10.0.0.3:2379"];
+    ///
+    ///     let client = Client::connect(curp_members, ClientOptions::default())
+    ///         .await?
+    ///         .maintenance_client();
+    ///
+    ///     let mut changes = Box::pin(client.watch_alarms(Duration::from_secs(1)));
+    ///     while let Some(change) = changes.next().await {
+    ///         if let AlarmChange::AlarmRaised(alarm) = change? {
+    ///             println!("alarm raised: {alarm}");
+    ///         }
+    ///     }
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    #[inline]
+    pub fn watch_alarms(&self, interval: Duration) -> impl Stream<Item = Result<AlarmChange>> {
+        let state = (self.clone(), Vec::new(), VecDeque::new(), false);
+        stream::unfold(Some(state), move |state| async move {
+            let (mut client, mut alarms, mut pending, failed) = state?;
+            if failed {
+                tokio::time::sleep(interval).await;
+            }
+            loop {
+                if let Some(change) = pending.pop_front() {
+                    return Some((Ok(change), Some((client, alarms, pending, false))));
+                }
+                let request = AlarmRequest::new(AlarmAction::Get, 0, AlarmType::None);
+                let current = match client.alarm(request).await {
+                    Ok(resp) => resp.alarms,
+                    Err(e) => {
+                        let next = is_transient(&e).then_some((client, alarms, pending, true));
+                        return Some((Err(e), next));
+                    }
+                };
+                pending.extend(AlarmChange::diff(&alarms, &current));
+                alarms = current;
+                if pending.is_empty() {
+                    tokio::time::sleep(interval).await;
+                }
+            }
+        })
+    }
+
     /// Sends a status request
     ///
     /// # Errors
@@ -139,4 +211,314 @@ impl MaintenanceClient {
             .await?
             .into_inner())
     }
+
+    /// Gets the information of the latest snapshot of the member, which is taken to calibrate
+    /// a lagging follower or installed from the leader, `None` if the member has not made a
+    /// snapshot since it started
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the inner RPC client encountered a propose failure,
+    /// or the member returns malformed snapshot information
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use xline_client::{Client, ClientOptions};
+    /// use anyhow::Result;
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<()> {
+    ///     // the name and address of all curp members
+    ///     let curp_members = ["10.0.0.1:2379", "10.0.0.2:2379", "10.0.0.3:2379"];
+    ///
+    ///     let mut client = Client::connect(curp_members, ClientOptions::default())
+    ///         .await?
+    ///         .maintenance_client();
+    ///
+    ///     if let Some(info) = client.snapshot_info().await? {
+    ///         println!("last snapshot includes the log index {}", info.last_included_index);
+    ///     }
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    #[inline]
+    pub async fn snapshot_info(&mut self) -> Result<Option<SnapshotInfo>> {
+        let resp = self.inner.status(StatusRequest::default()).await?;
+        SnapshotInfo::from_metadata(resp.metadata())
+    }
+
+    /// Gets the utilization of the command execution pool of the member, so that the pool can
+    /// be sized to the CPU budget, `None` if the member does not report it
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the inner RPC client encountered a propose failure,
+    /// or the member returns a malformed execution pool status
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use xline_client::{Client, ClientOptions};
+    /// use anyhow::Result;
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<()> {
+    ///     // the name and address of all curp members
+    ///     let curp_members = ["10.0.0.1:2379", "10.0.0.2:2379", "10.0.0.3:2379"];
+    ///
+    ///     let mut client = Client::connect(curp_members, ClientOptions::default())
+    ///         .await?
+    ///         .maintenance_client();
+    ///
+    ///     if let Some(status) = client.execution_pool_status().await? {
+    ///         println!("{} of {} workers are busy", status.busy, status.size);
+    ///     }
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    #[inline]
+    pub async fn execution_pool_status(&mut self) -> Result<Option<ExecutionPoolStatus>> {
+        let resp = self.inner.status(StatusRequest::default()).await?;
+        ExecutionPoolStatus::from_metadata(resp.metadata())
+    }
+
+    /// Gets the progress of the latest physical compaction of the member, `None` if the member
+    /// has not run a physical compaction since it started
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the inner RPC client encountered a propose failure,
+    /// or the member returns a malformed compaction progress
+    #[inline]
+    pub async fn compaction_progress(&mut self) -> Result<Option<CompactionProgress>> {
+        let resp = self.inner.status(StatusRequest::default()).await?;
+        CompactionProgress::from_metadata(resp.metadata())
+    }
+
+    /// Gets the log index of the source cluster up to which the member has replicated, `None`
+    /// if the member has never replicated from a source cluster
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the inner RPC client encountered a propose failure,
+    /// or the member returns a malformed replication watermark
+    #[inline]
+    pub async fn replication_watermark(&mut self) -> Result<Option<u64>> {
+        let resp = self.inner.status(StatusRequest::default()).await?;
+        replication_watermark(resp.metadata())
+    }
+
+    /// Gets the replication lag between a source cluster and the passive cluster of this
+    /// member, which is the number of log entries applied by the member of the source but not
+    /// replicated to this member yet. All entries of the source are lagging if this member
+    /// has never replicated.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if a status request to either member fails, or this
+    /// member returns a malformed replication watermark
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use xline_client::{Client, ClientOptions};
+    /// use anyhow::Result;
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<()> {
+    ///     let mut source = Client::connect(["10.0.0.1:2379"], ClientOptions::default())
+    ///         .await?
+    ///         .maintenance_client();
+    ///     let mut target = Client::connect(["10.0.1.1:2379"], ClientOptions::default())
+    ///         .await?
+    ///         .maintenance_client();
+    ///
+    ///     let lag = target.replication_lag(&mut source).await?;
+    ///     println!("the passive cluster lags {lag} entries behind");
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    #[inline]
+    pub async fn replication_lag(&mut self, source: &mut MaintenanceClient) -> Result<u64> {
+        let source_watermark = source.status().await?.raft_applied_index;
+        let watermark = self.replication_watermark().await?.unwrap_or(0);
+        Ok(source_watermark.saturating_sub(watermark))
+    }
+
+    /// Watches the progress of the physical compaction to `revision` on the member by polling
+    /// its status every `interval`. A progress is yielded whenever it changes, and the stream
+    /// ends after the finished progress is yielded.
+    ///
+    /// A failed status request is yielded as an error, and the stream keeps polling after a
+    /// transient error, such as an unreachable member or a timeout, while it ends after other
+    /// errors.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use std::{pin::pin, time::Duration};
+    ///
+    /// use futures::StreamExt;
+    /// use xline_client::{Client, ClientOptions};
+    /// use anyhow::Result;
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<()> {
+    ///     // the name and address of all curp members
+    ///     let curp_members = ["10.0.0.1:2379", "10.0.0.2:2379", "10.0.0.3:2379"];
+    ///
+    ///     let client = Client::connect(curp_members, ClientOptions::default())
+    ///         .await?
+    ///         .maintenance_client();
+    ///
+    ///     let mut progress = pin!(client.watch_compaction_progress(100, Duration::from_millis(500)));
+    ///     while let Some(p) = progress.next().await {
+    ///         println!("compaction is {}% complete", p?.percent());
+    ///     }
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    #[inline]
+    pub fn watch_compaction_progress(
+        &self,
+        revision: i64,
+        interval: Duration,
+    ) -> impl Stream<Item = Result<CompactionProgress>> {
+        let state = (self.clone(), None, false);
+        stream::unfold(Some(state), move |state| async move {
+            let (mut client, mut last, failed) = state?;
+            if failed {
+                tokio::time::sleep(interval).await;
+            }
+            loop {
+                let progress = match client.compaction_progress().await {
+                    Ok(progress) => progress.filter(|p| p.revision == revision),
+                    Err(e) => {
+                        let next = is_transient(&e).then_some((client, last, true));
+                        return Some((Err(e), next));
+                    }
+                };
+                if let Some(progress) = progress {
+                    if last != Some(progress) {
+                        last = Some(progress);
+                        let next = (!progress.finished).then_some((client, last, false));
+                        return Some((Ok(progress), next));
+                    }
+                }
+                tokio::time::sleep(interval).await;
+            }
+        })
+    }
+
+    /// Gets the hash of the keyspace up to the given revision, together with the compact
+    /// revision of the member. A revision of zero or less hashes up to the current revision.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the inner RPC client encountered a propose failure
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use xline_client::{Client, ClientOptions};
+    /// use anyhow::Result;
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<()> {
+    ///     // the name and address of all curp members
+    ///     let curp_members = ["10.0.0.1:2379", "10.0.0.2:2379", "10.0.0.3:2379"];
+    ///
+    ///     let mut client = Client::connect(curp_members, ClientOptions::default())
+    ///         .await?
+    ///         .maintenance_client();
+    ///
+    ///     let (hash, compact_revision) = client.hashkv(0).await?;
+    ///     println!("hash: {hash}, compact revision: {compact_revision}");
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    #[inline]
+    pub async fn hashkv(&mut self, revision: i64) -> Result<(u32, i64)> {
+        let resp = self
+            .inner
+            .hash_kv(HashKvRequest { revision })
+            .await?
+            .into_inner();
+        Ok((resp.hash, resp.compact_revision))
+    }
+
+    /// Compares the keyspace hash of this member with another member at the given revision.
+    /// Pass an explicit revision, as members may be at different current revisions.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the inner RPC client encountered a propose failure
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use xline_client::{Client, ClientOptions};
+    /// use anyhow::Result;
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<()> {
+    ///     let mut local = Client::connect(["10.0.0.1:2379"], ClientOptions::default())
+    ///         .await?
+    ///         .maintenance_client();
+    ///     let mut remote = Client::connect(["10.0.0.2:2379"], ClientOptions::default())
+    ///         .await?
+    ///         .maintenance_client();
+    ///
+    ///     let verification = local.verify_against(&mut remote, 10).await?;
+    ///     println!("consistent: {}", verification.is_consistent());
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    #[inline]
+    pub async fn verify_against(
+        &mut self,
+        other_member: &mut MaintenanceClient,
+        revision: i64,
+    ) -> Result<HashKvVerification> {
+        let local = self.hashkv(revision).await?;
+        let remote = other_member.hashkv(revision).await?;
+        Ok(HashKvVerification::compare(local, remote))
+    }
+}
+
+/// Whether a failed request of a polling stream may succeed later, so that the stream keeps
+/// polling after it. Errors such as malformed responses or unsupported requests are permanent.
+fn is_transient(err: &XlineClientError<Command>) -> bool {
+    matches!(
+        *err,
+        XlineClientError::RpcError(_) | XlineClientError::Timeout
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn polling_streams_should_survive_transient_errors_only() {
+        assert!(is_transient(
+            &tonic::Status::unavailable("member down").into()
+        ));
+        assert!(is_transient(
+            &tonic::Status::deadline_exceeded("timeout").into()
+        ));
+        assert!(!is_transient(
+            &tonic::Status::unimplemented("no alarm").into()
+        ));
+        assert!(!is_transient(&XlineClientError::InvalidArgs(
+            "invalid compaction progress".to_owned()
+        )));
+    }
 }
@@ -1,14 +1,30 @@
+use std::{
+    future::Future,
+    time::{Instant, SystemTime},
+};
+
+use curp::client::{ClientApi, TraceOutcome};
+use xlineapi::command::CurpClient;
+
+use crate::error::Result;
+
 pub use auth::AuthClient;
+pub use cached_view::CachedView;
 pub use cluster::ClusterClient;
 pub use election::ElectionClient;
-pub use kv::KvClient;
+pub(crate) use kv::SizeLimits;
+pub use kv::{GetBuilder, KvClient, TxnScope};
 pub use lease::LeaseClient;
-pub use lock::LockClient;
+pub use lock::{LockClient, MultiLockGuard};
 pub use maintenance::MaintenanceClient;
-pub use watch::WatchClient;
+pub use namespace::{NamespacedClient, NamespacedWatchStreaming, NamespacedWatcher};
+pub use ordered::{OrderedAck, OrderedProposeQueue};
+pub use watch::{CheckpointedWatchStreaming, EphemeralWatchStreaming, WatchClient};
 
 /// Auth client.
 mod auth;
+/// Cached view of a key range.
+mod cached_view;
 /// Cluster client
 mod cluster;
 /// Election client.
@@ -21,5 +37,27 @@ mod lease;
 mod lock;
 /// Maintenance client.
 mod maintenance;
+/// Namespaced client.
+mod namespace;
+/// Ordered propose queue.
+mod ordered;
 /// Watch client.
 mod watch;
+
+/// Run a request which is sent on the own channel of a client and record its trace in the
+/// curp client, see `ClientApi::trace_rpc`
+pub(crate) async fn traced<R>(
+    curp_client: &CurpClient,
+    method: &'static str,
+    request: impl Future<Output = Result<R>>,
+) -> Result<R> {
+    let start = SystemTime::now();
+    let timer = Instant::now();
+    let res = request.await;
+    let outcome = match res {
+        Ok(_) => TraceOutcome::Succeeded,
+        Err(ref err) => TraceOutcome::Failed(tonic::Status::unknown(err.to_string())),
+    };
+    curp_client.trace_rpc(method, None, outcome, start, timer.elapsed());
+    res
+}
@@ -0,0 +1,348 @@
+use xlineapi::{
+    command::KeyRange, DeleteRangeResponse, KeyValue, PutResponse, RangeResponse, Request,
+    RequestOp, Response, ResponseOp, TxnResponse, WatchResponse,
+};
+
+use crate::{
+    clients::{KvClient, WatchClient},
+    error::Result,
+    types::{
+        kv::{DeleteRangeRequest, PutRequest, RangeRequest, TxnRequest},
+        watch::{WatchRequest, WatchStreaming, Watcher},
+    },
+    Client,
+};
+
+/// Client confining all keys into a namespace, the prefix of the namespace is prepended to keys
+/// of requests and stripped from keys of responses, so keys outside the namespace are invisible
+/// through this client.
+#[derive(Clone, Debug)]
+pub struct NamespacedClient {
+    /// The kv client
+    kv: KvClient,
+    /// The watch client
+    watch: WatchClient,
+    /// Prefix of the namespace
+    prefix: Vec<u8>,
+}
+
+impl NamespacedClient {
+    /// New `NamespacedClient` of the namespace `prefix`
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use xline_client::{
+    ///     clients::NamespacedClient, types::kv::PutRequest, Client, ClientOptions,
+    /// };
+    /// use anyhow::Result;
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<()> {
+    ///     let curp_members = ["10.0.0.1:2379", "10.0.0.2:2379", "10.0.0.3:2379"];
+    ///
+    ///     let client = Client::connect(curp_members, ClientOptions::default()).await?;
+    ///     let tenant = NamespacedClient::new(&client, "tenant/");
+    ///
+    ///     // actually writes the key `tenant/key1`
+    ///     tenant.put(PutRequest::new("key1", "value1")).await?;
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    #[inline]
+    #[must_use]
+    pub fn new(inner: &Client, prefix: impl Into<Vec<u8>>) -> Self {
+        Self {
+            kv: inner.kv_client(),
+            watch: inner.watch_client(),
+            prefix: prefix.into(),
+        }
+    }
+
+    /// Get the prefix of the namespace
+    #[inline]
+    #[must_use]
+    pub fn prefix(&self) -> &[u8] {
+        &self.prefix
+    }
+
+    /// Put a key-value into the namespace
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the inner CURP client encountered a propose failure,
+    /// or the key or value exceeds the size limits set in `ClientOptions`
+    #[inline]
+    pub async fn put(&self, mut request: PutRequest) -> Result<PutResponse> {
+        prefix_key(&self.prefix, &mut request.inner.key);
+        let mut resp = self.kv.put(request).await?;
+        self.strip_put_response(&mut resp);
+        Ok(resp)
+    }
+
+    /// Get a range of keys in the namespace
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the inner CURP client encountered a propose failure
+    #[inline]
+    pub async fn range(&self, mut request: RangeRequest) -> Result<RangeResponse> {
+        prefix_range(
+            &self.prefix,
+            &mut request.inner.key,
+            &mut request.inner.range_end,
+        );
+        let mut resp = self.kv.range(request).await?;
+        self.strip_range_response(&mut resp);
+        Ok(resp)
+    }
+
+    /// Delete a range of keys in the namespace
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the inner CURP client encountered a propose failure
+    #[inline]
+    pub async fn delete(&self, mut request: DeleteRangeRequest) -> Result<DeleteRangeResponse> {
+        prefix_range(
+            &self.prefix,
+            &mut request.inner.key,
+            &mut request.inner.range_end,
+        );
+        let mut resp = self.kv.delete(request).await?;
+        self.strip_delete_response(&mut resp);
+        Ok(resp)
+    }
+
+    /// Create a transaction of keys in the namespace
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the inner CURP client encountered a propose failure
+    #[inline]
+    pub async fn txn(&self, mut request: TxnRequest) -> Result<TxnResponse> {
+        self.prefix_txn(&mut request.inner);
+        let mut resp = self.kv.txn(request).await?;
+        self.strip_txn_response(&mut resp);
+        Ok(resp)
+    }
+
+    /// Watch a range of keys in the namespace. The new watches created by the returned
+    /// watcher are confined into the namespace as well, and keys of the events are stripped
+    /// by the returned stream.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the RPC client fails to send request
+    #[inline]
+    pub async fn watch(
+        &mut self,
+        mut request: WatchRequest,
+    ) -> Result<(NamespacedWatcher, NamespacedWatchStreaming)> {
+        prefix_range(
+            &self.prefix,
+            &mut request.inner.key,
+            &mut request.inner.range_end,
+        );
+        let (inner, stream) = self.watch.watch(request).await?;
+        Ok((
+            NamespacedWatcher {
+                inner,
+                prefix: self.prefix.clone(),
+            },
+            NamespacedWatchStreaming {
+                inner: stream,
+                prefix: self.prefix.clone(),
+            },
+        ))
+    }
+
+    /// Prepend the prefix to all keys of the transaction
+    fn prefix_txn(&self, txn: &mut xlineapi::TxnRequest) {
+        for cmp in &mut txn.compare {
+            prefix_range(&self.prefix, &mut cmp.key, &mut cmp.range_end);
+        }
+        for op in txn.success.iter_mut().chain(txn.failure.iter_mut()) {
+            self.prefix_request_op(op);
+        }
+    }
+
+    /// Prepend the prefix to keys of the request op
+    fn prefix_request_op(&self, op: &mut RequestOp) {
+        match op.request {
+            Some(Request::RequestRange(ref mut req)) => {
+                prefix_range(&self.prefix, &mut req.key, &mut req.range_end);
+            }
+            Some(Request::RequestPut(ref mut req)) => prefix_key(&self.prefix, &mut req.key),
+            Some(Request::RequestDeleteRange(ref mut req)) => {
+                prefix_range(&self.prefix, &mut req.key, &mut req.range_end);
+            }
+            Some(Request::RequestTxn(ref mut req)) => self.prefix_txn(req),
+            None => {}
+        }
+    }
+
+    /// Strip the prefix from keys of the put response
+    fn strip_put_response(&self, resp: &mut PutResponse) {
+        if let Some(ref mut kv) = resp.prev_kv {
+            strip_key(&self.prefix, kv);
+        }
+    }
+
+    /// Strip the prefix from keys of the range response
+    fn strip_range_response(&self, resp: &mut RangeResponse) {
+        for kv in &mut resp.kvs {
+            strip_key(&self.prefix, kv);
+        }
+    }
+
+    /// Strip the prefix from keys of the delete response
+    fn strip_delete_response(&self, resp: &mut DeleteRangeResponse) {
+        for kv in &mut resp.prev_kvs {
+            strip_key(&self.prefix, kv);
+        }
+    }
+
+    /// Strip the prefix from keys of the txn response
+    fn strip_txn_response(&self, resp: &mut TxnResponse) {
+        for op in &mut resp.responses {
+            self.strip_response_op(op);
+        }
+    }
+
+    /// Strip the prefix from keys of the response op
+    fn strip_response_op(&self, op: &mut ResponseOp) {
+        match op.response {
+            Some(Response::ResponseRange(ref mut resp)) => self.strip_range_response(resp),
+            Some(Response::ResponsePut(ref mut resp)) => self.strip_put_response(resp),
+            Some(Response::ResponseDeleteRange(ref mut resp)) => {
+                self.strip_delete_response(resp);
+            }
+            Some(Response::ResponseTxn(ref mut resp)) => self.strip_txn_response(resp),
+            None => {}
+        }
+    }
+}
+
+/// Watching handle of a namespace, keys of the new watches are prefixed
+#[derive(Debug)]
+pub struct NamespacedWatcher {
+    /// Inner watcher
+    inner: Watcher,
+    /// Prefix of the namespace
+    prefix: Vec<u8>,
+}
+
+impl NamespacedWatcher {
+    /// The ID of the watcher.
+    #[inline]
+    #[must_use]
+    pub const fn watch_id(&self) -> i64 {
+        self.inner.watch_id()
+    }
+
+    /// Watches for events of a range of keys in the namespace.
+    ///
+    /// # Errors
+    ///
+    /// If sender fails to send to channel
+    #[inline]
+    pub fn watch(&mut self, mut request: WatchRequest) -> Result<()> {
+        prefix_range(
+            &self.prefix,
+            &mut request.inner.key,
+            &mut request.inner.range_end,
+        );
+        self.inner.watch(request)
+    }
+
+    /// Cancels this watcher.
+    ///
+    /// # Errors
+    ///
+    /// If sender fails to send to channel
+    #[inline]
+    pub fn cancel(&mut self) -> Result<()> {
+        self.inner.cancel()
+    }
+
+    /// Cancels watch by specified `watch_id`.
+    ///
+    /// # Errors
+    ///
+    /// If sender fails to send to channel
+    #[inline]
+    pub fn cancel_by_id(&mut self, watch_id: i64) -> Result<()> {
+        self.inner.cancel_by_id(watch_id)
+    }
+
+    /// Requests a watch stream progress status be sent in the watch response stream as soon as
+    /// possible.
+    ///
+    /// # Errors
+    ///
+    /// If sender fails to send to channel
+    #[inline]
+    pub fn request_progress(&mut self) -> Result<()> {
+        self.inner.request_progress()
+    }
+}
+
+/// Watch response stream of a namespace, keys of the events are stripped of the prefix
+#[derive(Debug)]
+pub struct NamespacedWatchStreaming {
+    /// Inner watch stream
+    inner: WatchStreaming,
+    /// Prefix of the namespace
+    prefix: Vec<u8>,
+}
+
+impl NamespacedWatchStreaming {
+    /// Fetch the next message from the stream
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the stream returns an error status
+    #[inline]
+    pub async fn message(&mut self) -> Result<Option<WatchResponse>> {
+        let Some(mut resp) = self.inner.message().await? else {
+            return Ok(None);
+        };
+        for event in &mut resp.events {
+            for kv in event.kv.iter_mut().chain(event.prev_kv.iter_mut()) {
+                strip_key(&self.prefix, kv);
+            }
+        }
+        Ok(Some(resp))
+    }
+}
+
+/// Prepend the prefix to the key
+fn prefix_key(prefix: &[u8], key: &mut Vec<u8>) {
+    let _ig = key.splice(..0, prefix.iter().copied());
+}
+
+/// Prepend the prefix to the range, ranges to the end of the key space are confined to
+/// the end of the namespace
+fn prefix_range(prefix: &[u8], key: &mut Vec<u8>, range_end: &mut Vec<u8>) {
+    if key.as_slice() == [0] && range_end.as_slice() == [0] {
+        // all keys
+        key.clear();
+    }
+    prefix_key(prefix, key);
+    if range_end.as_slice() == [0] {
+        *range_end = KeyRange::get_prefix(prefix);
+    } else if !range_end.is_empty() {
+        prefix_key(prefix, range_end);
+    } else {
+        // a single key
+    }
+}
+
+/// Strip the prefix from the key of the key-value
+fn strip_key(prefix: &[u8], kv: &mut KeyValue) {
+    if kv.key.starts_with(prefix) {
+        let _ig = kv.key.drain(..prefix.len());
+    }
+}
@@ -0,0 +1,116 @@
+use tokio::sync::{mpsc, oneshot};
+use xlineapi::ResponseOp;
+
+use crate::{
+    clients::KvClient,
+    error::{Result, XlineClientError},
+    types::kv::TxnOp,
+};
+
+/// An operation in the queue and the sender of its ack
+type QueuedOp = (xlineapi::Request, oneshot::Sender<Result<ResponseOp>>);
+
+/// A client-side queue of operations, which are proposed one after another in the exact order
+/// they are pushed. Each operation is proposed through the slow path once the previous one is
+/// synced, so that the operations are applied in the pushed order, and each of them is acked
+/// separately.
+///
+/// The order is kept by the client, as the protocol has no streaming propose: it only holds
+/// among the operations pushed to the same queue, and proposing them costs the same as
+/// proposing them one by one. The queue lives in memory, so it does not survive a restart of
+/// the client, and an operation whose ack was not received may or may not have been applied.
+///
+/// Once an operation fails, the queue is closed and all later operations fail without being
+/// proposed, so no operation is applied without the operations pushed before it. Operations
+/// pushed before the queue is dropped are still proposed.
+#[derive(Debug)]
+pub struct OrderedProposeQueue {
+    /// Sender of the operations to the proposing task
+    tx: mpsc::UnboundedSender<QueuedOp>,
+}
+
+impl OrderedProposeQueue {
+    /// Create a new `OrderedProposeQueue` and spawn the proposing task
+    pub(crate) fn new(kv_client: KvClient) -> Self {
+        let (tx, mut rx) = mpsc::unbounded_channel::<QueuedOp>();
+        let _ignore = tokio::spawn(async move {
+            let mut failed = false;
+            while let Some((op, ack)) = rx.recv().await {
+                let res = if failed {
+                    Err(XlineClientError::InternalError(
+                        "an earlier operation of the ordered propose queue failed".to_owned(),
+                    ))
+                } else {
+                    kv_client.propose_op(op).await
+                };
+                failed = res.is_err();
+                // the ack may be dropped by the caller
+                let _ignore = ack.send(res);
+            }
+        });
+        Self { tx }
+    }
+
+    /// Push an operation to the queue, which is proposed after all operations pushed before
+    /// it. The returned ack is resolved once the operation is applied.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use xline_client::{
+    ///     types::kv::{PutRequest, TxnOp},
+    ///     Client, ClientOptions,
+    /// };
+    /// use anyhow::Result;
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<()> {
+    ///     let curp_members = ["10.0.0.1:2379", "10.0.0.2:2379", "10.0.0.3:2379"];
+    ///
+    ///     let client = Client::connect(curp_members, ClientOptions::default())
+    ///         .await?
+    ///         .kv_client();
+    ///
+    ///     let queue = client.ordered_propose_queue();
+    ///     let first = queue.push(TxnOp::put(PutRequest::new("key1", "value1")));
+    ///     let second = queue.push(TxnOp::put(PutRequest::new("key1", "value2")));
+    ///     // the second put is always applied after the first one
+    ///     first.wait().await?;
+    ///     second.wait().await?;
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    #[inline]
+    #[must_use]
+    pub fn push(&self, op: TxnOp) -> OrderedAck {
+        let (tx, rx) = oneshot::channel();
+        // the task keeps running until the queue is dropped
+        let _ignore = self.tx.send((op.into(), tx));
+        OrderedAck { rx }
+    }
+}
+
+/// The ack of an operation pushed to an `OrderedProposeQueue`
+#[derive(Debug)]
+pub struct OrderedAck {
+    /// Receiver of the result of the operation
+    rx: oneshot::Receiver<Result<ResponseOp>>,
+}
+
+impl OrderedAck {
+    /// Wait until the operation is applied and get its response
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the propose of the operation fails, or an earlier
+    /// operation of the queue failed
+    #[inline]
+    pub async fn wait(self) -> Result<ResponseOp> {
+        self.rx.await.unwrap_or_else(|_closed| {
+            Err(XlineClientError::InternalError(
+                "the ordered propose queue stopped unexpectedly".to_owned(),
+            ))
+        })
+    }
+}
@@ -1,27 +1,73 @@
-use std::{fmt::Debug, sync::Arc};
+use std::{
+    collections::VecDeque,
+    fmt::Debug,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
 
 use futures::channel::mpsc::channel;
 use tonic::transport::Channel;
-use xlineapi::{self, RequestUnion};
+use xlineapi::{self, command::CurpClient, RequestUnion, WatchCancelReason};
 
 use crate::{
+    clients::traced,
     error::{Result, XlineClientError},
-    types::watch::{WatchRequest, WatchStreaming, Watcher},
+    key_codec::KeyCodec,
+    types::watch::{
+        ActiveWatches, CheckpointStore, EphemeralEvent, Event, EventType, WatchCheckpoint,
+        WatchRequest, WatchResponse, WatchStreaming, Watcher,
+    },
     AuthService,
 };
 
 /// Channel size for watch request stream
 const CHANNEL_SIZE: usize = 128;
 
+/// Initial delay before re-watching a broken watch stream
+const REWATCH_INIT_DELAY: Duration = Duration::from_millis(50);
+
+/// Max delay before re-watching a broken watch stream
+const REWATCH_MAX_DELAY: Duration = Duration::from_secs(5);
+
+/// Max consecutive re-watches without receiving a response, the stream fails after them
+const REWATCH_MAX_ATTEMPTS: usize = 10;
+
 /// Client for Watch operations.
-#[derive(Clone, Debug)]
+#[derive(Clone)]
 pub struct WatchClient {
+    /// The client running the CURP protocol, used to record the traces of watches
+    curp_client: Option<Arc<CurpClient>>,
     /// The watch RPC client, only communicate with one server at a time
     #[cfg(not(madsim))]
     inner: xlineapi::WatchClient<AuthService<Channel>>,
     /// The watch RPC client, only communicate with one server at a time
     #[cfg(madsim)]
     inner: xlineapi::WatchClient<Channel>,
+    /// The lease RPC client, used to tell whether a deleted key's lease has expired
+    #[cfg(not(madsim))]
+    lease: xlineapi::LeaseClient<AuthService<Channel>>,
+    /// The lease RPC client, used to tell whether a deleted key's lease has expired
+    #[cfg(madsim)]
+    lease: xlineapi::LeaseClient<Channel>,
+    /// Codec of the keys of watches, no codec if not set
+    key_codec: Option<KeyCodec>,
+    /// Active watches of this client and its clones
+    active_watches: Arc<AtomicUsize>,
+}
+
+impl Debug for WatchClient {
+    #[inline]
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("WatchClient")
+            .field("inner", &self.inner)
+            .field("lease", &self.lease)
+            .field("key_codec", &self.key_codec)
+            .field("active_watches", &self.active_watches)
+            .finish()
+    }
 }
 
 impl WatchClient {
@@ -29,14 +75,38 @@ impl WatchClient {
     #[inline]
     #[must_use]
     pub fn new(channel: Channel, token: Option<String>) -> Self {
+        let token = token.and_then(|t| t.parse().ok().map(Arc::new));
         Self {
-            inner: xlineapi::WatchClient::new(AuthService::new(
-                channel,
-                token.and_then(|t| t.parse().ok().map(Arc::new)),
-            )),
+            curp_client: None,
+            inner: xlineapi::WatchClient::new(AuthService::new(channel.clone(), token.clone())),
+            lease: xlineapi::LeaseClient::new(AuthService::new(channel, token)),
+            key_codec: None,
+            active_watches: Arc::new(AtomicUsize::new(0)),
         }
     }
 
+    /// Set the client running the CURP protocol, the creation of watches is traced by it
+    pub(crate) fn with_curp_client(self, curp_client: Arc<CurpClient>) -> Self {
+        Self {
+            curp_client: Some(curp_client),
+            ..self
+        }
+    }
+
+    /// Set the codec of the keys of watches
+    pub(crate) fn with_key_codec(self, key_codec: Option<KeyCodec>) -> Self {
+        Self { key_codec, ..self }
+    }
+
+    /// Get the number of active watches of this client and its clones, which are created and
+    /// neither canceled nor dropped. Watches created on the stream returned by `watch` are
+    /// counted as their responses are fetched from the stream.
+    #[inline]
+    #[must_use]
+    pub fn active_watch_count(&self) -> usize {
+        self.active_watches.load(Ordering::Relaxed)
+    }
+
     /// Watches for events happening or that have happened. Both input and output
     /// are streams; the input stream is for creating and canceling watcher and the output
     /// stream sends events. The entire event history can be watched starting from the
@@ -44,7 +114,10 @@ impl WatchClient {
     ///
     /// # Errors
     ///
-    /// This function will return an error if the RPC client fails to send request
+    /// This function will return an error if the RPC client fails to send request, or
+    /// `XlineClientError::WatchLimitExceeded` if the server rejects the watch because the
+    /// client has too many active watches, or `XlineClientError::PermissionDenied` if the
+    /// client lacks the permission of the watched range
     ///
     /// # Panics
     ///
@@ -87,11 +160,23 @@ impl WatchClient {
     /// ```
     #[inline]
     pub async fn watch(&mut self, request: WatchRequest) -> Result<(Watcher, WatchStreaming)> {
+        match self.curp_client.clone() {
+            Some(curp_client) => traced(&*curp_client, "watch", self.create_watch(request)).await,
+            None => self.create_watch(request).await,
+        }
+    }
+
+    /// Create the watch on a new watch stream
+    async fn create_watch(&mut self, request: WatchRequest) -> Result<(Watcher, WatchStreaming)> {
         let (mut request_sender, request_receiver) =
             channel::<xlineapi::WatchRequest>(CHANNEL_SIZE);
 
+        let mut create = xlineapi::WatchCreateRequest::from(request);
+        if let Some(codec) = self.key_codec {
+            codec.encode_watch(&mut create)?;
+        }
         let request = xlineapi::WatchRequest {
-            request_union: Some(RequestUnion::CreateRequest(request.into())),
+            request_union: Some(RequestUnion::CreateRequest(create)),
         };
 
         request_sender
@@ -103,6 +188,18 @@ impl WatchClient {
         let watch_id = match response_stream.message().await? {
             Some(resp) => {
                 assert!(resp.created, "not a create watch response");
+                if resp.canceled {
+                    let reason = WatchCancelReason::parse(&resp.cancel_reason);
+                    if reason == Some(WatchCancelReason::WatchLimitExceeded) {
+                        return Err(XlineClientError::WatchLimitExceeded);
+                    }
+                    if reason == Some(WatchCancelReason::PermissionDenied) {
+                        let detail = WatchCancelReason::detail(&resp.cancel_reason);
+                        return Err(XlineClientError::PermissionDenied(
+                            detail.unwrap_or_default().to_owned(),
+                        ));
+                    }
+                }
                 resp.watch_id
             }
             None => {
@@ -113,8 +210,541 @@ impl WatchClient {
         };
 
         Ok((
-            Watcher::new(watch_id, request_sender.clone()),
-            WatchStreaming::new(response_stream, request_sender),
+            Watcher::new(watch_id, request_sender.clone()).with_key_codec(self.key_codec),
+            WatchStreaming::new(response_stream, request_sender)
+                .with_key_codec(self.key_codec)
+                .with_active_watches(ActiveWatches::new(Arc::clone(&self.active_watches))),
         ))
     }
+
+    /// Watches until the first event matching the `predicate` arrives, then cancels the watcher
+    /// and returns that event. If `timeout` is given and no matching event arrives in time,
+    /// the watcher will be canceled and `XlineClientError::Timeout` will be returned.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the RPC client fails to send request, the watch
+    /// stream is closed or canceled before a matching event arrives, or the timeout expires
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use std::time::Duration;
+    ///
+    /// use xline_client::{types::watch::WatchRequest, Client, ClientOptions};
+    /// use anyhow::Result;
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<()> {
+    ///     let curp_members = ["10.0.0.1:2379", "10.0.0.2:2379", "10.0.0.3:2379"];
+    ///
+    ///     let client = Client::connect(curp_members, ClientOptions::default()).await?;
+    ///     let mut watch_client = client.watch_client();
+    ///
+    ///     let event = watch_client
+    ///         .watch_until(
+    ///             WatchRequest::new("key1"),
+    ///             |event| event.kv.as_ref().is_some_and(|kv| kv.value == b"ready"),
+    ///             Some(Duration::from_secs(10)),
+    ///         )
+    ///         .await?;
+    ///
+    ///     println!("got event: {event:?}");
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    #[inline]
+    pub async fn watch_until(
+        &mut self,
+        request: WatchRequest,
+        predicate: impl Fn(&Event) -> bool,
+        timeout: Option<Duration>,
+    ) -> Result<Event> {
+        let (mut watcher, mut stream) = self.watch(request).await?;
+        let wait = Self::wait_for_event(&mut stream, &predicate);
+        let result = match timeout {
+            Some(timeout) => tokio::time::timeout(timeout, wait)
+                .await
+                .unwrap_or(Err(XlineClientError::Timeout)),
+            None => wait.await,
+        };
+        // the stream may already be closed, in which case there is nothing to cancel
+        let _ig = watcher.cancel();
+        result
+    }
+
+    /// Watches a single ephemeral key, i.e. a key attached to a lease. Unlike a plain watch,
+    /// the returned stream tells deletions caused by the expiry or revocation of the lease
+    /// from explicit deletions, and re-watches from the last received revision if the
+    /// underlying watch stream breaks. The re-watches back off exponentially and the stream
+    /// fails after 10 consecutive re-watches without a response.
+    ///
+    /// A deletion is regarded as lease-driven if the deleted key was attached to a lease which
+    /// no longer exists when the deletion is received.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the RPC client fails to send request
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use xline_client::{types::watch::EphemeralEvent, Client, ClientOptions};
+    /// use anyhow::Result;
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<()> {
+    ///     let curp_members = ["10.0.0.1:2379", "10.0.0.2:2379", "10.0.0.3:2379"];
+    ///
+    ///     let client = Client::connect(curp_members, ClientOptions::default()).await?;
+    ///     let mut watch_client = client.watch_client();
+    ///
+    ///     let mut stream = watch_client.watch_ephemeral("service/node1").await?;
+    ///     while let Some(event) = stream.message().await? {
+    ///         if let EphemeralEvent::LeaseExpiredDelete(kv) = event {
+    ///             println!("node is gone: {:?}", kv.key);
+    ///         }
+    ///     }
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    #[inline]
+    pub async fn watch_ephemeral(
+        &mut self,
+        key: impl Into<Vec<u8>>,
+    ) -> Result<EphemeralWatchStreaming> {
+        let key = key.into();
+        let (watcher, stream) = self.watch(Self::ephemeral_request(&key, 0)).await?;
+        Ok(EphemeralWatchStreaming {
+            inner: RewatchingStream::new(self.clone(), watcher, stream),
+            key,
+            received: VecDeque::new(),
+            pending: VecDeque::new(),
+            revision: 0,
+        })
+    }
+
+    /// Watches with checkpoints persisted to a user-provided `store`, so that a restarted
+    /// process resumes exactly after the last processed event without duplicates. If the store
+    /// holds a checkpoint, the watch resumes from it and the start revision of the `request` is
+    /// ignored. The stream also re-watches from the last processed event if it breaks, with the
+    /// same backoff as [`WatchClient::watch_ephemeral`].
+    ///
+    /// An event returned by the stream is regarded as processed once the next event is
+    /// requested or [`CheckpointedWatchStreaming::checkpoint`] is called, and the checkpoint is
+    /// saved every `checkpoint_interval` processed events. An interval of 1 saves after every
+    /// event, larger intervals save less often and at most `checkpoint_interval - 1` processed
+    /// events are received again after a restart.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the store fails to load, or the RPC client fails
+    /// to send request
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use std::sync::Mutex;
+    ///
+    /// use xline_client::{
+    ///     error::Result,
+    ///     types::watch::{CheckpointStore, WatchCheckpoint, WatchRequest},
+    ///     Client, ClientOptions,
+    /// };
+    ///
+    /// #[derive(Debug, Default)]
+    /// struct MemStore(Mutex<Option<WatchCheckpoint>>);
+    ///
+    /// impl CheckpointStore for MemStore {
+    ///     fn load(&self) -> Result<Option<WatchCheckpoint>> {
+    ///         Ok(*self.0.lock().unwrap())
+    ///     }
+    ///
+    ///     fn save(&self, checkpoint: WatchCheckpoint) -> Result<()> {
+    ///         *self.0.lock().unwrap() = Some(checkpoint);
+    ///         Ok(())
+    ///     }
+    /// }
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> anyhow::Result<()> {
+    ///     let curp_members = ["10.0.0.1:2379", "10.0.0.2:2379", "10.0.0.3:2379"];
+    ///
+    ///     let client = Client::connect(curp_members, ClientOptions::default()).await?;
+    ///     let mut watch_client = client.watch_client();
+    ///
+    ///     let mut stream = watch_client
+    ///         .watch_with_checkpoint(WatchRequest::new("key1"), MemStore::default(), 1)
+    ///         .await?;
+    ///     while let Some(event) = stream.message().await? {
+    ///         println!("got event: {event:?}");
+    ///     }
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    #[inline]
+    pub async fn watch_with_checkpoint<S: CheckpointStore>(
+        &mut self,
+        request: WatchRequest,
+        store: S,
+        checkpoint_interval: usize,
+    ) -> Result<CheckpointedWatchStreaming<S>> {
+        let resume = store.load()?;
+        let start = match resume {
+            Some(checkpoint) => request.clone().with_start_revision(checkpoint.revision),
+            None => request.clone(),
+        };
+        let (watcher, stream) = self.watch(start).await?;
+        Ok(CheckpointedWatchStreaming {
+            inner: RewatchingStream::new(self.clone(), watcher, stream),
+            request,
+            store,
+            checkpoint_interval: checkpoint_interval.max(1),
+            pending: VecDeque::new(),
+            cursor: None,
+            resume,
+            last: None,
+            unsaved: 0,
+        })
+    }
+
+    /// Build the watch request of an ephemeral key, a zero `start_revision` watches from now
+    fn ephemeral_request(key: &[u8], start_revision: i64) -> WatchRequest {
+        // progress notifications keep the revision to re-watch from up to date
+        WatchRequest::new(key)
+            .with_prev_kv()
+            .with_progress_notify()
+            .with_start_revision(start_revision)
+    }
+
+    /// Whether the lease no longer exists
+    async fn lease_expired(&mut self, lease: i64) -> Result<bool> {
+        let req = xlineapi::LeaseTimeToLiveRequest {
+            id: lease,
+            keys: false,
+        };
+        match self.lease.lease_time_to_live(req).await {
+            Ok(resp) => Ok(resp.into_inner().ttl < 0),
+            Err(status) if status.code() == tonic::Code::NotFound => Ok(true),
+            Err(status) => Err(status.into()),
+        }
+    }
+
+    /// Waits on the stream for the first event matching the `predicate`
+    async fn wait_for_event(
+        stream: &mut WatchStreaming,
+        predicate: &impl Fn(&Event) -> bool,
+    ) -> Result<Event> {
+        while let Some(resp) = stream.message().await? {
+            if let Some(event) = resp.events.into_iter().find(|event| predicate(event)) {
+                return Ok(event);
+            }
+            if resp.canceled {
+                return Err(XlineClientError::WatchError(format!(
+                    "watch canceled before a matching event arrived: {}",
+                    resp.cancel_reason
+                )));
+            }
+        }
+        Err(XlineClientError::WatchError(String::from(
+            "watch stream closed before a matching event arrived",
+        )))
+    }
+}
+
+/// Watch stream which re-watches after it breaks, with a bounded exponential backoff between
+/// the re-watches
+#[derive(Debug)]
+struct RewatchingStream {
+    /// The watch client, used to re-watch
+    client: WatchClient,
+    /// The current watcher
+    watcher: Watcher,
+    /// The current watch stream
+    stream: WatchStreaming,
+    /// Delay before the next re-watch
+    delay: Duration,
+    /// Re-watches since the last received response
+    attempts: usize,
+}
+
+impl RewatchingStream {
+    /// New `RewatchingStream` of a created watch
+    fn new(client: WatchClient, watcher: Watcher, stream: WatchStreaming) -> Self {
+        Self {
+            client,
+            watcher,
+            stream,
+            delay: REWATCH_INIT_DELAY,
+            attempts: 0,
+        }
+    }
+
+    /// Fetch the next response, return `None` if the watch is canceled. If the stream breaks,
+    /// it re-watches with the request built by `rewatch_request`, which is called before
+    /// every re-watch.
+    async fn message(
+        &mut self,
+        mut rewatch_request: impl FnMut() -> WatchRequest,
+    ) -> Result<Option<WatchResponse>> {
+        loop {
+            let mut err = match self.stream.message().await {
+                Ok(Some(resp)) => {
+                    self.delay = REWATCH_INIT_DELAY;
+                    self.attempts = 0;
+                    if resp.compact_revision != 0 {
+                        return Err(XlineClientError::WatchError(format!(
+                            "required revision {} has been compacted",
+                            resp.compact_revision
+                        )));
+                    }
+                    if resp.canceled {
+                        return Ok(None);
+                    }
+                    return Ok(Some(resp));
+                }
+                Ok(None) => XlineClientError::WatchError(String::from("watch stream closed")),
+                Err(err) => err.into(),
+            };
+            loop {
+                self.back_off(err).await?;
+                match self.client.watch(rewatch_request()).await {
+                    Ok((watcher, stream)) => {
+                        self.watcher = watcher;
+                        self.stream = stream;
+                        break;
+                    }
+                    Err(e) => err = e,
+                }
+            }
+        }
+    }
+
+    /// Wait before the next re-watch, return the last error `err` if the re-watches keep
+    /// failing
+    async fn back_off(&mut self, err: XlineClientError) -> Result<()> {
+        if self.attempts >= REWATCH_MAX_ATTEMPTS {
+            return Err(err);
+        }
+        self.attempts = self.attempts.saturating_add(1);
+        tokio::time::sleep(self.delay).await;
+        self.delay = self.delay.saturating_mul(2).min(REWATCH_MAX_DELAY);
+        Ok(())
+    }
+}
+
+/// Event stream of an ephemeral key, see [`WatchClient::watch_ephemeral`]
+#[derive(Debug)]
+pub struct EphemeralWatchStreaming {
+    /// The underlying watch stream
+    inner: RewatchingStream,
+    /// The watched key
+    key: Vec<u8>,
+    /// Received events not yet classified
+    received: VecDeque<Event>,
+    /// Classified events not yet returned
+    pending: VecDeque<EphemeralEvent>,
+    /// The latest revision received
+    revision: i64,
+}
+
+impl EphemeralWatchStreaming {
+    /// Fetch the next event of the key, return `None` if the watch is canceled
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the watched revisions have been compacted, the
+    /// stream keeps failing to re-watch, or the lease of a deleted key can not be looked up.
+    /// The events of a failed lookup are classified again in the next call.
+    #[inline]
+    pub async fn message(&mut self) -> Result<Option<EphemeralEvent>> {
+        loop {
+            // classify all events of a response as soon as it is received rather than when
+            // they are returned, so that a slow consumer does not mistake an explicit delete
+            // for a lease-driven one when the lease expires in the meantime
+            while let Some(event) = self.received.pop_front() {
+                match self.classify(&event).await {
+                    Ok(classified) => self.pending.push_back(classified),
+                    Err(err) => {
+                        self.received.push_front(event);
+                        return Err(err);
+                    }
+                }
+            }
+            if let Some(event) = self.pending.pop_front() {
+                return Ok(Some(event));
+            }
+            let key = &self.key;
+            let revision = self.revision;
+            // re-watch from the next revision of the latest revision received
+            let Some(resp) = self
+                .inner
+                .message(|| {
+                    let start_revision = if revision > 0 {
+                        revision.saturating_add(1)
+                    } else {
+                        0
+                    };
+                    WatchClient::ephemeral_request(key, start_revision)
+                })
+                .await?
+            else {
+                return Ok(None);
+            };
+            self.advance(&resp);
+            self.received.extend(resp.events);
+        }
+    }
+
+    /// Get the watcher of the current watch, which is replaced after a re-watch
+    #[inline]
+    #[must_use]
+    pub fn watcher(&mut self) -> &mut Watcher {
+        &mut self.inner.watcher
+    }
+
+    /// Advance the latest revision received by the response
+    fn advance(&mut self, resp: &WatchResponse) {
+        let revision = if resp.events.is_empty() {
+            resp.header.as_ref().map_or(0, |h| h.revision)
+        } else {
+            resp.events
+                .iter()
+                .filter_map(|event| event.kv.as_ref().map(|kv| kv.mod_revision))
+                .max()
+                .unwrap_or(0)
+        };
+        self.revision = self.revision.max(revision);
+    }
+
+    /// Tell a lease-driven deletion from an explicit one
+    async fn classify(&mut self, event: &Event) -> Result<EphemeralEvent> {
+        let is_delete = event.r#type() == EventType::Delete;
+        let Some(kv) = (if is_delete {
+            event.prev_kv.as_ref().or(event.kv.as_ref())
+        } else {
+            event.kv.as_ref()
+        }) else {
+            return Err(XlineClientError::WatchError(
+                "watch event without key-value".to_owned(),
+            ));
+        };
+        if !is_delete {
+            return Ok(EphemeralEvent::Put(kv.clone()));
+        }
+        if kv.lease != 0 && self.inner.client.lease_expired(kv.lease).await? {
+            return Ok(EphemeralEvent::LeaseExpiredDelete(kv.clone()));
+        }
+        Ok(EphemeralEvent::Delete(kv.clone()))
+    }
+}
+
+/// Event stream with persisted checkpoints, see [`WatchClient::watch_with_checkpoint`]
+#[derive(Debug)]
+pub struct CheckpointedWatchStreaming<S> {
+    /// The underlying watch stream
+    inner: RewatchingStream,
+    /// The original watch request
+    request: WatchRequest,
+    /// The checkpoint store
+    store: S,
+    /// Number of processed events between two checkpoints
+    checkpoint_interval: usize,
+    /// Received events not yet returned
+    pending: VecDeque<Event>,
+    /// Revision and index of the latest event received by the current watch
+    cursor: Option<(i64, usize)>,
+    /// The checkpoint the current watch resumes from, events it covers are skipped
+    resume: Option<WatchCheckpoint>,
+    /// Position of the last returned event
+    last: Option<WatchCheckpoint>,
+    /// Number of processed events since the last checkpoint
+    unsaved: usize,
+}
+
+impl<S: CheckpointStore> CheckpointedWatchStreaming<S> {
+    /// Fetch the next event, which marks the previously returned event as processed. Return
+    /// `None` if the watch is canceled.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the store fails to save, the watched revisions
+    /// have been compacted, or the stream keeps failing to re-watch
+    #[inline]
+    pub async fn message(&mut self) -> Result<Option<Event>> {
+        if self.last.is_some() {
+            self.unsaved = self.unsaved.saturating_add(1);
+            if self.unsaved >= self.checkpoint_interval {
+                self.checkpoint()?;
+            }
+        }
+        loop {
+            while let Some(event) = self.pending.pop_front() {
+                let revision = event.kv.as_ref().map_or(0, |kv| kv.mod_revision);
+                let event_index = match self.cursor {
+                    Some((prev, index)) if prev == revision => index.saturating_add(1),
+                    _ => 0,
+                };
+                self.cursor = Some((revision, event_index));
+                if self
+                    .resume
+                    .is_some_and(|resume| resume.covers(revision, event_index))
+                {
+                    continue;
+                }
+                self.last = Some(WatchCheckpoint::new(
+                    self.inner.watcher.watch_id(),
+                    revision,
+                    event_index,
+                ));
+                return Ok(Some(event));
+            }
+            let (request, last, resume) = (&self.request, self.last, &mut self.resume);
+            let (pending, cursor) = (&mut self.pending, &mut self.cursor);
+            // re-watch from the revision of the last returned event, events up to it are
+            // skipped
+            let Some(resp) = self
+                .inner
+                .message(|| {
+                    *resume = last.or(*resume);
+                    pending.clear();
+                    *cursor = None;
+                    match *resume {
+                        Some(checkpoint) => {
+                            request.clone().with_start_revision(checkpoint.revision)
+                        }
+                        None => request.clone(),
+                    }
+                })
+                .await?
+            else {
+                return Ok(None);
+            };
+            self.pending.extend(resp.events);
+        }
+    }
+
+    /// Mark the last returned event as processed and save its position to the store
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the store fails to save
+    #[inline]
+    pub fn checkpoint(&mut self) -> Result<()> {
+        if let Some(last) = self.last {
+            self.store.save(last)?;
+        }
+        self.unsaved = 0;
+        Ok(())
+    }
+
+    /// Get the watcher of the current watch, which is replaced after a re-watch
+    #[inline]
+    #[must_use]
+    pub fn watcher(&mut self) -> &mut Watcher {
+        &mut self.inner.watcher
+    }
 }
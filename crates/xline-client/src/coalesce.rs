@@ -0,0 +1,262 @@
+use std::{
+    collections::{hash_map::Entry, HashMap},
+    future::Future,
+    mem,
+    sync::Arc,
+    time::Duration,
+};
+
+use parking_lot::Mutex;
+use tokio::sync::oneshot;
+
+use crate::{
+    error::{Result, XlineClientError},
+    types::kv::{PutRequest, PutResponse},
+};
+
+/// Sender of the result of a put
+type ResultSender = oneshot::Sender<Result<PutResponse>>;
+
+/// Puts of a key with identical options, which are coalesced into the latest one
+#[derive(Debug)]
+struct PutGroup {
+    /// The latest put, which supersedes the earlier ones
+    latest: PutRequest,
+    /// Sender of the result of the latest put
+    latest_tx: ResultSender,
+    /// Senders of the results of the superseded puts
+    superseded: Vec<ResultSender>,
+}
+
+impl PutGroup {
+    /// Create a new `PutGroup` of a single put
+    fn new(request: PutRequest, tx: ResultSender) -> Self {
+        Self {
+            latest: request,
+            latest_tx: tx,
+            superseded: Vec::new(),
+        }
+    }
+}
+
+/// Whether two puts of the same key have identical options other than the value, so that the
+/// later one can supersede the earlier one
+fn same_options(earlier: &PutRequest, later: &PutRequest) -> bool {
+    earlier.lease() == later.lease()
+        && earlier.prev_kv() == later.prev_kv()
+        && earlier.ignore_value() == later.ignore_value()
+        && earlier.ignore_lease() == later.ignore_lease()
+        && earlier.delta_encode() == later.delta_encode()
+}
+
+/// Coalesces the puts of the same key within a window into the latest one, so that a key
+/// updated continuously is proposed once per window. Only consecutive puts with identical
+/// options are coalesced, a put with other options is proposed on its own after the puts
+/// before it. All puts coalesced resolve with the result of the latest one, except that the
+/// previous key-value is only returned to the latest put, as the superseded ones are never
+/// applied.
+#[derive(Debug)]
+pub(crate) struct WriteCoalescer {
+    /// The window in which puts of the same key are coalesced
+    window: Duration,
+    /// Pending puts indexed by the key, grouped in the order they are put
+    pending: Mutex<HashMap<Vec<u8>, Vec<PutGroup>>>,
+}
+
+impl WriteCoalescer {
+    /// Create a new `WriteCoalescer`
+    pub(crate) fn new(window: Duration) -> Self {
+        Self {
+            window,
+            pending: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Put `request` coalesced with the other puts of the same key within the window. The
+    /// first put of a window spawns a task proposing the latest put of each group by its
+    /// `propose` once the window ends, the `propose` of the later puts is not used.
+    pub(crate) async fn put<F, Fut>(
+        self: &Arc<Self>,
+        request: PutRequest,
+        propose: F,
+    ) -> Result<PutResponse>
+    where
+        F: Fn(PutRequest) -> Fut + Send + 'static,
+        Fut: Future<Output = Result<PutResponse>> + Send + 'static,
+    {
+        let (tx, rx) = oneshot::channel();
+        let key = request.key().to_vec();
+        let first = match self.pending.lock().entry(key.clone()) {
+            Entry::Occupied(mut entry) => {
+                let groups = entry.get_mut();
+                match groups.last_mut() {
+                    Some(group) if same_options(&group.latest, &request) => {
+                        group.latest = request;
+                        let prev_tx = mem::replace(&mut group.latest_tx, tx);
+                        group.superseded.push(prev_tx);
+                    }
+                    _ => groups.push(PutGroup::new(request, tx)),
+                }
+                false
+            }
+            Entry::Vacant(entry) => {
+                let _groups = entry.insert(vec![PutGroup::new(request, tx)]);
+                true
+            }
+        };
+        if first {
+            let coalescer = Arc::clone(self);
+            let _ignore = tokio::spawn(async move { coalescer.flush(key, propose).await });
+        }
+        rx.await.unwrap_or_else(|_closed| {
+            Err(XlineClientError::InternalError(
+                "the coalesced put stopped unexpectedly".to_owned(),
+            ))
+        })
+    }
+
+    /// Propose the latest put of each group of `key` in order once the window ends, and
+    /// resolve all puts coalesced
+    async fn flush<F, Fut>(&self, key: Vec<u8>, propose: F)
+    where
+        F: Fn(PutRequest) -> Fut,
+        Fut: Future<Output = Result<PutResponse>>,
+    {
+        tokio::time::sleep(self.window).await;
+        let Some(groups) = self.pending.lock().remove(&key) else {
+            return;
+        };
+        for group in groups {
+            let res = propose(group.latest).await;
+            for tx in group.superseded {
+                let superseded_res = match res {
+                    Ok(ref resp) => Ok(PutResponse {
+                        prev_kv: None,
+                        ..resp.clone()
+                    }),
+                    Err(ref err) => Err(err.clone()),
+                };
+                // the caller may not wait for the result
+                let _ignore = tx.send(superseded_res);
+            }
+            let _ignore = group.latest_tx.send(res);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use xlineapi::{KeyValue, ResponseHeader};
+
+    use super::*;
+
+    /// Put to the coalescer, which records the proposed values and their leases in `proposed`
+    async fn recorded_put(
+        coalescer: &Arc<WriteCoalescer>,
+        proposed: &Arc<Mutex<Vec<(Vec<u8>, i64)>>>,
+        request: PutRequest,
+    ) -> Result<PutResponse> {
+        let proposed = Arc::clone(proposed);
+        coalescer
+            .put(request, move |request| {
+                let proposed = Arc::clone(&proposed);
+                async move {
+                    proposed
+                        .lock()
+                        .push((request.value().to_vec(), request.lease()));
+                    Ok(PutResponse {
+                        header: Some(ResponseHeader {
+                            revision: 2,
+                            ..ResponseHeader::default()
+                        }),
+                        prev_kv: request.prev_kv().then(|| KeyValue {
+                            value: b"v0".to_vec(),
+                            ..KeyValue::default()
+                        }),
+                    })
+                }
+            })
+            .await
+    }
+
+    #[tokio::test]
+    async fn puts_within_window_should_be_coalesced_into_the_latest() {
+        let coalescer = Arc::new(WriteCoalescer::new(Duration::from_millis(100)));
+        let proposed = Arc::new(Mutex::new(Vec::new()));
+        let put = |value: &'static str| {
+            recorded_put(&coalescer, &proposed, PutRequest::new("key", value))
+        };
+        let results = tokio::join!(put("v1"), put("v2"), put("v3"), put("v4"), put("v5"));
+        assert_eq!(*proposed.lock(), vec![(b"v5".to_vec(), 0)]);
+        for res in [results.0, results.1, results.2, results.3, results.4] {
+            assert_eq!(res.unwrap().header.unwrap().revision, 2);
+        }
+    }
+
+    #[tokio::test]
+    async fn puts_with_other_options_should_not_be_coalesced() {
+        let coalescer = Arc::new(WriteCoalescer::new(Duration::from_millis(100)));
+        let proposed = Arc::new(Mutex::new(Vec::new()));
+        let put = |value: &'static str, lease: i64| {
+            recorded_put(
+                &coalescer,
+                &proposed,
+                PutRequest::new("key", value).with_lease(lease),
+            )
+        };
+        let _results = tokio::join!(put("v1", 0), put("v2", 0), put("v3", 1), put("v4", 1));
+        // the groups of identical options are proposed in the order they are put
+        assert_eq!(
+            *proposed.lock(),
+            vec![(b"v2".to_vec(), 0), (b"v4".to_vec(), 1)]
+        );
+    }
+
+    #[tokio::test]
+    async fn superseded_puts_should_not_get_the_prev_kv() {
+        let coalescer = Arc::new(WriteCoalescer::new(Duration::from_millis(100)));
+        let proposed = Arc::new(Mutex::new(Vec::new()));
+        let put = |value: &'static str| {
+            recorded_put(
+                &coalescer,
+                &proposed,
+                PutRequest::new("key", value).with_prev_kv(true),
+            )
+        };
+        let (superseded, latest) = tokio::join!(put("v1"), put("v2"));
+        assert_eq!(superseded.unwrap().prev_kv, None);
+        assert_eq!(latest.unwrap().prev_kv.unwrap().value, b"v0");
+    }
+
+    #[tokio::test]
+    async fn coalesced_puts_should_get_the_original_error() {
+        let coalescer = Arc::new(WriteCoalescer::new(Duration::from_millis(100)));
+        let put = |value: &'static str| {
+            coalescer.put(PutRequest::new("key", value), |_request| async {
+                Err(XlineClientError::Timeout)
+            })
+        };
+        let (superseded, latest) = tokio::join!(put("v1"), put("v2"));
+        assert!(matches!(superseded, Err(XlineClientError::Timeout)));
+        assert!(matches!(latest, Err(XlineClientError::Timeout)));
+    }
+
+    #[tokio::test]
+    async fn puts_after_window_should_be_proposed_again() {
+        let coalescer = Arc::new(WriteCoalescer::new(Duration::from_millis(10)));
+        let count = Arc::new(AtomicUsize::new(0));
+        for value in ["v1", "v2"] {
+            let count = Arc::clone(&count);
+            let res = coalescer
+                .put(PutRequest::new("key", value), move |_request| {
+                    let _prev = count.fetch_add(1, Ordering::Relaxed);
+                    async { Err(XlineClientError::Timeout) }
+                })
+                .await;
+            assert!(matches!(res, Err(XlineClientError::Timeout)));
+        }
+        assert_eq!(count.load(Ordering::Relaxed), 2);
+    }
+}
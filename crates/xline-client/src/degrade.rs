@@ -0,0 +1,207 @@
+use std::{
+    future::Future,
+    sync::{Mutex, PoisonError},
+    time::{Duration, Instant},
+};
+
+use curp::rpc::{CurpError, InternalErrorKind};
+use xlineapi::{
+    command::{Command, CommandResponse, SyncResponse},
+    execute_error::ExecuteError,
+};
+
+use crate::{
+    error::{Result, XlineClientError},
+    CurpClient,
+};
+
+/// Interval between two probing writes in the read-only mode
+const PROBE_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Mode of the client
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ClientMode {
+    /// Reads and writes are served by the cluster
+    #[default]
+    Normal,
+    /// The quorum of the cluster is lost. Serializable reads are served by the available
+    /// servers, and writes fail fast with `XlineClientError::ClusterDegraded`, except for a
+    /// probing write in a while, which brings the client back to normal once it succeeds.
+    ReadOnly,
+}
+
+/// State of the degraded mode
+#[derive(Debug, Default)]
+struct DegradeStateInner {
+    /// Current mode
+    mode: ClientMode,
+    /// Number of consecutive writes failed for the unreachable quorum
+    failures: usize,
+    /// Time of the last probing write in the read-only mode
+    last_probe: Option<Instant>,
+}
+
+/// Tracks the failures of writes, and switches the client to the read-only mode after
+/// `threshold` consecutive writes fail for the unreachable quorum
+#[derive(Debug, Default)]
+pub(crate) struct DegradeState {
+    /// Number of consecutive failures to switch to the read-only mode, `None` means the
+    /// client never degrades
+    threshold: Option<usize>,
+    /// Inner state
+    inner: Mutex<DegradeStateInner>,
+}
+
+impl DegradeState {
+    /// Create a new `DegradeState`
+    pub(crate) fn new(threshold: Option<usize>) -> Self {
+        Self {
+            threshold,
+            inner: Mutex::new(DegradeStateInner::default()),
+        }
+    }
+
+    /// Get the current mode
+    pub(crate) fn mode(&self) -> ClientMode {
+        self.inner
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .mode
+    }
+
+    /// Whether a write is sent to the cluster at `now`, in the read-only mode only a probing
+    /// write is permitted in every `PROBE_INTERVAL`
+    pub(crate) fn permits_write(&self, now: Instant) -> bool {
+        let mut inner = self.inner.lock().unwrap_or_else(PoisonError::into_inner);
+        if inner.mode == ClientMode::Normal {
+            return true;
+        }
+        let probe_due = inner.last_probe.map_or(true, |last| {
+            now.saturating_duration_since(last) >= PROBE_INTERVAL
+        });
+        if probe_due {
+            inner.last_probe = Some(now);
+        }
+        probe_due
+    }
+
+    /// Send a write tracked by the degraded mode. The write fails fast with
+    /// `XlineClientError::ClusterDegraded` if the client is in the read-only mode. A write
+    /// served by the cluster brings the client back to normal, even if the command fails,
+    /// while a write whose retries are exhausted for the unreachable quorum counts towards
+    /// the sustained quorum loss. Other failures change nothing.
+    pub(crate) async fn track<R>(
+        &self,
+        write: impl Future<Output = std::result::Result<R, tonic::Status>>,
+    ) -> Result<R> {
+        if !self.permits_write(Instant::now()) {
+            return Err(XlineClientError::ClusterDegraded);
+        }
+        let res = write.await;
+        match res {
+            Ok(_) => self.observe_success(),
+            Err(ref err) if Self::is_quorum_lost(err) => self.observe_quorum_loss(),
+            Err(_) => {}
+        }
+        res.map_err(Into::into)
+    }
+
+    /// Propose a write through the CURP client, which is tracked by the degraded mode
+    pub(crate) async fn propose_write(
+        &self,
+        curp_client: &CurpClient,
+        cmd: &Command,
+        token: Option<&String>,
+        use_fast_path: bool,
+    ) -> Result<std::result::Result<(CommandResponse, Option<SyncResponse>), ExecuteError>> {
+        self.track(curp_client.propose(cmd, token, use_fast_path))
+            .await
+    }
+
+    /// Whether a write fails for the unreachable quorum. The server giving up on the deadline
+    /// of a slow command does not mean a quorum loss, neither do servers disagreeing on the
+    /// leader during an election, which is reported as aborted.
+    fn is_quorum_lost(err: &tonic::Status) -> bool {
+        matches!(
+            err.code(),
+            tonic::Code::DeadlineExceeded | tonic::Code::Unavailable
+        ) && CurpError::from(err.clone()).internal_kind()
+            != Some(InternalErrorKind::DeadlineExceeded)
+    }
+
+    /// Record a write served by the cluster, which brings the client back to normal
+    fn observe_success(&self) {
+        if self.threshold.is_some() {
+            *self.inner.lock().unwrap_or_else(PoisonError::into_inner) =
+                DegradeStateInner::default();
+        }
+    }
+
+    /// Record a write failed for the unreachable quorum
+    fn observe_quorum_loss(&self) {
+        let Some(threshold) = self.threshold else {
+            return;
+        };
+        let mut inner = self.inner.lock().unwrap_or_else(PoisonError::into_inner);
+        inner.failures = inner.failures.saturating_add(1);
+        if inner.failures >= threshold {
+            inner.mode = ClientMode::ReadOnly;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn sustained_quorum_loss_should_switch_to_read_only() {
+        let state = DegradeState::new(Some(2));
+        let now = Instant::now();
+        state.observe_quorum_loss();
+        assert_eq!(state.mode(), ClientMode::Normal);
+        assert!(state.permits_write(now));
+        state.observe_quorum_loss();
+        assert_eq!(state.mode(), ClientMode::ReadOnly);
+
+        // writes fail fast except for a probing write in a while
+        assert!(state.permits_write(now));
+        state.observe_quorum_loss();
+        assert!(!state.permits_write(now));
+        assert!(!state.permits_write(now + Duration::from_secs(1)));
+        assert!(state.permits_write(now + PROBE_INTERVAL));
+
+        // a successful probe brings the client back
+        state.observe_success();
+        assert_eq!(state.mode(), ClientMode::Normal);
+        assert!(state.permits_write(now));
+    }
+
+    #[test]
+    fn only_quorum_loss_should_count_towards_read_only() {
+        let timeout = tonic::Status::deadline_exceeded("request timeout");
+        assert!(DegradeState::is_quorum_lost(&timeout));
+        let unavailable = tonic::Status::unavailable("no leader");
+        assert!(DegradeState::is_quorum_lost(&unavailable));
+        let denied = tonic::Status::permission_denied("no permission");
+        assert!(!DegradeState::is_quorum_lost(&denied));
+        let leader_conflict = tonic::Status::from(CurpError::Internal(
+            "curp internal: leader conflict".to_owned(),
+        ));
+        assert!(!DegradeState::is_quorum_lost(&leader_conflict));
+        let deadline_exceeded = tonic::Status::from(CurpError::Internal(
+            "curp internal: deadline exceeded".to_owned(),
+        ));
+        assert!(!DegradeState::is_quorum_lost(&deadline_exceeded));
+    }
+
+    #[test]
+    fn client_without_threshold_should_never_degrade() {
+        let state = DegradeState::new(None);
+        for _ in 0..10 {
+            state.observe_quorum_loss();
+        }
+        assert_eq!(state.mode(), ClientMode::Normal);
+    }
+}
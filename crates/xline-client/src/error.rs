@@ -45,7 +45,7 @@ impl From<tonic::Status> for XlineClientBuildError {
 }
 
 /// The error type for `xline-client`
-#[derive(Error, Debug)]
+#[derive(Error, Debug, Clone)]
 #[non_exhaustive]
 pub enum XlineClientError<C: CurpCommand> {
     /// Command error
@@ -69,6 +69,12 @@ pub enum XlineClientError<C: CurpCommand> {
     /// Error in watch client
     #[error("Watch client error: {0}")]
     WatchError(String),
+    /// The server rejects the watch because the client has too many active watches
+    #[error("Watch limit exceeded, the client has too many active watches")]
+    WatchLimitExceeded,
+    /// The server rejects the watch because the client lacks the permission of the range
+    #[error("Permission denied: {0}")]
+    PermissionDenied(String),
     /// Error in lease client
     #[error("Lease client error: {0}")]
     LeaseError(String),
@@ -78,12 +84,27 @@ pub enum XlineClientError<C: CurpCommand> {
     /// Server is shutting down
     #[error("Curp Server is shutting down")]
     ShuttingDown,
+    /// No leader is elected, linearizable reads are unavailable until the election completes
+    #[error("No leader is elected, linearizable reads are unavailable during the election")]
+    LeaderElection,
+    /// The client is in the read-only mode for the sustained quorum loss, writes fail fast
+    #[error("Cluster is degraded, writes are rejected in the read-only mode")]
+    ClusterDegraded,
     /// Serialize and Deserialize Error
     #[error("EncodeDecode error: {0}")]
     EncodeDecode(String),
     /// Wrong cluster version
     #[error("Wrong cluster version")]
     WrongClusterVersion,
+    /// The server does not support a feature required by the request
+    #[error("Unsupported by server: {0}")]
+    UnsupportedByServer(String),
+    /// Key size exceeds the configured limit, the first field is the size and the second is the limit
+    #[error("Key too large: size {0} exceeds the limit {1}")]
+    KeyTooLarge(usize, usize),
+    /// Value size exceeds the configured limit, the first field is the size and the second is the limit
+    #[error("Value too large: size {0} exceeds the limit {1}")]
+    ValueTooLarge(usize, usize),
 }
 
 impl From<tonic::transport::Error> for XlineClientError<Command> {
@@ -96,6 +117,12 @@ impl From<tonic::transport::Error> for XlineClientError<Command> {
 impl From<tonic::Status> for XlineClientError<Command> {
     #[inline]
     fn from(e: tonic::Status) -> Self {
+        if e.code() == tonic::Code::Unimplemented {
+            return Self::UnsupportedByServer(e.message().to_owned());
+        }
+        if e.code() == tonic::Code::DeadlineExceeded {
+            return Self::Timeout;
+        }
         Self::RpcError(e.to_string())
     }
 }
@@ -0,0 +1,338 @@
+use xlineapi::{
+    command::KeyRange, Compare, DeleteRangeRequest, Event, KeyValue, PutRequest, RangeRequest,
+    RangeResponse, Request, RequestOp, RequestWrapper, Response, ResponseOp, ResponseWrapper,
+    TxnRequest, TxnResponse, WatchCreateRequest, WatchResponse,
+};
+
+use crate::error::{Result, XlineClientError};
+
+/// Digits of the hex encoding
+const HEX_DIGITS: &[u8; 16] = b"0123456789abcdef";
+
+/// Codec of keys applied by the client before sending them to the cluster and reversed on
+/// reads, so that keys containing bytes mangled by some intermediaries are transported as
+/// printable ascii. A key which can not be decoded, e.g. one written by a client without the
+/// codec, is returned as is.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum KeyCodec {
+    /// Each byte is encoded as two lowercase hex digits. The encoding preserves the order of
+    /// keys, so ranges with any `range_end` are supported.
+    Hex,
+    /// Bytes other than ascii alphanumerics and `-._~/` are encoded as `%XX`. The encoding is
+    /// more compact for mostly printable keys, but it does not preserve the order of keys, so
+    /// only single keys, prefixes and all keys can be ranged over.
+    Percent,
+}
+
+impl KeyCodec {
+    /// Encode a key
+    #[inline]
+    #[must_use]
+    pub fn encode(self, key: &[u8]) -> Vec<u8> {
+        let mut encoded = Vec::with_capacity(key.len().saturating_mul(2));
+        for &byte in key {
+            let unreserved =
+                byte.is_ascii_alphanumeric() || matches!(byte, b'-' | b'.' | b'_' | b'~' | b'/');
+            if self == Self::Percent && unreserved {
+                encoded.push(byte);
+                continue;
+            }
+            if self == Self::Percent {
+                encoded.push(b'%');
+            }
+            encoded.extend(hex_digits(byte));
+        }
+        encoded
+    }
+
+    /// Decode an encoded key, `None` if it is not encoded by this codec
+    #[inline]
+    #[must_use]
+    pub fn decode(self, encoded: &[u8]) -> Option<Vec<u8>> {
+        let mut key = Vec::with_capacity(encoded.len());
+        let mut bytes = encoded.iter().copied();
+        while let Some(byte) = bytes.next() {
+            let hi = match self {
+                Self::Hex => byte,
+                Self::Percent if byte == b'%' => bytes.next()?,
+                Self::Percent => {
+                    key.push(byte);
+                    continue;
+                }
+            };
+            let lo = bytes.next()?;
+            key.push(
+                hex_value(hi)?
+                    .checked_mul(16)?
+                    .checked_add(hex_value(lo)?)?,
+            );
+        }
+        Some(key)
+    }
+
+    /// Encode a key range. A prefix range, i.e. `range_end` is the prefix end of `key`, is
+    /// encoded as the prefix range of the encoded key, and a `range_end` of `\0` still means
+    /// all keys greater than or equal to `key`.
+    ///
+    /// # Errors
+    ///
+    /// Return `XlineClientError::InvalidArgs` if the range is not supported by the codec
+    pub(crate) fn encode_range(self, key: &[u8], range_end: &[u8]) -> Result<(Vec<u8>, Vec<u8>)> {
+        let all_keys = key == [0] && range_end == [0];
+        if range_end.is_empty() || all_keys {
+            let key = if all_keys {
+                key.to_vec()
+            } else {
+                self.encode(key)
+            };
+            return Ok((key, range_end.to_vec()));
+        }
+        let encoded_key = self.encode(key);
+        if range_end == KeyRange::get_prefix(key).as_slice() {
+            let encoded_end = KeyRange::get_prefix(&encoded_key);
+            return Ok((encoded_key, encoded_end));
+        }
+        match self {
+            Self::Hex if range_end == [0] => Ok((encoded_key, range_end.to_vec())),
+            Self::Hex => Ok((encoded_key, self.encode(range_end))),
+            Self::Percent => Err(XlineClientError::InvalidArgs(format!(
+                "range [{key:?}, {range_end:?}) is unsupported by the percent key codec"
+            ))),
+        }
+    }
+
+    /// Encode the keys of a request
+    pub(crate) fn encode_request(self, request: &mut RequestWrapper) -> Result<()> {
+        match *request {
+            RequestWrapper::RangeRequest(ref mut req) => self.encode_range_request(req),
+            RequestWrapper::PutRequest(ref mut req) => {
+                self.encode_put(req);
+                Ok(())
+            }
+            RequestWrapper::DeleteRangeRequest(ref mut req) => self.encode_delete(req),
+            RequestWrapper::TxnRequest(ref mut req) => self.encode_txn(req),
+            // other requests carry no keys of the kv store
+            RequestWrapper::CompactionRequest(_)
+            | RequestWrapper::AuthEnableRequest(_)
+            | RequestWrapper::AuthDisableRequest(_)
+            | RequestWrapper::AuthStatusRequest(_)
+            | RequestWrapper::AuthRoleAddRequest(_)
+            | RequestWrapper::AuthRoleDeleteRequest(_)
+            | RequestWrapper::AuthRoleGetRequest(_)
+            | RequestWrapper::AuthRoleGrantPermissionRequest(_)
+            | RequestWrapper::AuthRoleListRequest(_)
+            | RequestWrapper::AuthRoleRevokePermissionRequest(_)
+            | RequestWrapper::AuthUserAddRequest(_)
+            | RequestWrapper::AuthUserChangePasswordRequest(_)
+            | RequestWrapper::AuthUserDeleteRequest(_)
+            | RequestWrapper::AuthUserGetRequest(_)
+            | RequestWrapper::AuthUserGrantRoleRequest(_)
+            | RequestWrapper::AuthUserListRequest(_)
+            | RequestWrapper::AuthUserRevokeRoleRequest(_)
+            | RequestWrapper::AuthenticateRequest(_)
+            | RequestWrapper::LeaseGrantRequest(_)
+            | RequestWrapper::LeaseRevokeRequest(_)
+            | RequestWrapper::LeaseLeasesRequest(_)
+            | RequestWrapper::AlarmRequest(_) => Ok(()),
+        }
+    }
+
+    /// Encode the keys of a range request
+    pub(crate) fn encode_range_request(self, req: &mut RangeRequest) -> Result<()> {
+        (req.key, req.range_end) = self.encode_range(&req.key, &req.range_end)?;
+        Ok(())
+    }
+
+    /// Encode the keys of a watch create request
+    pub(crate) fn encode_watch(self, req: &mut WatchCreateRequest) -> Result<()> {
+        (req.key, req.range_end) = self.encode_range(&req.key, &req.range_end)?;
+        Ok(())
+    }
+
+    /// Encode the key of a put request
+    fn encode_put(self, req: &mut PutRequest) {
+        req.key = self.encode(&req.key);
+    }
+
+    /// Encode the keys of a delete range request
+    fn encode_delete(self, req: &mut DeleteRangeRequest) -> Result<()> {
+        (req.key, req.range_end) = self.encode_range(&req.key, &req.range_end)?;
+        Ok(())
+    }
+
+    /// Encode the keys of the compares and operations of a txn request
+    fn encode_txn(self, req: &mut TxnRequest) -> Result<()> {
+        for cmp in &mut req.compare {
+            self.encode_compare(cmp)?;
+        }
+        for op in req.success.iter_mut().chain(req.failure.iter_mut()) {
+            self.encode_op(op)?;
+        }
+        Ok(())
+    }
+
+    /// Encode the keys of a compare
+    fn encode_compare(self, cmp: &mut Compare) -> Result<()> {
+        (cmp.key, cmp.range_end) = self.encode_range(&cmp.key, &cmp.range_end)?;
+        Ok(())
+    }
+
+    /// Encode the keys of an operation of a txn
+    fn encode_op(self, op: &mut RequestOp) -> Result<()> {
+        match op.request {
+            Some(Request::RequestRange(ref mut req)) => self.encode_range_request(req),
+            Some(Request::RequestPut(ref mut req)) => {
+                self.encode_put(req);
+                Ok(())
+            }
+            Some(Request::RequestDeleteRange(ref mut req)) => self.encode_delete(req),
+            Some(Request::RequestTxn(ref mut req)) => self.encode_txn(req),
+            None => Ok(()),
+        }
+    }
+
+    /// Decode the keys of a response
+    pub(crate) fn decode_response(self, resp: &mut ResponseWrapper) {
+        match *resp {
+            ResponseWrapper::RangeResponse(ref mut resp) => self.decode_range_response(resp),
+            ResponseWrapper::PutResponse(ref mut resp) => {
+                resp.prev_kv.iter_mut().for_each(|kv| self.decode_kv(kv));
+            }
+            ResponseWrapper::DeleteRangeResponse(ref mut resp) => {
+                resp.prev_kvs.iter_mut().for_each(|kv| self.decode_kv(kv));
+            }
+            ResponseWrapper::TxnResponse(ref mut resp) => self.decode_txn(resp),
+            // other responses carry no keys of the kv store
+            ResponseWrapper::CompactionResponse(_)
+            | ResponseWrapper::AuthEnableResponse(_)
+            | ResponseWrapper::AuthDisableResponse(_)
+            | ResponseWrapper::AuthStatusResponse(_)
+            | ResponseWrapper::AuthRoleAddResponse(_)
+            | ResponseWrapper::AuthRoleDeleteResponse(_)
+            | ResponseWrapper::AuthRoleGetResponse(_)
+            | ResponseWrapper::AuthRoleGrantPermissionResponse(_)
+            | ResponseWrapper::AuthRoleListResponse(_)
+            | ResponseWrapper::AuthRoleRevokePermissionResponse(_)
+            | ResponseWrapper::AuthUserAddResponse(_)
+            | ResponseWrapper::AuthUserChangePasswordResponse(_)
+            | ResponseWrapper::AuthUserDeleteResponse(_)
+            | ResponseWrapper::AuthUserGetResponse(_)
+            | ResponseWrapper::AuthUserGrantRoleResponse(_)
+            | ResponseWrapper::AuthUserListResponse(_)
+            | ResponseWrapper::AuthUserRevokeRoleResponse(_)
+            | ResponseWrapper::AuthenticateResponse(_)
+            | ResponseWrapper::LeaseGrantResponse(_)
+            | ResponseWrapper::LeaseRevokeResponse(_)
+            | ResponseWrapper::LeaseLeasesResponse(_)
+            | ResponseWrapper::AlarmResponse(_) => {}
+        }
+    }
+
+    /// Decode the keys of a range response
+    pub(crate) fn decode_range_response(self, resp: &mut RangeResponse) {
+        resp.kvs.iter_mut().for_each(|kv| self.decode_kv(kv));
+    }
+
+    /// Decode the keys of the events of a watch response
+    pub(crate) fn decode_watch_response(self, resp: &mut WatchResponse) {
+        resp.events
+            .iter_mut()
+            .for_each(|event| self.decode_event(event));
+    }
+
+    /// Decode the keys of an event
+    fn decode_event(self, event: &mut Event) {
+        event
+            .kv
+            .iter_mut()
+            .chain(event.prev_kv.iter_mut())
+            .for_each(|kv| self.decode_kv(kv));
+    }
+
+    /// Decode the keys of the responses of a txn response
+    fn decode_txn(self, resp: &mut TxnResponse) {
+        for op in &mut resp.responses {
+            self.decode_op(op);
+        }
+    }
+
+    /// Decode the keys of a response of a txn
+    fn decode_op(self, op: &mut ResponseOp) {
+        match op.response {
+            Some(Response::ResponseRange(ref mut resp)) => self.decode_range_response(resp),
+            Some(Response::ResponsePut(ref mut resp)) => {
+                resp.prev_kv.iter_mut().for_each(|kv| self.decode_kv(kv));
+            }
+            Some(Response::ResponseDeleteRange(ref mut resp)) => {
+                resp.prev_kvs.iter_mut().for_each(|kv| self.decode_kv(kv));
+            }
+            Some(Response::ResponseTxn(ref mut resp)) => self.decode_txn(resp),
+            None => {}
+        }
+    }
+
+    /// Decode the key of a key-value, it is kept as is if it can not be decoded
+    fn decode_kv(self, kv: &mut KeyValue) {
+        if let Some(key) = self.decode(&kv.key) {
+            kv.key = key;
+        }
+    }
+}
+
+/// Get the two lowercase hex digits of a byte
+fn hex_digits(byte: u8) -> [u8; 2] {
+    [
+        HEX_DIGITS
+            .get(usize::from(byte >> 4))
+            .copied()
+            .unwrap_or(b'0'),
+        HEX_DIGITS
+            .get(usize::from(byte & 0xF))
+            .copied()
+            .unwrap_or(b'0'),
+    ]
+}
+
+/// Get the value of a hex digit, both cases are accepted
+fn hex_value(digit: u8) -> Option<u8> {
+    char::from(digit)
+        .to_digit(16)
+        .and_then(|v| u8::try_from(v).ok())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn codecs_should_round_trip_control_bytes() {
+        let key = b"job/\x00\x01\r\n\x7f\xff end";
+        for codec in [KeyCodec::Hex, KeyCodec::Percent] {
+            let encoded = codec.encode(key);
+            assert!(encoded.iter().all(|b| b.is_ascii_graphic()), "{encoded:?}");
+            assert_eq!(codec.decode(&encoded), Some(key.to_vec()));
+        }
+        assert_eq!(
+            KeyCodec::Percent.encode(key),
+            b"job/%00%01%0d%0a%7f%ff%20end"
+        );
+        assert_eq!(KeyCodec::Hex.decode(b"abc"), None);
+        assert_eq!(KeyCodec::Percent.decode(b"abc%2"), None);
+    }
+
+    #[test]
+    fn range_end_should_be_encoded_by_the_codec() {
+        let prefix = b"a\x00".to_vec();
+        let prefix_end = KeyRange::get_prefix(&prefix);
+        for codec in [KeyCodec::Hex, KeyCodec::Percent] {
+            let (key, range_end) = codec.encode_range(&prefix, &prefix_end).unwrap();
+            assert_eq!(range_end, KeyRange::get_prefix(&key));
+            assert_eq!(codec.encode_range(&[0], &[0]).unwrap(), (vec![0], vec![0]));
+            assert_eq!(codec.encode_range(b"k", &[]).unwrap().1, Vec::<u8>::new());
+        }
+        let (key, range_end) = KeyCodec::Hex.encode_range(b"a", b"c").unwrap();
+        assert_eq!((key, range_end), (b"61".to_vec(), b"63".to_vec()));
+        assert!(KeyCodec::Percent.encode_range(b"a", b"c").is_err());
+    }
+}
@@ -0,0 +1,80 @@
+use std::{collections::HashMap, time::Duration};
+
+use clippy_utilities::OverflowArithmetic;
+use parking_lot::Mutex;
+
+/// The weight of a new sample in the EWMA is `1 / EWMA_WEIGHT_DIVISOR`
+const EWMA_WEIGHT_DIVISOR: u64 = 4;
+
+/// Exponentially weighted moving average of the latencies of servers, measured by this client
+#[derive(Debug, Default)]
+pub(crate) struct LatencyTracker {
+    /// EWMA latencies in microseconds indexed by the server id
+    ewma: Mutex<HashMap<u64, u64>>,
+}
+
+impl LatencyTracker {
+    /// Record a latency sample of the server
+    pub(crate) fn observe(&self, id: u64, latency: Duration) {
+        let sample = u64::try_from(latency.as_micros()).unwrap_or(u64::MAX);
+        let mut ewma = self.ewma.lock();
+        let _ig = ewma
+            .entry(id)
+            .and_modify(|avg| {
+                let decayed = avg.saturating_sub(avg.overflow_div(EWMA_WEIGHT_DIVISOR));
+                *avg = decayed.saturating_add(sample.overflow_div(EWMA_WEIGHT_DIVISOR));
+            })
+            .or_insert(sample);
+    }
+
+    /// Get the EWMA latency of the server, `None` if it has not been measured yet
+    pub(crate) fn ewma(&self, id: u64) -> Option<Duration> {
+        self.ewma
+            .lock()
+            .get(&id)
+            .map(|avg| Duration::from_micros(*avg))
+    }
+
+    /// Select the server with the lowest EWMA latency from `members`. A server not measured
+    /// yet is regarded as the fastest, so that every server gets measured, and the leader wins
+    /// a tie so that reads stay on the leader unless a follower is strictly faster.
+    pub(crate) fn select(&self, leader: Option<u64>, members: &[u64]) -> Option<u64> {
+        let ewma = self.ewma.lock();
+        members
+            .iter()
+            .copied()
+            .min_by_key(|id| (ewma.get(id).copied().unwrap_or(0), Some(*id) != leader))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn ewma_should_weight_new_samples() {
+        let tracker = LatencyTracker::default();
+        assert_eq!(tracker.ewma(1), None);
+        tracker.observe(1, Duration::from_millis(8));
+        assert_eq!(tracker.ewma(1), Some(Duration::from_millis(8)));
+        tracker.observe(1, Duration::from_millis(4));
+        assert_eq!(tracker.ewma(1), Some(Duration::from_millis(7)));
+    }
+
+    #[test]
+    fn select_should_prefer_the_fastest_server() {
+        let tracker = LatencyTracker::default();
+        // unmeasured servers are tried first, the leader wins the tie
+        assert_eq!(tracker.select(Some(2), &[1, 2, 3]), Some(2));
+        tracker.observe(1, Duration::from_millis(5));
+        tracker.observe(2, Duration::from_millis(1));
+        tracker.observe(3, Duration::from_millis(3));
+        assert_eq!(tracker.select(Some(2), &[1, 2, 3]), Some(2));
+        // the leader becomes slower than a follower
+        for _ in 0..10 {
+            tracker.observe(2, Duration::from_millis(10));
+        }
+        assert_eq!(tracker.select(Some(2), &[1, 2, 3]), Some(3));
+        assert_eq!(tracker.select(None, &[]), None);
+    }
+}
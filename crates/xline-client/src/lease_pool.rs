@@ -0,0 +1,109 @@
+use std::{
+    collections::HashMap,
+    sync::{Mutex, PoisonError},
+    time::{Duration, Instant},
+};
+
+use clippy_utilities::Cast;
+
+/// Granularity of the ttl buckets in seconds
+const TTL_BUCKET_SECS: i64 = 5;
+
+/// A lease shared by all keys in the same ttl bucket
+#[derive(Debug, Clone, Copy)]
+struct PooledLease {
+    /// The lease id
+    id: i64,
+    /// The time when the lease expires at the latest
+    expires_at: Instant,
+}
+
+/// Pool of leases shared by keys with similar ttl, so that many short-lived keys don't need
+/// a lease each. A key attached to a pooled lease never expires earlier than its ttl, and
+/// at most two buckets later.
+#[derive(Debug, Default)]
+pub(crate) struct LeasePool {
+    /// Leases indexed by the ttl bucket in seconds
+    leases: Mutex<HashMap<i64, PooledLease>>,
+}
+
+impl LeasePool {
+    /// Round the ttl up to its bucket in seconds
+    fn bucket(ttl: Duration) -> i64 {
+        let secs: i64 = ttl.as_secs().cast();
+        let secs = if ttl.subsec_nanos() > 0 {
+            secs.saturating_add(1)
+        } else {
+            secs
+        };
+        secs.saturating_add(TTL_BUCKET_SECS.saturating_sub(1))
+            .checked_div(TTL_BUCKET_SECS)
+            .unwrap_or_else(|| unreachable!("bucket size is not zero"))
+            .max(1)
+            .saturating_mul(TTL_BUCKET_SECS)
+    }
+
+    /// The ttl of the lease to grant for the bucket of `ttl`, one bucket longer than the
+    /// bucket so that the lease can be shared for a while
+    pub(crate) fn grant_ttl(ttl: Duration) -> i64 {
+        Self::bucket(ttl).saturating_add(TTL_BUCKET_SECS)
+    }
+
+    /// Get the pooled lease for `ttl`, if it still lives for at least `ttl` from `now`
+    pub(crate) fn get(&self, ttl: Duration, now: Instant) -> Option<i64> {
+        let leases = self.leases.lock().unwrap_or_else(PoisonError::into_inner);
+        leases
+            .get(&Self::bucket(ttl))
+            .filter(|lease| {
+                now.checked_add(ttl)
+                    .map_or(false, |deadline| lease.expires_at >= deadline)
+            })
+            .map(|lease| lease.id)
+    }
+
+    /// Record a lease granted with `grant_ttl(ttl)` at `granted_at`
+    pub(crate) fn insert(&self, ttl: Duration, id: i64, granted_at: Instant) {
+        let Some(expires_at) =
+            granted_at.checked_add(Duration::from_secs(Self::grant_ttl(ttl).cast()))
+        else {
+            return;
+        };
+        let _prev = self
+            .leases
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert(Self::bucket(ttl), PooledLease { id, expires_at });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn keys_with_similar_ttl_should_share_one_lease() {
+        let pool = LeasePool::default();
+        let now = Instant::now();
+        assert_eq!(pool.get(Duration::from_secs(3), now), None);
+        pool.insert(Duration::from_secs(3), 1, now);
+
+        for secs in 1..=5 {
+            assert_eq!(pool.get(Duration::from_secs(secs), now), Some(1));
+        }
+        // a different bucket
+        assert_eq!(pool.get(Duration::from_secs(6), now), None);
+        // the lease no longer lives long enough
+        assert_eq!(
+            pool.get(Duration::from_secs(5), now + Duration::from_secs(6)),
+            None
+        );
+    }
+
+    #[test]
+    fn ttl_should_be_rounded_up_to_bucket() {
+        assert_eq!(LeasePool::bucket(Duration::from_millis(100)), 5);
+        assert_eq!(LeasePool::bucket(Duration::from_secs(5)), 5);
+        assert_eq!(LeasePool::bucket(Duration::from_millis(5001)), 10);
+        assert_eq!(LeasePool::grant_ttl(Duration::from_secs(7)), 15);
+    }
+}
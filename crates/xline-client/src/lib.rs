@@ -157,9 +157,11 @@ use std::{
     fmt::Debug,
     sync::Arc,
     task::{Context, Poll},
+    time::Duration,
 };
 
-use curp::client::ClientBuilder as CurpClientBuilder;
+use curp::client::{ClientApi, ClientBuilder as CurpClientBuilder, TraceEntry};
+pub use curp::rpc::{CompressionCodec, ProposeCompression};
 use http::{header::AUTHORIZATION, HeaderValue, Request};
 use tonic::transport::Channel;
 #[cfg(not(madsim))]
@@ -173,15 +175,34 @@ use xlineapi::command::{Command, CurpClient};
 use crate::{
     clients::{
         AuthClient, ClusterClient, ElectionClient, KvClient, LeaseClient, LockClient,
-        MaintenanceClient, WatchClient,
+        MaintenanceClient, SizeLimits, WatchClient,
     },
+    degrade::DegradeState,
     error::XlineClientBuildError,
+    types::kv::ReadSelectionPolicy,
 };
+pub use crate::{degrade::ClientMode, key_codec::KeyCodec};
 
 /// Sub-clients for each type of API
 pub mod clients;
+/// Coalescing of puts of the same key
+mod coalesce;
+/// Read-only degraded mode on quorum loss
+mod degrade;
+/// Codec of keys for binary-unsafe transports
+mod key_codec;
+/// Latency tracking of servers
+mod latency;
 /// Lease Id generator
 mod lease_gen;
+/// Pool of leases shared by keys with similar ttl
+mod lease_pool;
+/// Channels to specific members of the cluster
+mod member_channels;
+/// Cache of responses with the stale-while-revalidate policy
+mod response_cache;
+/// Speculative values of writes in flight
+mod speculative;
 /// Request type definitions.
 pub mod types;
 
@@ -207,6 +228,10 @@ pub struct Client {
     cluster: ClusterClient,
     /// Election client
     election: ElectionClient,
+    /// The curp client shared by sub-clients
+    curp_client: Arc<CurpClient>,
+    /// State of the degraded mode
+    degrade: Arc<DegradeState>,
 }
 
 impl Client {
@@ -232,15 +257,31 @@ impl Client {
             .map(|addr| addr.as_ref().to_owned())
             .collect();
         let channel = Self::build_channel(addrs.clone(), options.tls_config.as_ref()).await?;
+        let mut builder = CurpClientBuilder::new(options.client_config, false)
+            .tls_config(options.tls_config.clone());
+        if let Some(capacity) = options.trace_capacity {
+            builder = builder.trace_capacity(capacity);
+        }
+        if let Some(compression) = options.propose_compression {
+            builder = builder.propose_compression(compression);
+        }
+        if let Some(fast_quorum) = options.fast_quorum {
+            if fast_quorum == 0 {
+                return Err(XlineClientBuildError::invalid_arguments(
+                    "fast quorum must be positive",
+                ));
+            }
+            builder = builder.fast_quorum(fast_quorum);
+        }
         let curp_client = Arc::new(
-            CurpClientBuilder::new(options.client_config, false)
-                .tls_config(options.tls_config)
+            builder
                 .discover_from(addrs)
                 .await?
                 .build::<Command>()
                 .await?,
         ) as Arc<CurpClient>;
         let id_gen = Arc::new(lease_gen::LeaseIdGenerator::new());
+        let degrade = Arc::new(DegradeState::new(options.read_only_threshold));
 
         let token = match options.user {
             Some((username, password)) => {
@@ -255,23 +296,45 @@ impl Client {
             None => None,
         };
 
-        let kv = KvClient::new(Arc::clone(&curp_client), channel.clone(), token.clone());
+        let kv = KvClient::new(
+            Arc::clone(&curp_client),
+            channel.clone(),
+            token.clone(),
+            Arc::clone(&id_gen),
+        )
+        .with_size_limits(SizeLimits::new(
+            options.max_key_size,
+            options.max_value_size,
+        ))
+        .with_quorum_read(options.tls_config.clone(), options.read_repair)
+        .with_read_selection(options.read_selection)
+        .with_degrade_state(Arc::clone(&degrade))
+        .with_stale_while_revalidate(options.stale_while_revalidate)
+        .with_speculative_local_reads(options.speculative_local_reads)
+        .with_coalesce_same_key(options.coalesce_same_key)
+        .with_key_codec(options.key_codec);
         let lease = LeaseClient::new(
             Arc::clone(&curp_client),
             channel.clone(),
             token.clone(),
             Arc::clone(&id_gen),
-        );
+        )
+        .with_degrade_state(Arc::clone(&degrade));
         let lock = LockClient::new(
             Arc::clone(&curp_client),
             channel.clone(),
             token.clone(),
             id_gen,
-        );
-        let auth = AuthClient::new(curp_client, channel.clone(), token.clone());
+        )
+        .with_degrade_state(Arc::clone(&degrade));
+        let auth = AuthClient::new(Arc::clone(&curp_client), channel.clone(), token.clone());
         let maintenance = MaintenanceClient::new(channel.clone(), token.clone());
-        let cluster = ClusterClient::new(channel.clone(), token.clone());
-        let watch = WatchClient::new(channel, token);
+        let cluster = ClusterClient::new(channel.clone(), token.clone())
+            .with_curp_client(Arc::clone(&curp_client))
+            .with_tls_config(options.tls_config);
+        let watch = WatchClient::new(channel, token)
+            .with_curp_client(Arc::clone(&curp_client))
+            .with_key_codec(options.key_codec);
         let election = ElectionClient::new();
 
         Ok(Self {
@@ -283,6 +346,8 @@ impl Client {
             watch,
             cluster,
             election,
+            curp_client,
+            degrade,
         })
     }
 
@@ -358,6 +423,25 @@ impl Client {
     pub fn election_client(&self) -> ElectionClient {
         self.election.clone()
     }
+
+    /// Export the traces of the latest operations, from the oldest to the latest. They are
+    /// the proposes, the cluster fetches, and the creation of watches and lease keep alive
+    /// streams. Each trace records the operation, the server which served it, the outcome and
+    /// the latency, so that the proposes can be replayed against a test cluster. It is empty
+    /// unless tracing is enabled by `ClientOptions::with_trace_capacity`.
+    #[inline]
+    #[must_use]
+    pub fn export_trace(&self) -> Vec<TraceEntry<Command>> {
+        self.curp_client.export_trace()
+    }
+
+    /// Get the current mode of the client. It is always `ClientMode::Normal` unless the
+    /// read-only mode is enabled by `ClientOptions::with_read_only_on_quorum_loss`.
+    #[inline]
+    #[must_use]
+    pub fn mode(&self) -> ClientMode {
+        self.degrade.mode()
+    }
 }
 
 /// Options for a client connection
@@ -369,6 +453,31 @@ pub struct ClientOptions {
     tls_config: Option<ClientTlsConfig>,
     /// config for the curp client
     client_config: ClientConfig,
+    /// Max key size of puts in bytes, no limit if not set
+    max_key_size: Option<usize>,
+    /// Max value size of puts in bytes, no limit if not set
+    max_value_size: Option<usize>,
+    /// Whether to repair stale members found by quorum reads
+    read_repair: bool,
+    /// Number of latest operations to trace, no tracing if not set
+    trace_capacity: Option<usize>,
+    /// Policy selecting the server which serves serializable reads
+    read_selection: ReadSelectionPolicy,
+    /// Number of consecutive writes failing for the unreachable quorum to switch to the
+    /// read-only mode, never switch if not set
+    read_only_threshold: Option<usize>,
+    /// The stale-while-revalidate window of cached serializable reads, no cache if not set
+    stale_while_revalidate: Option<Duration>,
+    /// Whether to serve the values of puts in flight to reads of this client
+    speculative_local_reads: bool,
+    /// Codec of keys applied before sending them to the cluster, no codec if not set
+    key_codec: Option<KeyCodec>,
+    /// Compression of the commands of proposes, never compress if not set
+    propose_compression: Option<ProposeCompression>,
+    /// The window in which puts of the same key are coalesced, no coalescing if not set
+    coalesce_same_key: Option<Duration>,
+    /// Number of acks required by the fast path, the super quorum if not set
+    fast_quorum: Option<usize>,
 }
 
 impl ClientOptions {
@@ -384,6 +493,18 @@ impl ClientOptions {
             user,
             tls_config,
             client_config,
+            max_key_size: None,
+            max_value_size: None,
+            read_repair: false,
+            trace_capacity: None,
+            read_selection: ReadSelectionPolicy::default(),
+            read_only_threshold: None,
+            stale_while_revalidate: None,
+            speculative_local_reads: false,
+            key_codec: None,
+            propose_compression: None,
+            coalesce_same_key: None,
+            fast_quorum: None,
         }
     }
 
@@ -408,6 +529,90 @@ impl ClientOptions {
         &self.client_config
     }
 
+    /// Get `max_key_size`
+    #[inline]
+    #[must_use]
+    pub fn max_key_size(&self) -> Option<usize> {
+        self.max_key_size
+    }
+
+    /// Get `max_value_size`
+    #[inline]
+    #[must_use]
+    pub fn max_value_size(&self) -> Option<usize> {
+        self.max_value_size
+    }
+
+    /// Get `read_repair`
+    #[inline]
+    #[must_use]
+    pub fn read_repair(&self) -> bool {
+        self.read_repair
+    }
+
+    /// Get `trace_capacity`
+    #[inline]
+    #[must_use]
+    pub fn trace_capacity(&self) -> Option<usize> {
+        self.trace_capacity
+    }
+
+    /// Get `read_selection`
+    #[inline]
+    #[must_use]
+    pub fn read_selection(&self) -> ReadSelectionPolicy {
+        self.read_selection
+    }
+
+    /// Get `read_only_threshold`
+    #[inline]
+    #[must_use]
+    pub fn read_only_threshold(&self) -> Option<usize> {
+        self.read_only_threshold
+    }
+
+    /// Get `stale_while_revalidate`
+    #[inline]
+    #[must_use]
+    pub fn stale_while_revalidate(&self) -> Option<Duration> {
+        self.stale_while_revalidate
+    }
+
+    /// Get `speculative_local_reads`
+    #[inline]
+    #[must_use]
+    pub fn speculative_local_reads(&self) -> bool {
+        self.speculative_local_reads
+    }
+
+    /// Get `key_codec`
+    #[inline]
+    #[must_use]
+    pub fn key_codec(&self) -> Option<KeyCodec> {
+        self.key_codec
+    }
+
+    /// Get `propose_compression`
+    #[inline]
+    #[must_use]
+    pub fn propose_compression(&self) -> Option<ProposeCompression> {
+        self.propose_compression
+    }
+
+    /// Get `coalesce_same_key`
+    #[inline]
+    #[must_use]
+    pub fn coalesce_same_key(&self) -> Option<Duration> {
+        self.coalesce_same_key
+    }
+
+    /// Get `fast_quorum`
+    #[inline]
+    #[must_use]
+    pub fn fast_quorum(&self) -> Option<usize> {
+        self.fast_quorum
+    }
+
     /// Set `user`
     #[inline]
     #[must_use]
@@ -437,6 +642,152 @@ impl ClientOptions {
             ..self
         }
     }
+
+    /// Set `max_key_size`, puts with a larger key will be rejected before sending to the cluster.
+    /// It should be consistent with the limit of the server.
+    #[inline]
+    #[must_use]
+    pub fn with_max_key_size(self, max_key_size: usize) -> Self {
+        Self {
+            max_key_size: Some(max_key_size),
+            ..self
+        }
+    }
+
+    /// Set `max_value_size`, puts with a larger value will be rejected before sending to the cluster.
+    /// It should be consistent with the limit of the server.
+    #[inline]
+    #[must_use]
+    pub fn with_max_value_size(self, max_value_size: usize) -> Self {
+        Self {
+            max_value_size: Some(max_value_size),
+            ..self
+        }
+    }
+
+    /// Set `read_repair`, if a quorum read finds members returning stale values, the freshest
+    /// values will be written back through the leader to make them converge. Note that the
+    /// write back is a regular txn, so it bumps the revision of the cluster and the mod revision
+    /// of the repaired keys, and watchers of these keys receive put events of unchanged values.
+    #[inline]
+    #[must_use]
+    pub fn with_read_repair(self, read_repair: bool) -> Self {
+        Self {
+            read_repair,
+            ..self
+        }
+    }
+
+    /// Set `trace_capacity`, the client keeps traces of the latest `trace_capacity` operations,
+    /// which can be exported by `Client::export_trace`.
+    #[inline]
+    #[must_use]
+    pub fn with_trace_capacity(self, trace_capacity: usize) -> Self {
+        Self {
+            trace_capacity: Some(trace_capacity),
+            ..self
+        }
+    }
+
+    /// Set `read_selection`, the policy selecting the server which serves serializable reads
+    #[inline]
+    #[must_use]
+    pub fn with_read_selection(self, read_selection: ReadSelectionPolicy) -> Self {
+        Self {
+            read_selection,
+            ..self
+        }
+    }
+
+    /// Switch the client to the read-only mode after `threshold` consecutive writes fail for
+    /// the unreachable quorum. In the read-only mode, serializable reads are served by the
+    /// available servers, and the kv, lease and lock writes fail fast with
+    /// `XlineClientError::ClusterDegraded` instead of retrying, except for a probing write in a
+    /// while, which brings the client back to normal once it succeeds.
+    #[inline]
+    #[must_use]
+    pub fn with_read_only_on_quorum_loss(self, threshold: usize) -> Self {
+        Self {
+            read_only_threshold: Some(threshold),
+            ..self
+        }
+    }
+
+    /// Set `stale_while_revalidate`, serializable reads are cached by the client. A cached
+    /// response fetched within the window is returned immediately, and a refresh is sent in
+    /// the background to revalidate it, so that the tail latency of read-heavy workloads is
+    /// reduced at the cost of reading slightly stale values. A response older than the window
+    /// is fetched again before returning. The cache is dropped after a write of this client.
+    #[inline]
+    #[must_use]
+    pub fn with_stale_while_revalidate(self, window: Duration) -> Self {
+        Self {
+            stale_while_revalidate: Some(window),
+            ..self
+        }
+    }
+
+    /// Set `speculative_local_reads`, a read of a single key by this client is served by the
+    /// value of its put in flight, without waiting for the put to be confirmed by the cluster.
+    /// If the put fails, the speculative value is rolled back and later reads are sent to the
+    /// cluster again. The revisions of a speculative value are unknown and left as zero.
+    #[inline]
+    #[must_use]
+    pub fn with_speculative_local_reads(self, speculative_local_reads: bool) -> Self {
+        Self {
+            speculative_local_reads,
+            ..self
+        }
+    }
+
+    /// Set `key_codec`, keys of kv requests and watches are encoded by the codec before they
+    /// are sent to the cluster, and decoded in responses and events, including the prefix ends
+    /// of ranges. All clients reading and writing the keys should use the same codec.
+    #[inline]
+    #[must_use]
+    pub fn with_key_codec(self, key_codec: KeyCodec) -> Self {
+        Self {
+            key_codec: Some(key_codec),
+            ..self
+        }
+    }
+
+    /// Set `propose_compression`, the commands of proposes larger than its threshold are
+    /// compressed by its codec, once the servers report the support of the compression.
+    #[inline]
+    #[must_use]
+    pub fn with_propose_compression(self, propose_compression: ProposeCompression) -> Self {
+        Self {
+            propose_compression: Some(propose_compression),
+            ..self
+        }
+    }
+
+    /// Set `coalesce_same_key`, consecutive puts of the same key with identical options within
+    /// the window collapse to the latest one, which is proposed once the window ends, so that a
+    /// key updated continuously takes one proposal per window. All puts coalesced resolve with
+    /// the result of the latest one, but only the latest one gets the previous key-value. Every
+    /// put is delayed by the window.
+    #[inline]
+    #[must_use]
+    pub fn with_coalesce_same_key(self, window: Duration) -> Self {
+        Self {
+            coalesce_same_key: Some(window),
+            ..self
+        }
+    }
+
+    /// Set `fast_quorum`, the number of acks the fast path requires. It must be within
+    /// `[super_quorum, cluster_size]`, e.g. 4 or 5 in a 5-node cluster, otherwise puts and
+    /// other writes fail with an invalid config error without being proposed.
+    #[inline]
+    #[must_use]
+    pub fn with_fast_quorum(self, fast_quorum: usize) -> Self {
+        Self {
+            fast_quorum: Some(fast_quorum),
+            ..self
+        }
+    }
 }
 
 /// Authentication service.
@@ -0,0 +1,69 @@
+use std::{collections::HashMap, time::Duration};
+
+use parking_lot::Mutex;
+use tonic::transport::Channel;
+#[cfg(not(madsim))]
+use tonic::transport::ClientTlsConfig;
+use utils::build_endpoint;
+#[cfg(madsim)]
+use utils::ClientTlsConfig;
+
+/// Channels to specific members of the cluster, used by the reads served by a chosen member.
+/// A channel is connected on the first use and reused until the client urls of the member
+/// change or a request through it fails.
+#[derive(Debug)]
+pub(crate) struct MemberChannels {
+    /// Channels and the client urls they are connected to, indexed by the member id
+    channels: Mutex<HashMap<u64, (Vec<String>, Channel)>>,
+    /// Connect and request timeout of the channels
+    timeout: Duration,
+}
+
+impl MemberChannels {
+    /// New `MemberChannels` whose channels time out after `timeout`
+    pub(crate) fn new(timeout: Duration) -> Self {
+        Self {
+            channels: Mutex::new(HashMap::new()),
+            timeout,
+        }
+    }
+
+    /// Get the channel to the member, connect to one of its client urls if there is no
+    /// channel connected to these urls yet
+    pub(crate) async fn get(
+        &self,
+        id: u64,
+        client_urls: &[String],
+        tls_config: Option<&ClientTlsConfig>,
+    ) -> Option<Channel> {
+        if let Some(&(ref urls, ref channel)) = self.channels.lock().get(&id) {
+            if urls.as_slice() == client_urls {
+                return Some(channel.clone());
+            }
+        }
+        for url in client_urls {
+            let Ok(endpoint) = build_endpoint(url, tls_config) else {
+                continue;
+            };
+            let Ok(channel) = endpoint
+                .connect_timeout(self.timeout)
+                .timeout(self.timeout)
+                .connect()
+                .await
+            else {
+                continue;
+            };
+            let _prev = self
+                .channels
+                .lock()
+                .insert(id, (client_urls.to_vec(), channel.clone()));
+            return Some(channel);
+        }
+        None
+    }
+
+    /// Remove the channel to the member, the next request reconnects to it
+    pub(crate) fn remove(&self, id: u64) {
+        let _prev = self.channels.lock().remove(&id);
+    }
+}
@@ -0,0 +1,255 @@
+use std::{
+    collections::HashMap,
+    future::Future,
+    hash::Hash,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex, PoisonError,
+    },
+    time::{Duration, Instant},
+};
+
+use crate::error::Result;
+
+/// Max number of responses kept, the oldest one is evicted if the cache is full
+const MAX_CACHED_RESPONSES: usize = 1024;
+
+/// A cached response
+#[derive(Debug)]
+struct CacheEntry<V> {
+    /// The response
+    value: V,
+    /// Time when the response was fetched
+    fetched_at: Instant,
+    /// Whether a background refresh of the entry is in flight
+    refreshing: bool,
+}
+
+/// Cache of responses with the stale-while-revalidate policy: a response fetched within the
+/// `stale_while_revalidate` window is returned immediately, and a background refresh is
+/// triggered to revalidate it. A response older than the window is fetched again before
+/// returning.
+#[derive(Debug)]
+pub(crate) struct ResponseCache<K, V> {
+    /// The stale-while-revalidate window
+    stale_while_revalidate: Duration,
+    /// Cached responses indexed by the request
+    entries: Mutex<HashMap<K, CacheEntry<V>>>,
+    /// Generation of the cached responses, which is bumped on every clear so that the
+    /// responses fetched before are not cached. Only bumped and compared with `entries`
+    /// locked.
+    generation: AtomicU64,
+}
+
+impl<K, V> ResponseCache<K, V>
+where
+    K: Eq + Hash + Clone + Send + 'static,
+    V: Clone + Send + 'static,
+{
+    /// Create a new `ResponseCache`
+    pub(crate) fn new(stale_while_revalidate: Duration) -> Self {
+        Self {
+            stale_while_revalidate,
+            entries: Mutex::new(HashMap::new()),
+            generation: AtomicU64::new(0),
+        }
+    }
+
+    /// Get the cached response of `key` if it is within the stale-while-revalidate window, a
+    /// background refresh by `fetch` is spawned unless one is in flight already. Otherwise the
+    /// response is fetched by `fetch` and cached.
+    pub(crate) async fn get_or_fetch<F, Fut>(self: &Arc<Self>, key: K, fetch: F) -> Result<V>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<V>> + Send + 'static,
+    {
+        let generation = self.generation.load(Ordering::Acquire);
+        if let Some(value) = self.get_or_refresh(&key, Instant::now(), fetch) {
+            return Ok(value);
+        }
+        let value = fetch().await?;
+        self.insert(key, value.clone(), Instant::now(), generation);
+        Ok(value)
+    }
+
+    /// Drop all cached responses, called after a write of this client so that it reads its
+    /// own writes. The fetches and refreshes in flight are not cached once they finish, as
+    /// they may be served before the write.
+    pub(crate) fn clear(&self) {
+        let mut entries = self.entries.lock().unwrap_or_else(PoisonError::into_inner);
+        let _prev = self.generation.fetch_add(1, Ordering::AcqRel);
+        entries.clear();
+    }
+
+    /// Get the cached response of `key` within the window at `now`, and spawn the refresh
+    fn get_or_refresh<F, Fut>(self: &Arc<Self>, key: &K, now: Instant, fetch: F) -> Option<V>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<V>> + Send + 'static,
+    {
+        let mut entries = self.entries.lock().unwrap_or_else(PoisonError::into_inner);
+        let entry = entries.get_mut(key)?;
+        if now.saturating_duration_since(entry.fetched_at) >= self.stale_while_revalidate {
+            return None;
+        }
+        if !entry.refreshing {
+            entry.refreshing = true;
+            let refresh = fetch();
+            let generation = self.generation.load(Ordering::Acquire);
+            let cache = Arc::clone(self);
+            let key = key.clone();
+            let _ignore = tokio::spawn(async move {
+                match refresh.await {
+                    Ok(value) => cache.insert(key, value, Instant::now(), generation),
+                    Err(_err) => cache.refresh_failed(&key),
+                }
+            });
+        }
+        Some(entry.value.clone())
+    }
+
+    /// Cache the response of `key` fetched at `fetched_at` by a fetch started in
+    /// `generation`, the response is dropped if the cache is cleared since then
+    fn insert(&self, key: K, value: V, fetched_at: Instant, generation: u64) {
+        let mut entries = self.entries.lock().unwrap_or_else(PoisonError::into_inner);
+        if self.generation.load(Ordering::Acquire) != generation {
+            return;
+        }
+        if entries.len() >= MAX_CACHED_RESPONSES && !entries.contains_key(&key) {
+            let oldest = entries
+                .iter()
+                .min_by_key(|&(_k, entry)| entry.fetched_at)
+                .map(|(k, _entry)| k.clone());
+            if let Some(oldest) = oldest {
+                let _prev = entries.remove(&oldest);
+            }
+        }
+        let _prev = entries.insert(
+            key,
+            CacheEntry {
+                value,
+                fetched_at,
+                refreshing: false,
+            },
+        );
+    }
+
+    /// Permit another refresh of `key` after the refresh failed, the stale response is kept
+    /// until it falls out of the window
+    fn refresh_failed(&self, key: &K) {
+        if let Some(entry) = self
+            .entries
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get_mut(key)
+        {
+            entry.refreshing = false;
+        }
+    }
+
+    /// Get the cached response of `key` regardless of its age
+    #[cfg(test)]
+    fn cached(&self, key: &K) -> Option<V> {
+        self.entries
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get(key)
+            .map(|entry| entry.value.clone())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use tokio::sync::oneshot;
+
+    use super::*;
+    use crate::error::XlineClientError;
+
+    #[tokio::test]
+    async fn read_within_window_should_return_cached_and_refresh() {
+        let cache = Arc::new(ResponseCache::new(Duration::from_secs(60)));
+        let first = cache.get_or_fetch(1, || async { Ok(1) }).await.unwrap();
+        assert_eq!(first, 1);
+
+        // the refresh is blocked, the cached response is returned without waiting for it
+        let (tx, rx) = oneshot::channel::<()>();
+        let cached = tokio::time::timeout(
+            Duration::from_millis(100),
+            cache.get_or_fetch(1, || async move {
+                rx.await.unwrap();
+                Ok(2)
+            }),
+        )
+        .await
+        .unwrap()
+        .unwrap();
+        assert_eq!(cached, 1);
+        // no more refreshes while one is in flight
+        let cached = cache
+            .get_or_fetch(1, || async { panic!("refreshed twice") })
+            .await
+            .unwrap();
+        assert_eq!(cached, 1);
+
+        tx.send(()).unwrap();
+        tokio::time::timeout(Duration::from_secs(1), async {
+            while cache.cached(&1) != Some(2) {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn read_out_of_window_should_fetch_again() {
+        let cache = Arc::new(ResponseCache::new(Duration::ZERO));
+        assert_eq!(cache.get_or_fetch(1, || async { Ok(1) }).await.unwrap(), 1);
+        assert_eq!(cache.get_or_fetch(1, || async { Ok(2) }).await.unwrap(), 2);
+        assert!(cache
+            .get_or_fetch(1, || async { Err(XlineClientError::ClusterDegraded) })
+            .await
+            .is_err());
+        cache.clear();
+        assert_eq!(cache.cached(&1), None);
+    }
+
+    #[tokio::test]
+    async fn refresh_started_before_write_should_not_be_cached() {
+        let cache = Arc::new(ResponseCache::new(Duration::from_secs(60)));
+        assert_eq!(cache.get_or_fetch(1, || async { Ok(1) }).await.unwrap(), 1);
+
+        // a slow refresh is served before the write, and finishes after it
+        let (tx, rx) = oneshot::channel::<()>();
+        let cached = cache
+            .get_or_fetch(1, || async move {
+                rx.await.unwrap();
+                Ok(1)
+            })
+            .await
+            .unwrap();
+        assert_eq!(cached, 1);
+        cache.clear();
+        tx.send(()).unwrap();
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        assert_eq!(cache.cached(&1), None);
+        assert_eq!(cache.get_or_fetch(1, || async { Ok(2) }).await.unwrap(), 2);
+    }
+
+    #[tokio::test]
+    async fn fetch_started_before_write_should_not_be_cached() {
+        let cache = Arc::new(ResponseCache::new(Duration::from_secs(60)));
+        let (tx, rx) = oneshot::channel::<()>();
+        let fetch = cache.get_or_fetch(1, || async move {
+            rx.await.unwrap();
+            Ok(1)
+        });
+        let write = async {
+            cache.clear();
+            tx.send(()).unwrap();
+        };
+        let (fetched, ()) = tokio::join!(fetch, write);
+        assert_eq!(fetched.unwrap(), 1);
+        assert_eq!(cache.cached(&1), None);
+    }
+}
@@ -0,0 +1,130 @@
+use std::{
+    collections::HashMap,
+    future::Future,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex, PoisonError,
+    },
+};
+
+/// A speculative value and the id of the write which wrote it
+#[derive(Debug)]
+struct SpeculativeValue {
+    /// Id of the write, a later write of the same key overrides the earlier one
+    id: u64,
+    /// The written value
+    value: Vec<u8>,
+}
+
+/// Values of puts in flight, which are served to reads of this client before the puts are
+/// confirmed by the cluster, so that the client reads its own writes without waiting
+#[derive(Debug, Default)]
+pub(crate) struct SpeculativeWrites {
+    /// Id of the next write
+    next_id: AtomicU64,
+    /// Speculative values indexed by the key
+    values: Mutex<HashMap<Vec<u8>, SpeculativeValue>>,
+}
+
+impl SpeculativeWrites {
+    /// Serve `value` of `key` speculatively while `propose` is in flight. Once the propose
+    /// completes, the value is served by the cluster if it succeeds, or rolled back if it
+    /// fails, so the speculative value is dropped either way.
+    pub(crate) async fn speculate<F: Future>(
+        &self,
+        key: Vec<u8>,
+        value: Vec<u8>,
+        propose: F,
+    ) -> F::Output {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let _prev = self
+            .values
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert(key.clone(), SpeculativeValue { id, value });
+        // drop the value even if the propose is cancelled
+        let _guard = RollbackGuard {
+            writes: self,
+            key,
+            id,
+        };
+        propose.await
+    }
+
+    /// Get the speculative value of `key`
+    pub(crate) fn get(&self, key: &[u8]) -> Option<Vec<u8>> {
+        self.values
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get(key)
+            .map(|v| v.value.clone())
+    }
+}
+
+/// Drops the speculative value of a write when the propose completes
+struct RollbackGuard<'a> {
+    /// The speculative writes
+    writes: &'a SpeculativeWrites,
+    /// Key of the write
+    key: Vec<u8>,
+    /// Id of the write
+    id: u64,
+}
+
+impl Drop for RollbackGuard<'_> {
+    fn drop(&mut self) {
+        let mut values = self
+            .writes
+            .values
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        // the value may have been overridden by a later write
+        if values.get(&self.key).map_or(false, |v| v.id == self.id) {
+            let _prev = values.remove(&self.key);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use futures::poll;
+    use tokio::sync::oneshot;
+
+    use super::*;
+    use crate::error::{Result, XlineClientError};
+
+    #[tokio::test]
+    async fn failed_propose_should_roll_back_the_speculative_value() {
+        let writes = SpeculativeWrites::default();
+        let (tx, rx) = oneshot::channel::<()>();
+        let mut put = Box::pin(writes.speculate(b"key".to_vec(), b"value".to_vec(), async {
+            rx.await.unwrap();
+            Result::<()>::Err(XlineClientError::Timeout)
+        }));
+        // the value is served before the propose completes
+        assert!(poll!(&mut put).is_pending());
+        assert_eq!(writes.get(b"key"), Some(b"value".to_vec()));
+
+        tx.send(()).unwrap();
+        assert!(put.await.is_err());
+        assert_eq!(writes.get(b"key"), None);
+    }
+
+    #[tokio::test]
+    async fn earlier_write_should_not_drop_a_later_value() {
+        let writes = SpeculativeWrites::default();
+        let (tx, rx) = oneshot::channel::<()>();
+        let mut earlier = Box::pin(writes.speculate(b"key".to_vec(), b"earlier".to_vec(), rx));
+        assert!(poll!(&mut earlier).is_pending());
+        let (_later_tx, later_rx) = oneshot::channel::<()>();
+        let mut later = Box::pin(writes.speculate(b"key".to_vec(), b"later".to_vec(), later_rx));
+        assert!(poll!(&mut later).is_pending());
+
+        tx.send(()).unwrap();
+        earlier.await.unwrap();
+        assert_eq!(writes.get(b"key"), Some(b"later".to_vec()));
+        // a cancelled write is rolled back as well
+        drop(later);
+        assert_eq!(writes.get(b"key"), None);
+    }
+}
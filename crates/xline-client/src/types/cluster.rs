@@ -131,3 +131,73 @@ impl From<MemberUpdateRequest> for xlineapi::MemberUpdateRequest {
         req.inner
     }
 }
+
+/// A member of the cluster with its reachability
+#[derive(Clone, Debug, PartialEq)]
+pub struct DetailedMember {
+    /// The inner member
+    inner: Member,
+    /// Whether the member answered a probe on its client urls
+    reachable: bool,
+}
+
+impl DetailedMember {
+    /// Creates a new `DetailedMember`
+    #[inline]
+    #[must_use]
+    pub fn new(member: Member, reachable: bool) -> Self {
+        Self {
+            inner: member,
+            reachable,
+        }
+    }
+
+    /// Get the id of the member
+    #[inline]
+    #[must_use]
+    pub fn id(&self) -> u64 {
+        self.inner.id
+    }
+
+    /// Get the name of the member
+    #[inline]
+    #[must_use]
+    pub fn name(&self) -> &str {
+        &self.inner.name
+    }
+
+    /// Get the urls used to communicate with other members
+    #[inline]
+    #[must_use]
+    pub fn peer_urls(&self) -> &[String] {
+        &self.inner.peer_ur_ls
+    }
+
+    /// Get the urls used to serve clients, empty if the member has not started yet
+    #[inline]
+    #[must_use]
+    pub fn client_urls(&self) -> &[String] {
+        &self.inner.client_ur_ls
+    }
+
+    /// Whether the member is a learner
+    #[inline]
+    #[must_use]
+    pub fn is_learner(&self) -> bool {
+        self.inner.is_learner
+    }
+
+    /// Whether the member is reachable from this client
+    #[inline]
+    #[must_use]
+    pub fn is_reachable(&self) -> bool {
+        self.reachable
+    }
+}
+
+impl From<DetailedMember> for Member {
+    #[inline]
+    fn from(member: DetailedMember) -> Self {
+        member.inner
+    }
+}
@@ -8,7 +8,9 @@ pub use xlineapi::{
 #[derive(Debug, PartialEq)]
 pub struct PutRequest {
     /// Inner request
-    inner: xlineapi::PutRequest,
+    pub(crate) inner: xlineapi::PutRequest,
+    /// Whether the value is stored as a delta against a materialized version of the key
+    delta_encode: bool,
 }
 
 impl PutRequest {
@@ -24,6 +26,7 @@ impl PutRequest {
                 value: value.into(),
                 ..Default::default()
             },
+            delta_encode: false,
         }
     }
 
@@ -63,6 +66,16 @@ impl PutRequest {
         self
     }
 
+    /// If `delta_encode` is set, Xline stores the value as a delta against a materialized
+    /// version of the key, which saves space for large values with small updates.
+    /// It only applies to a plain put, the puts in a transaction are always materialized.
+    #[inline]
+    #[must_use]
+    pub fn with_delta_encode(mut self, delta_encode: bool) -> Self {
+        self.delta_encode = delta_encode;
+        self
+    }
+
     /// Get `key`
     #[inline]
     #[must_use]
@@ -104,6 +117,13 @@ impl PutRequest {
     pub fn ignore_lease(&self) -> bool {
         self.inner.ignore_lease
     }
+
+    /// Get `delta_encode`
+    #[inline]
+    #[must_use]
+    pub fn delta_encode(&self) -> bool {
+        self.delta_encode
+    }
 }
 
 impl From<PutRequest> for xlineapi::PutRequest {
@@ -117,7 +137,9 @@ impl From<PutRequest> for xlineapi::PutRequest {
 #[derive(Debug, PartialEq)]
 pub struct RangeRequest {
     /// Inner request
-    inner: xlineapi::RangeRequest,
+    pub(crate) inner: xlineapi::RangeRequest,
+    /// Fields of the key-values to return
+    projection: RangeProjection,
 }
 
 impl RangeRequest {
@@ -131,6 +153,7 @@ impl RangeRequest {
                 key: key.into(),
                 ..Default::default()
             },
+            projection: RangeProjection::default(),
         }
     }
 
@@ -224,6 +247,17 @@ impl RangeRequest {
         self
     }
 
+    /// Sets the fields of the key-values to return. Values are trimmed by the server,
+    /// so they are not transferred if not included. A request already set to return only
+    /// the keys keeps doing so.
+    #[inline]
+    #[must_use]
+    pub fn with_projection(mut self, projection: RangeProjection) -> Self {
+        self.inner.keys_only |= !projection.include_values;
+        self.projection = projection;
+        self
+    }
+
     /// If set, Xline will return only the count of the keys
     #[inline]
     #[must_use]
@@ -331,6 +365,13 @@ impl RangeRequest {
         self.inner.count_only
     }
 
+    /// Get `projection`
+    #[inline]
+    #[must_use]
+    pub fn projection(&self) -> RangeProjection {
+        self.projection
+    }
+
     /// Get `min_mod_revision`
     #[inline]
     #[must_use]
@@ -371,7 +412,7 @@ impl From<RangeRequest> for xlineapi::RangeRequest {
 #[derive(Debug, PartialEq)]
 pub struct DeleteRangeRequest {
     /// Inner request
-    inner: xlineapi::DeleteRangeRequest,
+    pub(crate) inner: xlineapi::DeleteRangeRequest,
 }
 
 impl DeleteRangeRequest {
@@ -721,6 +762,8 @@ impl From<TxnRequest> for xlineapi::TxnRequest {
 pub struct CompactionRequest {
     /// The inner request
     inner: xlineapi::CompactionRequest,
+    /// Whether not to compact revisions that active watchers still need
+    respect_watchers: bool,
 }
 
 impl CompactionRequest {
@@ -735,6 +778,7 @@ impl CompactionRequest {
                 revision,
                 ..Default::default()
             },
+            respect_watchers: false,
         }
     }
 
@@ -754,6 +798,30 @@ impl CompactionRequest {
     pub fn physical(&self) -> bool {
         self.inner.physical
     }
+
+    /// Get `revision`
+    #[inline]
+    #[must_use]
+    pub fn revision(&self) -> i64 {
+        self.inner.revision
+    }
+
+    /// Set whether the server should refuse to compact below the min revision which any
+    /// active watcher on it still needs, that is the revision after the last one sent to
+    /// it, the compaction then stops at that revision
+    #[inline]
+    #[must_use]
+    pub fn with_respect_watchers(mut self, respect_watchers: bool) -> Self {
+        self.respect_watchers = respect_watchers;
+        self
+    }
+
+    /// Get `respect_watchers`
+    #[inline]
+    #[must_use]
+    pub fn respect_watchers(&self) -> bool {
+        self.respect_watchers
+    }
 }
 
 impl From<CompactionRequest> for xlineapi::CompactionRequest {
@@ -762,3 +830,107 @@ impl From<CompactionRequest> for xlineapi::CompactionRequest {
         req.inner
     }
 }
+
+/// Consistency level of a read
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Consistency {
+    /// The read reflects all writes committed before it starts
+    #[default]
+    Linearizable,
+    /// The read is served by the local state of a server, which may be stale
+    Serializable,
+    /// The read is served by the local state of a server only if it lags behind the latest
+    /// leader revision known by the client for at most the given number of revisions,
+    /// otherwise it falls back to the leader
+    BoundedStaleness(i64),
+    /// The read is served by the local state of every server, and the freshest response of
+    /// a majority of servers is returned
+    Quorum,
+}
+
+/// Response of a [`Consistency::Serializable`] read, which is served by the most caught-up
+/// member if no leader is elected, e.g. during an election
+#[derive(Clone, Debug, PartialEq)]
+#[non_exhaustive]
+pub struct SerializableRead {
+    /// The range response
+    pub response: RangeResponse,
+    /// Whether the read is served while no leader is elected. Such a response is potentially
+    /// stale, as even the most caught-up member may miss writes committed by the last leader.
+    pub leaderless: bool,
+}
+
+/// Policy selecting the server which serves [`Consistency::Serializable`] reads
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ReadSelectionPolicy {
+    /// The read is proposed through the CURP client
+    #[default]
+    Propose,
+    /// The read is sent to the leader if its EWMA latency measured by this client is the
+    /// lowest, otherwise to the fastest follower. It saves the extra hops when the leader is
+    /// actually the fastest server.
+    SerializablePreferLeader,
+}
+
+/// Projection of the fields returned for each key-value of a range, the key and the
+/// revisions are always returned
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub struct RangeProjection {
+    /// Whether to return the values
+    pub include_values: bool,
+    /// Whether to return the lease ids
+    pub include_lease: bool,
+}
+
+impl Default for RangeProjection {
+    #[inline]
+    fn default() -> Self {
+        Self {
+            include_values: true,
+            include_lease: true,
+        }
+    }
+}
+
+impl RangeProjection {
+    /// Returns only the keys and the revisions
+    #[inline]
+    #[must_use]
+    pub fn keys_only() -> Self {
+        Self {
+            include_values: false,
+            include_lease: false,
+        }
+    }
+
+    /// Sets whether to return the values
+    #[inline]
+    #[must_use]
+    pub fn with_values(mut self, include_values: bool) -> Self {
+        self.include_values = include_values;
+        self
+    }
+
+    /// Sets whether to return the lease ids
+    #[inline]
+    #[must_use]
+    pub fn with_lease(mut self, include_lease: bool) -> Self {
+        self.include_lease = include_lease;
+        self
+    }
+
+    /// Trim the fields excluded by the projection from the response
+    pub(crate) fn apply(self, resp: &mut RangeResponse) {
+        for kv in &mut resp.kvs {
+            if !self.include_values {
+                kv.value.clear();
+            }
+            if !self.include_lease {
+                kv.lease = 0;
+            }
+        }
+    }
+}
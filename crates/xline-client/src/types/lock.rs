@@ -1,7 +1,7 @@
 pub use xlineapi::{LockResponse, UnlockResponse};
 
 /// Default session ttl
-const DEFAULT_SESSION_TTL: i64 = 60;
+pub(crate) const DEFAULT_SESSION_TTL: i64 = 60;
 
 /// Request for `Lock`
 #[derive(Debug, PartialEq)]
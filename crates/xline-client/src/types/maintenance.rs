@@ -1 +1,391 @@
-pub use xlineapi::SnapshotResponse;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use tonic::metadata::MetadataMap;
+pub use xlineapi::{AlarmMember, SnapshotResponse};
+use xlineapi::{
+    COMPACTION_PROGRESS_KEY, EXECUTION_POOL_KEY, REPLICATION_WATERMARK_KEY, SNAPSHOT_INFO_KEY,
+};
+
+use crate::error::{Result, XlineClientError};
+
+/// A change of the alarms of the cluster
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+#[allow(clippy::enum_variant_names)] // named after the alarm events
+pub enum AlarmChange {
+    /// The alarm is raised on the member
+    AlarmRaised(AlarmMember),
+    /// The alarm is disarmed on the member
+    AlarmDisarmed(AlarmMember),
+}
+
+impl AlarmChange {
+    /// Get the changes from the `prev` alarms to the `current` alarms
+    pub(crate) fn diff(prev: &[AlarmMember], current: &[AlarmMember]) -> Vec<Self> {
+        let raised = current
+            .iter()
+            .filter(|alarm| !prev.contains(alarm))
+            .cloned()
+            .map(Self::AlarmRaised);
+        let disarmed = prev
+            .iter()
+            .filter(|alarm| !current.contains(alarm))
+            .cloned()
+            .map(Self::AlarmDisarmed);
+        raised.chain(disarmed).collect()
+    }
+}
+
+/// Outcome of comparing the keyspace hashes of two members at the same revision
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum HashKvVerification {
+    /// Both members hold the same keyspace
+    Consistent,
+    /// The members are compacted to different revisions, so their hashes are not comparable
+    CompactRevisionMismatch {
+        /// Compact revision of the local member
+        local: i64,
+        /// Compact revision of the remote member
+        remote: i64,
+    },
+    /// The members hold different keyspaces
+    HashMismatch {
+        /// Hash of the local member
+        local: u32,
+        /// Hash of the remote member
+        remote: u32,
+    },
+}
+
+impl HashKvVerification {
+    /// Compares two `(hash, compact_revision)` pairs returned by `hashkv`
+    #[inline]
+    #[must_use]
+    pub fn compare(local: (u32, i64), remote: (u32, i64)) -> Self {
+        let ((local_hash, local_compact), (remote_hash, remote_compact)) = (local, remote);
+        if local_compact != remote_compact {
+            return Self::CompactRevisionMismatch {
+                local: local_compact,
+                remote: remote_compact,
+            };
+        }
+        if local_hash != remote_hash {
+            return Self::HashMismatch {
+                local: local_hash,
+                remote: remote_hash,
+            };
+        }
+        Self::Consistent
+    }
+
+    /// Returns `true` if the two members hold the same keyspace
+    #[inline]
+    #[must_use]
+    pub fn is_consistent(&self) -> bool {
+        matches!(*self, Self::Consistent)
+    }
+}
+
+/// Information of the latest snapshot of a member
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct SnapshotInfo {
+    /// Last log index included by the snapshot
+    pub last_included_index: u64,
+    /// Term of the last log entry included by the snapshot
+    pub last_included_term: u64,
+    /// Size of the snapshot in bytes
+    pub size: u64,
+    /// Time when the snapshot was taken or installed by the member
+    pub created_at: SystemTime,
+}
+
+impl SnapshotInfo {
+    /// Parse the snapshot information piggybacked on the metadata of a status response,
+    /// `None` if the member has not made a snapshot since it started
+    pub(crate) fn from_metadata(metadata: &MetadataMap) -> Result<Option<Self>> {
+        let Some(value) = metadata.get(SNAPSHOT_INFO_KEY) else {
+            return Ok(None);
+        };
+        let invalid = || XlineClientError::InvalidArgs(format!("invalid snapshot info {value:?}"));
+        let fields: Vec<u64> = value
+            .to_str()
+            .map_err(|_e| invalid())?
+            .split(',')
+            .map(str::parse)
+            .collect::<std::result::Result<_, _>>()
+            .map_err(|_e| invalid())?;
+        let &[last_included_index, last_included_term, size, created_at] = fields.as_slice() else {
+            return Err(invalid());
+        };
+        Ok(Some(Self {
+            last_included_index,
+            last_included_term,
+            size,
+            created_at: UNIX_EPOCH
+                .checked_add(Duration::from_millis(created_at))
+                .ok_or_else(invalid)?,
+        }))
+    }
+}
+
+/// Utilization of the command execution pool of a member
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct ExecutionPoolStatus {
+    /// Number of workers executing a command
+    pub busy: u64,
+    /// Size of the pool, i.e. the number of workers
+    pub size: u64,
+}
+
+impl ExecutionPoolStatus {
+    /// Parse the execution pool status piggybacked on the metadata of a status response,
+    /// `None` if the member does not report it
+    pub(crate) fn from_metadata(metadata: &MetadataMap) -> Result<Option<Self>> {
+        let Some(value) = metadata.get(EXECUTION_POOL_KEY) else {
+            return Ok(None);
+        };
+        let invalid =
+            || XlineClientError::InvalidArgs(format!("invalid execution pool status {value:?}"));
+        let fields: Vec<u64> = value
+            .to_str()
+            .map_err(|_e| invalid())?
+            .split(',')
+            .map(str::parse)
+            .collect::<std::result::Result<_, _>>()
+            .map_err(|_e| invalid())?;
+        let &[busy, size] = fields.as_slice() else {
+            return Err(invalid());
+        };
+        Ok(Some(Self { busy, size }))
+    }
+
+    /// The fraction of the workers executing a command, in `[0, 1]`
+    #[inline]
+    #[must_use]
+    #[allow(clippy::arithmetic_side_effects, clippy::float_arithmetic)] // float calculation
+    #[allow(clippy::as_conversions, clippy::cast_precision_loss)] // pool sizes are small
+    pub fn utilization(&self) -> f64 {
+        if self.size == 0 {
+            return 0.0;
+        }
+        (self.busy as f64 / self.size as f64).min(1.0)
+    }
+}
+
+/// Parse the replication watermark piggybacked on the metadata of a status response, `None` if
+/// the member has never replicated from a source cluster
+pub(crate) fn replication_watermark(metadata: &MetadataMap) -> Result<Option<u64>> {
+    let Some(value) = metadata.get(REPLICATION_WATERMARK_KEY) else {
+        return Ok(None);
+    };
+    value
+        .to_str()
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .map(Some)
+        .ok_or_else(|| {
+            XlineClientError::InvalidArgs(format!("invalid replication watermark {value:?}"))
+        })
+}
+
+/// Progress of the latest physical compaction of a member
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct CompactionProgress {
+    /// The revision to compact to
+    pub revision: i64,
+    /// The number of key revisions removed so far
+    pub compacted: u64,
+    /// The number of key revisions to remove in total
+    pub total: u64,
+    /// Whether the compaction is finished
+    pub finished: bool,
+}
+
+impl CompactionProgress {
+    /// Parse the compaction progress piggybacked on the metadata of a status response, `None`
+    /// if the member has not run a physical compaction since it started
+    pub(crate) fn from_metadata(metadata: &MetadataMap) -> Result<Option<Self>> {
+        let Some(value) = metadata.get(COMPACTION_PROGRESS_KEY) else {
+            return Ok(None);
+        };
+        let invalid =
+            || XlineClientError::InvalidArgs(format!("invalid compaction progress {value:?}"));
+        let fields: Vec<&str> = value.to_str().map_err(|_e| invalid())?.split(',').collect();
+        let &[revision, compacted, total, finished] = fields.as_slice() else {
+            return Err(invalid());
+        };
+        let finished = match finished {
+            "0" => false,
+            "1" => true,
+            _ => return Err(invalid()),
+        };
+        Ok(Some(Self {
+            revision: revision.parse().map_err(|_e| invalid())?,
+            compacted: compacted.parse().map_err(|_e| invalid())?,
+            total: total.parse().map_err(|_e| invalid())?,
+            finished,
+        }))
+    }
+
+    /// Get the percent complete of the compaction
+    #[inline]
+    #[must_use]
+    pub fn percent(&self) -> u64 {
+        self.compacted
+            .saturating_mul(100)
+            .checked_div(self.total)
+            .unwrap_or(100)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use xlineapi::{AlarmType, StatusResponse};
+
+    use super::*;
+
+    #[test]
+    fn diff_should_emit_raised_and_disarmed_alarms() {
+        let nospace = AlarmMember::new(1, AlarmType::Nospace);
+        let corrupt = AlarmMember::new(2, AlarmType::Corrupt);
+        assert!(AlarmChange::diff(&[], &[]).is_empty());
+        assert_eq!(
+            AlarmChange::diff(&[], &[nospace.clone()]),
+            vec![AlarmChange::AlarmRaised(nospace.clone())]
+        );
+        assert!(AlarmChange::diff(&[nospace.clone()], &[nospace.clone()]).is_empty());
+        assert_eq!(
+            AlarmChange::diff(&[nospace.clone()], &[corrupt.clone()]),
+            vec![
+                AlarmChange::AlarmRaised(corrupt),
+                AlarmChange::AlarmDisarmed(nospace)
+            ]
+        );
+    }
+
+    #[test]
+    fn snapshot_info_should_be_parsed_from_status_metadata() {
+        let mut resp = tonic::Response::new(StatusResponse::default());
+        assert_eq!(SnapshotInfo::from_metadata(resp.metadata()).unwrap(), None);
+
+        let _ig = resp.metadata_mut().insert(
+            SNAPSHOT_INFO_KEY,
+            "42,3,1024,1700000000000".parse().unwrap(),
+        );
+        assert_eq!(
+            SnapshotInfo::from_metadata(resp.metadata()).unwrap(),
+            Some(SnapshotInfo {
+                last_included_index: 42,
+                last_included_term: 3,
+                size: 1024,
+                created_at: UNIX_EPOCH + Duration::from_secs(1_700_000_000),
+            })
+        );
+
+        for malformed in ["42,3,1024", "42,3,x,1700000000000", "42,3,1024,1,2"] {
+            let _ig = resp
+                .metadata_mut()
+                .insert(SNAPSHOT_INFO_KEY, malformed.parse().unwrap());
+            assert!(SnapshotInfo::from_metadata(resp.metadata()).is_err());
+        }
+    }
+
+    #[test]
+    fn replication_watermark_should_be_parsed_from_status_metadata() {
+        let mut resp = tonic::Response::new(StatusResponse::default());
+        assert_eq!(replication_watermark(resp.metadata()).unwrap(), None);
+
+        let _ig = resp
+            .metadata_mut()
+            .insert(REPLICATION_WATERMARK_KEY, 42_u64.into());
+        assert_eq!(replication_watermark(resp.metadata()).unwrap(), Some(42));
+
+        let _ig = resp
+            .metadata_mut()
+            .insert(REPLICATION_WATERMARK_KEY, "x".parse().unwrap());
+        assert!(replication_watermark(resp.metadata()).is_err());
+    }
+
+    #[test]
+    fn compaction_progress_should_be_parsed_from_status_metadata() {
+        let mut resp = tonic::Response::new(StatusResponse::default());
+        assert_eq!(
+            CompactionProgress::from_metadata(resp.metadata()).unwrap(),
+            None
+        );
+
+        let _ig = resp
+            .metadata_mut()
+            .insert(COMPACTION_PROGRESS_KEY, "5,1,4,0".parse().unwrap());
+        let progress = CompactionProgress::from_metadata(resp.metadata())
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            progress,
+            CompactionProgress {
+                revision: 5,
+                compacted: 1,
+                total: 4,
+                finished: false,
+            }
+        );
+        assert_eq!(progress.percent(), 25);
+
+        for malformed in ["5,1,4", "5,1,4,2", "5,x,4,1", "5,1,4,1,0"] {
+            let _ig = resp
+                .metadata_mut()
+                .insert(COMPACTION_PROGRESS_KEY, malformed.parse().unwrap());
+            assert!(CompactionProgress::from_metadata(resp.metadata()).is_err());
+        }
+    }
+
+    #[test]
+    fn execution_pool_status_should_be_parsed_from_status_metadata() {
+        let mut resp = tonic::Response::new(StatusResponse::default());
+        assert_eq!(
+            ExecutionPoolStatus::from_metadata(resp.metadata()).unwrap(),
+            None
+        );
+
+        let _ig = resp
+            .metadata_mut()
+            .insert(EXECUTION_POOL_KEY, "2,8".parse().unwrap());
+        let status = ExecutionPoolStatus::from_metadata(resp.metadata())
+            .unwrap()
+            .unwrap();
+        assert_eq!(status, ExecutionPoolStatus { busy: 2, size: 8 });
+        assert!((status.utilization() - 0.25).abs() < f64::EPSILON);
+
+        for malformed in ["8", "2,x", "2,8,1"] {
+            let _ig = resp
+                .metadata_mut()
+                .insert(EXECUTION_POOL_KEY, malformed.parse().unwrap());
+            assert!(ExecutionPoolStatus::from_metadata(resp.metadata()).is_err());
+        }
+    }
+
+    #[test]
+    fn compare_should_flag_the_diverged_member() {
+        let members = [(0x1234, 5), (0x1234, 5), (0x4321, 5)];
+
+        assert!(HashKvVerification::compare(members[0], members[1]).is_consistent());
+        assert_eq!(
+            HashKvVerification::compare(members[0], members[2]),
+            HashKvVerification::HashMismatch {
+                local: 0x1234,
+                remote: 0x4321
+            }
+        );
+        assert_eq!(
+            HashKvVerification::compare(members[1], (0x1234, 3)),
+            HashKvVerification::CompactRevisionMismatch {
+                local: 5,
+                remote: 3
+            }
+        );
+    }
+}
@@ -1,13 +1,20 @@
 use std::{
     fmt::Debug,
     ops::{Deref, DerefMut},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
 };
 
 use futures::channel::mpsc::Sender;
 use xlineapi::{command::KeyRange, RequestUnion, WatchCancelRequest, WatchProgressRequest};
 pub use xlineapi::{Event, EventType, KeyValue, WatchResponse};
 
-use crate::error::{Result, XlineClientError};
+use crate::{
+    error::{Result, XlineClientError},
+    key_codec::KeyCodec,
+};
 
 /// The watching handle.
 #[derive(Debug)]
@@ -16,6 +23,8 @@ pub struct Watcher {
     watch_id: i64,
     /// The channel sender
     sender: Sender<xlineapi::WatchRequest>,
+    /// Codec of the keys of new watches, no codec if not set
+    key_codec: Option<KeyCodec>,
 }
 
 impl Watcher {
@@ -23,7 +32,16 @@ impl Watcher {
     #[inline]
     #[must_use]
     pub fn new(watch_id: i64, sender: Sender<xlineapi::WatchRequest>) -> Self {
-        Self { watch_id, sender }
+        Self {
+            watch_id,
+            sender,
+            key_codec: None,
+        }
+    }
+
+    /// Set the codec of the keys of new watches
+    pub(crate) fn with_key_codec(self, key_codec: Option<KeyCodec>) -> Self {
+        Self { key_codec, ..self }
     }
 
     /// The ID of the watcher.
@@ -40,8 +58,12 @@ impl Watcher {
     /// If sender fails to send to channel
     #[inline]
     pub fn watch(&mut self, request: WatchRequest) -> Result<()> {
+        let mut create = xlineapi::WatchCreateRequest::from(request);
+        if let Some(codec) = self.key_codec {
+            codec.encode_watch(&mut create)?;
+        }
         let request = xlineapi::WatchRequest {
-            request_union: Some(RequestUnion::CreateRequest(request.into())),
+            request_union: Some(RequestUnion::CreateRequest(create)),
         };
 
         self.sender
@@ -105,7 +127,7 @@ impl Watcher {
 #[derive(Clone, Debug, PartialEq)]
 pub struct WatchRequest {
     /// Inner watch create request
-    inner: xlineapi::WatchCreateRequest,
+    pub(crate) inner: xlineapi::WatchCreateRequest,
 }
 
 impl WatchRequest {
@@ -239,6 +261,70 @@ impl From<WatchFilterType> for i32 {
     }
 }
 
+/// Event of an ephemeral key, see [`crate::clients::WatchClient::watch_ephemeral`]
+#[derive(Clone, Debug, PartialEq)]
+#[non_exhaustive]
+pub enum EphemeralEvent {
+    /// The key is put, carries the new key-value
+    Put(KeyValue),
+    /// The key is deleted explicitly, carries the deleted key-value
+    Delete(KeyValue),
+    /// The key is deleted because its lease has expired or been revoked, carries the deleted
+    /// key-value
+    LeaseExpiredDelete(KeyValue),
+}
+
+/// Position of the last processed event of a watch, see
+/// [`crate::clients::WatchClient::watch_with_checkpoint`]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct WatchCheckpoint {
+    /// Id of the watcher which received the event, a resumed watch gets a new id
+    pub watch_id: i64,
+    /// Revision of the event
+    pub revision: i64,
+    /// Index of the event among the events of the same revision
+    pub event_index: usize,
+}
+
+impl WatchCheckpoint {
+    /// Creates a new `WatchCheckpoint`
+    #[inline]
+    #[must_use]
+    pub const fn new(watch_id: i64, revision: i64, event_index: usize) -> Self {
+        Self {
+            watch_id,
+            revision,
+            event_index,
+        }
+    }
+
+    /// Whether the event at `revision` and `event_index` is not after this checkpoint
+    #[inline]
+    #[must_use]
+    pub const fn covers(&self, revision: i64, event_index: usize) -> bool {
+        revision < self.revision || (revision == self.revision && event_index <= self.event_index)
+    }
+}
+
+/// A user-provided store of watch checkpoints, which should outlive the process so that a
+/// restarted watch can resume from the last processed event
+pub trait CheckpointStore {
+    /// Load the saved checkpoint, return `None` if there is none
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the store fails to load
+    fn load(&self) -> Result<Option<WatchCheckpoint>>;
+
+    /// Save the checkpoint, replacing the previous one
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the store fails to save
+    fn save(&self, checkpoint: WatchCheckpoint) -> Result<()>;
+}
+
 /// Watch response stream
 #[derive(Debug)]
 pub struct WatchStreaming {
@@ -246,6 +332,10 @@ pub struct WatchStreaming {
     inner: tonic::Streaming<WatchResponse>,
     /// A sender of WatchResponse, used to keep response stream alive
     _sender: Sender<xlineapi::WatchRequest>,
+    /// Codec of the keys of events, no codec if not set
+    key_codec: Option<KeyCodec>,
+    /// Active watches of the stream counted in the client, not counted if not set
+    active_watches: Option<ActiveWatches>,
 }
 
 impl WatchStreaming {
@@ -259,7 +349,93 @@ impl WatchStreaming {
         Self {
             inner,
             _sender: sender,
+            key_codec: None,
+            active_watches: None,
+        }
+    }
+
+    /// Set the codec of the keys of events
+    pub(crate) fn with_key_codec(self, key_codec: Option<KeyCodec>) -> Self {
+        Self { key_codec, ..self }
+    }
+
+    /// Count the active watches of the stream in `active_watches`
+    pub(crate) fn with_active_watches(self, active_watches: ActiveWatches) -> Self {
+        Self {
+            active_watches: Some(active_watches),
+            ..self
+        }
+    }
+
+    /// Fetch the next message of the stream, the keys of events are decoded by the key codec
+    /// of the client
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the stream fails to receive a message
+    #[inline]
+    pub async fn message(&mut self) -> std::result::Result<Option<WatchResponse>, tonic::Status> {
+        let mut resp = match self.inner.message().await {
+            Ok(resp) => resp,
+            Err(err) => {
+                if let Some(ref mut active_watches) = self.active_watches {
+                    active_watches.close();
+                }
+                return Err(err);
+            }
+        };
+        if let Some(ref mut active_watches) = self.active_watches {
+            match resp {
+                Some(ref resp) => active_watches.observe(resp),
+                None => active_watches.close(),
+            }
         }
+        if let (Some(codec), Some(resp)) = (self.key_codec, resp.as_mut()) {
+            codec.decode_watch_response(resp);
+        }
+        Ok(resp)
+    }
+}
+
+/// Active watches of a watch stream, which are counted in the active watches of the client
+/// until they are canceled or the stream is dropped
+#[derive(Debug)]
+pub(crate) struct ActiveWatches {
+    /// Active watches of the client
+    total: Arc<AtomicUsize>,
+    /// Active watches of this stream
+    own: usize,
+}
+
+impl ActiveWatches {
+    /// Count the watch created by the first response of the stream in `total`
+    pub(crate) fn new(total: Arc<AtomicUsize>) -> Self {
+        let _prev = total.fetch_add(1, Ordering::Relaxed);
+        Self { total, own: 1 }
+    }
+
+    /// Update the count by a response of the stream, a creation rejected by the server is
+    /// both created and canceled, which does not change the count
+    fn observe(&mut self, resp: &WatchResponse) {
+        if resp.created && !resp.canceled {
+            self.own = self.own.saturating_add(1);
+            let _prev = self.total.fetch_add(1, Ordering::Relaxed);
+        } else if resp.canceled && !resp.created && self.own > 0 {
+            self.own = self.own.saturating_sub(1);
+            let _prev = self.total.fetch_sub(1, Ordering::Relaxed);
+        }
+    }
+
+    /// Release all watches of the stream once it is closed
+    fn close(&mut self) {
+        let _prev = self.total.fetch_sub(self.own, Ordering::Relaxed);
+        self.own = 0;
+    }
+}
+
+impl Drop for ActiveWatches {
+    fn drop(&mut self) {
+        self.close();
     }
 }
 
@@ -1,11 +1,19 @@
 //! The following tests are originally from `etcd-client`
+use std::{collections::HashSet, time::Duration};
+
 use test_macros::abort_on_panic;
 use xline_client::{
-    error::Result,
-    types::kv::{
-        CompactionRequest, Compare, CompareResult, DeleteRangeRequest, PutRequest, RangeRequest,
-        TxnOp, TxnRequest,
+    clients::NamespacedClient,
+    error::{Result, XlineClientError},
+    types::{
+        kv::{
+            CompactionRequest, Compare, CompareResult, Consistency, DeleteRangeRequest, PutRequest,
+            RangeProjection, RangeRequest, TxnOp, TxnRequest,
+        },
+        lease::{LeaseGrantRequest, LeaseRevokeRequest, LeaseTimeToLiveRequest},
+        watch::WatchRequest,
     },
+    Client, ClientOptions, KeyCodec,
 };
 
 use super::common::get_cluster_client;
@@ -44,6 +52,31 @@ async fn put_should_success_in_normal_path() -> Result<()> {
     Ok(())
 }
 
+#[tokio::test(flavor = "multi_thread")]
+#[abort_on_panic]
+async fn delta_encoded_puts_should_read_back_every_version() -> Result<()> {
+    let (_cluster, client) = get_cluster_client().await.unwrap();
+    let client = client.kv_client();
+
+    let mut value = vec![0; 1024];
+    let mut revisions = Vec::new();
+    for i in 0..8 {
+        value[i] = 1;
+        let resp = client
+            .put(PutRequest::new("delta", value.clone()).with_delta_encode(true))
+            .await?;
+        revisions.push((resp.header.unwrap().revision, value.clone()));
+    }
+    for (revision, expected) in revisions {
+        let resp = client
+            .range(RangeRequest::new("delta").with_revision(revision))
+            .await?;
+        assert_eq!(resp.kvs[0].value, expected);
+    }
+
+    Ok(())
+}
+
 #[tokio::test(flavor = "multi_thread")]
 #[abort_on_panic]
 async fn range_should_fetches_previously_put_keys() -> Result<()> {
@@ -270,3 +303,947 @@ async fn compact_should_remove_previous_revision() -> Result<()> {
 
     Ok(())
 }
+
+#[tokio::test(flavor = "multi_thread")]
+#[abort_on_panic]
+async fn compact_respecting_watchers_should_stop_at_watched_revision() -> Result<()> {
+    let (cluster, _client) = get_cluster_client().await.unwrap();
+    // watchers are per server, so both the watch and the compaction go to the same one
+    let client = Client::connect([cluster.get_client_url(0)], ClientOptions::default())
+        .await
+        .unwrap();
+    let kv_client = client.kv_client();
+    let mut watch_client = client.watch_client();
+
+    for i in 0..10 {
+        kv_client
+            .put(PutRequest::new("compact", i.to_string()))
+            .await?;
+    }
+    let (_watcher, _stream) = watch_client
+        .watch(WatchRequest::new("compact").with_start_revision(5))
+        .await?;
+
+    let (_resp, revision) = kv_client
+        .compact_with_revision(CompactionRequest::new(10).with_respect_watchers(true))
+        .await?;
+    assert_eq!(revision, 5);
+    // revisions watched from are kept
+    let resp = kv_client
+        .range(RangeRequest::new("compact").with_revision(5))
+        .await?;
+    assert_eq!(resp.kvs[0].mod_revision, 5);
+    let resp = kv_client
+        .range(RangeRequest::new("compact").with_revision(4))
+        .await;
+    assert!(resp.is_err(), "revision 4 should be compacted");
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+#[abort_on_panic]
+async fn exists_should_tell_whether_key_exists() -> Result<()> {
+    let (_cluster, client) = get_cluster_client().await.unwrap();
+    let client = client.kv_client();
+
+    client.put(PutRequest::new("exists", "value")).await?;
+
+    assert!(client.exists("exists", Consistency::Linearizable).await?);
+    assert!(client.exists("exists", Consistency::Serializable).await?);
+    assert!(
+        !client
+            .exists("not_exists", Consistency::Linearizable)
+            .await?
+    );
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+#[abort_on_panic]
+async fn create_should_create_absent_key() -> Result<()> {
+    let (_cluster, client) = get_cluster_client().await.unwrap();
+    let client = client.kv_client();
+
+    assert!(client.create("create", "value", None).await?);
+
+    let resp = client.range(RangeRequest::new("create")).await?;
+    assert_eq!(resp.kvs.len(), 1);
+    assert_eq!(resp.kvs[0].value, b"value");
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+#[abort_on_panic]
+async fn create_should_not_overwrite_existing_key() -> Result<()> {
+    let (_cluster, client) = get_cluster_client().await.unwrap();
+    let client = client.kv_client();
+
+    client.put(PutRequest::new("create", "old")).await?;
+    assert!(!client.create("create", "new", None).await?);
+
+    let resp = client.range(RangeRequest::new("create")).await?;
+    assert_eq!(resp.kvs.len(), 1);
+    assert_eq!(resp.kvs[0].value, b"old");
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+#[abort_on_panic]
+async fn create_should_attach_lease() -> Result<()> {
+    let (_cluster, client) = get_cluster_client().await.unwrap();
+    let lease_id = client
+        .lease_client()
+        .grant(LeaseGrantRequest::new(60))
+        .await?
+        .id;
+    let client = client.kv_client();
+
+    assert!(client.create("create", "value", Some(lease_id)).await?);
+
+    let resp = client.range(RangeRequest::new("create")).await?;
+    assert_eq!(resp.kvs.len(), 1);
+    assert_eq!(resp.kvs[0].lease, lease_id);
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+#[abort_on_panic]
+async fn compare_and_delete_should_delete_matching_value() -> Result<()> {
+    let (_cluster, client) = get_cluster_client().await.unwrap();
+    let client = client.kv_client();
+
+    client.put(PutRequest::new("cad", "owner")).await?;
+    assert!(client.compare_and_delete("cad", "owner").await?);
+
+    let resp = client.range(RangeRequest::new("cad")).await?;
+    assert!(resp.kvs.is_empty());
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+#[abort_on_panic]
+async fn compare_and_delete_should_not_delete_mismatched_value() -> Result<()> {
+    let (_cluster, client) = get_cluster_client().await.unwrap();
+    let client = client.kv_client();
+
+    client.put(PutRequest::new("cad", "owner")).await?;
+    assert!(!client.compare_and_delete("cad", "other").await?);
+
+    let resp = client.range(RangeRequest::new("cad")).await?;
+    assert_eq!(resp.kvs.len(), 1);
+    assert_eq!(resp.kvs[0].value, b"owner");
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+#[abort_on_panic]
+async fn compare_and_delete_should_not_delete_absent_key() -> Result<()> {
+    let (_cluster, client) = get_cluster_client().await.unwrap();
+    let client = client.kv_client();
+
+    assert!(!client.compare_and_delete("cad", "").await?);
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+#[abort_on_panic]
+async fn delete_many_if_should_delete_all_keys_only_if_guard_matches() -> Result<()> {
+    let (_cluster, client) = get_cluster_client().await.unwrap();
+    let client = client.kv_client();
+    let keys = ["job/a", "job/b", "job/c"];
+
+    client.put(PutRequest::new("job/owner", "me")).await?;
+    for key in keys {
+        client.put(PutRequest::new(key, "x")).await?;
+    }
+
+    // the guard does not match, none is deleted
+    assert!(!client.delete_many_if("job/owner", "other", keys).await?);
+    let resp = client
+        .range(
+            RangeRequest::new("job/")
+                .with_prefix()
+                .with_count_only(true),
+        )
+        .await?;
+    assert_eq!(resp.count, 4);
+
+    // the guard matches, all are deleted
+    assert!(client.delete_many_if("job/owner", "me", keys).await?);
+    let resp = client
+        .range(RangeRequest::new("job/").with_prefix())
+        .await?;
+    assert_eq!(resp.kvs.len(), 1);
+    assert_eq!(resp.kvs[0].key, b"job/owner");
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+#[abort_on_panic]
+async fn key_history_should_list_all_versions_and_deletes() -> Result<()> {
+    let (_cluster, client) = get_cluster_client().await.unwrap();
+    let client = client.kv_client();
+
+    let mut revisions = Vec::new();
+    for value in ["v1", "v2", "v3"] {
+        let resp = client.put(PutRequest::new("history", value)).await?;
+        revisions.push(resp.header.unwrap().revision);
+    }
+    let resp = client.delete(DeleteRangeRequest::new("history")).await?;
+    revisions.push(resp.header.unwrap().revision);
+    // changes of other keys are not in the history
+    client.put(PutRequest::new("history2", "v")).await?;
+
+    let history = client.key_history("history", 1, i64::MAX).await?;
+    assert_eq!(
+        history,
+        vec![
+            (revisions[0], Some(b"v1".to_vec())),
+            (revisions[1], Some(b"v2".to_vec())),
+            (revisions[2], Some(b"v3".to_vec())),
+            (revisions[3], None),
+        ]
+    );
+
+    let history = client
+        .key_history("history", revisions[1], revisions[2])
+        .await?;
+    assert_eq!(
+        history,
+        vec![
+            (revisions[1], Some(b"v2".to_vec())),
+            (revisions[2], Some(b"v3".to_vec())),
+        ]
+    );
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+#[abort_on_panic]
+async fn wait_value_should_return_immediately_if_value_already_matches() -> Result<()> {
+    let (_cluster, client) = get_cluster_client().await.unwrap();
+    let client = client.kv_client();
+
+    client.put(PutRequest::new("wait", "ready")).await?;
+    let value = client
+        .wait_value(
+            "wait",
+            |value| value == Some(b"ready".as_slice()),
+            Some(Duration::from_secs(1)),
+        )
+        .await?;
+    assert_eq!(value, Some(b"ready".to_vec()));
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+#[abort_on_panic]
+async fn wait_value_should_return_after_value_matches() -> Result<()> {
+    let (_cluster, client) = get_cluster_client().await.unwrap();
+    let client = client.kv_client();
+
+    client.put(PutRequest::new("wait", "pending")).await?;
+    let client_c = client.clone();
+    let handle = tokio::spawn(async move {
+        client_c
+            .wait_value(
+                "wait",
+                |value| value == Some(b"ready".as_slice()),
+                Some(Duration::from_secs(5)),
+            )
+            .await
+    });
+    tokio::time::sleep(Duration::from_millis(500)).await;
+    assert!(!handle.is_finished());
+
+    client.put(PutRequest::new("wait", "still pending")).await?;
+    client.put(PutRequest::new("wait", "ready")).await?;
+    let value = handle.await.unwrap()?;
+    assert_eq!(value, Some(b"ready".to_vec()));
+
+    // an absent key never matches, so the wait times out
+    let res = client
+        .wait_value(
+            "absent",
+            |value| value.is_some(),
+            Some(Duration::from_millis(500)),
+        )
+        .await;
+    assert!(matches!(res, Err(XlineClientError::Timeout)));
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+#[abort_on_panic]
+async fn put_many_with_lease_should_expire_together() -> Result<()> {
+    let (_cluster, client) = get_cluster_client().await.unwrap();
+    let mut lease_client = client.lease_client();
+    let lease_id = lease_client.grant(LeaseGrantRequest::new(60)).await?.id;
+    let client = client.kv_client();
+
+    let kvs = [
+        ("lease1", "value1"),
+        ("lease2", "value2"),
+        ("lease3", "value3"),
+    ];
+    let resp = client.put_many_with_lease(&kvs, lease_id).await?;
+    assert!(resp.succeeded);
+
+    for (key, value) in kvs {
+        let resp = client.range(RangeRequest::new(key)).await?;
+        assert_eq!(resp.kvs.len(), 1);
+        assert_eq!(resp.kvs[0].value, value.as_bytes());
+        assert_eq!(resp.kvs[0].lease, lease_id);
+    }
+
+    lease_client
+        .revoke(LeaseRevokeRequest::new(lease_id))
+        .await?;
+    let resp = client
+        .range(RangeRequest::new("lease").with_prefix())
+        .await?;
+    assert!(resp.kvs.is_empty());
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+#[abort_on_panic]
+async fn ordered_propose_queue_should_apply_in_the_pushed_order() -> Result<()> {
+    let (_cluster, client) = get_cluster_client().await.unwrap();
+    let client = client.kv_client();
+
+    let queue = client.ordered_propose_queue();
+    let acks: Vec<_> = (0..10)
+        .map(|i| {
+            queue.push(TxnOp::put(
+                PutRequest::new("ordered", i.to_string()).with_prev_kv(true),
+            ))
+        })
+        .collect();
+
+    let mut last_revision = 0;
+    for (i, ack) in acks.into_iter().enumerate() {
+        let Some(xlineapi::Response::ResponsePut(resp)) = ack.wait().await?.response else {
+            panic!("the response of a put should be a put response");
+        };
+        // each put observes the value of the put pushed right before it
+        let prev = resp.prev_kv.map(|kv| kv.value);
+        let expected = i.checked_sub(1).map(|prev| prev.to_string().into_bytes());
+        assert_eq!(prev, expected);
+        let revision = resp.header.unwrap().revision;
+        assert!(revision > last_revision);
+        last_revision = revision;
+    }
+
+    let resp = client.range(RangeRequest::new("ordered")).await?;
+    assert_eq!(resp.kvs[0].value, b"9");
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+#[abort_on_panic]
+async fn reattach_lease_should_move_the_key_to_the_new_lease() -> Result<()> {
+    let (_cluster, client) = get_cluster_client().await.unwrap();
+    let mut lease_client = client.lease_client();
+    let lease_a = lease_client.grant(LeaseGrantRequest::new(60)).await?.id;
+    let lease_b = lease_client.grant(LeaseGrantRequest::new(60)).await?.id;
+    let client = client.kv_client();
+
+    client
+        .put(PutRequest::new("reattach", "value").with_lease(lease_a))
+        .await?;
+    assert!(client.reattach_lease("reattach", lease_b).await?);
+    assert!(!client.reattach_lease("reattach-absent", lease_b).await?);
+
+    // the key survives the old lease
+    lease_client
+        .revoke(LeaseRevokeRequest::new(lease_a))
+        .await?;
+    let resp = client.range(RangeRequest::new("reattach")).await?;
+    assert_eq!(resp.kvs.len(), 1);
+    assert_eq!(resp.kvs[0].value, b"value");
+    assert_eq!(resp.kvs[0].lease, lease_b);
+
+    // and is deleted with the new one
+    lease_client
+        .revoke(LeaseRevokeRequest::new(lease_b))
+        .await?;
+    let resp = client.range(RangeRequest::new("reattach")).await?;
+    assert!(resp.kvs.is_empty());
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+#[abort_on_panic]
+async fn claim_one_should_claim_each_item_exactly_once() -> Result<()> {
+    let (_cluster, client) = get_cluster_client().await.unwrap();
+    let mut lease_client = client.lease_client();
+    let lease_a = lease_client.grant(LeaseGrantRequest::new(60)).await?.id;
+    let lease_b = lease_client.grant(LeaseGrantRequest::new(60)).await?.id;
+    let client = client.kv_client();
+    for i in 0..10 {
+        client
+            .put(PutRequest::new(format!("queue/{i}"), format!("job{i}")))
+            .await?;
+    }
+
+    let worker = |claimant: &'static str, lease_id: i64| {
+        let client = client.clone();
+        tokio::spawn(async move {
+            let mut claimed = Vec::new();
+            while let Some((key, _value)) = client.claim_one("queue/", claimant, lease_id).await? {
+                claimed.push(key);
+            }
+            Result::Ok(claimed)
+        })
+    };
+    let worker_a = worker("worker-a", lease_a);
+    let worker_b = worker("worker-b", lease_b);
+    let claimed_a = worker_a.await.unwrap()?;
+    let claimed_b = worker_b.await.unwrap()?;
+    let claimed: HashSet<_> = claimed_a.iter().chain(claimed_b.iter()).cloned().collect();
+    assert_eq!(claimed_a.len() + claimed_b.len(), 10);
+    assert_eq!(claimed.len(), 10);
+
+    // the claims of worker a are released once its lease expires
+    lease_client
+        .revoke(LeaseRevokeRequest::new(lease_a))
+        .await?;
+    let lease_c = lease_client.grant(LeaseGrantRequest::new(60)).await?.id;
+    let mut reclaimed = HashSet::new();
+    while let Some((key, _value)) = client.claim_one("queue/", "worker-c", lease_c).await? {
+        assert!(reclaimed.insert(key));
+    }
+    assert_eq!(reclaimed, claimed_a.into_iter().collect());
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+#[abort_on_panic]
+async fn bounded_staleness_range_should_observe_latest_write() -> Result<()> {
+    let (_cluster, client) = get_cluster_client().await.unwrap();
+    let client = client.kv_client();
+
+    client.put(PutRequest::new("bounded", "value1")).await?;
+    client.put(PutRequest::new("bounded", "value2")).await?;
+
+    // with a zero bound, a lagging server can't serve the read, so it always observes the latest write
+    let resp = client
+        .range_with_consistency(
+            RangeRequest::new("bounded"),
+            Consistency::BoundedStaleness(0),
+        )
+        .await?;
+    assert_eq!(resp.kvs.len(), 1);
+    assert_eq!(resp.kvs[0].value, b"value2");
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+#[abort_on_panic]
+async fn put_with_ttl_should_share_one_lease() -> Result<()> {
+    let (_cluster, client) = get_cluster_client().await.unwrap();
+    let mut lease_client = client.lease_client();
+    let client = client.kv_client();
+
+    for i in 0..10 {
+        client
+            .put_with_ttl(format!("ttl{i}"), "value", Duration::from_secs(10))
+            .await?;
+    }
+
+    let resp = client.range(RangeRequest::new("ttl").with_prefix()).await?;
+    assert_eq!(resp.kvs.len(), 10);
+    let lease_id = resp.kvs[0].lease;
+    assert_ne!(lease_id, 0);
+    assert!(resp.kvs.iter().all(|kv| kv.lease == lease_id));
+
+    let resp = lease_client
+        .time_to_live(LeaseTimeToLiveRequest::new(lease_id))
+        .await?;
+    assert!(resp.ttl >= 10);
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+#[abort_on_panic]
+async fn range_projection_should_trim_values() -> Result<()> {
+    let (_cluster, client) = get_cluster_client().await.unwrap();
+    let lease_client = client.lease_client();
+    let lease_id = lease_client.grant(LeaseGrantRequest::new(60)).await?.id;
+    let client = client.kv_client();
+
+    for key in ["projection1", "projection2"] {
+        client
+            .put(PutRequest::new(key, "large value").with_lease(lease_id))
+            .await?;
+    }
+
+    let projection = RangeProjection::keys_only().with_lease(true);
+    let resp = client
+        .range(
+            RangeRequest::new("projection")
+                .with_prefix()
+                .with_projection(projection),
+        )
+        .await?;
+    assert_eq!(resp.kvs.len(), 2);
+    for kv in &resp.kvs {
+        assert!(kv.value.is_empty());
+        assert_eq!(kv.lease, lease_id);
+    }
+
+    let resp = client
+        .range_with_consistency(
+            RangeRequest::new("projection")
+                .with_prefix()
+                .with_projection(RangeProjection::keys_only()),
+            Consistency::Serializable,
+        )
+        .await?;
+    assert!(resp
+        .kvs
+        .iter()
+        .all(|kv| kv.value.is_empty() && kv.lease == 0 && !kv.key.is_empty()));
+
+    // a projection including the values does not override keys only
+    let resp = client
+        .range(
+            RangeRequest::new("projection")
+                .with_prefix()
+                .with_keys_only(true)
+                .with_projection(RangeProjection::default()),
+        )
+        .await?;
+    assert!(resp
+        .kvs
+        .iter()
+        .all(|kv| kv.value.is_empty() && kv.lease == lease_id));
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+#[abort_on_panic]
+async fn txn_scope_should_commit_accumulated_ops() -> Result<()> {
+    let (_cluster, client) = get_cluster_client().await.unwrap();
+    let client = client.kv_client();
+
+    let mut scope = client.scope();
+    scope
+        .when(Compare::version("scope", CompareResult::Equal, 0))
+        .put(PutRequest::new("scope", "created"))
+        .or_else(TxnOp::range(RangeRequest::new("scope")));
+    let resp = scope.commit().await?;
+    assert!(resp.succeeded);
+
+    let resp = client.range(RangeRequest::new("scope")).await?;
+    assert_eq!(resp.kvs.len(), 1);
+    assert_eq!(resp.kvs[0].value, b"created");
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+#[abort_on_panic]
+async fn txn_scope_should_discard_ops_on_drop() -> Result<()> {
+    let (_cluster, client) = get_cluster_client().await.unwrap();
+    let client = client.kv_client();
+
+    {
+        let mut scope = client.scope();
+        scope
+            .put(PutRequest::new("discarded", "1"))
+            .delete(DeleteRangeRequest::new("discarded"));
+        assert!(!scope.is_empty());
+    }
+
+    let resp = client.range(RangeRequest::new("discarded")).await?;
+    assert!(resp.kvs.is_empty());
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+#[abort_on_panic]
+async fn namespaced_client_should_confine_keys_into_namespace() -> Result<()> {
+    let (_cluster, client) = get_cluster_client().await.unwrap();
+    let tenant = NamespacedClient::new(&client, "tenant/");
+    let client = client.kv_client();
+
+    client.put(PutRequest::new("outside", "0")).await?;
+    client.put(PutRequest::new("tenant0", "0")).await?;
+    tenant.put(PutRequest::new("k", "1")).await?;
+
+    let resp = client.range(RangeRequest::new("tenant/k")).await?;
+    assert_eq!(resp.kvs.len(), 1);
+    assert_eq!(resp.kvs[0].value, b"1");
+    let resp = client.range(RangeRequest::new("k")).await?;
+    assert!(resp.kvs.is_empty());
+
+    let resp = tenant.range(RangeRequest::new("").with_prefix()).await?;
+    assert_eq!(resp.kvs.len(), 1);
+    assert_eq!(resp.kvs[0].key, b"k");
+    assert_eq!(resp.kvs[0].value, b"1");
+
+    let resp = tenant.range(RangeRequest::new("k").with_from_key()).await?;
+    assert_eq!(resp.kvs.len(), 1);
+    assert_eq!(resp.kvs[0].key, b"k");
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+#[abort_on_panic]
+async fn quorum_read_should_return_the_latest_value() -> Result<()> {
+    let (_cluster, client) = get_cluster_client().await.unwrap();
+    let client = client.kv_client();
+
+    client.put(PutRequest::new("quorum", "1")).await?;
+    client.put(PutRequest::new("quorum", "2")).await?;
+
+    let resp = client
+        .range_with_consistency(RangeRequest::new("quorum"), Consistency::Quorum)
+        .await?;
+    assert_eq!(resp.kvs.len(), 1);
+    assert_eq!(resp.kvs[0].value, b"2");
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+#[abort_on_panic]
+async fn count_prefix_all_replicas_should_count_on_every_member() -> Result<()> {
+    let (_cluster, client) = get_cluster_client().await.unwrap();
+    let client = client.kv_client();
+
+    for i in 0..4 {
+        client
+            .put(PutRequest::new(format!("count/{i}"), "v"))
+            .await?;
+    }
+    client.put(PutRequest::new("counter", "v")).await?;
+    // wait for the followers to apply the puts
+    tokio::time::sleep(Duration::from_secs(1)).await;
+
+    let counts = client.count_prefix_all_replicas("count/").await?;
+    assert_eq!(counts.len(), 3);
+    assert!(counts.values().all(|&count| count == 4));
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+#[abort_on_panic]
+async fn serializable_range_should_carry_leader_revision_hint() -> Result<()> {
+    let (cluster, client) = get_cluster_client().await.unwrap();
+    let put_revision = client
+        .kv_client()
+        .put(PutRequest::new("hint", "1"))
+        .await?
+        .header
+        .unwrap()
+        .revision;
+    // wait for the heartbeats to bring the leader revision to followers
+    tokio::time::sleep(Duration::from_secs(1)).await;
+
+    for i in 0..3 {
+        let member = Client::connect([cluster.get_client_url(i)], ClientOptions::default())
+            .await
+            .unwrap()
+            .kv_client();
+        let (resp, leader_revision) = member.serializable_range(RangeRequest::new("hint")).await?;
+        assert!(leader_revision >= put_revision);
+        assert!(leader_revision >= resp.header.unwrap().revision);
+    }
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+#[abort_on_panic]
+async fn range_and_watch_should_not_lose_or_duplicate_events() -> Result<()> {
+    let (_cluster, client) = get_cluster_client().await.unwrap();
+    let client = client.kv_client();
+    for i in 0..5 {
+        client
+            .put(PutRequest::new(format!("cache/{i}"), "v"))
+            .await?;
+    }
+    // keys are written around the operation, each key is written only once
+    let writer = {
+        let client = client.clone();
+        tokio::spawn(async move {
+            for i in 5..15 {
+                client
+                    .put(PutRequest::new(format!("cache/{i}"), "v"))
+                    .await?;
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+            Ok::<_, XlineClientError>(())
+        })
+    };
+    tokio::time::sleep(Duration::from_millis(30)).await;
+    let (snapshot, _watcher, mut stream) = client.range_and_watch("cache/").await?;
+    writer.await.unwrap()?;
+
+    let revision = snapshot.header.unwrap().revision;
+    let mut seen: HashSet<Vec<u8>> = HashSet::new();
+    for kv in snapshot.kvs {
+        assert!(kv.mod_revision <= revision);
+        assert!(seen.insert(kv.key));
+    }
+    while seen.len() < 15 {
+        let resp = tokio::time::timeout(Duration::from_secs(3), stream.message())
+            .await
+            .expect("an event is lost")?
+            .unwrap();
+        for event in resp.events {
+            let kv = event.kv.unwrap();
+            assert!(kv.mod_revision > revision);
+            assert!(seen.insert(kv.key), "an event is duplicated");
+        }
+    }
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+#[abort_on_panic]
+async fn next_sequence_should_be_unique_and_contiguous() -> Result<()> {
+    let (_cluster, client) = get_cluster_client().await.unwrap();
+    let client = client.kv_client();
+    let callers: Vec<_> = (0..5)
+        .map(|_| {
+            let client = client.clone();
+            tokio::spawn(async move {
+                let mut ids = Vec::new();
+                for _ in 0..4 {
+                    ids.push(client.next_sequence("seq").await?);
+                }
+                Ok::<_, XlineClientError>(ids)
+            })
+        })
+        .collect();
+    let mut ids = Vec::new();
+    for caller in callers {
+        ids.extend(caller.await.unwrap()?);
+    }
+    ids.sort_unstable();
+    assert_eq!(ids, (1..=20).collect::<Vec<i64>>());
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+#[abort_on_panic]
+async fn fluent_get_should_match_the_explicit_request() -> Result<()> {
+    let (_cluster, client) = get_cluster_client().await.unwrap();
+    let client = client.kv_client();
+
+    for i in 0..3 {
+        client
+            .put(PutRequest::new(format!("fluent/{i}"), "v"))
+            .await?;
+    }
+    let revision = client
+        .put(PutRequest::new("fluent/3", "v"))
+        .await?
+        .header
+        .unwrap()
+        .revision;
+    client.put(PutRequest::new("fluent/4", "v")).await?;
+
+    let fluent = client
+        .get("fluent/")
+        .count_only()
+        .with_revision(revision)
+        .consistency(Consistency::Linearizable)
+        .with_prefix()
+        .send()
+        .await?;
+    let explicit = client
+        .range_with_consistency(
+            RangeRequest::new("fluent/")
+                .with_prefix()
+                .with_revision(revision)
+                .with_count_only(true),
+            Consistency::Linearizable,
+        )
+        .await?;
+    assert_eq!(fluent.count, 4);
+    assert!(fluent.kvs.is_empty());
+    assert_eq!(fluent.count, explicit.count);
+    assert_eq!(fluent.kvs, explicit.kvs);
+
+    let fluent = client
+        .get("fluent/")
+        .with_prefix()
+        .keys_only()
+        .send()
+        .await?;
+    let explicit = client
+        .range(
+            RangeRequest::new("fluent/")
+                .with_prefix()
+                .with_keys_only(true),
+        )
+        .await?;
+    assert_eq!(fluent.kvs, explicit.kvs);
+    assert!(fluent.kvs.iter().all(|kv| kv.value.is_empty()));
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+#[abort_on_panic]
+async fn keys_with_control_bytes_should_round_trip_through_the_key_codec() -> Result<()> {
+    let (cluster, _client) = get_cluster_client().await.unwrap();
+    let plain = Client::connect(cluster.all_client_addrs(), ClientOptions::default())
+        .await
+        .unwrap()
+        .kv_client();
+    for codec in [KeyCodec::Hex, KeyCodec::Percent] {
+        let options = ClientOptions::default().with_key_codec(codec);
+        let client = Client::connect(cluster.all_client_addrs(), options)
+            .await
+            .unwrap()
+            .kv_client();
+        let prefix = format!("codec/{codec:?}/\x00\r\n");
+        let key = format!("{prefix}\x01\x7f");
+        client.put(PutRequest::new(key.clone(), "value")).await?;
+
+        let resp = client.range(RangeRequest::new(key.clone())).await?;
+        assert_eq!(resp.kvs.len(), 1);
+        assert_eq!(resp.kvs[0].key, key.as_bytes());
+        assert_eq!(resp.kvs[0].value, b"value");
+        // the prefix end is computed on the encoded keys
+        let resp = client
+            .range(RangeRequest::new(prefix.clone()).with_prefix())
+            .await?;
+        assert_eq!(resp.kvs.len(), 1);
+        assert_eq!(resp.kvs[0].key, key.as_bytes());
+
+        // the key is stored encoded
+        let stored = plain
+            .range(RangeRequest::new(codec.encode(key.as_bytes())))
+            .await?;
+        assert_eq!(stored.kvs.len(), 1);
+        assert!(stored.kvs[0].key.iter().all(u8::is_ascii_graphic));
+    }
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+#[abort_on_panic]
+async fn puts_of_the_same_key_within_the_window_should_be_coalesced() -> Result<()> {
+    let (cluster, _client) = get_cluster_client().await.unwrap();
+    let options = ClientOptions::default().with_coalesce_same_key(Duration::from_millis(200));
+    let client = Client::connect(cluster.all_client_addrs(), options)
+        .await
+        .unwrap()
+        .kv_client();
+
+    let results = tokio::join!(
+        client.put(PutRequest::new("coalesce", "v1")),
+        client.put(PutRequest::new("coalesce", "v2")),
+        client.put(PutRequest::new("coalesce", "v3")),
+        client.put(PutRequest::new("coalesce", "v4")),
+        client.put(PutRequest::new("coalesce", "v5")),
+    );
+    let revisions: HashSet<_> = [results.0?, results.1?, results.2?, results.3?, results.4?]
+        .into_iter()
+        .map(|resp| resp.header.unwrap().revision)
+        .collect();
+    // all puts resolve with the result of the only one committed
+    assert_eq!(revisions.len(), 1);
+
+    let resp = client.range(RangeRequest::new("coalesce")).await?;
+    assert_eq!(resp.kvs.len(), 1);
+    assert_eq!(resp.kvs[0].value, b"v5");
+    assert_eq!(resp.kvs[0].version, 1);
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+#[abort_on_panic]
+async fn fast_quorum_out_of_the_safety_bounds_should_be_rejected() -> Result<()> {
+    let (cluster, client) = get_cluster_client().await.unwrap();
+    let res = Client::connect(
+        cluster.all_client_addrs(),
+        ClientOptions::default().with_fast_quorum(0),
+    )
+    .await;
+    assert!(res.is_err());
+
+    // the fast quorum of a 3-node cluster must be 3
+    let options = ClientOptions::default().with_fast_quorum(4);
+    let invalid = Client::connect(cluster.all_client_addrs(), options)
+        .await
+        .unwrap()
+        .kv_client();
+    assert!(invalid.put(PutRequest::new("fast", "v")).await.is_err());
+    let resp = client.kv_client().range(RangeRequest::new("fast")).await?;
+    assert!(resp.kvs.is_empty());
+
+    let options = ClientOptions::default().with_fast_quorum(3);
+    let valid = Client::connect(cluster.all_client_addrs(), options)
+        .await
+        .unwrap()
+        .kv_client();
+    valid.put(PutRequest::new("fast", "v")).await?;
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+#[abort_on_panic]
+async fn oversized_puts_and_txns_should_be_rejected_before_sending() -> Result<()> {
+    let (cluster, _client) = get_cluster_client().await.unwrap();
+    let options = ClientOptions::default()
+        .with_max_key_size(8)
+        .with_max_value_size(4);
+    let client = Client::connect(cluster.all_client_addrs(), options)
+        .await
+        .unwrap()
+        .kv_client();
+
+    let res = client.put(PutRequest::new("oversized/key", "v")).await;
+    assert!(matches!(res, Err(XlineClientError::KeyTooLarge(13, 8))));
+    let res = client.put(PutRequest::new("key", "value")).await;
+    assert!(matches!(res, Err(XlineClientError::ValueTooLarge(5, 4))));
+    let txn = TxnRequest::new()
+        .when([Compare::version("key", CompareResult::Equal, 0)])
+        .and_then([TxnOp::put(PutRequest::new("key", "v"))])
+        .or_else([TxnOp::put(PutRequest::new("key", "value"))]);
+    let res = client.txn(txn).await;
+    assert!(matches!(res, Err(XlineClientError::ValueTooLarge(5, 4))));
+
+    // none of them reaches the cluster
+    let resp = client.range(RangeRequest::new("").with_from_key()).await?;
+    assert!(resp.kvs.is_empty());
+    assert_eq!(resp.header.unwrap().revision, 1);
+
+    client.put(PutRequest::new("key", "v")).await?;
+    Ok(())
+}
@@ -1,4 +1,10 @@
-use std::time::Duration;
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
 
 use test_macros::abort_on_panic;
 use xline_client::{
@@ -110,3 +116,56 @@ async fn lock_should_unlock_after_cancelled() -> Result<()> {
 
     Ok(())
 }
+
+#[tokio::test(flavor = "multi_thread")]
+#[abort_on_panic]
+async fn lock_all_should_not_deadlock_in_different_orders() -> Result<()> {
+    let (_cluster, client) = get_cluster_client().await.unwrap();
+    let client = client.lock_client();
+    let holders = Arc::new(AtomicUsize::new(0));
+
+    let handles: Vec<_> = [["lock-a", "lock-b"], ["lock-b", "lock-a"]]
+        .into_iter()
+        .map(|order| {
+            let client = client.clone();
+            let holders = Arc::clone(&holders);
+            let names: Vec<String> = order.iter().map(|name| (*name).to_owned()).collect();
+            tokio::spawn(async move {
+                for _ in 0..5 {
+                    let guard = client.lock_all(&names, 0).await.unwrap();
+                    assert_eq!(guard.keys().len(), 2);
+                    assert!(guard.keys()[0].starts_with(b"lock-a/"));
+                    // no one else holds the locks
+                    assert_eq!(holders.fetch_add(1, Ordering::SeqCst), 0);
+                    tokio::time::sleep(Duration::from_millis(10)).await;
+                    assert_eq!(holders.fetch_sub(1, Ordering::SeqCst), 1);
+                    guard.unlock().await.unwrap();
+                }
+            })
+        })
+        .collect();
+
+    tokio::time::timeout(Duration::from_secs(30), futures::future::join_all(handles))
+        .await
+        .expect("lock_all deadlocked")
+        .into_iter()
+        .for_each(|res| res.unwrap());
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+#[abort_on_panic]
+async fn lock_all_should_revoke_its_lease_after_unlock() -> Result<()> {
+    let (_cluster, client) = get_cluster_client().await.unwrap();
+    let lease_client = client.lease_client();
+    let client = client.lock_client();
+
+    let names = ["lock-a".to_owned(), "lock-b".to_owned()];
+    let guard = client.lock_all(&names, 0).await?;
+    assert_eq!(lease_client.leases().await?.leases.len(), 1);
+    guard.unlock().await?;
+    assert!(lease_client.leases().await?.leases.is_empty());
+
+    Ok(())
+}
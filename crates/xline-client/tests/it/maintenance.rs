@@ -1,4 +1,15 @@
-use xline_client::error::Result;
+use std::time::Duration;
+
+use futures::StreamExt;
+use xline_client::{
+    error::Result,
+    types::{
+        kv::{CompactionRequest, PutRequest},
+        maintenance::AlarmChange,
+    },
+    Client, ClientOptions,
+};
+use xlineapi::{AlarmAction, AlarmMember, AlarmRequest, AlarmType};
 
 use super::common::get_cluster_client;
 
@@ -19,3 +30,108 @@ async fn snapshot_should_get_valid_data() -> Result<()> {
 
     Ok(())
 }
+
+#[tokio::test(flavor = "multi_thread")]
+async fn hashkv_should_be_consistent_across_members() -> Result<()> {
+    let (cluster, client) = get_cluster_client().await.unwrap();
+    let kv_client = client.kv_client();
+    for i in 0..10 {
+        kv_client
+            .put(PutRequest::new(format!("key{i}"), "value"))
+            .await?;
+    }
+    let revision = kv_client
+        .put(PutRequest::new("key", "value"))
+        .await?
+        .header
+        .unwrap()
+        .revision;
+    // wait for followers to apply the last put
+    tokio::time::sleep(Duration::from_millis(500)).await;
+
+    let mut members = Vec::new();
+    for idx in 0..3 {
+        let member = Client::connect([cluster.get_client_url(idx)], ClientOptions::default())
+            .await
+            .unwrap()
+            .maintenance_client();
+        members.push(member);
+    }
+    let (first, rest) = members.split_first_mut().unwrap();
+    for other in rest {
+        assert!(first.verify_against(other, revision).await?.is_consistent());
+    }
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn watch_alarms_should_emit_raised_and_disarmed() -> Result<()> {
+    let (_cluster, client) = get_cluster_client().await.unwrap();
+    let mut client = client.maintenance_client();
+    let member_id = client.status().await?.header.unwrap().member_id;
+    let mut changes = Box::pin(client.watch_alarms(Duration::from_millis(50)));
+
+    client
+        .alarm(AlarmRequest::new(
+            AlarmAction::Activate,
+            member_id,
+            AlarmType::Nospace,
+        ))
+        .await?;
+    let nospace = AlarmMember::new(member_id, AlarmType::Nospace);
+    let change = tokio::time::timeout(Duration::from_secs(3), changes.next())
+        .await
+        .unwrap()
+        .unwrap()?;
+    assert_eq!(change, AlarmChange::AlarmRaised(nospace.clone()));
+
+    client
+        .alarm(AlarmRequest::new(
+            AlarmAction::Deactivate,
+            member_id,
+            AlarmType::Nospace,
+        ))
+        .await?;
+    let change = tokio::time::timeout(Duration::from_secs(3), changes.next())
+        .await
+        .unwrap()
+        .unwrap()?;
+    assert_eq!(change, AlarmChange::AlarmDisarmed(nospace));
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn watch_compaction_progress_should_end_with_finished_progress() -> Result<()> {
+    let (_cluster, client) = get_cluster_client().await.unwrap();
+    let kv_client = client.kv_client();
+    let mut revision = 0;
+    for i in 0..10 {
+        revision = kv_client
+            .put(PutRequest::new("key", format!("value{i}")))
+            .await?
+            .header
+            .unwrap()
+            .revision;
+    }
+    kv_client
+        .compact(CompactionRequest::new(revision).with_physical())
+        .await?;
+
+    let progress: Vec<_> = tokio::time::timeout(
+        Duration::from_secs(3),
+        client
+            .maintenance_client()
+            .watch_compaction_progress(revision, Duration::from_millis(50))
+            .collect(),
+    )
+    .await
+    .unwrap();
+    let last = progress.last().unwrap().as_ref().unwrap();
+    assert_eq!(last.revision, revision);
+    assert!(last.finished);
+    assert_eq!(last.percent(), 100);
+
+    Ok(())
+}
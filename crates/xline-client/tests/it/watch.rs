@@ -1,11 +1,20 @@
 //! The following tests are originally from `etcd-client`
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
 use xline_client::{
-    error::Result,
+    clients::{CachedView, NamespacedClient},
+    error::{Result, XlineClientError},
     types::{
-        kv::PutRequest,
-        watch::{EventType, WatchRequest},
+        kv::{DeleteRangeRequest, PutRequest, TxnOp, TxnRequest},
+        lease::LeaseGrantRequest,
+        watch::{CheckpointStore, EphemeralEvent, EventType, WatchCheckpoint, WatchRequest},
     },
+    Client, ClientOptions,
 };
+use xline_test_utils::Cluster;
 
 use super::common::get_cluster_client;
 
@@ -37,6 +46,30 @@ async fn watch_should_receive_consistent_events() -> Result<()> {
     Ok(())
 }
 
+#[tokio::test(flavor = "multi_thread")]
+async fn namespaced_watcher_should_confine_new_watches_into_namespace() -> Result<()> {
+    let (_cluster, client) = get_cluster_client().await.unwrap();
+    let mut tenant = NamespacedClient::new(&client, "tenant/");
+    let kv_client = client.kv_client();
+
+    let (mut watcher, mut stream) = tenant.watch(WatchRequest::new("k1")).await?;
+    watcher.watch(WatchRequest::new("k2"))?;
+    let resp = stream.message().await?.unwrap();
+    assert!(resp.created);
+
+    kv_client.put(PutRequest::new("k2", "outside")).await?;
+    tenant.put(PutRequest::new("k2", "inside")).await?;
+
+    let resp = stream.message().await?.unwrap();
+    assert_ne!(resp.watch_id, watcher.watch_id());
+    assert_eq!(resp.events.len(), 1);
+    let kv = resp.events[0].kv.as_ref().unwrap();
+    assert_eq!(kv.key, b"k2");
+    assert_eq!(kv.value, b"inside");
+
+    Ok(())
+}
+
 /// To ensure #505 is fixed
 #[tokio::test(flavor = "multi_thread")]
 async fn watch_stream_should_work_after_watcher_dropped() -> Result<()> {
@@ -58,3 +91,186 @@ async fn watch_stream_should_work_after_watcher_dropped() -> Result<()> {
 
     Ok(())
 }
+
+#[tokio::test(flavor = "multi_thread")]
+async fn watch_until_should_return_first_matching_event() -> Result<()> {
+    let (_cluster, client) = get_cluster_client().await.unwrap();
+    let mut watch_client = client.watch_client();
+    let kv_client = client.kv_client();
+
+    let first = kv_client.put(PutRequest::new("watch02", "01")).await?;
+    kv_client.put(PutRequest::new("watch02", "02")).await?;
+    kv_client.put(PutRequest::new("watch02", "03")).await?;
+    let start_rev = first.header.unwrap().revision;
+
+    let event = watch_client
+        .watch_until(
+            WatchRequest::new("watch02").with_start_revision(start_rev),
+            |event| event.kv.as_ref().is_some_and(|kv| kv.value == b"03"),
+            Some(Duration::from_secs(5)),
+        )
+        .await?;
+
+    let kv = event.kv.as_ref().unwrap();
+    assert_eq!(kv.key, b"watch02");
+    assert_eq!(kv.value, b"03");
+    assert_eq!(kv.mod_revision, start_rev + 2);
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn cached_view_should_reflect_updates() -> Result<()> {
+    let (_cluster, client) = get_cluster_client().await.unwrap();
+    let kv_client = client.kv_client();
+
+    kv_client.put(PutRequest::new("view/a", "1")).await?;
+    let view = CachedView::new(&client, "view/", "view0").await?;
+    let (value, revision) = view.get("view/a")?;
+    assert_eq!(value.as_deref(), Some(b"1".as_slice()));
+
+    let resp = kv_client.put(PutRequest::new("view/a", "2")).await?;
+    let put_revision = resp.header.unwrap().revision;
+    assert!(put_revision > revision);
+    tokio::time::timeout(Duration::from_secs(3), async {
+        while view.revision() < put_revision {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .unwrap();
+
+    let (value, revision) = view.get("view/a")?;
+    assert_eq!(value.as_deref(), Some(b"2".as_slice()));
+    assert_eq!(revision, put_revision);
+    assert!(view.get("view/b")?.0.is_none());
+    assert!(!view.is_stale());
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn watch_ephemeral_should_tell_lease_expired_deletes() -> Result<()> {
+    let (_cluster, client) = get_cluster_client().await.unwrap();
+    let kv_client = client.kv_client();
+    let lease_client = client.lease_client();
+    let mut watch_client = client.watch_client();
+
+    let mut stream = watch_client.watch_ephemeral("ephemeral").await?;
+
+    // an explicit delete of a leased key
+    let lease_id = lease_client.grant(LeaseGrantRequest::new(60)).await?.id;
+    kv_client
+        .put(PutRequest::new("ephemeral", "1").with_lease(lease_id))
+        .await?;
+    kv_client
+        .delete(DeleteRangeRequest::new("ephemeral"))
+        .await?;
+    assert!(matches!(stream.message().await?, Some(EphemeralEvent::Put(kv)) if kv.value == b"1"));
+    assert!(
+        matches!(stream.message().await?, Some(EphemeralEvent::Delete(kv)) if kv.lease == lease_id)
+    );
+
+    // a delete driven by the lease expiry
+    let lease_id = lease_client.grant(LeaseGrantRequest::new(1)).await?.id;
+    kv_client
+        .put(PutRequest::new("ephemeral", "2").with_lease(lease_id))
+        .await?;
+    assert!(matches!(stream.message().await?, Some(EphemeralEvent::Put(kv)) if kv.value == b"2"));
+    let event = tokio::time::timeout(Duration::from_secs(10), stream.message())
+        .await
+        .unwrap()?;
+    assert!(matches!(event, Some(EphemeralEvent::LeaseExpiredDelete(kv)) if kv.lease == lease_id));
+
+    Ok(())
+}
+
+/// An in-memory checkpoint store which outlives the watch streams
+#[derive(Clone, Debug, Default)]
+struct MemStore(Arc<Mutex<Option<WatchCheckpoint>>>);
+
+impl CheckpointStore for MemStore {
+    fn load(&self) -> Result<Option<WatchCheckpoint>> {
+        Ok(*self.0.lock().unwrap())
+    }
+
+    fn save(&self, checkpoint: WatchCheckpoint) -> Result<()> {
+        *self.0.lock().unwrap() = Some(checkpoint);
+        Ok(())
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn checkpointed_watch_should_resume_without_duplicates() -> Result<()> {
+    let (_cluster, client) = get_cluster_client().await.unwrap();
+    let mut watch_client = client.watch_client();
+    let kv_client = client.kv_client();
+    let store = MemStore::default();
+    let request = WatchRequest::new("ckpt").with_prefix();
+
+    let mut stream = watch_client
+        .watch_with_checkpoint(request.clone(), store.clone(), 1)
+        .await?;
+    // three events of the same revision, then another one
+    let _resp = kv_client
+        .txn(TxnRequest::new().and_then(
+            &[
+                TxnOp::put(PutRequest::new("ckpt1", "1")),
+                TxnOp::put(PutRequest::new("ckpt2", "2")),
+                TxnOp::put(PutRequest::new("ckpt3", "3")),
+            ][..],
+        ))
+        .await?;
+    let _resp = kv_client.put(PutRequest::new("ckpt4", "4")).await?;
+
+    let event = stream.message().await?.unwrap();
+    assert_eq!(event.kv.unwrap().key, b"ckpt1");
+    let event = stream.message().await?.unwrap();
+    assert_eq!(event.kv.unwrap().key, b"ckpt2");
+    stream.checkpoint()?;
+    // crash in the middle of the revision
+    drop(stream);
+
+    let checkpoint = store.load()?.unwrap();
+    assert_eq!(checkpoint.event_index, 1);
+    let mut stream = watch_client
+        .watch_with_checkpoint(request, store.clone(), 1)
+        .await?;
+    let event = stream.message().await?.unwrap();
+    assert_eq!(event.kv.unwrap().key, b"ckpt3");
+    let event = stream.message().await?.unwrap();
+    assert_eq!(event.kv.unwrap().key, b"ckpt4");
+    // the previous event is saved once the next one is requested
+    assert_eq!(store.load()?.unwrap().revision, checkpoint.revision);
+    assert_eq!(store.load()?.unwrap().event_index, 2);
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn watch_beyond_the_limit_should_be_rejected() -> Result<()> {
+    let mut cluster =
+        Cluster::new_with_configs(vec![Cluster::default_watch_limit_config(2); 3]).await;
+    cluster.start().await;
+    let client = Client::connect(cluster.all_client_addrs(), ClientOptions::default())
+        .await
+        .unwrap();
+    let mut watch_client = client.watch_client();
+
+    let (mut watcher1, mut stream1) = watch_client.watch(WatchRequest::new("limit01")).await?;
+    let (_watcher2, _stream2) = watch_client.watch(WatchRequest::new("limit02")).await?;
+    assert_eq!(watch_client.active_watch_count(), 2);
+
+    let res = watch_client.watch(WatchRequest::new("limit03")).await;
+    assert!(matches!(res, Err(XlineClientError::WatchLimitExceeded)));
+    assert_eq!(watch_client.active_watch_count(), 2);
+
+    // a watch can be created again once an active one is canceled
+    watcher1.cancel()?;
+    assert!(stream1.message().await?.unwrap().canceled);
+    assert_eq!(watch_client.active_watch_count(), 1);
+    let (_watcher3, _stream3) = watch_client.watch(WatchRequest::new("limit03")).await?;
+    assert_eq!(watch_client.active_watch_count(), 2);
+
+    Ok(())
+}
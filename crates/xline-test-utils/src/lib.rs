@@ -10,8 +10,9 @@ use tokio::{
 };
 use tonic::transport::ClientTlsConfig;
 use utils::config::{
-    default_quota, AuthConfig, ClusterConfig, CompactConfig, EngineConfig, InitialClusterState,
-    LogConfig, MetricsConfig, StorageConfig, TlsConfig, TraceConfig, XlineServerConfig,
+    default_quota, AuthConfig, ClusterConfig, CompactConfig, CurpConfig, EngineConfig,
+    InitialClusterState, LogConfig, MetricsConfig, StorageConfig, TlsConfig, TraceConfig,
+    XlineServerConfig,
 };
 use xline::server::XlineServer;
 use xline_client::types::auth::{
@@ -268,6 +269,38 @@ impl Cluster {
         Self::default_config_with_quota_and_rocks_path(path, quota)
     }
 
+    pub fn default_config_with_cluster(cluster: ClusterConfig) -> XlineServerConfig {
+        let default = XlineServerConfig::default();
+        XlineServerConfig::new(
+            cluster,
+            default.storage().clone(),
+            default.log().clone(),
+            default.trace().clone(),
+            default.auth().clone(),
+            *default.compact(),
+            default.tls().clone(),
+            default.metrics().clone(),
+        )
+    }
+
+    pub fn default_etcd_compat_config() -> XlineServerConfig {
+        Self::default_config_with_cluster(ClusterConfig::default().with_etcd_compat(true))
+    }
+
+    pub fn default_watch_limit_config(max_watches_per_client: usize) -> XlineServerConfig {
+        Self::default_config_with_cluster(
+            ClusterConfig::default().with_max_watches_per_client(max_watches_per_client),
+        )
+    }
+
+    pub fn default_execution_pool_config(execution_pool_size: u8) -> XlineServerConfig {
+        let curp_config = CurpConfig {
+            cmd_workers: execution_pool_size,
+            ..CurpConfig::default()
+        };
+        Self::default_config_with_cluster(ClusterConfig::default().with_curp_config(curp_config))
+    }
+
     fn merge_config(
         base_config: &XlineServerConfig,
         name: String,
@@ -290,6 +323,9 @@ impl Cluster {
             *old_cluster.client_config(),
             *old_cluster.server_timeout(),
             initial_cluster_state,
+            *old_cluster.etcd_compat(),
+            *old_cluster.max_watch_buffer_bytes(),
+            *old_cluster.max_watches_per_client(),
         );
         XlineServerConfig::new(
             new_cluster,
@@ -18,6 +18,8 @@ pub(crate) struct HeaderGenerator {
     general_revision: Arc<RevisionNumberGenerator>,
     /// Revision of auth store
     auth_revision: Arc<RevisionNumberGenerator>,
+    /// Whether to format headers exactly as etcd does
+    etcd_compat: bool,
 }
 
 impl HeaderGenerator {
@@ -29,6 +31,15 @@ impl HeaderGenerator {
             term: Arc::new(Mutex::new(0)),
             general_revision: Arc::new(RevisionNumberGenerator::default()),
             auth_revision: Arc::new(RevisionNumberGenerator::default()),
+            etcd_compat: false,
+        }
+    }
+
+    /// Enable or disable the etcd compatibility mode
+    pub(crate) fn with_etcd_compat(self, etcd_compat: bool) -> Self {
+        Self {
+            etcd_compat,
+            ..self
         }
     }
 
@@ -52,8 +63,26 @@ impl HeaderGenerator {
         }
     }
 
+    /// Generate `ResponseHeader` at the given revision for watch responses.
+    /// Only the revision is filled unless the etcd compatibility mode is enabled,
+    /// in which case the header is as complete as the one generated by etcd.
+    pub(crate) fn gen_watch_header(&self, revision: i64) -> ResponseHeader {
+        if self.etcd_compat {
+            ResponseHeader {
+                cluster_id: self.cluster_id,
+                member_id: self.member_id,
+                raft_term: *self.term.lock(),
+                revision,
+            }
+        } else {
+            ResponseHeader {
+                revision,
+                ..ResponseHeader::default()
+            }
+        }
+    }
+
     /// Set term
-    #[allow(dead_code)] // Will be used in the future
     pub(crate) fn set_term(&self, term: u64) {
         *self.term.lock() = term;
     }
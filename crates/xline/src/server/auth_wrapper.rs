@@ -3,10 +3,11 @@ use std::sync::Arc;
 use curp::{
     cmd::PbCodec,
     rpc::{
-        FetchClusterRequest, FetchClusterResponse, FetchReadStateRequest, FetchReadStateResponse,
-        LeaseKeepAliveMsg, MoveLeaderRequest, MoveLeaderResponse, ProposeConfChangeRequest,
-        ProposeConfChangeResponse, ProposeRequest, ProposeResponse, Protocol, PublishRequest,
-        PublishResponse, ShutdownRequest, ShutdownResponse, WaitSyncedRequest, WaitSyncedResponse,
+        decompress_propose, FetchClusterRequest, FetchClusterResponse, FetchReadStateRequest,
+        FetchReadStateResponse, LeaseKeepAliveMsg, MoveLeaderRequest, MoveLeaderResponse,
+        ProposeConfChangeRequest, ProposeConfChangeResponse, ProposeRequest, ProposeResponse,
+        Protocol, PublishRequest, PublishResponse, ShutdownRequest, ShutdownResponse,
+        WaitSyncedRequest, WaitSyncedResponse,
     },
 };
 use tracing::debug;
@@ -52,6 +53,8 @@ where
             "AuthWrapper received propose request: {}",
             request.get_ref().propose_id()
         );
+        // the command must be decompressed before it is decoded with the auth info
+        decompress_propose(&mut request)?;
         if let Some(auth_info) = self.auth_store.try_get_auth_info_from_request(&request)? {
             let mut command: Command = request
                 .get_ref()
@@ -348,7 +348,11 @@ where
         let mut ops = vec![WriteOp::PutAppliedIndex(index)];
         let wrapper = cmd.request();
         let (res, mut wr_ops) = match wrapper.backend() {
-            RequestBackend::Kv => self.kv_storage.after_sync(wrapper, revision).await?,
+            RequestBackend::Kv => {
+                self.kv_storage
+                    .after_sync(wrapper, revision, cmd.delta_encode())
+                    .await?
+            }
             RequestBackend::Auth => self.auth_storage.after_sync(wrapper, revision)?,
             RequestBackend::Lease => self.lease_storage.after_sync(wrapper, revision).await?,
             RequestBackend::Alarm => self.alarm_storage.after_sync(wrapper, revision),
@@ -1,4 +1,5 @@
 use std::{
+    pin::pin,
     sync::{
         atomic::{AtomicU64, Ordering},
         Arc,
@@ -6,17 +7,21 @@ use std::{
     time::Duration,
 };
 
-use curp::rpc::ReadState;
+use clippy_utilities::Cast;
+use curp::{rpc::ReadState, server::RawCurp};
 use dashmap::DashMap;
 use event_listener::Event;
 use futures::future::{join_all, Either};
-use tokio::time::timeout;
+use tokio::{
+    sync::broadcast::error::RecvError,
+    time::{timeout, timeout_at, Instant},
+};
 use tracing::{debug, instrument};
 use xlineapi::{
     command::{Command, CommandResponse, CurpClient, SyncResponse},
     execute_error::ExecuteError,
     request_validation::RequestValidator,
-    AuthInfo, ResponseWrapper,
+    AuthInfo, ResponseWrapper, COMPACTED_REVISION_KEY, LEADER_REVISION_KEY, RESPECT_WATCHERS_KEY,
 };
 
 use super::barriers::{IdBarrier, IndexBarrier};
@@ -28,7 +33,12 @@ use crate::{
         PutRequest, PutResponse, RangeRequest, RangeResponse, RequestWrapper, Response, ResponseOp,
         TxnRequest, TxnResponse,
     },
-    storage::{storage_api::StorageApi, AuthStore, KvStore},
+    state::State,
+    storage::{
+        kvwatcher::{KvWatcher, KvWatcherOps},
+        storage_api::StorageApi,
+        AuthStore, KvStore,
+    },
 };
 
 /// KV Server
@@ -54,6 +64,10 @@ where
     compact_events: Arc<DashMap<u64, Arc<Event>>>,
     /// Next compact_id
     next_compact_id: AtomicU64,
+    /// KV watcher, which tells the revisions that watchers depend on
+    kv_watcher: Arc<KvWatcher<S>>,
+    /// Raw curp, which tells the latest revision of the leader
+    raw_curp: Arc<RawCurp<Command, State<S, Arc<CurpClient>>>>,
 }
 
 impl<S> KvServer<S>
@@ -71,6 +85,8 @@ where
         compact_timeout: Duration,
         client: Arc<CurpClient>,
         compact_events: Arc<DashMap<u64, Arc<Event>>>,
+        kv_watcher: Arc<KvWatcher<S>>,
+        raw_curp: Arc<RawCurp<Command, State<S, Arc<CurpClient>>>>,
     ) -> Self {
         Self {
             kv_storage,
@@ -82,6 +98,8 @@ where
             client,
             compact_events,
             next_compact_id: AtomicU64::new(0),
+            kv_watcher,
+            raw_curp,
         }
     }
 
@@ -228,7 +246,15 @@ where
 
         let res = self.do_serializable(&cmd)?;
         if let Response::ResponseRange(response) = res {
-            Ok(tonic::Response::new(response))
+            let mut response = tonic::Response::new(response);
+            if is_serializable {
+                // the hint tells clients how stale the local read is
+                let leader_revision: i64 = self.raw_curp.leader_hint().cast();
+                let _ig = response
+                    .metadata_mut()
+                    .insert(LEADER_REVISION_KEY, leader_revision.into());
+            }
+            Ok(response)
         } else {
             unreachable!("Receive wrong response {res:?} for RangeRequest");
         }
@@ -342,15 +368,30 @@ where
     /// store should be periodically compacted or the event history will continue to grow
     /// indefinitely.
     #[instrument(skip_all)]
+    #[allow(clippy::arithmetic_side_effects)] // introduced by tokio::select! macro
     async fn compact(
         &self,
-        request: tonic::Request<CompactionRequest>,
+        mut request: tonic::Request<CompactionRequest>,
     ) -> Result<tonic::Response<CompactionResponse>, tonic::Status> {
         debug!("Receive CompactionRequest {:?}", request);
+        let respect_watchers = request.metadata().contains_key(RESPECT_WATCHERS_KEY);
+        if respect_watchers {
+            if let Some(min_revision) = self.kv_watcher.min_next_revision() {
+                let req = request.get_mut();
+                if min_revision < req.revision {
+                    debug!(
+                        "compaction to revision {} is limited to {min_revision} by watchers",
+                        req.revision
+                    );
+                    req.revision = min_revision;
+                }
+            }
+        }
         let compacted_revision = self.kv_storage.compacted_revision();
         let current_revision = self.kv_storage.revision();
         let req = request.get_ref();
         req.check_revision(compacted_revision, current_revision)?;
+        let revision = req.revision;
         let auth_info = self.auth_storage.try_get_auth_info_from_request(&request)?;
         let physical = req.physical;
         let request = RequestWrapper::from(request.into_inner());
@@ -364,17 +405,52 @@ where
         } else {
             Either::Right(async {})
         };
+        let mut progress_rx = self.kv_storage.compaction_progress();
         let (cmd_res, _sync_res) = self.client.propose(&cmd, None, !physical).await??;
         let resp = cmd_res.into_inner();
-        if timeout(self.compact_timeout, compact_physical_fut)
-            .await
-            .is_err()
-        {
-            return Err(tonic::Status::deadline_exceeded("Compact timeout"));
+        let mut compact_physical_fut = pin!(compact_physical_fut);
+        // a long physical compaction won't time out as long as it keeps making progress
+        let mut deadline = Instant::now() + self.compact_timeout;
+        loop {
+            let next = async {
+                tokio::select! {
+                    () = &mut compact_physical_fut => None,
+                    progress = progress_rx.recv() => Some(progress),
+                }
+            };
+            match timeout_at(deadline, next).await {
+                Err(_elapsed) => return Err(tonic::Status::deadline_exceeded("Compact timeout")),
+                Ok(None) => break,
+                // the progress of other compactions does not renew the timeout
+                Ok(Some(Ok(progress))) if progress.revision == revision => {
+                    debug!(
+                        "compaction to revision {} is {}% complete",
+                        progress.revision,
+                        progress.percent()
+                    );
+                    deadline = Instant::now() + self.compact_timeout;
+                }
+                Ok(Some(Ok(_) | Err(RecvError::Lagged(_)))) => {}
+                Ok(Some(Err(RecvError::Closed))) => {
+                    if timeout_at(deadline, &mut compact_physical_fut)
+                        .await
+                        .is_err()
+                    {
+                        return Err(tonic::Status::deadline_exceeded("Compact timeout"));
+                    }
+                    break;
+                }
+            }
         }
 
         if let ResponseWrapper::CompactionResponse(response) = resp {
-            Ok(tonic::Response::new(response))
+            let mut response = tonic::Response::new(response);
+            if respect_watchers {
+                let _prev = response
+                    .metadata_mut()
+                    .insert(COMPACTED_REVISION_KEY, revision.into());
+            }
+            Ok(response)
         } else {
             panic!("Receive wrong response {resp:?} for CompactionRequest");
         }
@@ -168,7 +168,19 @@ where
                     })),
                 };
             };
-            let mut response_stream = watch_client.watch(request_stream).await?.into_inner();
+            let mut request = tonic::Request::new(request_stream);
+            // the watch is authorized as the user who requests the lock
+            if let Some(token) =
+                auth_info.and_then(|info| self.auth_store.assign(&info.username).ok())
+            {
+                let _ignore = request.metadata_mut().insert(
+                    "token",
+                    token
+                        .parse()
+                        .unwrap_or_else(|e| panic!("metadata value parse error: {e}")),
+                );
+            }
+            let mut response_stream = watch_client.watch(request).await?.into_inner();
             while let Some(watch_res) = response_stream.message().await? {
                 #[allow(clippy::as_conversions)] // this cast is always safe
                 if watch_res
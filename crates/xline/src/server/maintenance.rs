@@ -1,4 +1,4 @@
-use std::{fmt::Debug, pin::Pin, sync::Arc};
+use std::{fmt::Debug, pin::Pin, sync::Arc, time::UNIX_EPOCH};
 
 use async_stream::try_stream;
 use bytes::BytesMut;
@@ -10,7 +10,8 @@ use sha2::{Digest, Sha256};
 use tracing::{debug, error};
 use xlineapi::{
     command::{Command, CommandResponse, CurpClient, SyncResponse},
-    RequestWrapper,
+    RequestWrapper, COMPACTION_PROGRESS_KEY, EXECUTION_POOL_KEY, REPLICATION_WATERMARK_KEY,
+    SNAPSHOT_INFO_KEY,
 };
 
 use super::command::CommandExecutor;
@@ -159,7 +160,49 @@ where
             db_size_in_use: size.cast(),
             is_learner,
         };
-        Ok(tonic::Response::new(response))
+        let mut response = tonic::Response::new(response);
+        if let Some(info) = self.raw_curp.snapshot_info() {
+            let created_at = info
+                .created_at
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_millis();
+            let value = format!(
+                "{},{},{},{created_at}",
+                info.last_included_index, info.last_included_term, info.size
+            );
+            if let Ok(value) = value.parse() {
+                let _ig = response.metadata_mut().insert(SNAPSHOT_INFO_KEY, value);
+            }
+        }
+        let execution_pool = format!(
+            "{},{}",
+            self.raw_curp.busy_workers(),
+            self.raw_curp.execution_pool_size()
+        );
+        if let Ok(value) = execution_pool.parse() {
+            let _ig = response.metadata_mut().insert(EXECUTION_POOL_KEY, value);
+        }
+        if let Some(progress) = self.kv_store.last_compaction_progress() {
+            let value = format!(
+                "{},{},{},{}",
+                progress.revision,
+                progress.compacted,
+                progress.total,
+                u8::from(progress.finished)
+            );
+            if let Ok(value) = value.parse() {
+                let _ig = response
+                    .metadata_mut()
+                    .insert(COMPACTION_PROGRESS_KEY, value);
+            }
+        }
+        if let Some(watermark) = self.raw_curp.replication_watermark() {
+            let _ig = response
+                .metadata_mut()
+                .insert(REPLICATION_WATERMARK_KEY, watermark.into());
+        }
+        Ok(response)
     }
 
     async fn defragment(
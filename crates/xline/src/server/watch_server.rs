@@ -1,31 +1,235 @@
 use std::{
     collections::{HashMap, HashSet},
+    fmt,
+    net::SocketAddr,
+    pin::Pin,
     sync::Arc,
+    task::{ready, Context, Poll},
     time::Duration,
 };
 
+use clippy_utilities::{Cast, OverflowArithmetic};
 use event_listener::Event;
+use parking_lot::Mutex;
+use prost::Message;
 use tokio::sync::mpsc;
 use tokio_stream::{wrappers::ReceiverStream, Stream, StreamExt};
+#[cfg(not(madsim))]
+use tonic::transport::server::{TcpConnectInfo, TlsConnectInfo};
 use tracing::{debug, warn};
 use utils::task_manager::{tasks::TaskName, Listener, TaskManager};
-use xlineapi::command::KeyRange;
+use xlineapi::{command::KeyRange, WatchCancelReason};
 
 use crate::{
     header_gen::HeaderGenerator,
     rpc::{
-        RequestUnion, ResponseHeader, Watch, WatchCancelRequest, WatchCreateRequest,
-        WatchProgressRequest, WatchRequest, WatchResponse,
+        RequestUnion, Watch, WatchCancelRequest, WatchCreateRequest, WatchProgressRequest,
+        WatchRequest, WatchResponse,
     },
     storage::{
         kvwatcher::{KvWatcher, KvWatcherOps, WatchEvent, WatchId, WatchIdGenerator},
         storage_api::StorageApi,
+        AuthStore,
     },
 };
 
 /// Default channel size
 pub(crate) const CHANNEL_SIZE: usize = 1024;
 
+/// Checker of whether the user of a watch connection is permitted to watch a key range
+struct PermissionChecker(Box<dyn Fn(&[u8], &[u8]) -> Result<(), tonic::Status> + Send + Sync>);
+
+impl fmt::Debug for PermissionChecker {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PermissionChecker").finish_non_exhaustive()
+    }
+}
+
+/// Bytes of event responses buffered for each watch of a watch connection, which are sent to
+/// the response channel but not consumed by the client yet
+#[derive(Debug)]
+struct WatchBuffer {
+    /// Max bytes buffered for one watch
+    limit: usize,
+    /// Buffered bytes of each watch
+    buffered: Mutex<HashMap<WatchId, usize>>,
+}
+
+impl WatchBuffer {
+    /// New `WatchBuffer`
+    fn new(limit: usize) -> Self {
+        Self {
+            limit,
+            buffered: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Buffer a response of the watch, return false if the watch would exceed the limit
+    fn try_buffer(&self, watch_id: WatchId, size: usize) -> bool {
+        let mut buffered = self.buffered.lock();
+        let bytes = buffered.entry(watch_id).or_insert(0);
+        let new_bytes = bytes.overflow_add(size);
+        if new_bytes > self.limit {
+            return false;
+        }
+        *bytes = new_bytes;
+        true
+    }
+
+    /// Release a response of the watch consumed by the client
+    fn release(&self, watch_id: WatchId, size: usize) {
+        if let Some(bytes) = self.buffered.lock().get_mut(&watch_id) {
+            *bytes = bytes.saturating_sub(size);
+        }
+    }
+
+    /// Remove the watch
+    fn remove(&self, watch_id: WatchId) {
+        let _prev = self.buffered.lock().remove(&watch_id);
+    }
+}
+
+/// A client connection, identified by the pair of the local and the remote addresses of its
+/// TCP connection, which is unique among the open connections of the server
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct ConnectionKey {
+    /// Local address of the connection, unknown under madsim
+    local: Option<SocketAddr>,
+    /// Remote address of the connection
+    remote: SocketAddr,
+}
+
+impl ConnectionKey {
+    /// Get the connection of a request, `None` if it is unknown
+    fn of<T>(request: &tonic::Request<T>) -> Option<Self> {
+        #[cfg(not(madsim))]
+        {
+            let extensions = request.extensions();
+            let info = extensions.get::<TcpConnectInfo>().or_else(|| {
+                extensions
+                    .get::<TlsConnectInfo<TcpConnectInfo>>()
+                    .map(TlsConnectInfo::get_ref)
+            });
+            if let Some(info) = info {
+                return info.remote_addr().map(|remote| Self {
+                    local: info.local_addr(),
+                    remote,
+                });
+            }
+        }
+        request.remote_addr().map(|remote| Self {
+            local: None,
+            remote,
+        })
+    }
+}
+
+/// Active watches of each client connection, so that all watch streams multiplexed on a
+/// connection share the limit, while clients sharing an address, like the ones behind a NAT,
+/// are limited separately
+#[derive(Debug)]
+struct WatchLimiter {
+    /// Max active watches of a client connection
+    limit: usize,
+    /// Active watches of each client connection
+    counts: Mutex<HashMap<ConnectionKey, usize>>,
+}
+
+impl WatchLimiter {
+    /// New `WatchLimiter`
+    fn new(limit: usize) -> Self {
+        Self {
+            limit,
+            counts: Mutex::new(HashMap::new()),
+        }
+    }
+}
+
+/// Quota of the active watches of a watch stream, which are counted in the client connection
+/// of the stream. A stream whose connection is unknown is limited on its own.
+#[derive(Debug)]
+struct WatchQuota {
+    /// The limiter shared by all watch streams
+    limiter: Arc<WatchLimiter>,
+    /// Connection of the client
+    conn: Option<ConnectionKey>,
+    /// Active watches of this stream
+    acquired: usize,
+}
+
+impl WatchQuota {
+    /// New `WatchQuota`
+    fn new(limiter: Arc<WatchLimiter>, conn: Option<ConnectionKey>) -> Self {
+        Self {
+            limiter,
+            conn,
+            acquired: 0,
+        }
+    }
+
+    /// Acquire a watch, return false if the client would exceed the limit
+    fn try_acquire(&mut self) -> bool {
+        if let Some(conn) = self.conn {
+            let mut counts = self.limiter.counts.lock();
+            let count = counts.entry(conn).or_insert(0);
+            if *count >= self.limiter.limit {
+                return false;
+            }
+            *count = count.overflow_add(1);
+        } else if self.acquired >= self.limiter.limit {
+            return false;
+        }
+        self.acquired = self.acquired.overflow_add(1);
+        true
+    }
+
+    /// Release `n` watches which are canceled
+    fn release(&mut self, n: usize) {
+        let n = n.min(self.acquired);
+        self.acquired = self.acquired.overflow_sub(n);
+        let Some(conn) = self.conn else {
+            return;
+        };
+        let mut counts = self.limiter.counts.lock();
+        if let Some(count) = counts.get_mut(&conn) {
+            *count = count.saturating_sub(n);
+            if *count == 0 {
+                let _prev = counts.remove(&conn);
+            }
+        }
+    }
+}
+
+impl Drop for WatchQuota {
+    fn drop(&mut self) {
+        self.release(self.acquired);
+    }
+}
+
+/// Response stream of a watch connection, which releases the buffered bytes of event
+/// responses once they are consumed by the client
+#[derive(Debug)]
+pub(crate) struct WatchResponseStream {
+    /// Inner stream
+    inner: ReceiverStream<Result<WatchResponse, tonic::Status>>,
+    /// Buffered bytes of watches, `None` means unlimited
+    buffer: Option<Arc<WatchBuffer>>,
+}
+
+impl Stream for WatchResponseStream {
+    type Item = Result<WatchResponse, tonic::Status>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let item = ready!(Pin::new(&mut self.inner).poll_next(cx));
+        if let (Some(buffer), Some(Ok(resp))) = (self.buffer.as_ref(), item.as_ref()) {
+            if !resp.events.is_empty() {
+                buffer.release(resp.watch_id, resp.encoded_len());
+            }
+        }
+        Poll::Ready(item)
+    }
+}
+
 /// Watch Server
 #[derive(Debug)]
 pub(crate) struct WatchServer<S>
@@ -42,6 +246,12 @@ where
     watch_progress_notify_interval: Duration,
     /// Task manager
     task_manager: Arc<TaskManager>,
+    /// Auth storage
+    auth_storage: Arc<AuthStore<S>>,
+    /// Max bytes of responses buffered for one watch, 0 means unlimited
+    max_watch_buffer_bytes: u64,
+    /// Limiter of the active watches of each client, `None` means unlimited
+    watch_limiter: Option<Arc<WatchLimiter>>,
 }
 
 impl<S> WatchServer<S>
@@ -54,6 +264,9 @@ where
         header_gen: Arc<HeaderGenerator>,
         watch_progress_notify_interval: Duration,
         task_manager: Arc<TaskManager>,
+        auth_storage: Arc<AuthStore<S>>,
+        max_watch_buffer_bytes: u64,
+        max_watches_per_client: usize,
     ) -> Self {
         Self {
             watcher,
@@ -61,17 +274,42 @@ where
             header_gen,
             watch_progress_notify_interval,
             task_manager,
+            auth_storage,
+            max_watch_buffer_bytes,
+            watch_limiter: (max_watches_per_client > 0)
+                .then(|| Arc::new(WatchLimiter::new(max_watches_per_client))),
+        }
+    }
+
+    /// Forward the events queued in `event_rx` to the watch handle. At most `CHANNEL_SIZE`
+    /// events are forwarded, which covers every event queued before the call as the channel
+    /// holds no more, while the events keep arriving can not starve the caller.
+    async fn forward_queued_events<W>(
+        watch_handle: &mut WatchHandle<W>,
+        event_rx: &mut mpsc::Receiver<WatchEvent>,
+    ) where
+        W: KvWatcherOps,
+    {
+        for _ in 0..CHANNEL_SIZE {
+            let Ok(event) = event_rx.try_recv() else {
+                break;
+            };
+            watch_handle.handle_watch_event(event).await;
         }
     }
 
     /// bg task for handle watch connection
     #[allow(clippy::arithmetic_side_effects)] // Introduced by tokio::select!
+    #[allow(clippy::too_many_arguments)]
     async fn task<ST, W>(
         next_id_gen: Arc<WatchIdGenerator>,
         kv_watcher: Arc<W>,
         res_tx: mpsc::Sender<Result<WatchResponse, tonic::Status>>,
         mut req_rx: ST,
         header_gen: Arc<HeaderGenerator>,
+        permission_checker: Option<PermissionChecker>,
+        watch_buffer: Option<Arc<WatchBuffer>>,
+        watch_quota: Option<WatchQuota>,
         watch_progress_notify_interval: Duration,
         shutdown_listener: Listener,
     ) where
@@ -87,6 +325,9 @@ where
             Arc::clone(&stop_notify),
             next_id_gen,
             header_gen,
+            permission_checker,
+            watch_buffer,
+            watch_quota,
         );
         let mut ticker = tokio::time::interval(watch_progress_notify_interval);
         let stop_listener = stop_notify.listen();
@@ -98,6 +339,11 @@ where
                     if let Some(req) = req {
                         match req {
                             Ok(req) => {
+                                // events already queued are sent before handling the request,
+                                // so that a progress response implies all events before its
+                                // revision have been sent
+                                Self::forward_queued_events(&mut watch_handle, &mut event_rx)
+                                    .await;
                                 watch_handle.handle_watch_request(req).await;
                             }
                             Err(e) => {
@@ -158,6 +404,12 @@ where
     ///
     /// `false` means the next tick should be skipped
     progress: HashMap<WatchId, bool>,
+    /// Permission checker of watch creation, `None` means no check
+    permission_checker: Option<PermissionChecker>,
+    /// Buffered bytes of watches, `None` means unlimited
+    watch_buffer: Option<Arc<WatchBuffer>>,
+    /// Quota of the active watches, `None` means unlimited
+    watch_quota: Option<WatchQuota>,
 }
 
 impl<W> WatchHandle<W>
@@ -172,6 +424,9 @@ where
         stop_notify: Arc<Event>,
         next_id_gen: Arc<WatchIdGenerator>,
         header_gen: Arc<HeaderGenerator>,
+        permission_checker: Option<PermissionChecker>,
+        watch_buffer: Option<Arc<WatchBuffer>>,
+        watch_quota: Option<WatchQuota>,
     ) -> Self {
         Self {
             kv_watcher,
@@ -183,6 +438,9 @@ where
             header_gen,
             prev_kv: HashSet::new(),
             progress: HashMap::new(),
+            permission_checker,
+            watch_buffer,
+            watch_quota,
         }
     }
 
@@ -215,6 +473,42 @@ where
             }
             return;
         };
+        // only the watch is rejected, the other watches of the stream are kept
+        if let Some(ref checker) = self.permission_checker {
+            if let Err(status) = (checker.0)(&req.key, &req.range_end) {
+                debug!("reject watch {watch_id}, permission denied");
+                let response = WatchResponse {
+                    header: Some(self.header_gen.gen_header()),
+                    watch_id,
+                    created: true,
+                    canceled: true,
+                    cancel_reason: WatchCancelReason::PermissionDenied
+                        .with_detail(status.message()),
+                    ..WatchResponse::default()
+                };
+                if self.response_tx.send(Ok(response)).await.is_err() {
+                    self.stop_notify.notify(1);
+                }
+                return;
+            }
+        }
+        if let Some(ref mut quota) = self.watch_quota {
+            if !quota.try_acquire() {
+                debug!("reject watch {watch_id}, the client has too many active watches");
+                let response = WatchResponse {
+                    header: Some(self.header_gen.gen_header()),
+                    watch_id,
+                    created: true,
+                    canceled: true,
+                    cancel_reason: WatchCancelReason::WatchLimitExceeded.as_str().to_owned(),
+                    ..WatchResponse::default()
+                };
+                if self.response_tx.send(Ok(response)).await.is_err() {
+                    self.stop_notify.notify(1);
+                }
+                return;
+            }
+        }
 
         let key_range = KeyRange::new(req.key, req.range_end);
         self.kv_watcher.watch(
@@ -259,6 +553,12 @@ where
         let result = if self.active_watch_ids.remove(&watch_id) {
             self.kv_watcher.cancel(watch_id);
             let _prev = self.active_watch_ids.remove(&watch_id);
+            if let Some(ref buffer) = self.watch_buffer {
+                buffer.remove(watch_id);
+            }
+            if let Some(ref mut quota) = self.watch_quota {
+                quota.release(1);
+            }
             let response = WatchResponse {
                 header: Some(self.header_gen.gen_header()),
                 watch_id,
@@ -297,11 +597,12 @@ where
     /// Handle watch event
     async fn handle_watch_event(&mut self, mut watch_event: WatchEvent) {
         let watch_id = watch_event.watch_id();
+        // events of a canceled watch may still be in the channel
+        if !self.active_watch_ids.contains(&watch_id) {
+            return;
+        }
         let mut response = WatchResponse {
-            header: Some(ResponseHeader {
-                revision: watch_event.revision(),
-                ..ResponseHeader::default()
-            }),
+            header: Some(self.header_gen.gen_watch_header(watch_event.revision())),
             watch_id,
             ..WatchResponse::default()
         };
@@ -326,6 +627,12 @@ where
                 }
             }
             response.events = events;
+            if let Some(ref buffer) = self.watch_buffer {
+                if !buffer.try_buffer(watch_id, response.encoded_len()) {
+                    self.cancel_slow_watch(watch_id).await;
+                    return;
+                }
+            }
         };
 
         if self.response_tx.send(Ok(response)).await.is_err() {
@@ -336,6 +643,32 @@ where
         }
     }
 
+    /// Cancel a watch whose client consumes responses too slowly, the client could recreate
+    /// the watch from the revision of the last received event
+    async fn cancel_slow_watch(&mut self, watch_id: WatchId) {
+        warn!("cancel watch {watch_id}, its client consumes responses too slowly");
+        self.kv_watcher.cancel(watch_id);
+        let active = self.active_watch_ids.remove(&watch_id);
+        let _prev = self.prev_kv.remove(&watch_id);
+        let _prev = self.progress.remove(&watch_id);
+        if let Some(ref buffer) = self.watch_buffer {
+            buffer.remove(watch_id);
+        }
+        if let (true, Some(quota)) = (active, self.watch_quota.as_mut()) {
+            quota.release(1);
+        }
+        let response = WatchResponse {
+            header: Some(self.header_gen.gen_header()),
+            watch_id,
+            canceled: true,
+            cancel_reason: WatchCancelReason::SlowConsumer.as_str().to_owned(),
+            ..WatchResponse::default()
+        };
+        if self.response_tx.send(Ok(response)).await.is_err() {
+            self.stop_notify.notify(1);
+        }
+    }
+
     /// Handle progress for request
     async fn handle_watch_progress(&mut self, _req: WatchProgressRequest) {
         if self
@@ -392,7 +725,7 @@ where
     S: StorageApi,
 {
     ///Server streaming response type for the Watch method.
-    type WatchStream = ReceiverStream<Result<WatchResponse, tonic::Status>>;
+    type WatchStream = WatchResponseStream;
 
     /// Watch watches for events happening or that have happened. Both input and output
     /// are streams; the input stream is for creating and canceling watchers and the output
@@ -404,8 +737,27 @@ where
         request: tonic::Request<tonic::Streaming<WatchRequest>>,
     ) -> Result<tonic::Response<Self::WatchStream>, tonic::Status> {
         debug!("Receive Watch Connection {:?}", request);
+        let conn = ConnectionKey::of(&request);
+        let auth_info = self.auth_storage.try_get_auth_info_from_request(&request)?;
+        let auth_storage = Arc::clone(&self.auth_storage);
+        let permission_checker =
+            PermissionChecker(Box::new(move |key: &[u8], range_end: &[u8]| {
+                auth_storage
+                    .check_watch_permission(auth_info.as_ref(), key, range_end)
+                    .map_err(Into::into)
+            }));
         let req_stream = request.into_inner();
         let (tx, rx) = mpsc::channel(CHANNEL_SIZE);
+        let watch_buffer = (self.max_watch_buffer_bytes > 0)
+            .then(|| Arc::new(WatchBuffer::new(self.max_watch_buffer_bytes.cast())));
+        let stream = WatchResponseStream {
+            inner: ReceiverStream::new(rx),
+            buffer: watch_buffer.clone(),
+        };
+        let watch_quota = self
+            .watch_limiter
+            .as_ref()
+            .map(|limiter| WatchQuota::new(Arc::clone(limiter), conn));
         self.task_manager.spawn(TaskName::WatchTask, |n| {
             Self::task(
                 Arc::clone(&self.next_id_gen),
@@ -413,11 +765,14 @@ where
                 tx,
                 req_stream,
                 Arc::clone(&self.header_gen),
+                Some(permission_checker),
+                watch_buffer,
+                watch_quota,
                 self.watch_progress_notify_interval,
                 n,
             )
         });
-        Ok(tonic::Response::new(ReceiverStream::new(rx)))
+        Ok(tonic::Response::new(stream))
     }
 }
 
@@ -467,7 +822,7 @@ mod test {
             value: value.into(),
             ..Default::default()
         });
-        let (_sync_res, ops) = store.after_sync(&req, revision).await.unwrap();
+        let (_sync_res, ops) = store.after_sync(&req, revision, false).await.unwrap();
         let key_revisions = db.flush_ops(ops).unwrap();
         store.insert_index(key_revisions);
     }
@@ -496,6 +851,9 @@ mod test {
             res_tx,
             req_stream,
             header_gen,
+            None,
+            None,
+            None,
             default_watch_progress_notify_interval(),
             n,
         ));
@@ -517,6 +875,86 @@ mod test {
         Ok(())
     }
 
+    #[tokio::test]
+    #[abort_on_panic]
+    async fn watch_beyond_the_limit_should_be_rejected() -> Result<(), Box<dyn std::error::Error>> {
+        let task_manager = Arc::new(TaskManager::new());
+        let (req_tx, req_rx) = mpsc::channel(CHANNEL_SIZE);
+        let (res_tx, mut res_rx) = mpsc::channel(CHANNEL_SIZE);
+        let req_stream: ReceiverStream<Result<WatchRequest, tonic::Status>> =
+            ReceiverStream::new(req_rx);
+        let header_gen = Arc::new(HeaderGenerator::new(0, 0));
+        let mut mock_watcher = MockKvWatcherOps::new();
+        let _ = mock_watcher.expect_watch().times(3).return_const(());
+        let _ = mock_watcher.expect_cancel().return_const(());
+        let _ = mock_watcher
+            .expect_compacted_revision()
+            .return_const(-1_i64);
+        let watcher = Arc::new(mock_watcher);
+        let next_id = Arc::new(WatchIdGenerator::new(1));
+        let n = task_manager.get_shutdown_listener(TaskName::WatchTask);
+        let limiter = Arc::new(WatchLimiter::new(2));
+        let conn = "127.0.0.1:2379".parse().ok().map(|remote| ConnectionKey {
+            local: None,
+            remote,
+        });
+        let handle = tokio::spawn(WatchServer::<DB>::task(
+            next_id,
+            Arc::clone(&watcher),
+            res_tx,
+            req_stream,
+            header_gen,
+            None,
+            None,
+            Some(WatchQuota::new(Arc::clone(&limiter), conn)),
+            default_watch_progress_notify_interval(),
+            n,
+        ));
+        let create = |watch_id| {
+            Ok(WatchRequest {
+                request_union: Some(RequestUnion::CreateRequest(WatchCreateRequest {
+                    key: vec![0],
+                    range_end: vec![0],
+                    watch_id,
+                    ..Default::default()
+                })),
+            })
+        };
+
+        for watch_id in [1, 2] {
+            req_tx.send(create(watch_id)).await?;
+            let res = res_rx.recv().await.unwrap()?;
+            assert!(res.created && !res.canceled);
+        }
+        req_tx.send(create(3)).await?;
+        let rejected = res_rx.recv().await.unwrap()?;
+        assert!(rejected.created && rejected.canceled);
+        assert_eq!(
+            WatchCancelReason::parse(&rejected.cancel_reason),
+            Some(WatchCancelReason::WatchLimitExceeded)
+        );
+
+        // the quota is released once a watch is canceled
+        req_tx
+            .send(Ok(WatchRequest {
+                request_union: Some(RequestUnion::CancelRequest(WatchCancelRequest {
+                    watch_id: 1,
+                })),
+            }))
+            .await?;
+        assert!(res_rx.recv().await.unwrap()?.canceled);
+        req_tx.send(create(3)).await?;
+        let res = res_rx.recv().await.unwrap()?;
+        assert!(res.created && !res.canceled);
+
+        drop(req_tx);
+        timeout(Duration::from_secs(3), handle).await??;
+        // all watches of the connection are released once the stream is closed
+        assert!(limiter.counts.lock().is_empty());
+        task_manager.shutdown(true).await;
+        Ok(())
+    }
+
     #[tokio::test]
     #[abort_on_panic]
     #[allow(clippy::similar_names)] // use num as suffix
@@ -551,6 +989,9 @@ mod test {
                 res_tx1,
                 req_stream1,
                 Arc::clone(&header_gen),
+                None,
+                None,
+                None,
                 default_watch_progress_notify_interval(),
                 n,
             )
@@ -567,6 +1008,9 @@ mod test {
                 res_tx2,
                 req_stream2,
                 header_gen,
+                None,
+                None,
+                None,
                 default_watch_progress_notify_interval(),
                 n,
             )
@@ -638,6 +1082,9 @@ mod test {
                 res_tx,
                 req_stream,
                 Arc::clone(&header_gen),
+                None,
+                None,
+                None,
                 default_watch_progress_notify_interval(),
                 n,
             )
@@ -661,6 +1108,88 @@ mod test {
         task_manager.shutdown(true).await;
     }
 
+    #[tokio::test]
+    #[abort_on_panic]
+    async fn test_slow_watcher_should_be_canceled() {
+        let task_manager = Arc::new(TaskManager::new());
+        let (compact_tx, _compact_rx) = mpsc::channel(COMPACT_CHANNEL_SIZE);
+        let index = Arc::new(Index::new());
+        let db = DB::open(&EngineConfig::Memory).unwrap();
+        let header_gen = Arc::new(HeaderGenerator::new(0, 0));
+        let lease_collection = Arc::new(LeaseCollection::new(0));
+        let next_id_gen = Arc::new(WatchIdGenerator::new(1));
+        let (kv_update_tx, kv_update_rx) = mpsc::channel(CHANNEL_SIZE);
+        let kv_store_inner = Arc::new(KvStoreInner::new(index, Arc::clone(&db)));
+        let kv_store = Arc::new(KvStore::new(
+            Arc::clone(&kv_store_inner),
+            Arc::clone(&header_gen),
+            kv_update_tx,
+            compact_tx,
+            lease_collection,
+        ));
+        let kv_watcher = KvWatcher::new_arc(
+            kv_store_inner,
+            kv_update_rx,
+            Duration::from_millis(10),
+            &task_manager,
+        );
+
+        let (req_tx, req_rx) = mpsc::channel(CHANNEL_SIZE);
+        let req_stream = ReceiverStream::new(req_rx);
+        req_tx
+            .send(Ok(WatchRequest {
+                request_union: Some(RequestUnion::CreateRequest(WatchCreateRequest {
+                    watch_id: 1,
+                    key: "foo".into(),
+                    ..Default::default()
+                })),
+            }))
+            .await
+            .unwrap();
+        // the receiver is never wrapped in a `WatchResponseStream`, so no buffered
+        // response is released, just like a client that never consumes responses
+        let (res_tx, mut res_rx) = mpsc::channel(CHANNEL_SIZE);
+        task_manager.spawn(TaskName::WatchTask, |n| {
+            WatchServer::<DB>::task(
+                Arc::clone(&next_id_gen),
+                Arc::clone(&kv_watcher),
+                res_tx,
+                req_stream,
+                Arc::clone(&header_gen),
+                None,
+                Some(Arc::new(WatchBuffer::new(2048))),
+                None,
+                default_watch_progress_notify_interval(),
+                n,
+            )
+        });
+        let create_res = res_rx.recv().await.unwrap().unwrap();
+        assert!(create_res.created);
+
+        for revision in 2..10 {
+            put(&kv_store, &db, "foo", vec![0; 1024], revision).await;
+        }
+
+        let canceled_res = timeout(Duration::from_secs(3), async {
+            loop {
+                let res = res_rx.recv().await.unwrap().unwrap();
+                if res.canceled {
+                    break res;
+                }
+                assert!(!res.events.is_empty());
+            }
+        })
+        .await
+        .unwrap();
+        assert_eq!(canceled_res.watch_id, 1);
+        assert_eq!(
+            WatchCancelReason::parse(&canceled_res.cancel_reason),
+            Some(WatchCancelReason::SlowConsumer)
+        );
+        drop(kv_store);
+        task_manager.shutdown(true).await;
+    }
+
     #[tokio::test]
     #[abort_on_panic]
     async fn test_watch_progress() -> Result<(), Box<dyn std::error::Error>> {
@@ -685,6 +1214,9 @@ mod test {
                 res_tx,
                 req_stream,
                 header_gen,
+                None,
+                None,
+                None,
                 Duration::from_millis(100),
                 n,
             )
@@ -748,6 +1280,9 @@ mod test {
             res_tx,
             req_stream,
             header_gen,
+            None,
+            None,
+            None,
             Duration::from_millis(100),
             n,
         ));
@@ -825,6 +1360,9 @@ mod test {
                 res_tx,
                 req_stream,
                 Arc::clone(&header_gen),
+                None,
+                None,
+                None,
                 default_watch_progress_notify_interval(),
                 n,
             )
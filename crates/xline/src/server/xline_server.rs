@@ -4,18 +4,23 @@ use anyhow::{anyhow, Result};
 use clippy_utilities::{Cast, OverflowArithmetic};
 use curp::{
     client::ClientBuilder as CurpClientBuilder,
-    members::{get_cluster_info_from_remote, ClusterInfo},
+    members::{get_cluster_info_from_remote, ClusterInfo, ServerId},
     rpc::{InnerProtocolServer, ProtocolServer},
-    server::{Rpc, StorageApi as _, DB as CurpDB},
+    server::{RawCurp, Rpc, StorageApi as _, DB as CurpDB},
 };
 use dashmap::DashMap;
-use engine::{MemorySnapshotAllocator, RocksSnapshotAllocator, SnapshotAllocator};
+use engine::{
+    MemorySnapshotAllocator, RocksSnapshotAllocator, SledSnapshotAllocator, SnapshotAllocator,
+};
 #[cfg(not(madsim))]
 use futures::Stream;
 use jsonwebtoken::{DecodingKey, EncodingKey};
 #[cfg(not(madsim))]
 use tokio::io::{AsyncRead, AsyncWrite};
-use tokio::{fs, sync::mpsc::channel};
+use tokio::{
+    fs,
+    sync::{broadcast, mpsc::channel},
+};
 #[cfg(not(madsim))]
 use tonic::transport::{
     server::Connected, Certificate, ClientTlsConfig, Identity, ServerTlsConfig,
@@ -27,7 +32,7 @@ use utils::{
         AuthConfig, ClusterConfig, CompactConfig, EngineConfig, InitialClusterState, StorageConfig,
         TlsConfig,
     },
-    task_manager::{tasks::TaskName, TaskManager},
+    task_manager::{tasks::TaskName, Listener, TaskManager},
 };
 #[cfg(madsim)]
 use utils::{ClientTlsConfig, ServerTlsConfig};
@@ -97,7 +102,7 @@ pub struct XlineServer {
 impl XlineServer {
     /// New `XlineServer`
     /// # Errors
-    /// Return error if init cluster info failed
+    /// Return error if the config is invalid or init cluster info failed
     #[inline]
     pub async fn new(
         cluster_config: ClusterConfig,
@@ -106,6 +111,9 @@ impl XlineServer {
         auth_config: AuthConfig,
         #[cfg_attr(madsim, allow(unused_variables))] tls_config: TlsConfig,
     ) -> Result<Self> {
+        if cluster_config.curp_config().cmd_workers == 0 {
+            return Err(anyhow!("execution_pool_size must be greater than 0"));
+        }
         #[cfg(not(madsim))]
         let (client_tls_config, server_tls_config) = Self::read_tls_config(&tls_config).await?;
         #[cfg(madsim)]
@@ -230,6 +238,7 @@ impl XlineServer {
                 Arc::clone(&kv_storage),
                 Arc::clone(&index),
                 *self.compact_config.compact_batch_size(),
+                *self.compact_config.incremental_compaction(),
                 *self.compact_config.compact_sleep_interval(),
                 compact_task_rx,
                 n,
@@ -273,11 +282,14 @@ impl XlineServer {
 
     /// Construct a header generator
     #[inline]
-    fn construct_generator(cluster_info: &ClusterInfo) -> (Arc<HeaderGenerator>, Arc<IdGenerator>) {
+    fn construct_generator(
+        cluster_info: &ClusterInfo,
+        etcd_compat: bool,
+    ) -> (Arc<HeaderGenerator>, Arc<IdGenerator>) {
         let member_id = cluster_info.self_id();
         let cluster_id = cluster_info.cluster_id();
         (
-            Arc::new(HeaderGenerator::new(cluster_id, member_id)),
+            Arc::new(HeaderGenerator::new(cluster_id, member_id).with_etcd_compat(etcd_compat)),
             Arc::new(IdGenerator::new(member_id)),
         )
     }
@@ -450,7 +462,8 @@ impl XlineServer {
         AuthWrapper<S>,
         Arc<CurpClient>,
     )> {
-        let (header_gen, id_gen) = Self::construct_generator(&self.cluster_info);
+        let (header_gen, id_gen) =
+            Self::construct_generator(&self.cluster_info, *self.cluster_config.etcd_compat());
         let lease_collection = Self::construct_lease_collection(
             self.cluster_config.curp_config().heartbeat_interval,
             self.cluster_config.curp_config().candidate_timeout_ticks,
@@ -484,6 +497,7 @@ impl XlineServer {
         let snapshot_allocator: Box<dyn SnapshotAllocator> = match self.storage_config.engine {
             EngineConfig::Memory => Box::<MemorySnapshotAllocator>::default(),
             EngineConfig::RocksDB(_) => Box::<RocksSnapshotAllocator>::default(),
+            EngineConfig::Sled(_) => Box::<SledSnapshotAllocator>::default(),
             #[allow(clippy::unimplemented)]
             _ => unimplemented!(),
         };
@@ -539,6 +553,16 @@ impl XlineServer {
             Arc::clone(&client),
         ));
         let raw_curp = curp_server.raw_curp();
+        let general_revision = header_gen.general_revision_arc();
+        raw_curp.set_leader_hint_source(move || general_revision.get().cast());
+        if *self.cluster_config.etcd_compat() {
+            let header_gen_c = Arc::clone(&header_gen);
+            let raw_curp_c = Arc::clone(&raw_curp);
+            let leader_rx = curp_server.leader_rx();
+            self.task_manager.spawn(TaskName::SyncTerm, |n| {
+                Self::sync_term_task(header_gen_c, raw_curp_c, leader_rx, n)
+            });
+        }
 
         Metrics::register_callback()?;
 
@@ -553,6 +577,8 @@ impl XlineServer {
                 *server_timeout.compact_timeout(),
                 Arc::clone(&client),
                 compact_events,
+                Arc::clone(&watcher),
+                Arc::clone(&raw_curp),
             ),
             LockServer::new(
                 Arc::clone(&client),
@@ -576,6 +602,9 @@ impl XlineServer {
                 Arc::clone(&header_gen),
                 *server_timeout.watch_progress_notify_interval(),
                 Arc::clone(&self.task_manager),
+                Arc::clone(&auth_storage),
+                *self.cluster_config.max_watch_buffer_bytes(),
+                *self.cluster_config.max_watches_per_client(),
             ),
             MaintenanceServer::new(
                 kv_storage,
@@ -595,6 +624,27 @@ impl XlineServer {
         ))
     }
 
+    /// Keep the raft term reported in response headers in sync with curp
+    #[allow(clippy::arithmetic_side_effects)] // Introduced by tokio::select!
+    async fn sync_term_task<S: StorageApi>(
+        header_gen: Arc<HeaderGenerator>,
+        raw_curp: Arc<RawCurp<Command, State<S, Arc<CurpClient>>>>,
+        mut leader_rx: broadcast::Receiver<Option<ServerId>>,
+        shutdown_listener: Listener,
+    ) {
+        loop {
+            header_gen.set_term(raw_curp.leader().1);
+            tokio::select! {
+                _ = shutdown_listener.wait() => return,
+                res = leader_rx.recv() => {
+                    if matches!(res, Err(broadcast::error::RecvError::Closed)) {
+                        return;
+                    }
+                }
+            }
+        }
+    }
+
     /// Publish the name of current node to cluster
     async fn publish(&self, curp_client: Arc<CurpClient>) -> Result<(), tonic::Status> {
         curp_client
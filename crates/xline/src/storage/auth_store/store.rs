@@ -1012,6 +1012,21 @@ where
         Ok(())
     }
 
+    /// check if the key range is permitted to be watched
+    pub(crate) fn check_watch_permission(
+        &self,
+        auth_info: Option<&AuthInfo>,
+        key: &[u8],
+        range_end: &[u8],
+    ) -> Result<(), ExecuteError> {
+        let wrapper = RequestWrapper::from(RangeRequest {
+            key: key.to_vec(),
+            range_end: range_end.to_vec(),
+            ..RangeRequest::default()
+        });
+        self.check_permission(&wrapper, auth_info)
+    }
+
     /// check if range request is permitted
     fn check_range_permission(
         &self,
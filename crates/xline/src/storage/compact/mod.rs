@@ -5,7 +5,7 @@ use curp::client::ClientApi;
 use event_listener::Event;
 use periodic_compactor::PeriodicCompactor;
 use revision_compactor::RevisionCompactor;
-use tokio::{sync::mpsc::Receiver, time::sleep};
+use tokio::{sync::mpsc::Receiver, task::yield_now, time::sleep};
 use utils::{
     config::AutoCompactConfig,
     task_manager::{tasks::TaskName, Listener, TaskManager},
@@ -28,6 +28,32 @@ mod periodic_compactor;
 /// compact task channel size
 pub(crate) const COMPACT_CHANNEL_SIZE: usize = 32;
 
+/// compaction progress channel size
+pub(crate) const COMPACT_PROGRESS_CHANNEL_SIZE: usize = 64;
+
+/// Progress of a compaction, reported by the background compact executor after each batch
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct CompactionProgress {
+    /// The revision to compact to
+    pub(crate) revision: i64,
+    /// The number of key revisions removed so far
+    pub(crate) compacted: usize,
+    /// The number of key revisions to remove in total
+    pub(crate) total: usize,
+    /// Whether the compaction is finished
+    pub(crate) finished: bool,
+}
+
+impl CompactionProgress {
+    /// Get the percent complete of the compaction
+    pub(crate) fn percent(&self) -> usize {
+        self.compacted
+            .saturating_mul(100)
+            .checked_div(self.total)
+            .unwrap_or(100)
+    }
+}
+
 /// Compactor trait definition
 #[async_trait]
 pub(crate) trait Compactor<C: Compactable>: Send + Sync {
@@ -95,12 +121,41 @@ pub(crate) async fn auto_compactor<C: Compactable>(
     compactor_handle
 }
 
+/// Remove the historical versions of `revisions` chunk by chunk with `compact_chunk`. In the
+/// incremental mode, a chunk holds at most `chunk_size` revisions and the task yields for
+/// `interval` between two chunks, so that a write waits for a chunk instead of the whole
+/// compaction. Otherwise all revisions are removed in a single chunk.
+async fn compact_in_chunks<F>(
+    revisions: &[Vec<u8>],
+    chunk_size: Option<usize>,
+    interval: Duration,
+    mut compact_chunk: F,
+) where
+    F: FnMut(&[Vec<u8>]),
+{
+    let Some(size) = chunk_size else {
+        if !revisions.is_empty() {
+            compact_chunk(revisions);
+        }
+        return;
+    };
+    for chunk in revisions.chunks(size.max(1)) {
+        compact_chunk(chunk);
+        if interval.is_zero() {
+            yield_now().await;
+        } else {
+            sleep(interval).await;
+        }
+    }
+}
+
 /// background compact executor
 #[allow(clippy::arithmetic_side_effects)] // introduced bt tokio::select! macro
 pub(crate) async fn compact_bg_task<DB>(
     kv_store: Arc<KvStore<DB>>,
     index: Arc<Index>,
     batch_limit: usize,
+    incremental: bool,
     interval: Duration,
     mut compact_task_rx: Receiver<(i64, Option<Arc<Event>>)>,
     shutdown_listener: Listener,
@@ -123,18 +178,78 @@ pub(crate) async fn compact_bg_task<DB>(
             .into_iter()
             .map(|key_rev| key_rev.as_revision().encode_to_vec())
             .collect::<Vec<Vec<_>>>();
+        let mut progress = CompactionProgress {
+            revision,
+            compacted: 0,
+            total: target_revisions.len(),
+            finished: false,
+        };
         // Given that the Xline uses a lim-tree database with smaller write amplification as the storage backend ,  does using progressive compaction really good at improving performance?
-        for revision_chunk in target_revisions.chunks(batch_limit) {
+        let chunk_size = incremental.then_some(batch_limit);
+        compact_in_chunks(&target_revisions, chunk_size, interval, |revision_chunk| {
             if let Err(e) = kv_store.compact(revision_chunk) {
                 panic!("failed to compact revision chunk {revision_chunk:?} due to {e}");
             }
-            sleep(interval).await;
-        }
+            progress.compacted = progress.compacted.saturating_add(revision_chunk.len());
+            kv_store.report_compaction_progress(progress);
+        })
+        .await;
         if let Err(e) = kv_store.compact_finished(revision) {
             panic!("failed to set finished compact revision {revision:?} due to {e}");
         }
+        progress.finished = true;
+        kv_store.report_compaction_progress(progress);
         if let Some(notifier) = listener {
             notifier.notify(usize::MAX);
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+    use super::*;
+
+    /// Compact 1000 revisions on a mock engine while a writer keeps writing to it, and return
+    /// the max latency of the writes in ticks of a mock clock. Removing a revision takes a
+    /// tick, and a write is served once the compactor yields the engine.
+    async fn max_write_latency(chunk_size: Option<usize>) -> usize {
+        let revisions: Vec<Vec<u8>> = (0..1000_u32).map(|i| i.to_be_bytes().to_vec()).collect();
+        let clock = AtomicUsize::new(0);
+        let compacted = AtomicUsize::new(0);
+        let finished = AtomicBool::new(false);
+        let write = async {
+            let mut max_latency = 0;
+            while !finished.load(Ordering::Relaxed) {
+                let issued = clock.load(Ordering::Relaxed);
+                yield_now().await;
+                // serving the write takes a tick too
+                let served = clock.fetch_add(1, Ordering::Relaxed);
+                max_latency = max_latency.max(served - issued);
+            }
+            max_latency
+        };
+        let compact = async {
+            compact_in_chunks(&revisions, chunk_size, Duration::ZERO, |chunk| {
+                let _prev = clock.fetch_add(chunk.len(), Ordering::Relaxed);
+                let _prev = compacted.fetch_add(chunk.len(), Ordering::Relaxed);
+            })
+            .await;
+            finished.store(true, Ordering::Relaxed);
+        };
+        // the writer is polled first, so the first write arrives before the compaction starts
+        let (max_latency, ()) = tokio::join!(write, compact);
+        assert_eq!(compacted.load(Ordering::Relaxed), revisions.len());
+        max_latency
+    }
+
+    #[tokio::test]
+    async fn incremental_compaction_should_bound_write_latency() {
+        let threshold = 100;
+        assert!(max_write_latency(Some(threshold)).await <= threshold + 1);
+        assert!(max_write_latency(Some(10)).await <= 10 + 1);
+        // a write arriving during the blocking compaction waits for all the revisions
+        assert!(max_write_latency(None).await >= 1000);
+    }
+}
@@ -44,6 +44,7 @@ impl DB {
         let engine_type = match *config {
             EngineConfig::Memory => EngineType::Memory,
             EngineConfig::RocksDB(ref path) => EngineType::Rocks(path.clone()),
+            EngineConfig::Sled(ref path) => EngineType::Sled(path.clone()),
             _ => unreachable!("Not supported storage type"),
         };
         let engine = Engine::new(engine_type, &XLINE_TABLES)
@@ -165,6 +166,18 @@ impl StorageApi for DB {
                     let key = rev.encode_to_vec();
                     WriteOperation::new_put(KV_TABLE, key, value.encode_to_vec())
                 }
+                WriteOp::PutDeltaKeyValue(rev, value, encoded) => {
+                    revs.push((
+                        value.key,
+                        KeyRevision::new(
+                            value.create_revision,
+                            value.version,
+                            rev.revision(),
+                            rev.sub_revision(),
+                        ),
+                    ));
+                    WriteOperation::new_put(KV_TABLE, rev.encode_to_vec(), encoded)
+                }
                 WriteOp::PutAppliedIndex(index) => WriteOperation::new_put(
                     META_TABLE,
                     APPLIED_INDEX_KEY.as_bytes().to_vec(),
@@ -264,6 +277,9 @@ impl StorageApi for DB {
 pub enum WriteOp<'a> {
     /// Put a key-value pair to kv table
     PutKeyValue(Revision, KeyValue),
+    /// Put a key-value pair to kv table with its encoded bytes, the value is delta encoded
+    /// in the bytes
+    PutDeltaKeyValue(Revision, KeyValue, Vec<u8>),
     /// Put the applied index to meta table
     PutAppliedIndex(u64),
     /// Put a lease to lease table
@@ -0,0 +1,169 @@
+use clippy_utilities::{Cast, OverflowArithmetic};
+use prost::Message;
+use xlineapi::execute_error::ExecuteError;
+
+use crate::rpc::KeyValue;
+
+/// Max number of deltas based on one materialized value, the next put of the key will be
+/// materialized after this number of deltas
+pub(crate) const MAX_DELTA_CHAIN: u32 = 16;
+
+/// Delta of a value against a materialized base value of the same key
+///
+/// The value is reconstructed by `base[..prefix_len] + middle + base[base.len() - suffix_len..]`
+#[derive(Clone, PartialEq, Message)]
+pub(crate) struct Delta {
+    /// Encoded revision of the base value
+    #[prost(bytes = "vec", tag = "1")]
+    pub(crate) base: Vec<u8>,
+    /// Number of deltas since the base value
+    #[prost(uint32, tag = "2")]
+    pub(crate) chain: u32,
+    /// Length of the common prefix with the base value
+    #[prost(uint64, tag = "3")]
+    prefix_len: u64,
+    /// Length of the common suffix with the base value
+    #[prost(uint64, tag = "4")]
+    suffix_len: u64,
+    /// Bytes between the common prefix and suffix
+    #[prost(bytes = "vec", tag = "5")]
+    middle: Vec<u8>,
+}
+
+/// Delta field appended to an encoded `KeyValue`, decoding the bytes as a `KeyValue`
+/// just skips this field
+#[derive(Clone, PartialEq, Message)]
+struct DeltaField {
+    /// The delta of the value
+    #[prost(message, optional, tag = "1000")]
+    delta: Option<Delta>,
+}
+
+impl Delta {
+    /// Compute the delta of `value` against `base_value`
+    pub(crate) fn new(base: Vec<u8>, chain: u32, base_value: &[u8], value: &[u8]) -> Self {
+        let prefix_len = base_value
+            .iter()
+            .zip(value)
+            .take_while(|&(a, b)| a == b)
+            .count();
+        let max_suffix_len = base_value.len().min(value.len()).overflow_sub(prefix_len);
+        let suffix_len = base_value
+            .iter()
+            .rev()
+            .zip(value.iter().rev())
+            .take(max_suffix_len)
+            .take_while(|&(a, b)| a == b)
+            .count();
+        let middle = value
+            .get(prefix_len..value.len().overflow_sub(suffix_len))
+            .map(<[u8]>::to_vec)
+            .unwrap_or_default();
+        Self {
+            base,
+            chain,
+            prefix_len: prefix_len.cast(),
+            suffix_len: suffix_len.cast(),
+            middle,
+        }
+    }
+
+    /// Reconstruct the value from the base value
+    pub(crate) fn apply(&self, base_value: &[u8]) -> Result<Vec<u8>, ExecuteError> {
+        let prefix_len: usize = self.prefix_len.cast();
+        let suffix_len: usize = self.suffix_len.cast();
+        let (Some(prefix), Some(suffix)) = (
+            base_value.get(..prefix_len),
+            base_value
+                .len()
+                .checked_sub(suffix_len)
+                .and_then(|start| base_value.get(start..)),
+        ) else {
+            return Err(ExecuteError::DbError(
+                "delta does not match with its base value".to_owned(),
+            ));
+        };
+        let mut value = Vec::with_capacity(
+            prefix_len
+                .overflow_add(self.middle.len())
+                .overflow_add(suffix_len),
+        );
+        value.extend_from_slice(prefix);
+        value.extend_from_slice(&self.middle);
+        value.extend_from_slice(suffix);
+        Ok(value)
+    }
+
+    /// Encode the `KeyValue`, storing its value as this delta
+    pub(crate) fn encode_kv(&self, kv: &KeyValue) -> Vec<u8> {
+        let kv = KeyValue {
+            value: vec![],
+            ..kv.clone()
+        };
+        let field = DeltaField {
+            delta: Some(self.clone()),
+        };
+        let mut buf = Vec::with_capacity(kv.encoded_len().overflow_add(field.encoded_len()));
+        kv.encode(&mut buf)
+            .unwrap_or_else(|_| unreachable!("the buffer is large enough"));
+        field
+            .encode(&mut buf)
+            .unwrap_or_else(|_| unreachable!("the buffer is large enough"));
+        buf
+    }
+
+    /// Decode the delta from an encoded `KeyValue`, return `None` if the value is
+    /// not delta encoded
+    pub(crate) fn decode_from_kv(buf: &[u8]) -> Result<Option<Self>, ExecuteError> {
+        DeltaField::decode(buf)
+            .map(|field| field.delta)
+            .map_err(|e| {
+                ExecuteError::DbError(format!("Failed to decode delta from DB, error: {e}"))
+            })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn delta_should_reconstruct_value() {
+        let base_value = b"hello world, hello xline".to_vec();
+        for value in [
+            b"hello world, hello xline".to_vec(),
+            b"hello there, hello xline".to_vec(),
+            b"hello world".to_vec(),
+            b"hello world, hello xline and curp".to_vec(),
+            b"xline".to_vec(),
+            vec![],
+        ] {
+            let delta = Delta::new(vec![], 1, &base_value, &value);
+            assert_eq!(delta.apply(&base_value).unwrap(), value);
+        }
+    }
+
+    #[test]
+    fn delta_should_survive_kv_encoding() {
+        let kv = KeyValue {
+            key: b"foo".to_vec(),
+            value: b"hello there".to_vec(),
+            create_revision: 1,
+            mod_revision: 2,
+            version: 2,
+            lease: 0,
+        };
+        let delta = Delta::new(vec![1, 2, 3], 1, b"hello world", &kv.value);
+        let buf = delta.encode_kv(&kv);
+        let decoded_kv = KeyValue::decode(buf.as_slice()).unwrap();
+        assert_eq!(decoded_kv.key, kv.key);
+        assert_eq!(decoded_kv.mod_revision, kv.mod_revision);
+        assert!(decoded_kv.value.is_empty());
+        let decoded_delta = Delta::decode_from_kv(&buf).unwrap().unwrap();
+        assert_eq!(decoded_delta, delta);
+        assert_eq!(decoded_delta.apply(b"hello world").unwrap(), kv.value);
+        assert!(Delta::decode_from_kv(&kv.encode_to_vec())
+            .unwrap()
+            .is_none());
+    }
+}
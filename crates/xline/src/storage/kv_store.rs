@@ -1,6 +1,6 @@
 use std::{
     cmp::Ordering,
-    collections::{HashMap, VecDeque},
+    collections::{HashMap, HashSet, VecDeque},
     sync::{
         atomic::{AtomicI64, Ordering::Relaxed},
         Arc,
@@ -8,8 +8,9 @@ use std::{
 };
 
 use clippy_utilities::{Cast, OverflowArithmetic};
+use parking_lot::Mutex;
 use prost::Message;
-use tokio::sync::mpsc;
+use tokio::sync::{broadcast, mpsc};
 use tracing::{debug, warn};
 use utils::table_names::{KV_TABLE, META_TABLE};
 use xlineapi::{
@@ -18,7 +19,9 @@ use xlineapi::{
 };
 
 use super::{
+    compact::{CompactionProgress, COMPACT_PROGRESS_CHANNEL_SIZE},
     db::SCHEDULED_COMPACT_REVISION,
+    delta::{Delta, MAX_DELTA_CHAIN},
     index::{Index, IndexOperate},
     lease_store::LeaseCollection,
     revision::{KeyRevision, Revision},
@@ -53,6 +56,10 @@ where
     kv_update_tx: mpsc::Sender<(i64, Vec<Event>)>,
     /// Compact task submit sender
     compact_task_tx: mpsc::Sender<(i64, Option<Arc<event_listener::Event>>)>,
+    /// Compaction progress sender
+    compact_progress_tx: broadcast::Sender<CompactionProgress>,
+    /// The latest reported compaction progress
+    last_compact_progress: Mutex<Option<CompactionProgress>>,
     /// Lease collection
     lease_collection: Arc<LeaseCollection>,
 }
@@ -84,6 +91,30 @@ where
         }
     }
 
+    /// Decode a stored `KeyValue`, reconstruct its value if it's delta encoded
+    fn decode_kv(&self, buf: &[u8]) -> Result<KeyValue, ExecuteError> {
+        let mut kv = KeyValue::decode(buf).map_err(|e| {
+            ExecuteError::DbError(format!("Failed to decode key-value from DB, error: {e}"))
+        })?;
+        if let Some(delta) = Delta::decode_from_kv(buf)? {
+            kv.value = delta.apply(&self.get_base_value(&delta.base)?)?;
+        }
+        Ok(kv)
+    }
+
+    /// Get the materialized value of a base revision
+    fn get_base_value(&self, base: &[u8]) -> Result<Vec<u8>, ExecuteError> {
+        let buf = self
+            .db
+            .get_value(KV_TABLE, base)?
+            .ok_or_else(|| ExecuteError::DbError("base of a delta is not found".to_owned()))?;
+        KeyValue::decode(buf.as_slice())
+            .map(|kv| kv.value)
+            .map_err(|e| {
+                ExecuteError::DbError(format!("Failed to decode key-value from DB, error: {e}"))
+            })
+    }
+
     /// Get `KeyValue` from the `KvStoreInner`
     fn get_values(&self, revisions: &[Revision]) -> Result<Vec<KeyValue>, ExecuteError> {
         let revisions = revisions
@@ -94,15 +125,85 @@ where
         let kvs: Vec<KeyValue> = values
             .into_iter()
             .flatten()
-            .map(|v| KeyValue::decode(v.as_slice()))
-            .collect::<Result<_, _>>()
-            .map_err(|e| {
-                ExecuteError::DbError(format!("Failed to decode key-value from DB, error: {e}"))
-            })?;
+            .map(|v| self.decode_kv(&v))
+            .collect::<Result<_, _>>()?;
         debug_assert_eq!(kvs.len(), revisions.len(), "index does not match with db");
         Ok(kvs)
     }
 
+    /// Build the write op of a put, the value is stored as a delta against the latest
+    /// materialized value of the key if the put asks for delta encoding
+    fn put_op(
+        &self,
+        revision: Revision,
+        kv: KeyValue,
+        delta_encode: bool,
+    ) -> Result<WriteOp<'static>, ExecuteError> {
+        if !delta_encode {
+            return Ok(WriteOp::PutKeyValue(revision, kv));
+        }
+        let Some(prev_rev) = self.index.get(&kv.key, &[], 0).pop() else {
+            return Ok(WriteOp::PutKeyValue(revision, kv));
+        };
+        let prev_rev = prev_rev.encode_to_vec();
+        let Some(prev) = self.db.get_value(KV_TABLE, &prev_rev)? else {
+            return Ok(WriteOp::PutKeyValue(revision, kv));
+        };
+        let (base, chain) = match Delta::decode_from_kv(&prev)? {
+            Some(delta) => (delta.base, delta.chain.overflow_add(1)),
+            None => (prev_rev, 1),
+        };
+        if chain > MAX_DELTA_CHAIN {
+            return Ok(WriteOp::PutKeyValue(revision, kv));
+        }
+        let base_value = self.get_base_value(&base)?;
+        let delta = Delta::new(base, chain, &base_value, &kv.value);
+        if delta.encoded_len() >= kv.value.len() {
+            return Ok(WriteOp::PutKeyValue(revision, kv));
+        }
+        let encoded = delta.encode_kv(&kv);
+        Ok(WriteOp::PutDeltaKeyValue(revision, kv, encoded))
+    }
+
+    /// Materialize the retained deltas whose base values are going to be compacted
+    ///
+    /// Delta encoding is chosen per put, so any key may have deltas and every
+    /// compacted key has to be checked, or the retained deltas would be orphaned.
+    fn materialize_deltas(
+        &self,
+        compacted: &[Vec<u8>],
+    ) -> Result<Vec<WriteOp<'static>>, ExecuteError> {
+        let compacted_revs: HashSet<&[u8]> = compacted.iter().map(Vec::as_slice).collect();
+        let mut keys = HashSet::new();
+        for buf in self
+            .db
+            .get_values(KV_TABLE, compacted)?
+            .into_iter()
+            .flatten()
+        {
+            let kv = KeyValue::decode(buf.as_slice()).map_err(|e| {
+                ExecuteError::DbError(format!("Failed to decode key-value from DB, error: {e}"))
+            })?;
+            let _ignore = keys.insert(kv.key);
+        }
+        let mut ops = Vec::new();
+        for key in keys {
+            for rev in self.index.get_from_rev(&key, &[], 0) {
+                let rev_bytes = rev.encode_to_vec();
+                let Some(buf) = self.db.get_value(KV_TABLE, &rev_bytes)? else {
+                    continue;
+                };
+                let Some(delta) = Delta::decode_from_kv(&buf)? else {
+                    continue;
+                };
+                if compacted_revs.contains(delta.base.as_slice()) {
+                    ops.push(WriteOp::PutKeyValue(rev, self.decode_kv(&buf)?));
+                }
+            }
+        }
+        Ok(ops)
+    }
+
     /// Get `KeyValue` of a range
     ///
     /// If `range_end` is `&[]`, this function will return one or zero `KeyValue`.
@@ -194,13 +295,14 @@ where
         self.handle_kv_requests(request).map(CommandResponse::new)
     }
 
-    /// sync a kv request
+    /// sync a kv request, the values of its puts are delta encoded if `delta_encode` is set
     pub(crate) async fn after_sync(
         &self,
         request: &RequestWrapper,
         revision: i64,
+        delta_encode: bool,
     ) -> Result<(SyncResponse, Vec<WriteOp>), ExecuteError> {
-        self.sync_request(request, revision)
+        self.sync_request(request, revision, delta_encode)
             .await
             .map(|(rev, ops)| (SyncResponse::new(rev), ops))
     }
@@ -297,10 +399,29 @@ where
             header_gen,
             kv_update_tx,
             compact_task_tx,
+            compact_progress_tx: broadcast::channel(COMPACT_PROGRESS_CHANNEL_SIZE).0,
+            last_compact_progress: Mutex::new(None),
             lease_collection,
         }
     }
 
+    /// Subscribe the progress of compactions
+    pub(crate) fn compaction_progress(&self) -> broadcast::Receiver<CompactionProgress> {
+        self.compact_progress_tx.subscribe()
+    }
+
+    /// Report the progress of a compaction to all subscribers
+    pub(crate) fn report_compaction_progress(&self, progress: CompactionProgress) {
+        *self.last_compact_progress.lock() = Some(progress);
+        // it's fine that nobody is interested in the progress
+        let _ignore = self.compact_progress_tx.send(progress);
+    }
+
+    /// Get the latest reported compaction progress
+    pub(crate) fn last_compaction_progress(&self) -> Option<CompactionProgress> {
+        *self.last_compact_progress.lock()
+    }
+
     /// Get revision of KV store
     pub(crate) fn revision(&self) -> i64 {
         self.revision.get()
@@ -489,7 +610,7 @@ where
 
     /// Compact kv storage
     pub(crate) fn compact(&self, revisions: &[Vec<u8>]) -> Result<(), ExecuteError> {
-        let mut ops = Vec::new();
+        let mut ops = self.inner.materialize_deltas(revisions)?;
         revisions
             .iter()
             .for_each(|rev| ops.push(WriteOp::DeleteKeyValue(rev.as_ref())));
@@ -703,16 +824,21 @@ where
         &self,
         wrapper: &RequestWrapper,
         revision: i64,
+        delta_encode: bool,
     ) -> Result<(i64, Vec<WriteOp>), ExecuteError> {
         debug!("After Sync {:?} with revision {}", wrapper, revision);
         #[allow(clippy::wildcard_enum_match_arm)] // only kv requests can be sent to kv store
         let (ops, events) = match *wrapper {
             RequestWrapper::RangeRequest(_) => (Vec::new(), Vec::new()),
-            RequestWrapper::PutRequest(ref req) => self.sync_put_request(req, revision, 0)?,
+            RequestWrapper::PutRequest(ref req) => {
+                self.sync_put_request(req, revision, 0, delta_encode)?
+            }
             RequestWrapper::DeleteRangeRequest(ref req) => {
                 self.sync_delete_range_request(req, revision, 0)
             }
-            RequestWrapper::TxnRequest(ref req) => self.sync_txn_request(req, revision)?,
+            RequestWrapper::TxnRequest(ref req) => {
+                self.sync_txn_request(req, revision, delta_encode)?
+            }
             RequestWrapper::CompactionRequest(ref req) => {
                 self.sync_compaction_request(req, revision).await?
             }
@@ -754,6 +880,7 @@ where
         &self,
         req: &TxnRequest,
         revision: i64,
+        delta_encode: bool,
     ) -> Result<(Vec<WriteOp>, Vec<Event>), ExecuteError> {
         let mut sub_revision = 0;
         let mut origin_reqs = VecDeque::from([Request::RequestTxn(req.clone())]);
@@ -763,7 +890,7 @@ where
             let (mut ops, mut events) = match request {
                 Request::RequestRange(_) => (Vec::new(), Vec::new()),
                 Request::RequestPut(ref put_req) => {
-                    self.sync_put_request(put_req, revision, sub_revision)?
+                    self.sync_put_request(put_req, revision, sub_revision, delta_encode)?
                 }
                 Request::RequestDeleteRange(del_req) => {
                     self.sync_delete_range_request(&del_req, revision, sub_revision)
@@ -795,6 +922,7 @@ where
         req: &PutRequest,
         revision: i64,
         sub_revision: i64,
+        delta_encode: bool,
     ) -> Result<(Vec<WriteOp>, Vec<Event>), ExecuteError> {
         let mut ops = Vec::new();
         let new_rev = self
@@ -829,7 +957,10 @@ where
             self.attach(req.lease, kv.key.as_slice())
                 .unwrap_or_else(|e| panic!("unexpected error from lease Attach: {e}"));
         }
-        ops.push(WriteOp::PutKeyValue(new_rev.as_revision(), kv.clone()));
+        ops.push(
+            self.inner
+                .put_op(new_rev.as_revision(), kv.clone(), delta_encode)?,
+        );
         let event = Event {
             #[allow(clippy::as_conversions)] // This cast is always valid
             r#type: EventType::Put as i32,
@@ -1004,6 +1135,10 @@ mod test {
     }
 
     fn init_empty_store(db: Arc<DB>) -> StoreWrapper {
+        init_empty_store_with_batch_limit(db, 1000)
+    }
+
+    fn init_empty_store_with_batch_limit(db: Arc<DB>, batch_limit: usize) -> StoreWrapper {
         let task_manager = Arc::new(TaskManager::new());
         let (compact_tx, compact_rx) = mpsc::channel(COMPACT_CHANNEL_SIZE);
         let (kv_update_tx, kv_update_rx) = mpsc::channel(CHANNEL_SIZE);
@@ -1028,7 +1163,8 @@ mod test {
             compact_bg_task(
                 Arc::clone(&storage),
                 index,
-                1000,
+                batch_limit,
+                true,
                 Duration::from_millis(10),
                 compact_rx,
                 n,
@@ -1042,7 +1178,16 @@ mod test {
         request: &RequestWrapper,
         revision: i64,
     ) -> Result<(), ExecuteError> {
-        let (_sync_res, ops) = store.after_sync(request, revision).await?;
+        exe_as_and_flush_with_delta(store, request, revision, false).await
+    }
+
+    async fn exe_as_and_flush_with_delta(
+        store: &Arc<KvStore<DB>>,
+        request: &RequestWrapper,
+        revision: i64,
+        delta_encode: bool,
+    ) -> Result<(), ExecuteError> {
+        let (_sync_res, ops) = store.after_sync(request, revision, delta_encode).await?;
         let key_revs = store.inner.db.flush_ops(ops)?;
         store.insert_index(key_revs);
         Ok(())
@@ -1381,6 +1526,102 @@ mod test {
         Ok(())
     }
 
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_compaction_reports_progress() -> Result<(), ExecuteError> {
+        let db = DB::open(&EngineConfig::Memory)?;
+        let store = init_empty_store_with_batch_limit(db, 1);
+        let revision = RevisionNumberGenerator::default();
+        // (a, 1) (a, 2) (a, 3) (a, 4) at revisions 2, 3, 4, 5
+        for value in ["1", "2", "3", "4"] {
+            let req = RequestWrapper::from(PutRequest {
+                key: "a".into(),
+                value: value.into(),
+                ..Default::default()
+            });
+            exe_as_and_flush(&store, &req, revision.next()).await?;
+        }
+
+        let mut progress_rx = store.compaction_progress();
+        // a physical compaction only returns after the compaction finishes
+        let req = RequestWrapper::from(CompactionRequest {
+            revision: 5,
+            physical: true,
+        });
+        let _ig = store.after_sync(&req, revision.next(), false).await?;
+
+        let mut events = Vec::new();
+        while let Ok(progress) = progress_rx.try_recv() {
+            events.push(progress);
+        }
+        // one event for each removed revision, and a final completion
+        assert_eq!(
+            events.iter().map(|p| p.compacted).collect::<Vec<_>>(),
+            [1, 2, 3, 3]
+        );
+        assert!(events.iter().all(|p| p.revision == 5 && p.total == 3));
+        assert!(events[..3].iter().all(|p| !p.finished));
+        assert_eq!(events[1].percent(), 66);
+        let last = events.last().unwrap();
+        assert!(last.finished);
+        assert_eq!(last.percent(), 100);
+
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_delta_encoded_values() -> Result<(), ExecuteError> {
+        let db = DB::open(&EngineConfig::Memory)?;
+        let store = init_empty_store(db);
+        let revision = RevisionNumberGenerator::default();
+        let mut value = vec![0; 4096];
+        let mut history = Vec::new();
+        for i in 0..64 {
+            // a small change on the large value
+            value[i * 8] = 1;
+            for key in ["delta", "plain"] {
+                let req = RequestWrapper::from(PutRequest {
+                    key: key.into(),
+                    value: value.clone(),
+                    ..Default::default()
+                });
+                exe_as_and_flush_with_delta(&store, &req, revision.next(), key == "delta").await?;
+            }
+            history.push(value.clone());
+        }
+
+        let stored_size = |key: &[u8]| -> Result<usize, ExecuteError> {
+            let mut size = 0;
+            for (_rev, buf) in store.inner.db.get_all(KV_TABLE)? {
+                if KeyValue::decode(buf.as_slice()).unwrap().key == key {
+                    size += buf.len();
+                }
+            }
+            Ok(size)
+        };
+        let (delta_size, plain_size) = (stored_size(b"delta")?, stored_size(b"plain")?);
+        assert!(
+            delta_size * 8 < plain_size,
+            "delta encoded size {delta_size} should be much smaller than {plain_size}"
+        );
+
+        // reads reconstruct the value of every version
+        // the i-th version of the delta encoded key is at revision 2 + 2 * i
+        for (i, expected) in history.iter().enumerate() {
+            let kvs = store.inner.get_range(b"delta", &[], (i * 2 + 2).cast())?;
+            assert_eq!(&kvs[0].value, expected);
+        }
+
+        // the retained deltas are still readable after their bases are compacted
+        let revs = index_compact(&store, 100);
+        store.compact(&revs)?;
+        let kvs = store.inner.get_range(b"delta", &[], 0)?;
+        assert_eq!(&kvs[0].value, history.last().unwrap());
+        let kvs = store.inner.get_range(b"delta", &[], 101)?;
+        assert_eq!(kvs[0].value, history[49]);
+
+        Ok(())
+    }
+
     #[test]
     fn check_revision_will_return_correct_error_type() {
         let request = TxnRequest {
@@ -53,6 +53,9 @@ struct Watcher {
     watch_id: WatchId,
     /// Start revision of this watcher
     start_rev: i64,
+    /// The next revision this watcher needs, the events before it have been sent to the
+    /// watcher or do not concern it
+    next_rev: i64,
     /// Event filters
     filters: Vec<i32>,
     /// Stop notify
@@ -95,6 +98,7 @@ impl Watcher {
             key_range,
             watch_id,
             start_rev,
+            next_rev: start_rev,
             filters,
             stop_notify,
             event_tx,
@@ -113,6 +117,11 @@ impl Watcher {
         &self.key_range
     }
 
+    /// Advance the next revision needed by this watcher past `revision`
+    fn advance(&mut self, revision: i64) {
+        self.next_rev = self.next_rev.max(revision.saturating_add(1));
+    }
+
     /// filter out events
     fn filter_events(&self, mut events: Vec<Event>) -> Vec<Event> {
         events.retain(|event| {
@@ -150,6 +159,9 @@ impl Watcher {
         match self.event_tx.try_send(watch_event) {
             Ok(_) => {
                 let _ignore = self.notified_set.insert(revision);
+                if !self.compacted {
+                    self.advance(revision);
+                }
                 Ok(())
             }
             Err(TrySendError::Closed(_)) => {
@@ -305,6 +317,11 @@ pub(crate) trait KvWatcherOps {
 
     /// Get compacted revision from backend store
     fn compacted_revision(&self) -> i64;
+
+    /// Get the min revision active watchers still need to read from the history, which is
+    /// the revision after the ones already sent to them. Watchers of new events only are not
+    /// counted until they receive their first update.
+    fn min_next_revision(&self) -> Option<i64>;
 }
 
 #[async_trait::async_trait]
@@ -386,6 +403,17 @@ where
     fn compacted_revision(&self) -> i64 {
         self.kv_store_inner.compacted_revision()
     }
+
+    fn min_next_revision(&self) -> Option<i64> {
+        let watcher_map_r = self.watcher_map.read();
+        watcher_map_r
+            .watchers
+            .values()
+            .chain(watcher_map_r.victims.keys())
+            .map(|watcher| watcher.next_rev)
+            .filter(|&next_rev| next_rev > 0)
+            .min()
+    }
 }
 
 impl<S> KvWatcher<S>
@@ -464,9 +492,10 @@ where
                     );
                 } else {
                     let mut watcher_map_w = kv_watcher.watcher_map.write();
+                    // the events missed while the watcher was a victim
                     let initial_events = kv_watcher
                         .kv_store_inner
-                        .get_event_from_revision(watcher.key_range.clone(), watcher.start_rev)
+                        .get_event_from_revision(watcher.key_range.clone(), watcher.next_rev)
                         .unwrap_or_else(|e| {
                             warn!("failed to get initial events for watcher: {:?}", e);
                             vec![]
@@ -538,6 +567,11 @@ where
                         .move_to_victim(watch_id, (watch_event.revision, watch_event.events));
                 }
             }
+            // the watchers still registered have got every event of this revision they need,
+            // the victims keep their next revision until the events are resent
+            for watcher in watcher_map_w.watchers.values_mut() {
+                watcher.advance(revision);
+            }
         });
     }
 }
@@ -744,6 +778,41 @@ mod test {
         task_manager.shutdown(true).await;
     }
 
+    #[tokio::test(flavor = "multi_thread")]
+    #[abort_on_panic]
+    async fn min_next_revision_should_follow_the_sent_events() {
+        let task_manager = Arc::new(TaskManager::new());
+        let (store, db, kv_watcher) = init_empty_store(&task_manager);
+        for i in 1..=5_u8 {
+            put(store.as_ref(), db.as_ref(), "foo", vec![i], i.cast()).await;
+        }
+        let (event_tx, mut event_rx) = mpsc::channel(128);
+        let stop_notify = Arc::new(event_listener::Event::new());
+        kv_watcher.watch(
+            1,
+            KeyRange::new_one_key("foo"),
+            2,
+            vec![],
+            stop_notify,
+            event_tx,
+        );
+        // the history from revision 2 is sent on creation
+        let _initial = event_rx.recv().await.unwrap();
+        assert_eq!(kv_watcher.min_next_revision(), Some(6));
+
+        // updates of other keys also advance the watcher
+        put(store.as_ref(), db.as_ref(), "bar", vec![0], 6).await;
+        timeout(Duration::from_secs(1), async {
+            while kv_watcher.min_next_revision() != Some(7) {
+                sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .unwrap();
+        drop(store);
+        task_manager.shutdown(true).await;
+    }
+
     #[tokio::test(flavor = "multi_thread")]
     #[abort_on_panic]
     async fn test_cancel_watcher() {
@@ -780,7 +849,7 @@ mod test {
             value: value.into(),
             ..Default::default()
         });
-        let (_sync_res, ops) = store.after_sync(&req, revision).await.unwrap();
+        let (_sync_res, ops) = store.after_sync(&req, revision, false).await.unwrap();
         let key_revisions = db.flush_ops(ops).unwrap();
         store.insert_index(key_revisions);
     }
@@ -6,6 +6,8 @@ pub(crate) mod auth_store;
 pub(super) mod compact;
 /// Database module
 pub mod db;
+/// Delta encoding of values
+pub(crate) mod delta;
 /// Index module
 pub(crate) mod index;
 /// Storage for KV
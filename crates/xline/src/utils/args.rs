@@ -1,24 +1,27 @@
 use std::{collections::HashMap, env, path::PathBuf, time::Duration};
 
 use anyhow::Result;
-use clap::Parser;
+use clap::{ArgAction, Parser};
 use tokio::fs;
 use utils::{
     config::{
         default_batch_max_size, default_batch_timeout, default_candidate_timeout_ticks,
-        default_client_id_keep_alive_interval, default_client_wait_synced_timeout,
-        default_cmd_workers, default_compact_batch_size, default_compact_sleep_interval,
-        default_compact_timeout, default_follower_timeout_ticks, default_gc_interval,
-        default_heartbeat_interval, default_initial_retry_timeout, default_log_entries_cap,
-        default_log_level, default_max_retry_timeout, default_metrics_enable, default_metrics_path,
-        default_metrics_port, default_metrics_push_endpoint, default_metrics_push_protocol,
-        default_propose_timeout, default_quota, default_range_retry_timeout, default_retry_count,
-        default_rotation, default_rpc_timeout, default_server_wait_synced_timeout,
-        default_sync_victims_interval, default_watch_progress_notify_interval, AuthConfig,
-        AutoCompactConfig, ClientConfig, ClusterConfig, CompactConfig, CurpConfigBuilder,
-        EngineConfig, InitialClusterState, LevelConfig, LogConfig, MetricsConfig,
-        MetricsPushProtocol, RotationConfig, ServerTimeout, StorageConfig, TlsConfig, TraceConfig,
-        XlineServerConfig,
+        default_client_hint_fast_quorum, default_client_hint_timeout,
+        default_client_id_keep_alive_interval, default_client_qps_limit,
+        default_client_wait_synced_timeout, default_cmd_workers, default_compact_batch_size,
+        default_compact_sleep_interval, default_compact_timeout, default_enable_propose_deadline,
+        default_follower_timeout_ticks, default_gc_interval, default_heartbeat_interval,
+        default_incremental_compaction, default_initial_retry_timeout,
+        default_leader_lease_clock_drift, default_linearizable_read_timeout,
+        default_log_entries_cap, default_log_level, default_max_retry_timeout,
+        default_metrics_enable, default_metrics_path, default_metrics_port,
+        default_metrics_push_endpoint, default_metrics_push_protocol, default_propose_timeout,
+        default_quota, default_range_retry_timeout, default_retry_count, default_rotation,
+        default_rpc_timeout, default_server_wait_synced_timeout, default_sync_victims_interval,
+        default_watch_progress_notify_interval, AuthConfig, AutoCompactConfig, ClientConfig,
+        ClusterConfig, CompactConfig, CurpConfigBuilder, EngineConfig, InitialClusterState,
+        LevelConfig, LogConfig, MetricsConfig, MetricsPushProtocol, RotationConfig, ServerTimeout,
+        StorageConfig, TlsConfig, TraceConfig, XlineServerConfig,
     },
     parse_batch_bytes, parse_duration, parse_log_level, parse_members, parse_metrics_push_protocol,
     parse_rotation, parse_state, ConfigFileError,
@@ -55,6 +58,17 @@ pub struct ServerArgs {
     /// If node is leader
     #[clap(long)]
     is_leader: bool,
+    /// Format responses exactly as etcd does where xline diverges
+    #[clap(long)]
+    etcd_compat: bool,
+    /// Max bytes of responses buffered for one watch before it is canceled as a slow consumer,
+    /// 0 means unlimited [default: 0]
+    #[clap(long, default_value_t = 0)]
+    max_watch_buffer_bytes: u64,
+    /// Max active watches of a client connection, a watch creation beyond it is rejected,
+    /// 0 means unlimited [default: 0]
+    #[clap(long, default_value_t = 0)]
+    max_watches_per_client: usize,
     /// Private key used to sign the token
     #[clap(long)]
     auth_private_key: Option<PathBuf>,
@@ -130,6 +144,31 @@ pub struct ServerArgs {
     /// Number of log entries to keep in memory
     #[clap(long, default_value_t = default_log_entries_cap())]
     log_entries_cap: usize,
+    /// Serve linearizable reads locally on the leader while it holds a valid leader lease
+    #[clap(long)]
+    enable_leader_lease: bool,
+    /// Clock drift safety margin of the leader lease [default: 100ms]
+    #[clap(long, value_parser = parse_duration)]
+    leader_lease_clock_drift: Option<Duration>,
+    /// Timeout of confirming the leadership for a linearizable read
+    /// [default: heartbeat interval + rpc timeout]
+    #[clap(long, value_parser = parse_duration)]
+    linearizable_read_timeout: Option<Duration>,
+    /// Bound the wait for a propose result by the deadline of the propose
+    #[clap(long, default_value_t = default_enable_propose_deadline(), action = ArgAction::Set)]
+    enable_propose_deadline: bool,
+    /// Max proposes per second accepted from a single client, 0 means unlimited
+    #[clap(long, default_value_t = default_client_qps_limit())]
+    client_qps_limit: u64,
+    /// Propose timeout recommended to clients, no hint if not set
+    #[clap(long, value_parser = parse_duration)]
+    client_hint_propose_timeout: Option<Duration>,
+    /// Wait synced timeout recommended to clients, no hint if not set
+    #[clap(long, value_parser = parse_duration)]
+    client_hint_wait_synced_timeout: Option<Duration>,
+    /// Fast path quorum recommended to clients, 0 means no hint
+    #[clap(long, default_value_t = default_client_hint_fast_quorum())]
+    client_hint_fast_quorum: usize,
     /// Curp client wait synced timeout [default: 2s]
     #[clap(long, value_parser = parse_duration)]
     client_wait_synced_timeout: Option<Duration>,
@@ -163,7 +202,7 @@ pub struct ServerArgs {
     /// How often should watch progress notify send a response [default: 600s]
     #[clap(long, value_parser = parse_duration)]
     watch_progress_notify_interval: Option<Duration>,
-    /// Storage engine
+    /// Storage engine, one of `memory`, `rocksdb` and `sled`
     #[clap(long)]
     storage_engine: String,
     /// DB directory
@@ -171,12 +210,15 @@ pub struct ServerArgs {
     data_dir: PathBuf,
     /// Curp directory
     curp_dir: Option<PathBuf>,
-    /// Curp command workers count
-    #[clap(long, default_value_t = default_cmd_workers())]
+    /// Curp command workers count, i.e. the size of the command execution pool
+    #[clap(long, visible_alias = "execution-pool-size", default_value_t = default_cmd_workers())]
     cmd_workers: u8,
     /// The max number of historical versions processed in a single compact operation
     #[clap(long, default_value_t = default_compact_batch_size())]
     compact_batch_size: usize,
+    /// Reclaim the space in chunks of `compact_batch_size` to keep write latency bounded
+    #[clap(long, default_value_t = default_incremental_compaction(), action = ArgAction::Set)]
+    incremental_compaction: bool,
     /// Interval between two compaction operations [default: 10ms]
     #[clap(long, value_parser = parse_duration)]
     compact_sleep_interval: Option<Duration>,
@@ -220,17 +262,18 @@ impl From<ServerArgs> for XlineServerConfig {
     #[inline]
     #[allow(clippy::too_many_lines)] // not bad
     fn from(args: ServerArgs) -> Self {
+        let curp_dir = args.curp_dir.unwrap_or_else(|| args.data_dir.join("curp"));
         let (engine, curp_engine) = match args.storage_engine.as_str() {
             "memory" => (EngineConfig::Memory, EngineConfig::Memory),
             "rocksdb" => (
                 EngineConfig::RocksDB(args.data_dir.clone()),
-                EngineConfig::RocksDB(args.curp_dir.unwrap_or_else(|| {
-                    let mut path = args.data_dir;
-                    path.push("curp");
-                    path
-                })),
+                EngineConfig::RocksDB(curp_dir),
             ),
-            &_ => unreachable!("xline only supports memory and rocksdb engine"),
+            "sled" => (
+                EngineConfig::Sled(args.data_dir.clone()),
+                EngineConfig::Sled(curp_dir),
+            ),
+            &_ => unreachable!("xline only supports memory, rocksdb and sled engine"),
         };
 
         let storage = StorageConfig::new(engine, args.quota.unwrap_or_else(default_quota));
@@ -247,6 +290,18 @@ impl From<ServerArgs> for XlineServerConfig {
         .engine_cfg(curp_engine)
         .gc_interval(args.gc_interval.unwrap_or_else(default_gc_interval))
         .cmd_workers(args.cmd_workers)
+        .enable_leader_lease(args.enable_leader_lease)
+        .leader_lease_clock_drift(args.leader_lease_clock_drift
+            .unwrap_or_else(default_leader_lease_clock_drift))
+        .linearizable_read_timeout(args.linearizable_read_timeout
+            .unwrap_or_else(default_linearizable_read_timeout))
+        .enable_propose_deadline(args.enable_propose_deadline)
+        .client_qps_limit(args.client_qps_limit)
+        .client_hint_propose_timeout(args.client_hint_propose_timeout
+            .unwrap_or_else(default_client_hint_timeout))
+        .client_hint_wait_synced_timeout(args.client_hint_wait_synced_timeout
+            .unwrap_or_else(default_client_hint_timeout))
+        .client_hint_fast_quorum(args.client_hint_fast_quorum)
         .build() else { panic!("failed to create curp config") };
         let client_config = ClientConfig::new(
             args.client_wait_synced_timeout
@@ -284,6 +339,9 @@ impl From<ServerArgs> for XlineServerConfig {
             client_config,
             server_timeout,
             initial_cluster_state,
+            args.etcd_compat,
+            args.max_watch_buffer_bytes,
+            args.max_watches_per_client,
         );
         let log = LogConfig::new(args.log_file, args.log_rotate, args.log_level);
         let trace = TraceConfig::new(
@@ -319,7 +377,8 @@ impl From<ServerArgs> for XlineServerConfig {
             args.compact_sleep_interval
                 .unwrap_or_else(default_compact_sleep_interval),
             auto_compactor_cfg,
-        );
+        )
+        .with_incremental_compaction(args.incremental_compaction);
         let tls = TlsConfig::new(
             args.peer_ca_cert_path,
             args.peer_cert_path,
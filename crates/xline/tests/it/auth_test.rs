@@ -10,9 +10,11 @@ use xline_test_utils::{
     types::{
         auth::{AuthRoleDeleteRequest, AuthUserAddRequest, AuthUserGetRequest},
         kv::{PutRequest, RangeRequest},
+        watch::WatchRequest,
     },
     Client, ClientOptions, Cluster,
 };
+use xlineapi::WatchCancelReason;
 
 #[tokio::test(flavor = "multi_thread")]
 #[abort_on_panic]
@@ -143,6 +145,58 @@ async fn test_kv_authorization() -> Result<(), Box<dyn Error>> {
     Ok(())
 }
 
+#[tokio::test(flavor = "multi_thread")]
+#[abort_on_panic]
+async fn test_watch_authorization() -> Result<(), Box<dyn Error>> {
+    let mut cluster = Cluster::new_with_configs(configs_with_auth(3)).await;
+    cluster.start().await;
+    let client = cluster.client().await;
+
+    set_user(client, "u1", "123", "r1", b"foo", b"fop").await?;
+    set_user(client, "u2", "123", "r2", b"bar", &[]).await?;
+    enable_auth(client).await?;
+
+    let u1_client = Client::connect(
+        vec![cluster.get_client_url(0)],
+        ClientOptions::default().with_user("u1", "123"),
+    )
+    .await?;
+    let mut u2_client = Client::connect(
+        vec![cluster.get_client_url(0)],
+        ClientOptions::default().with_user("u2", "123"),
+    )
+    .await?
+    .watch_client();
+
+    let (mut watcher, mut stream) = u1_client
+        .watch_client()
+        .watch(WatchRequest::new("foo").with_prefix())
+        .await?;
+    let result = u2_client
+        .watch(WatchRequest::new("foo").with_prefix())
+        .await;
+    let err = result.err().unwrap().to_string();
+    assert!(err.contains("permission denied"), "unexpected error: {err}");
+
+    // an unauthorized watch is rejected alone, the other watches of the stream survive
+    watcher.watch(WatchRequest::new("bar"))?;
+    let rejected = stream.message().await?.unwrap();
+    assert!(rejected.created && rejected.canceled);
+    assert_eq!(
+        WatchCancelReason::parse(&rejected.cancel_reason),
+        Some(WatchCancelReason::PermissionDenied)
+    );
+    u1_client
+        .kv_client()
+        .put(PutRequest::new("foo1", "bar"))
+        .await?;
+    let resp = stream.message().await?.unwrap();
+    assert_eq!(resp.watch_id, watcher.watch_id());
+    assert_eq!(resp.events[0].kv.as_ref().unwrap().key, b"foo1");
+
+    Ok(())
+}
+
 #[tokio::test(flavor = "multi_thread")]
 #[abort_on_panic]
 async fn test_role_delete() -> Result<(), Box<dyn Error>> {
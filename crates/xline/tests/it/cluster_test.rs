@@ -101,3 +101,31 @@ async fn xline_update_node() -> Result<(), Box<dyn Error>> {
 
     Ok(())
 }
+
+#[tokio::test(flavor = "multi_thread")]
+#[abort_on_panic]
+async fn xline_member_list_detailed_flags_learners_and_reachability() -> Result<(), Box<dyn Error>>
+{
+    let mut cluster = Cluster::new(3).await;
+    cluster.start().await;
+    let mut cluster_client = Client::connect(cluster.all_client_addrs(), ClientOptions::default())
+        .await?
+        .cluster_client();
+    let learner_peer_listener = TcpListener::bind("0.0.0.0:0").await?;
+    let learner_peer_urls = vec![format!("http://{}", learner_peer_listener.local_addr()?)];
+    let add_res = cluster_client
+        .member_add(MemberAddRequest::new(learner_peer_urls.clone(), true))
+        .await?;
+    let learner_id = add_res.member.unwrap().id;
+
+    let members = cluster_client.member_list_detailed(true).await?;
+    assert_eq!(members.len(), 4);
+    let (learners, voters): (Vec<_>, Vec<_>) = members.iter().partition(|m| m.is_learner());
+    assert_eq!(voters.len(), 3);
+    assert!(voters.iter().all(|m| m.is_reachable()));
+    assert_eq!(learners.len(), 1);
+    assert_eq!(learners[0].id(), learner_id);
+    assert_eq!(learners[0].peer_urls(), learner_peer_urls.as_slice());
+    assert!(!learners[0].is_reachable());
+    Ok(())
+}
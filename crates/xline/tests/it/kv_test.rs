@@ -330,3 +330,40 @@ async fn test_txn() -> Result<(), Box<dyn Error>> {
 
     Ok(())
 }
+
+#[tokio::test(flavor = "multi_thread")]
+#[abort_on_panic]
+async fn test_range_header_in_etcd_compat_mode() -> Result<(), Box<dyn Error>> {
+    let mut native = Cluster::new(3).await;
+    native.start().await;
+    let client = native.client().await.kv_client();
+    let _ignore = client.put(PutRequest::new("foo", "bar")).await?;
+    let header = client
+        .range(RangeRequest::new("foo"))
+        .await?
+        .header
+        .unwrap();
+    assert_ne!(header.cluster_id, 0);
+    assert_ne!(header.member_id, 0);
+    assert_eq!(header.revision, 2);
+    // xline does not report the raft term unless the compat mode is enabled
+    assert_eq!(header.raft_term, 0);
+
+    let mut compat =
+        Cluster::new_with_configs(vec![Cluster::default_etcd_compat_config(); 3]).await;
+    compat.start().await;
+    let client = compat.client().await.kv_client();
+    let _ignore = client.put(PutRequest::new("foo", "bar")).await?;
+    let header = client
+        .range(RangeRequest::new("foo"))
+        .await?
+        .header
+        .unwrap();
+    assert_ne!(header.cluster_id, 0);
+    assert_ne!(header.member_id, 0);
+    assert_eq!(header.revision, 2);
+    // etcd always reports the term of the current leader, which starts from 1
+    assert!(header.raft_term >= 1);
+
+    Ok(())
+}
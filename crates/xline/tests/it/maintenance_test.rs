@@ -4,6 +4,7 @@ use test_macros::abort_on_panic;
 use tokio::io::AsyncWriteExt;
 #[cfg(test)]
 use xline::restore::restore;
+use xline::server::XlineServer;
 use xline_client::error::XlineClientError;
 use xline_test_utils::{
     types::kv::{PutRequest, RangeRequest},
@@ -121,3 +122,36 @@ async fn test_status() -> Result<(), Box<dyn std::error::Error>> {
 
     Ok(())
 }
+
+#[tokio::test(flavor = "multi_thread")]
+#[abort_on_panic]
+async fn startup_should_reject_zero_sized_execution_pool() {
+    let config = Cluster::default_execution_pool_config(0);
+    let res = XlineServer::new(
+        config.cluster().clone(),
+        config.storage().clone(),
+        *config.compact(),
+        config.auth().clone(),
+        config.tls().clone(),
+    )
+    .await;
+    assert!(res.is_err());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+#[abort_on_panic]
+async fn status_should_reflect_the_execution_pool_size() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cluster =
+        Cluster::new_with_configs(vec![Cluster::default_execution_pool_config(3); 3]).await;
+    cluster.start().await;
+    let mut maintenance_client = cluster.client().await.maintenance_client();
+    let status = maintenance_client
+        .execution_pool_status()
+        .await?
+        .expect("the member should report its execution pool");
+    assert_eq!(status.size, 3);
+    assert!(status.busy <= status.size);
+    assert!((0.0..=1.0).contains(&status.utilization()));
+
+    Ok(())
+}
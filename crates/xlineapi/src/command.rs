@@ -220,6 +220,21 @@ pub struct Command {
     compact_id: u64,
     /// Auth info
     auth_info: Option<AuthInfo>,
+    /// Whether the values of puts in the request are stored as deltas
+    #[serde(default)]
+    delta_encode: bool,
+}
+
+/// Fields of `Command` appended to an encoded `PbCommand`, decoding the bytes as a
+/// `PbCommand` just skips them
+///
+/// The command proto cannot be changed in this tree, so the fields use tags far
+/// beyond the ones of `PbCommand`.
+#[derive(Clone, PartialEq, Message)]
+struct CommandExt {
+    /// Whether the values of puts in the request are stored as deltas
+    #[prost(bool, tag = "1000")]
+    delta_encode: bool,
 }
 
 /// get all lease ids in the request wrapper
@@ -364,6 +379,7 @@ impl Command {
             keys,
             compact_id: 0,
             auth_info: None,
+            delta_encode: false,
         }
     }
 
@@ -380,6 +396,7 @@ impl Command {
             keys,
             compact_id: 0,
             auth_info,
+            delta_encode: false,
         }
     }
 
@@ -398,6 +415,22 @@ impl Command {
         self.compact_id
     }
 
+    /// With `delta_encode`, the values of puts in the request are stored as deltas
+    /// against a materialized version of the key
+    #[must_use]
+    #[inline]
+    pub fn with_delta_encode(mut self, delta_encode: bool) -> Self {
+        self.delta_encode = delta_encode;
+        self
+    }
+
+    /// Whether the values of puts in the request are stored as deltas
+    #[must_use]
+    #[inline]
+    pub fn delta_encode(&self) -> bool {
+        self.delta_encode
+    }
+
     /// get request
     #[must_use]
     #[inline]
@@ -552,12 +585,21 @@ impl PbCodec for Command {
             auth_info: self.auth_info.clone(),
             request_wrapper: Some(self.request.clone()),
         };
-        rpc_cmd.encode_to_vec()
+        let mut buf = rpc_cmd.encode_to_vec();
+        if self.delta_encode {
+            let ext = CommandExt {
+                delta_encode: self.delta_encode,
+            };
+            ext.encode(&mut buf)
+                .unwrap_or_else(|e| unreachable!("vec buffer should never overflow: {e}"));
+        }
+        buf
     }
 
     #[inline]
     fn decode(buf: &[u8]) -> Result<Self, PbSerializeError> {
         let rpc_cmd = PbCommand::decode(buf)?;
+        let ext = CommandExt::decode(buf)?;
         Ok(Self {
             keys: rpc_cmd.keys.into_iter().map(Into::into).collect(),
             compact_id: rpc_cmd.compact_id,
@@ -565,6 +607,7 @@ impl PbCodec for Command {
             request: rpc_cmd
                 .request_wrapper
                 .ok_or(PbSerializeError::EmptyField)?,
+            delta_encode: ext.delta_encode,
         })
     }
 }
@@ -786,6 +829,16 @@ mod test {
         let decoded_cmd =
             <Command as PbCodec>::decode(&cmd.encode()).expect("decode should success");
         assert_eq!(cmd, decoded_cmd);
+
+        let delta_cmd = cmd.clone().with_delta_encode(true);
+        let buf = delta_cmd.encode();
+        let decoded_cmd = <Command as PbCodec>::decode(&buf).expect("decode should success");
+        assert_eq!(delta_cmd, decoded_cmd);
+        // the appended fields are skipped by the command proto
+        assert_eq!(
+            PbCommand::decode(buf.as_slice()).expect("decode should success"),
+            PbCommand::decode(cmd.encode().as_slice()).expect("decode should success")
+        );
     }
 
     #[test]
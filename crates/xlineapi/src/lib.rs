@@ -322,6 +322,98 @@ pub enum RequestBackend {
     Alarm,
 }
 
+/// Metadata key of a compaction request, which asks the server not to compact revisions that
+/// active watchers still watch from
+pub const RESPECT_WATCHERS_KEY: &str = "respect-watchers";
+
+/// Reason of a watch canceled by the server. The watch response has no error code and the
+/// protos can not be changed, so each reason is carried by the cancel reason of the response.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum WatchCancelReason {
+    /// The watch creation is rejected for exceeding the max active watches of a connection
+    WatchLimitExceeded,
+    /// The client consumes the responses of the watch too slowly
+    SlowConsumer,
+    /// The watch creation is rejected for lacking the permission of the watched range, the
+    /// message of the permission check follows as the detail
+    PermissionDenied,
+}
+
+impl WatchCancelReason {
+    /// All reasons
+    const ALL: [Self; 3] = [
+        Self::WatchLimitExceeded,
+        Self::SlowConsumer,
+        Self::PermissionDenied,
+    ];
+
+    /// The cancel reason of the responses
+    #[inline]
+    #[must_use]
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::WatchLimitExceeded => "watch limit exceeded",
+            Self::SlowConsumer => "slow consumer",
+            Self::PermissionDenied => "permission denied",
+        }
+    }
+
+    /// The cancel reason of the responses followed by the `detail`
+    #[inline]
+    #[must_use]
+    pub fn with_detail(self, detail: &str) -> String {
+        format!("{}{REASON_DETAIL_SEPARATOR}{detail}", self.as_str())
+    }
+
+    /// Parse the cancel reason of a response, `None` if it is not a known reason
+    #[inline]
+    #[must_use]
+    pub fn parse(reason: &str) -> Option<Self> {
+        let reason = reason
+            .split_once(REASON_DETAIL_SEPARATOR)
+            .map_or(reason, |(reason, _detail)| reason);
+        Self::ALL.into_iter().find(|r| r.as_str() == reason)
+    }
+
+    /// The detail following the cancel reason of a response, `None` if there is no detail
+    #[inline]
+    #[must_use]
+    pub fn detail(reason: &str) -> Option<&str> {
+        reason
+            .split_once(REASON_DETAIL_SEPARATOR)
+            .map(|(_reason, detail)| detail)
+    }
+}
+
+/// Separator between a watch cancel reason and its detail
+const REASON_DETAIL_SEPARATOR: &str = ": ";
+
+/// Metadata key of a compaction response, which is the revision actually compacted to
+pub const COMPACTED_REVISION_KEY: &str = "compacted-revision";
+
+/// Metadata key of a serializable range response, which is the latest revision of the leader
+/// known by the serving member, learned from the heartbeats of the leader
+pub const LEADER_REVISION_KEY: &str = "leader-revision";
+
+/// Metadata key of a status response, which is the information of the latest snapshot of the
+/// member, formatted as `<last included index>,<last included term>,<size>,<created at>`, where
+/// the creation time is in milliseconds since the unix epoch
+pub const SNAPSHOT_INFO_KEY: &str = "snapshot-info";
+
+/// Metadata key of a status response, which is the utilization of the command execution pool
+/// of the member, formatted as `<busy workers>,<pool size>`
+pub const EXECUTION_POOL_KEY: &str = "execution-pool";
+
+/// Metadata key of a status response, which is the progress of the latest physical compaction
+/// of the member, formatted as `<revision>,<compacted>,<total>,<finished>`, where `finished` is
+/// `1` once the compaction is finished and `0` otherwise
+pub const COMPACTION_PROGRESS_KEY: &str = "compaction-progress";
+
+/// Metadata key of a status response, which is the log index of the source cluster up to
+/// which the member has replicated, present only if the member replicates from a source
+pub const REPLICATION_WATERMARK_KEY: &str = "replication-watermark";
+
 /// Get command keys from a Request for conflict check
 pub trait CommandKeys {
     /// Key ranges
@@ -1186,4 +1278,26 @@ mod test {
         let expect = "memberID:10276657743932975437 alarm:NOSPACE ";
         assert_eq!(expect, am.to_string());
     }
+
+    #[test]
+    fn watch_cancel_reason_should_be_parsed_from_its_str() {
+        for reason in WatchCancelReason::ALL {
+            assert_eq!(WatchCancelReason::parse(reason.as_str()), Some(reason));
+        }
+        assert_eq!(WatchCancelReason::parse("canceled by the client"), None);
+        let reason =
+            WatchCancelReason::PermissionDenied.with_detail("etcdserver: permission denied");
+        assert_eq!(
+            WatchCancelReason::parse(&reason),
+            Some(WatchCancelReason::PermissionDenied)
+        );
+        assert_eq!(
+            WatchCancelReason::detail(&reason),
+            Some("etcdserver: permission denied")
+        );
+        assert_eq!(
+            WatchCancelReason::detail(WatchCancelReason::SlowConsumer.as_str()),
+            None
+        );
+    }
 }